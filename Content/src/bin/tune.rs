@@ -0,0 +1,157 @@
+//! Measures the crossover points of the tuning profile on this machine and writes them to a profile file.
+//!
+//! Usage: `cargo run --release --bin tune [output]` (the default output is `tuning.profile`).
+//! Load the result by pointing the `MATH_ALGORITHMS_PROFILE` environment variable at the file.
+//!
+//! The profile is read once per process, so every candidate is timed in a child process started with
+//! `--measure <workload> <result>` and the candidate profile in `MATH_ALGORITHMS_PROFILE`, which writes the elapsed
+//! seconds to the result file (stdout is left to the algorithms, ECM logs its progress there).
+
+use std::{env, fs, process::Command, time::Instant};
+
+use math_algorithms::{
+    discrete_logarithm::discrete_log,
+    number_theory::{is_prime, random::generate_prime},
+    prime_factorization::prime_factorize,
+    tuning::{TuningProfile, MAX_BSGS_LIMIT, MIN_BSGS_LIMIT, PROFILE_ENV},
+};
+use rug::{rand::RandState, Integer};
+
+/// Seed of the workloads, so that every candidate is timed on the same inputs.
+const SEED: u32 = 0x7475_6e65;
+
+/// Numbers with a mix of small, medium and large factors: the small ones exercise trial division,
+/// the medium ones Pollard's rho, and the largest ones reach ECM.
+fn factor_workload() {
+    let mut rand = RandState::new();
+    rand.seed(&Integer::from(SEED));
+    for i in 0..24 {
+        let mut n = generate_prime(10 + (i % 8), &mut rand);
+        n *= generate_prime(14 + (i % 6), &mut rand);
+        n *= generate_prime(30 + (i % 4) * 2, &mut rand);
+        if i % 3 == 0 {
+            n *= generate_prime(52, &mut rand);
+        }
+        n *= generate_prime(80, &mut rand);
+        assert!(prime_factorize(&n).factors().iter().all(|(p, _)| is_prime(p)));
+    }
+}
+
+/// Discrete logs in subgroups of prime order q with `bits` bits, modulo primes p = 2kq + 1.
+fn dlog_workload(bits: u32) {
+    let mut rand = RandState::new();
+    rand.seed(&Integer::from(SEED + bits));
+    for _ in 0..64 {
+        let q = generate_prime(bits, &mut rand);
+        let mut k = 1u32;
+        let p = loop {
+            let p = Integer::from(&q * (2 * k)) + 1u32;
+            if is_prime(&p) {
+                break p;
+            }
+            k += 1;
+        };
+        // g has order q, since (p - 1) / q = 2k
+        let g = loop {
+            let a = Integer::from(p.random_below_ref(&mut rand));
+            let g = a.pow_mod(&Integer::from(2 * k), &p).unwrap();
+            if g > 1 {
+                break g;
+            }
+        };
+        let x = Integer::from(q.random_below_ref(&mut rand));
+        let h = Integer::from(g.pow_mod_ref(&x, &p).unwrap());
+        let solution = discrete_log(&g, &h, &p).expect("the discrete log exists");
+        assert_eq!(solution.exponent, x);
+    }
+}
+
+/// Runs a workload in a child process using the given profile, and returns the elapsed seconds.
+fn measure(workload: &str, profile: &TuningProfile) -> f64 {
+    let dir = env::temp_dir();
+    let path = dir.join(format!("math_algorithms_tune_{}.profile", std::process::id()));
+    let result = dir.join(format!("math_algorithms_tune_{}.result", std::process::id()));
+    profile.save(&path).expect("failed to write the candidate profile");
+    let output = Command::new(env::current_exe().unwrap())
+        .arg("--measure").arg(workload).arg(&result)
+        .env(PROFILE_ENV, &path)
+        .output()
+        .expect("failed to run the measurement");
+    let _ = fs::remove_file(&path);
+    assert!(output.status.success(), "measurement of {} failed:\n{}", workload, String::from_utf8_lossy(&output.stderr));
+    let time = fs::read_to_string(&result).ok().and_then(|text| text.trim().parse().ok());
+    let _ = fs::remove_file(&result);
+    time.expect("invalid measurement")
+}
+
+/// Times the workload with each candidate value of a parameter, keeps the fastest in the profile, and returns it.
+fn tune<T: Copy + std::fmt::Display>(
+    name: &str,
+    workload: &str,
+    candidates: &[T],
+    profile: &mut TuningProfile,
+    set: fn(&mut TuningProfile, T),
+) -> T {
+    let mut best = (f64::INFINITY, candidates[0]);
+    for &value in candidates {
+        let mut candidate = *profile;
+        set(&mut candidate, value);
+        let time = measure(workload, &candidate);
+        println!("  {} = {}: {:.3}s", name, value, time);
+        if time < best.0 {
+            best = (time, value);
+        }
+    }
+    set(profile, best.1);
+    println!("{} = {}", name, best.1);
+    best.1
+}
+
+fn tune_bsgs_limit(profile: &mut TuningProfile) {
+    // compare BSGS and Pollard's rho on growing subgroup orders, the limit is placed below the first size where rho wins.
+    // The search stops at 2^36, where BSGS already stores 2^18 elements per subgroup.
+    let mut bsgs = *profile;
+    bsgs.bsgs_limit = MAX_BSGS_LIMIT;
+    let mut rho = *profile;
+    rho.bsgs_limit = MIN_BSGS_LIMIT;
+    for bits in (16..=36).step_by(2) {
+        let workload = format!("dlog{}", bits);
+        let (bsgs_time, rho_time) = (measure(&workload, &bsgs), measure(&workload, &rho));
+        println!("  {} bit orders: bsgs {:.3}s, rho {:.3}s", bits, bsgs_time, rho_time);
+        profile.bsgs_limit = 1 << bits;
+        if rho_time < bsgs_time {
+            profile.bsgs_limit = 1 << (bits - 1);
+            break;
+        }
+    }
+    println!("bsgs_limit = {}", profile.bsgs_limit);
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() == 4 && args[1] == "--measure" {
+        let timer = Instant::now();
+        match args[2].strip_prefix("dlog") {
+            Some(bits) => dlog_workload(bits.parse().expect("invalid workload")),
+            None if args[2] == "factor" => factor_workload(),
+            None => panic!("unknown workload {}", args[2]),
+        }
+        fs::write(&args[3], timer.elapsed().as_secs_f64().to_string()).expect("failed to write the measurement");
+        return;
+    }
+    let output = args.get(1).map_or("tuning.profile", String::as_str);
+
+    let mut profile = TuningProfile::default();
+    tune("trial_division_bound", "factor", &[1_000, 3_000, 10_000, 30_000, 100_000], &mut profile,
+        |p, v| p.trial_division_bound = v);
+    tune("rho_attempts", "factor", &[1, 2, 3, 4, 6], &mut profile, |p, v| p.rho_attempts = v);
+    let block_size = tune("ecm_block_size_1", "factor", &[1000, 1500, 2000, 3000, 4000], &mut profile,
+        |p, v| p.ecm_block_size_1 = v);
+    // the second pass uses a 10 times larger B1, so the block size scales along (up to the phase 2 table size)
+    profile.ecm_block_size_2 = (block_size * 5 / 2).min(10_000);
+    println!("ecm_block_size_2 = {}", profile.ecm_block_size_2);
+    tune_bsgs_limit(&mut profile);
+
+    profile.save(output).expect("failed to write the profile");
+    println!("wrote {}", output);
+}
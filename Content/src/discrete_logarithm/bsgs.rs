@@ -0,0 +1,121 @@
+use std::collections::HashMap;
+
+use rug::{ops::RemRounding, Integer};
+
+use super::structs::{BsgsBackend, BsgsOptions, DlogError, DlogSolution, DlogStrategy};
+
+/// The baby steps g^j of `bsgs`, keyed by their residue, in the backend of the options.
+enum BabySteps {
+    Map(HashMap<Integer, u64>),
+    Sorted(Vec<(Integer, u64)>),
+}
+
+impl BabySteps {
+    /// The smallest j with g^j = x among the baby steps.
+    fn get(&self, x: &Integer) -> Option<u64> {
+        match self {
+            BabySteps::Map(map) => map.get(x).copied(),
+            BabySteps::Sorted(steps) => steps.binary_search_by(|(step, _)| step.cmp(x)).ok().map(|i| steps[i].1),
+        }
+    }
+}
+
+/// Baby-step giant-step on its own: the smallest x in [0, order) with g^x = h mod n, for a caller who already knows
+/// the order of g (or a multiple of it), e.g the prime order of a Diffie–Hellman subgroup. `discrete_log` runs the
+/// same search on the prime order subgroups it splits the group into, but it factorizes n and the orders first.
+///
+/// It takes m = ceil(sqrt(order)) baby steps, or fewer if they would take more than `options.memory_limit` bytes, and
+/// then up to order / m giant steps, so a tight memory bound trades space for time. `options.backend` picks how the
+/// baby steps are stored. The arithmetic is plain modular arithmetic, so n may be even.
+///
+/// # Returns
+/// * `Ok(solution)` - The smallest solution, with the order as its period.
+/// * `Err(DlogError::NoSolution)` - No x in [0, order) has g^x = h.
+/// * `Err(DlogError::NotInvertible)` - g is not invertible modulo n.
+pub fn bsgs(g: &Integer, h: &Integer, n: &Integer, order: u64, options: &BsgsOptions)
+    -> Result<DlogSolution, DlogError> {
+    if *n < 1 || order == 0 {
+        return Err(DlogError::NoSolution);
+    }
+    let g = g.clone().rem_euc(n);
+    let h = h.clone().rem_euc(n);
+    let g_inverse = g.invert_ref(n).map(Integer::from).ok_or(DlogError::NotInvertible)?;
+
+    // a residue, with the allocation of the Integer, and the exponent (and the hash map's overhead)
+    let entry_size = (n.significant_bits() / 8) as usize + match options.backend {
+        BsgsBackend::HashMap => 64,
+        BsgsBackend::SortedVec => 32,
+    };
+    let m = ((order as f64).sqrt().ceil() as u64).min((options.memory_limit / entry_size) as u64).max(1);
+
+    let mut x: Integer = 1 % n.clone();
+    let mut steps = Vec::with_capacity(m as usize);
+    for j in 0..m {
+        steps.push((x.clone(), j));
+        x *= &g;
+        x %= n;
+    }
+    let table = match options.backend {
+        BsgsBackend::HashMap => {
+            let mut map = HashMap::with_capacity(steps.len());
+            for (step, j) in steps {
+                map.entry(step).or_insert(j);
+            }
+            BabySteps::Map(map)
+        }
+        BsgsBackend::SortedVec => {
+            // sorted by residue then exponent, so the first of equal residues has the smallest j
+            steps.sort_unstable();
+            steps.dedup_by(|(step, _), (kept, _)| step == kept);
+            BabySteps::Sorted(steps)
+        }
+    };
+
+    // g^(-m)
+    let giant_step = Integer::from(g_inverse.pow_mod_ref(&Integer::from(m), n).unwrap());
+    let mut y = h;
+    for i in 0..=order / m {
+        if let Some(j) = table.get(&y) {
+            let x = i * m + j;
+            return match x < order {
+                true => Ok(DlogSolution { exponent: Integer::from(x), period: Integer::from(order),
+                    strategy: DlogStrategy::BabyStepGiantStep, transcript: None }),
+                false => Err(DlogError::NoSolution),
+            };
+        }
+        y *= &giant_step;
+        y %= n;
+    }
+    Err(DlogError::NoSolution)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bsgs() {
+        // 5 generates (Z/pZ)* for p = 1e9 + 7
+        let p = Integer::from(1_000_000_007);
+        let x = 987_654_321u64;
+        let h = Integer::from(Integer::from(5).pow_mod_ref(&Integer::from(x), &p).unwrap());
+        let small = BsgsOptions { memory_limit: 1 << 12, ..Default::default() };
+        for backend in [BsgsBackend::HashMap, BsgsBackend::SortedVec] {
+            for memory_limit in [BsgsOptions::default().memory_limit, small.memory_limit] {
+                let options = BsgsOptions { memory_limit, backend };
+                let solution = bsgs(&Integer::from(5), &h, &p, 1_000_000_006, &options).unwrap();
+                assert_eq!((solution.exponent, solution.period), (Integer::from(x), Integer::from(1_000_000_006)));
+            }
+        }
+
+        // 4 has order 500000003, so 5 is not one of its powers; 3 has order 256 mod 2^10, and with a multiple of
+        // its order the smallest solution still comes back
+        let options = BsgsOptions::default();
+        assert_eq!(bsgs(&Integer::from(4), &Integer::from(5), &p, 500_000_003, &options), Err(DlogError::NoSolution));
+        let n = Integer::from(1024);
+        let h = Integer::from(3).pow_mod(&Integer::from(300), &n).unwrap();
+        assert_eq!(bsgs(&Integer::from(3), &h, &n, 1024, &options).unwrap().exponent, 44);
+        assert_eq!(bsgs(&Integer::from(6), &Integer::from(2), &Integer::from(9), 6, &options),
+            Err(DlogError::NotInvertible));
+    }
+}
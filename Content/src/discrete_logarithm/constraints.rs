@@ -0,0 +1,125 @@
+use rug::{ops::{DivRounding, RemRounding}, Integer};
+
+use super::{verify_dlog, DlogRange, DlogSolution};
+use crate::number_theory::chinese_remainder_theorem;
+
+impl DlogSolution {
+    /// The solutions that also satisfy x ≡ residue mod modulus, e.g to pick the exponents that are 1 mod 8.
+    /// They are found with CRT, and form a solution again: the exponent is the smallest of them, and the period
+    /// becomes lcm(period, modulus). The strategy and the transcript are kept, since they still hold.
+    ///
+    /// # Returns
+    /// * `Some(solution)` - The refined solution.
+    /// * `None` - No solution satisfies the congruence, or the modulus is not positive.
+    pub fn with_congruence(&self, residue: &Integer, modulus: &Integer) -> Option<DlogSolution> {
+        if *modulus <= 0 {
+            return None;
+        }
+        let residue = Integer::from(residue.rem_euc(modulus));
+        if self.period == 0 {
+            let holds = Integer::from(&self.exponent - &residue).is_divisible(modulus);
+            return holds.then(|| self.clone());
+        }
+
+        let (x, period) = chinese_remainder_theorem(&self.exponent, &self.period, &residue, modulus)?;
+        // the smallest x that is at least the exponent, the solutions below it are not solutions of g^x = h
+        let mut exponent = x.rem_euc(&period);
+        if exponent < self.exponent {
+            let steps = Integer::from(&self.exponent - &exponent).div_ceil(&period);
+            exponent += steps * &period;
+        }
+        Some(DlogSolution { exponent, period, strategy: self.strategy, transcript: self.transcript.clone() })
+    }
+
+    /// The solutions in [low, high), e.g `in_range(&0.into(), &(Integer::from(1) << 64))` for the ones that fit in a u64.
+    /// The range can hold a huge number of solutions, so `count` is an `Integer` and they are only generated when iterated.
+    pub fn in_range(&self, low: &Integer, high: &Integer) -> DlogRange {
+        let empty = || DlogRange { first: self.exponent.clone(), period: self.period.clone(), count: Integer::ZERO };
+        if self.period == 0 {
+            return match *low <= self.exponent && self.exponent < *high {
+                true => DlogRange { count: Integer::ONE.clone(), ..empty() },
+                false => empty(),
+            };
+        }
+
+        let mut first = self.exponent.clone();
+        if first < *low {
+            let steps = Integer::from(low - &first).div_ceil(&self.period);
+            first += steps * &self.period;
+        }
+        if first >= *high {
+            return empty();
+        }
+        // first + period * (count - 1) is the last solution below high
+        let count = (Integer::from(high - &first) - 1u32) / &self.period + 1u32;
+        DlogRange { first, period: self.period.clone(), count }
+    }
+
+    /// The solution the others are generated from, i.e the exponent. For the solvers with a period (and after
+    /// `with_congruence`) it is the smallest solution, the first powers of g being skipped when g and n are not coprime
+    /// since they are no solutions even if they fit the period. It need not be the smallest after the kangaroo method of
+    /// `discrete_log_in_range`: its period is 0 because the order of g is unknown, so it is just the solution it found,
+    /// and smaller ones (even in the range) can't be ruled out.
+    pub fn representative(&self) -> &Integer {
+        &self.exponent
+    }
+
+    /// Whether g^x = h mod n holds for the solutions (see `verify_dlog`, which also checks the transcript if any).
+    pub fn verify(&self, g: &Integer, h: &Integer, n: &Integer) -> bool {
+        verify_dlog(g, h, n, self)
+    }
+
+    /// The solutions in [0, bound), smallest first, i.e `in_range(&0.into(), bound)`.
+    pub fn solutions_below(&self, bound: &Integer) -> DlogRange {
+        self.in_range(&Integer::ZERO, bound)
+    }
+}
+
+impl Iterator for DlogRange {
+    type Item = Integer;
+
+    fn next(&mut self) -> Option<Integer> {
+        if self.count == 0 {
+            return None;
+        }
+        let solution = self.first.clone();
+        self.first += &self.period;
+        self.count -= 1;
+        Some(solution)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::discrete_logarithm::discrete_log;
+
+    #[test]
+    fn test_constraints() {
+        // 2 has order 8 mod 17, and 2^3 = 8
+        let (g, h, n) = (Integer::from(2), Integer::from(8), Integer::from(17));
+        let solution = discrete_log(&g, &h, &n).unwrap();
+        let refined = solution.with_congruence(&Integer::from(1), &Integer::from(3)).unwrap();
+        assert_eq!((refined.exponent.clone(), refined.period.clone()), (Integer::from(19), Integer::from(24)));
+        assert!(verify_dlog(&g, &h, &n, &refined));
+        assert_eq!(solution.with_congruence(&Integer::from(0), &Integer::from(2)), None);
+        assert_eq!(solution.with_congruence(&Integer::from(-5), &Integer::from(4)).unwrap().exponent, 3);
+
+        let range = solution.in_range(&Integer::from(4), &Integer::from(100));
+        assert_eq!(range.count, 12);
+        assert_eq!(range.collect::<Vec<_>>(), (11..100).step_by(8).map(Integer::from).collect::<Vec<_>>());
+        assert_eq!(solution.in_range(&Integer::from(0), &Integer::from(3)).count, 0);
+        let huge = solution.in_range(&Integer::ZERO, &(Integer::from(1) << 64u32));
+        assert_eq!(huge.count, Integer::from(1) << 61u32);
+        assert!(solution.verify(&g, &h, &n) && !solution.verify(&g, &Integer::from(9), &n));
+        assert_eq!((solution.representative(), solution.solutions_below(&Integer::from(20)).collect::<Vec<_>>()),
+            (&Integer::from(3), vec![Integer::from(3), Integer::from(11), Integer::from(19)]));
+
+        // the gcd of 2^x and 64 keeps growing up to 2^6, so x = 3 is the only solution
+        let single = discrete_log(&Integer::from(2), &Integer::from(8), &Integer::from(64)).unwrap();
+        assert_eq!(single.period, 0);
+        assert_eq!(single.with_congruence(&Integer::from(1), &Integer::from(2)), Some(single.clone()));
+        assert_eq!(single.with_congruence(&Integer::from(0), &Integer::from(2)), None);
+        assert_eq!(single.in_range(&Integer::from(3), &Integer::from(4)).collect::<Vec<_>>(), [Integer::from(3)]);
+    }
+}
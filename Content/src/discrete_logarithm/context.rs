@@ -0,0 +1,319 @@
+use std::{fmt, fs, io, path::Path};
+
+use rug::{ops::{Pow, RemRounding}, Integer};
+
+use super::{baby_steps, discrete_log_mod_2pow, factorize_u64, prime_order_strategy, prime_power_digits, totient_factorization};
+use super::{BsgsTable, DlogError, DlogSolution, DlogStrategy, Trace};
+use crate::montgomery_mod_mult::Context;
+use crate::number_theory::{chinese_remainder_theorem, multiplicative_order_with_factorization};
+
+/// The subgroup of order prime^exponent of the powers of g modulo a prime power.
+#[derive(Clone, Debug)]
+struct Subgroup {
+    prime: u64,
+    exponent: u32,
+    /// The inverse of g^(order / prime^exponent), the generator of the subgroup.
+    generator_inverse: Integer,
+    /// g^(order / prime), which has order prime. Every digit of the exponent is a dlog to this base.
+    base: Integer,
+    /// The baby steps of the base, if its digits are solved with baby-step giant-step (see `prime_order_strategy`).
+    table: Option<BsgsTable>,
+}
+
+/// The powers of g modulo an odd prime power.
+#[derive(Clone, Debug)]
+struct PrimePowerGroup {
+    modulus: Integer,
+    /// The order of g modulo the modulus.
+    order: Integer,
+    ctx: Context,
+    subgroups: Vec<Subgroup>,
+}
+
+/// Precomputed data for solving g^x = h mod n for a fixed g and n, and many h.
+///
+/// `new` does all the work that does not depend on h: it factorizes n and the order of g modulo every prime power
+/// dividing n, finds the generators of the prime order subgroups, and builds the baby-step giant-step tables.
+/// `solve` then only does the Pohlig–Hellman steps for h, with the same solvers as `discrete_log`.
+/// The context can be written out with `to_string` (or `save`) and read back with `parse` (or `load`),
+/// so the setup can be skipped entirely in later runs.
+///
+/// Unlike `discrete_log`, g must be invertible modulo n.
+#[derive(Clone, Debug)]
+pub struct DlogContext {
+    g: Integer,
+    n: Integer,
+    /// The exponent of 2 in n.
+    two_exponent: u32,
+    groups: Vec<PrimePowerGroup>,
+}
+
+impl Subgroup {
+    fn new(prime: u64, exponent: u32, g: &Integer, order: &Integer, ctx: &mut Context) -> Result<Self, DlogError> {
+        let modulus = ctx.n.clone();
+        let cofactor = order / Integer::from(prime).pow(exponent);
+        let generator = ctx.pow_mod_standard(g, &cofactor);
+        let base = ctx.pow_mod_standard(&generator, &Integer::from(prime).pow(exponent - 1));
+        let generator_inverse = generator.invert(&modulus).map_err(|_| DlogError::NotInvertible)?;
+        let table = match prime_order_strategy(prime, &modulus) {
+            DlogStrategy::BabyStepGiantStep => {
+                let (baby_steps, giant_step) = baby_steps(&base, &prime, ctx).ok_or(DlogError::NotInvertible)?;
+                Some(BsgsTable { baby_steps, giant_step })
+            }
+            _ => None,
+        };
+        Ok(Self { prime, exponent, generator_inverse, base, table })
+    }
+
+    /// Solves generator^x = h, where h is in the subgroup, one base prime digit of x at a time.
+    fn solve(&self, h: &Integer, ctx: &mut Context, trace: &mut Trace) -> Result<Integer, DlogError> {
+        let modulus = ctx.n.clone();
+        prime_power_digits(&self.prime, &self.exponent, &modulus, ctx, &self.generator_inverse, &self.base, h,
+            self.table.as_ref(), trace)
+    }
+}
+
+impl PrimePowerGroup {
+    fn new(p: u64, e: u32, g: &Integer) -> Result<Self, DlogError> {
+        let modulus = Integer::from(p).pow(e);
+        let g = Integer::from(g % &modulus);
+        let order = multiplicative_order_with_factorization(&g, &modulus, &totient_factorization(p, e)?)
+            .ok_or(DlogError::NotInvertible)?;
+
+        let mut ctx = Context::new(modulus.clone());
+        let subgroups = factorize_u64(&order)?.into_iter()
+            .map(|(q, f)| Subgroup::new(q, f, &g, &order, &mut ctx))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self { modulus, order, ctx, subgroups })
+    }
+
+    /// Solves g^x = h modulo the prime power. Returns x and the order of g.
+    fn solve(&self, h: &Integer, trace: &mut Trace) -> Result<(Integer, Integer), DlogError> {
+        let h = Integer::from(h % &self.modulus);
+        let mut ctx = self.ctx.clone();
+        // the group is cyclic, so the powers of g are exactly the elements whose order divides that of g
+        if ctx.pow_mod_standard(&h, &self.order) != 1 {
+            return Err(DlogError::NoSolution);
+        }
+        let (mut value, mut ord_value) = (Integer::new(), Integer::from(1));
+        for subgroup in &self.subgroups {
+            let prime_power = Integer::from(subgroup.prime).pow(subgroup.exponent);
+            let h_i = ctx.pow_mod_standard(&h, &Integer::from(&self.order / &prime_power));
+            let x = subgroup.solve(&h_i, &mut ctx, trace)?;
+            (value, ord_value) = chinese_remainder_theorem(value, ord_value, &x, &prime_power).ok_or(DlogError::NoSolution)?;
+        }
+        Ok((value, ord_value))
+    }
+}
+
+impl DlogContext {
+    /// Does the setup for solving g^x = h mod n. n must be positive and g invertible modulo n.
+    /// Fails like `discrete_log` if n or a group order can't be factorized into primes that fit within a u64.
+    pub fn new(g: &Integer, n: &Integer) -> Result<Self, DlogError> {
+        if *n < 1 {
+            return Err(DlogError::NotInvertible);
+        }
+        let g = g.clone().rem_euc(n);
+        if g.clone().gcd(n) != 1 {
+            return Err(DlogError::NotInvertible);
+        }
+        let two_exponent = n.find_one(0).unwrap();
+        let groups = factorize_u64(&Integer::from(n >> two_exponent))?.into_iter()
+            .map(|(p, e)| PrimePowerGroup::new(p, e, &g))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self { g, n: n.clone(), two_exponent, groups })
+    }
+
+    pub fn generator(&self) -> &Integer {
+        &self.g
+    }
+
+    pub fn modulus(&self) -> &Integer {
+        &self.n
+    }
+
+    /// Solves g^x = h mod n, with the same result as `discrete_log(g, h, n)` (without a transcript).
+    pub fn solve(&self, h: &Integer) -> Result<DlogSolution, DlogError> {
+        let h = h.clone().rem_euc(&self.n);
+        if h.clone().gcd(&self.n) != 1 {
+            return Err(DlogError::NoSolution);
+        }
+        let mut trace = Trace { strategies: Vec::new(), transcript: None };
+        let (mut value, mut ord_value) = (Integer::new(), Integer::from(1));
+        if self.two_exponent > 0 {
+            let k = self.two_exponent;
+            (value, ord_value) = discrete_log_mod_2pow(&Integer::from(self.g.keep_bits_ref(k)), &Integer::from(h.keep_bits_ref(k)), k)?;
+            if k >= 2 {
+                trace.strategies.push(DlogStrategy::PowerOfTwo);
+            }
+        }
+        for group in &self.groups {
+            let (result, ord_result) = group.solve(&h, &mut trace)?;
+            (value, ord_value) = chinese_remainder_theorem(value, ord_value, &result, &ord_result).ok_or(DlogError::NoSolution)?;
+        }
+
+        let strategy = match trace.strategies.len() {
+            0 => DlogStrategy::Trivial,
+            1 => trace.strategies[0],
+            _ => DlogStrategy::PohligHellman,
+        };
+        Ok(DlogSolution { exponent: value, period: ord_value, strategy, transcript: None })
+    }
+
+    /// Reads a context in the format written by `to_string`. The baby-step giant-step tables are read as they are,
+    /// so only the shape of the context and the orders of the subgroup generators are checked. Whether a subgroup has
+    /// a table is read from its line, so a context reads back the same whatever the tuning profile.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut lines = text.lines().enumerate()
+            .map(|(number, line)| (number + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'));
+        let mut next = |keyword: &str| -> Result<(usize, Vec<Integer>), String> {
+            let (number, line) = lines.next().ok_or_else(|| format!("expected a `{}` line", keyword))?;
+            let mut words = line.split_whitespace();
+            if words.next() != Some(keyword) {
+                return Err(format!("line {}: expected a `{}` line", number, keyword));
+            }
+            let values = words.map(|word| word.parse::<Integer>().map_err(|_| format!("line {}: invalid number `{}`", number, word)))
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok((number, values))
+        };
+        let single = |(number, values): (usize, Vec<Integer>)| match <[Integer; 1]>::try_from(values) {
+            Ok([value]) => Ok(value),
+            Err(_) => Err(format!("line {}: expected a single number", number)),
+        };
+
+        let g = single(next("g")?)?;
+        let n = single(next("n")?)?;
+        if n < 1 || g < 0 || g >= n {
+            return Err("g must be in [0, n)".to_string());
+        }
+        let two_exponent = n.find_one(0).unwrap();
+        let group_count = single(next("groups")?)?.to_usize().ok_or("invalid number of groups")?;
+
+        let mut groups = Vec::new();
+        let mut remaining = Integer::from(&n >> two_exponent);
+        for _ in 0..group_count {
+            let (number, values) = next("group")?;
+            let [modulus, order, subgroup_count] = <[Integer; 3]>::try_from(values)
+                .map_err(|_| format!("line {}: expected the modulus, the order and the number of subgroups", number))?;
+            if modulus <= 1 || modulus.is_even() || !remaining.is_divisible(&modulus) {
+                return Err(format!("line {}: the modulus must be an odd divisor of n", number));
+            }
+            remaining.div_exact_mut(&modulus);
+            let mut ctx = Context::new(modulus.clone());
+
+            let mut subgroups = Vec::new();
+            for _ in 0..subgroup_count.to_usize().ok_or("invalid number of subgroups")? {
+                let (number, values) = next("subgroup")?;
+                let [prime, exponent, generator_inverse, base, tables] = <[Integer; 5]>::try_from(values)
+                    .map_err(|_| format!("line {}: expected the prime, the exponent, the generator inverse, the base and the number of tables", number))?;
+                let invalid = || format!("line {}: invalid subgroup", number);
+                let (prime, exponent) = (prime.to_u64().ok_or_else(invalid)?, exponent.to_u32().ok_or_else(invalid)?);
+                if prime < 2 || exponent < 1 || base < 0 || base >= modulus || ctx.pow_mod_standard(&base, &Integer::from(prime)) != 1 {
+                    return Err(invalid());
+                }
+                let table = match tables.to_u8() {
+                    Some(0) => None,
+                    Some(1) => {
+                        let (number, mut values) = next("table")?;
+                        if values.len() < 2 {
+                            return Err(format!("line {}: expected the giant step and the baby steps", number));
+                        }
+                        let giant_step = values.remove(0);
+                        let baby_steps = values.into_iter().zip(0..).collect();
+                        Some(BsgsTable { baby_steps, giant_step })
+                    }
+                    _ => return Err(format!("line {}: a subgroup has 0 or 1 tables", number)),
+                };
+                subgroups.push(Subgroup { prime, exponent, generator_inverse, base, table });
+            }
+            groups.push(PrimePowerGroup { modulus, order, ctx, subgroups });
+        }
+        if remaining != 1 {
+            return Err("the group moduli do not multiply to the odd part of n".to_string());
+        }
+        Ok(Self { g, n, two_exponent, groups })
+    }
+
+    /// Reads a context file.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let text = fs::read_to_string(path)?;
+        Self::parse(&text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Writes the context to a file, in the format read by `load`.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        fs::write(path, self.to_string())
+    }
+}
+
+/// One line per item: `g`, `n` and the number of groups, then every group followed by its subgroups. A subgroup line
+/// ends with its number of tables, 1 if it is solved with baby-step giant-step, in which case it is followed by its
+/// table: the giant step, then the baby steps in order.
+impl fmt::Display for DlogContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "g {}", self.g)?;
+        writeln!(f, "n {}", self.n)?;
+        writeln!(f, "groups {}", self.groups.len())?;
+        for group in &self.groups {
+            writeln!(f, "group {} {} {}", group.modulus, group.order, group.subgroups.len())?;
+            for subgroup in &group.subgroups {
+                writeln!(f, "subgroup {} {} {} {} {}", subgroup.prime, subgroup.exponent, subgroup.generator_inverse, subgroup.base,
+                    subgroup.table.is_some() as u32)?;
+                if let Some(table) = &subgroup.table {
+                    let mut steps = vec![&table.giant_step; table.baby_steps.len() + 1];
+                    for (x, &j) in &table.baby_steps {
+                        steps[j as usize + 1] = x;
+                    }
+                    write!(f, "table")?;
+                    for x in steps {
+                        write!(f, " {}", x)?;
+                    }
+                    writeln!(f)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::discrete_logarithm::discrete_log;
+
+    #[test]
+    fn test_context() {
+        // 1_000_000_007 - 1 = 2 * 500000003, so one of the subgroups is solved with Pollard's rho
+        let n = Integer::from(32 * 81 * 49) * 1_000_000_007u64;
+        let g = Integer::from(5);
+        let context = DlogContext::new(&g, &n).unwrap();
+        let parsed = DlogContext::parse(&context.to_string()).unwrap();
+        assert_eq!(parsed.to_string(), context.to_string());
+
+        for x in [0u64, 1, 2, 12345, 987_654_321_987] {
+            let h = Integer::from(g.pow_mod_ref(&Integer::from(x), &n).unwrap());
+            let expected = discrete_log(&g, &h, &n).unwrap();
+            for context in [&context, &parsed] {
+                let solution = context.solve(&h).unwrap();
+                assert_eq!((&solution.exponent, &solution.period), (&expected.exponent, &expected.period), "failed for x = {}", x);
+            }
+        }
+
+        assert_eq!(context.solve(&Integer::from(3)), Err(DlogError::NoSolution));
+        assert_eq!(context.solve(&Integer::from(7)), Err(DlogError::NoSolution));
+        assert_eq!(DlogContext::new(&Integer::from(2), &Integer::from(7)).unwrap().solve(&Integer::from(3)), Err(DlogError::NoSolution));
+        assert!(matches!(DlogContext::new(&Integer::from(6), &n), Err(DlogError::NotInvertible)));
+        assert!(DlogContext::parse("g 5\nn 7\ngroups 1\ngroup 5 1 0\n").is_err());
+
+        // every subgroup line says whether a table follows: 500000003 is above the bsgs limit, 3 is not
+        let text = context.to_string();
+        let subgroups: Vec<&str> = text.lines().filter(|line| line.starts_with("subgroup")).collect();
+        assert!(subgroups.iter().any(|line| line.starts_with("subgroup 500000003 1 ") && line.ends_with(" 0")));
+        assert!(subgroups.iter().any(|line| line.starts_with("subgroup 3 ") && line.ends_with(" 1")));
+        assert!(DlogContext::parse("g 2\nn 7\ngroups 1\ngroup 7 3 1\nsubgroup 3 1 4 2 2\n").is_err());
+        assert!(DlogContext::parse("g 2\nn 7\ngroups 1\ngroup 7 3 1\nsubgroup 3 1 4 2\n").is_err());
+        let parsed = DlogContext::parse("g 2\nn 7\ngroups 1\ngroup 7 3 1\nsubgroup 3 1 4 2 0\n").unwrap();
+        assert_eq!(parsed.solve(&Integer::from(4)).unwrap().exponent, 2);
+    }
+}
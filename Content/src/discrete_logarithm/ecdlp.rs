@@ -0,0 +1,313 @@
+use std::collections::HashMap;
+
+use rug::Integer;
+
+use super::structs::{DlogError, DlogSolution, DlogStrategy};
+use super::{factorize_u64, RHO_RETRIES};
+use crate::number_theory::{chinese_remainder_theorem, is_prime};
+use crate::number_theory::random::random_below_u64;
+use crate::number_theory::word::{add_mod, mul_mod, sub_mod};
+use crate::tuning::profile;
+
+/// The walks of `ec_rho` add one of ADDING_WALK_STEPS precomputed points at each step.
+const ADDING_WALK_STEPS: usize = 20;
+
+/// A point of an `EllipticCurve`: the point at infinity (the identity), or (x, y) with coordinates in [0, p).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum EcPoint {
+    Infinity,
+    Affine(u64, u64),
+}
+
+/// The short Weierstrass curve y^2 = x^3 + ax + b over the prime field F_p, for a prime 3 < p < 2^63 (so that the
+/// order of a point, at most p + 1 + 2 sqrt(p), fits in a u64). Create it with `new`, which checks the parameters.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EllipticCurve {
+    pub a: u64,
+    pub b: u64,
+    pub p: u64,
+}
+
+/// The inverse of a mod p for 0 < a < p, by the extended Euclidean algorithm.
+fn invert_mod(a: u64, p: u64) -> u64 {
+    let (mut r0, mut r1) = (p as i128, a as i128);
+    let (mut t0, mut t1) = (0i128, 1i128);
+    while r1 != 0 {
+        let q = r0 / r1;
+        (r0, r1) = (r1, r0 - q * r1);
+        (t0, t1) = (t1, t0 - q * t1);
+    }
+    t0.rem_euclid(p as i128) as u64
+}
+
+impl EllipticCurve {
+    /// The curve y^2 = x^3 + ax + b over F_p, with a and b reduced modulo p.
+    /// None if p is not a prime with 3 < p < 2^63, or if the curve is singular (4a^3 + 27b^2 = 0 mod p).
+    pub fn new(a: u64, b: u64, p: u64) -> Option<Self> {
+        if p <= 3 || p >= 1 << 63 || !is_prime(&Integer::from(p)) {
+            return None;
+        }
+        let (a, b) = (a % p, b % p);
+        let discriminant = add_mod(mul_mod(4, mul_mod(a, mul_mod(a, a, p), p), p), mul_mod(27, mul_mod(b, b, p), p), p);
+        (discriminant != 0).then_some(Self { a, b, p })
+    }
+
+    /// Whether the point lies on the curve.
+    pub fn contains(&self, point: &EcPoint) -> bool {
+        match *point {
+            EcPoint::Infinity => true,
+            EcPoint::Affine(x, y) => {
+                let p = self.p;
+                let rhs = add_mod(mul_mod(add_mod(mul_mod(x, x, p), self.a, p), x, p), self.b, p);
+                x < p && y < p && mul_mod(y, y, p) == rhs
+            }
+        }
+    }
+
+    pub fn neg(&self, point: &EcPoint) -> EcPoint {
+        match *point {
+            EcPoint::Infinity => EcPoint::Infinity,
+            EcPoint::Affine(x, y) => EcPoint::Affine(x, sub_mod(0, y, self.p)),
+        }
+    }
+
+    pub fn add(&self, s: &EcPoint, t: &EcPoint) -> EcPoint {
+        let ((x1, y1), (x2, y2)) = match (*s, *t) {
+            (EcPoint::Infinity, _) => return *t,
+            (_, EcPoint::Infinity) => return *s,
+            (EcPoint::Affine(x1, y1), EcPoint::Affine(x2, y2)) => ((x1, y1), (x2, y2)),
+        };
+        let p = self.p;
+        let slope = if x1 != x2 {
+            mul_mod(sub_mod(y2, y1, p), invert_mod(sub_mod(x2, x1, p), p), p)
+        } else if y1 == y2 && y1 != 0 {
+            // (3x^2 + a) / 2y
+            mul_mod(add_mod(mul_mod(3, mul_mod(x1, x1, p), p), self.a, p), invert_mod(add_mod(y1, y1, p), p), p)
+        } else {
+            return EcPoint::Infinity;
+        };
+        let x3 = sub_mod(sub_mod(mul_mod(slope, slope, p), x1, p), x2, p);
+        let y3 = sub_mod(mul_mod(slope, sub_mod(x1, x3, p), p), y1, p);
+        EcPoint::Affine(x3, y3)
+    }
+
+    /// k * point, by double-and-add.
+    pub fn mul(&self, k: u64, point: &EcPoint) -> EcPoint {
+        let mut result = EcPoint::Infinity;
+        for i in (0..64 - k.leading_zeros()).rev() {
+            result = self.add(&result, &result);
+            if k >> i & 1 == 1 {
+                result = self.add(&result, point);
+            }
+        }
+        result
+    }
+
+    /// The order of a point of the curve, the smallest n >= 1 with n * point = O. By Hasse's theorem the curve has
+    /// between p + 1 - 2 sqrt(p) and p + 1 + 2 sqrt(p) points, so a multiple of the order is found in that interval
+    /// by baby-step giant-step, in about 2 p^(1/4) steps. It is then factorized (with `prime_factorize`), and the
+    /// primes that don't divide the order are divided out.
+    ///
+    /// # Returns
+    /// * `Ok(order)` - The order of the point.
+    /// * `Err(DlogError::NotOnCurve)` - The point is not on the curve.
+    /// * `Err(DlogError::Unfactored(m))` - The multiple m of the order couldn't be factorized.
+    pub fn order(&self, point: &EcPoint) -> Result<u64, DlogError> {
+        if !self.contains(point) {
+            return Err(DlogError::NotOnCurve);
+        }
+        let multiple = self.order_multiple(point);
+        let mut order = multiple;
+        for (q, e) in factorize_u64(&Integer::from(multiple))? {
+            for _ in 0..e {
+                if self.mul(order / q, point) != EcPoint::Infinity {
+                    break;
+                }
+                order /= q;
+            }
+        }
+        Ok(order)
+    }
+
+    /// A multiple k >= 1 of the order of the point with k * point = O, from the Hasse interval (see `order`).
+    fn order_multiple(&self, point: &EcPoint) -> u64 {
+        let root = self.p.isqrt() + 1;
+        let low = (self.p + 1).saturating_sub(2 * root).max(1);
+        let width = 4 * root + 1;
+        let m = width.isqrt() + 1;
+
+        let mut table = HashMap::with_capacity(m as usize);
+        let mut baby = EcPoint::Infinity;
+        for j in 0..m {
+            table.entry(baby).or_insert(j);
+            baby = self.add(&baby, point);
+        }
+        // baby is now m * point, and (low + im) * point + j * point = O gives the multiple low + im + j
+        let mut giant = self.mul(low, point);
+        for i in 0..=width / m {
+            if let Some(&j) = table.get(&self.neg(&giant)) {
+                return low + i * m + j;
+            }
+            giant = self.add(&giant, &baby);
+        }
+        unreachable!("the curve has a number of points in the Hasse interval")
+    }
+}
+
+/// Finds x with x * g = h for g of prime order q with baby-step giant-step, or None if h is not a multiple of g.
+fn ec_bsgs(curve: &EllipticCurve, g: &EcPoint, h: &EcPoint, q: u64) -> Option<u64> {
+    let m = q.isqrt() + 1;
+    let mut table = HashMap::with_capacity(m as usize);
+    let mut baby = EcPoint::Infinity;
+    for j in 0..m {
+        table.entry(baby).or_insert(j);
+        baby = curve.add(&baby, g);
+    }
+    // h - im * g
+    let giant_step = curve.neg(&baby);
+    let mut y = *h;
+    for i in 0..m {
+        if let Some(&j) = table.get(&y) {
+            return Some((i * m + j) % q);
+        }
+        y = curve.add(&y, &giant_step);
+    }
+    None
+}
+
+/// Finds x with x * g = h for g of prime order q and h a multiple of it, with Pollard's rho: an adding walk
+/// (each step adds one of `ADDING_WALK_STEPS` random a_i * g + b_i * h, picked by the x coordinate) is followed
+/// with Floyd's cycle finding until it meets itself, and the two ways of writing the point as a * g + b * h give x.
+/// Returns None if no collision was found after `RHO_RETRIES` random walks.
+fn ec_rho(curve: &EllipticCurve, g: &EcPoint, h: &EcPoint, q: u64) -> Option<u64> {
+    let random_point = || {
+        let (a, b) = (random_below_u64(q), random_below_u64(q));
+        (curve.add(&curve.mul(a, g), &curve.mul(b, h)), a, b)
+    };
+    let step = |(x, a, b): (EcPoint, u64, u64), steps: &[(EcPoint, u64, u64)]| {
+        let i = match x {
+            EcPoint::Infinity => 0,
+            EcPoint::Affine(x, _) => (x.wrapping_mul(0x9E37_79B9_7F4A_7C15) >> 32) as usize % ADDING_WALK_STEPS,
+        };
+        let (m, c, d) = steps[i];
+        (curve.add(&x, &m), add_mod(a, c, q), add_mod(b, d, q))
+    };
+    let limit = 10 * (q as f64).sqrt() as u64 + 10;
+    for _ in 0..RHO_RETRIES {
+        let steps: Vec<_> = (0..ADDING_WALK_STEPS).map(|_| random_point()).collect();
+        let mut tortoise = random_point();
+        let mut hare = step(tortoise, &steps);
+        for _ in 0..limit {
+            if tortoise.0 == hare.0 {
+                break;
+            }
+            tortoise = step(tortoise, &steps);
+            hare = step(step(hare, &steps), &steps);
+        }
+        // a1 * g + b1 * h = a2 * g + b2 * h, so x = (a2 - a1) / (b1 - b2) mod q
+        let ((x1, a1, b1), (x2, a2, b2)) = (tortoise, hare);
+        if x1 != x2 || b1 == b2 {
+            continue;
+        }
+        let q128 = q as u128;
+        return Some((sub_mod(a2, a1, q) as u128 * invert_mod(sub_mod(b1, b2, q), q) as u128 % q128) as u64);
+    }
+    None
+}
+
+/// Solves x * g = h for g of prime order q, with baby-step giant-step up to the `bsgs_limit` of the tuning profile
+/// and Pollard's rho above it, as `discrete_log` does. The solver that was used is added to strategies.
+fn ec_prime_order_dlog(curve: &EllipticCurve, g: &EcPoint, h: &EcPoint, q: u64, strategies: &mut Vec<DlogStrategy>)
+    -> Result<u64, DlogError> {
+    if *h == EcPoint::Infinity {
+        return Ok(0);
+    }
+    if q <= profile().bsgs_limit {
+        strategies.push(DlogStrategy::BabyStepGiantStep);
+        ec_bsgs(curve, g, h, q).ok_or(DlogError::NoSolution)
+    } else {
+        strategies.push(DlogStrategy::PollardRho);
+        ec_rho(curve, g, h, q).ok_or(DlogError::GaveUp(q))
+    }
+}
+
+/// Solves the elliptic curve discrete logarithm x * g = h on a small curve (see `EllipticCurve`). The order n of g is
+/// found with `EllipticCurve::order`, and factorized, and Pohlig–Hellman splits the problem into one for each prime
+/// power q^e dividing n, solved one base q digit at a time in the subgroup of order q, by baby-step giant-step or
+/// Pollard's rho; the results are merged with CRT. The work is about sqrt(q) curve additions for the largest prime
+/// q of n, so curves whose points have a prime order above 2^40 or so are out of reach, as they should be.
+///
+/// # Returns
+/// * `Ok(solution)` - The smallest x, with the order of g as its period.
+/// * `Err(DlogError::NoSolution)` - h is not a multiple of g.
+/// * `Err(DlogError::NotOnCurve)` - g or h is not on the curve.
+/// * `Err(DlogError::GaveUp(q))` - Pollard's rho ran out of retries in the subgroup of order q.
+pub fn discrete_log_ec(curve: &EllipticCurve, g: &EcPoint, h: &EcPoint) -> Result<DlogSolution, DlogError> {
+    if !curve.contains(h) {
+        return Err(DlogError::NotOnCurve);
+    }
+    let n = curve.order(g)?;
+    if curve.mul(n, h) != EcPoint::Infinity {
+        return Err(DlogError::NoSolution);
+    }
+    let mut strategies = Vec::new();
+    let (mut value, mut period) = (Integer::new(), Integer::from(1));
+    for (q, e) in factorize_u64(&Integer::from(n))? {
+        let prime_power = q.pow(e);
+        let cofactor = n / prime_power;
+        let (g_i, h_i) = (curve.mul(cofactor, g), curve.mul(cofactor, h));
+        // g_i has order q^e, and gamma has order q
+        let gamma = curve.mul(prime_power / q, &g_i);
+        let mut x = 0u64;
+        let mut digit_weight = 1u64;
+        for k in 0..e {
+            let rest = curve.add(&h_i, &curve.neg(&curve.mul(x, &g_i)));
+            let h_k = curve.mul(q.pow(e - 1 - k), &rest);
+            x += ec_prime_order_dlog(curve, &gamma, &h_k, q, &mut strategies)? * digit_weight;
+            digit_weight *= q;
+        }
+        (value, period) = chinese_remainder_theorem(&value, &period, Integer::from(x), Integer::from(prime_power))
+            .ok_or(DlogError::NoSolution)?;
+    }
+    if curve.mul(value.to_u64().unwrap(), g) != *h {
+        return Err(DlogError::NoSolution);
+    }
+
+    let strategy = match strategies.len() {
+        0 => DlogStrategy::Trivial,
+        1 => strategies[0],
+        _ => DlogStrategy::PohligHellman,
+    };
+    Ok(DlogSolution { exponent: value, period, strategy, transcript: None })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_discrete_log_ec() {
+        // y^2 = x^3 + 2x + 3 over F_97, against the orders and multiples found by adding g to itself
+        let curve = EllipticCurve::new(2, 3, 97).unwrap();
+        let g = EcPoint::Affine(3, 6);
+        let multiples: Vec<EcPoint> = std::iter::successors(Some(g), |s| Some(curve.add(s, &g)))
+            .take_while(|s| *s != EcPoint::Infinity).collect();
+        assert_eq!(curve.order(&g), Ok(multiples.len() as u64 + 1));
+        for (x, h) in multiples.iter().enumerate() {
+            let solution = discrete_log_ec(&curve, &g, h).unwrap();
+            assert_eq!(solution.exponent, x + 1);
+        }
+        assert_eq!(discrete_log_ec(&curve, &g, &EcPoint::Affine(3, 7)), Err(DlogError::NotOnCurve));
+        assert!(EllipticCurve::new(0, 0, 97).is_none() && EllipticCurve::new(1, 1, 91).is_none());
+
+        // y^2 = x^3 + 4 over the prime 2^40 - 213, with a point of order 3^2 * 19 * 535823983, whose largest prime goes
+        // through Pollard's rho
+        let curve = EllipticCurve::new(0, 4, (1 << 40) - 213).unwrap();
+        let g = EcPoint::Affine(4, 768_437_105_130);
+        let n = curve.order(&g).unwrap();
+        assert_eq!((n, curve.mul(n, &g)), (91_625_901_093, EcPoint::Infinity));
+        let x = 0x1234_5678_9abc % n;
+        let solution = discrete_log_ec(&curve, &g, &curve.mul(x, &g)).unwrap();
+        assert_eq!((solution.exponent, solution.period), (Integer::from(x), Integer::from(n)));
+    }
+}
@@ -1,41 +1,59 @@
-use crate::number_theory::chinese_remainder_theorem;
-use crate::prime_factorization::prime_factorize;
+use crate::number_theory::{chinese_remainder_theorem, is_prime, multiplicative_order, multiplicative_order_with_factorization};
+use crate::number_theory::order::full_factorization;
+use crate::number_theory::random::random_below_u64;
+use crate::number_theory::roots::prime_power;
+use crate::number_theory::word::{mul_mod, pow_mod};
 use crate::montgomery_mod_mult::Context;
-use rand::Rng;
+use crate::tuning::profile;
 use rug::ops::{Pow, PowAssign};
-use rug::{Integer, Assign};
+use rug::{Assign, Complete, Integer};
+use std::collections::HashMap;
 use std::ops::ShrAssign;
-use num_traits::PrimInt;
-
-/// Computes (b^e) % m for generic integer types that support required operations.
-/// Works with u32, u64, i128, u128, etc.
-fn modpow<T>(mut b: T, mut e: u64, m: T) -> T where T: PrimInt + Copy, {
-    let mut result = T::one();
-    b = b % m;
-    while e > 0 {
-        if e % 2 == 1 {
-            result = result * b % m;
-        }
-        b = b * b % m;
-        e >>= 1;
-    }
 
-    result
-}
+mod bsgs;
+mod constraints;
+mod context;
+pub mod ecdlp;
+mod index_calculus;
+#[cfg(feature = "parallel")]
+mod parallel_rho;
+mod range;
+pub mod structs;
+mod subgroup;
+mod word;
+pub use bsgs::bsgs;
+pub use context::DlogContext;
+pub use ecdlp::{discrete_log_ec, EcPoint, EllipticCurve};
+pub use index_calculus::discrete_log_index_calculus;
+use index_calculus::INDEX_CALCULUS_MIN_ORDER;
+pub use range::{discrete_log_bounded, discrete_log_in_range};
+pub use subgroup::{clear_cofactor, in_subgroup};
+pub use structs::{BsgsBackend, BsgsOptions, DlogCongruence, DlogError, DlogRange, DlogSolution, DlogStrategy, DlogSubproblem, DlogTranscript};
+
+/// The number of times Pollard's rho restarts with a new random walk before giving up.
+const RHO_RETRIES: usize = 64;
 
 /// Converts the a vector of (Integer, u32) into (u64, u32). throws an error if 
 /// one of the values does not fit in a u64.
-fn convert_factors_u64(factors: Vec<(Integer, u32)>) -> Result<Vec<(u64, u32)>, String> {
+fn convert_factors_u64(factors: Vec<(Integer, u32)>) -> Result<Vec<(u64, u32)>, DlogError> {
     factors
         .into_iter()
         .map(|(i, exp)| {
-            i.to_u64()
-                .ok_or_else(|| format!("the prime factor {} is too large for u64", i))
-                .map(|val| (val, exp))
+            match i.to_u64() {
+                Some(val) => Ok((val, exp)),
+                None => Err(DlogError::FactorTooLarge(i)),
+            }
         })
         .collect()
 }
 
+/// Prime factorizes n, and converts the factors into (u64, u32). A cofactor that `prime_factorize` leaves over is
+/// taken as a prime if it is one, as in `number_theory::order`.
+/// Throws an error if the factorization is incomplete, or if one of the factors does not fit in a u64.
+fn factorize_u64(n: &Integer) -> Result<Vec<(u64, u32)>, DlogError> {
+    convert_factors_u64(full_factorization(n).map_err(DlogError::Unfactored)?)
+}
+
 /// Calculates the product of the prime factors of n, given as a vector of (p, e) tuples.
 fn product(factorization: &Vec<(u64, u32)>) -> Integer {
     let mut result = Integer::ONE.clone();
@@ -45,27 +63,13 @@ fn product(factorization: &Vec<(u64, u32)>) -> Integer {
     result
 }
 
-/// Calculates the order of an element g in (Z/nZ)*
-/// Pass it the factors of phi_n, NOT n.
-fn find_order(n: &Integer, phi_n: &Integer, g: &Integer, factors: &Vec<(u64, u32)>) -> Integer {
-    let mut ord = phi_n.clone();
-    let g_inv = Integer::from(g.invert_ref(&n).unwrap());
-
-    let mut p_pow = Integer::new();
-    let mut g_inv_pow = Integer::new();
-    let mut phi_n_over_p_pow = Integer::new();
-    for (p, e) in factors {
-        p_pow.assign(p);
-        for _ in 0..*e {
-            phi_n_over_p_pow.assign(phi_n / &p_pow);
-            g_inv_pow.assign(g_inv.pow_mod_ref(&phi_n_over_p_pow, &n).unwrap());
-            if g_inv_pow == 1 {
-                p_pow *= p;
-                ord /= p;
-            }
-        }
+/// The prime factorization of phi(p^e) = p^(e-1) * (p - 1), a multiple of the order of every element modulo p^e.
+fn totient_factorization(p: u64, e: u32) -> Result<Vec<(Integer, u32)>, DlogError> {
+    let mut factors = full_factorization(&Integer::from(p - 1)).map_err(DlogError::Unfactored)?;
+    if e > 1 {
+        factors.push((Integer::from(p), e - 1));
     }
-    ord
+    Ok(factors)
 }
 
 /// Updates a single step of Pollard's rho for discrete log.
@@ -93,16 +97,17 @@ fn update(x: &mut Integer, a: &mut u64, b: &mut u64, g: &Integer, h: &Integer, p
 
 /// Finds x such that g^x = h mod n, assuming a solution exists.
 /// Also assumes g has order p, and h is in the subgroup generated by g.
-fn pollard_rho_dlog(g: &Integer, h: &Integer, p: &u64, n: &Integer, ctx: &mut Context) -> u64 {
+/// Returns None if no collision was found after RHO_RETRIES random walks.
+fn pollard_rho_dlog(g: &Integer, h: &Integer, p: &u64, n: &Integer, ctx: &mut Context) -> Option<u64> {
     // println!("pollard's on g = {}, h = {}, n = {} and group order is {}", g, h, n, p);
     if g == h {
-        return 1;
+        return Some(1);
     }
     if h == Integer::ONE || *p == 2 {
-        return 0;
+        return Some(0);
     }
     if *p == 3 {
-       return 2;
+       return Some(2);
     }
 
     let mut g_m = ctx.to_montgomery(g);
@@ -111,9 +116,8 @@ fn pollard_rho_dlog(g: &Integer, h: &Integer, p: &u64, n: &Integer, ctx: &mut Co
     let limit  = 10 * (*p as f64).sqrt() as i128;
     let mut res_a: i128 = 1;
     let mut res_b: i128 = 0;
-    let mut rng = rand::rng();
 
-    loop {
+    for _ in 0..RHO_RETRIES {
         let mut x1 = starting_val.clone();
         let mut a1: u64 = 0;
         let mut b1: u64 = 0;
@@ -139,20 +143,20 @@ fn pollard_rho_dlog(g: &Integer, h: &Integer, p: &u64, n: &Integer, ctx: &mut Co
         b += *p as i128 * (b <  0) as i128;
         if b == 0 || x1 != x2 {
             //println!("bruh.");
-            res_a = rng.random_range(1..*p) as i128;
-            res_b = rng.random_range(0..*p) as i128;
-            g_m.assign(g.pow_mod_ref(&Integer::from(res_a), &n).unwrap());
-            h_m.assign(g.pow_mod_ref(&Integer::from(res_b), &n).unwrap());
+            res_a = (random_below_u64(*p - 1) + 1) as i128;
+            res_b = random_below_u64(*p) as i128;
+            g_m.assign(ctx.pow_mod_standard(g, &Integer::from(res_a)));
+            h_m.assign(ctx.pow_mod_standard(g, &Integer::from(res_b)));
             h_m *= h;
             h_m %= n;
             if g_m == h_m {
                 res_a -= res_b;
                 res_a += *p as i128 * (res_a < 0) as i128;
-                return res_a as u64;
+                return Some(res_a as u64);
             }
             if h_m == *Integer::ONE {
                 res_b = *p as i128 * (res_b != 0) as i128 - res_b;
-                return res_b as u64;
+                return Some(res_b as u64);
             }
     
             ctx.to_montgomery_mut(&mut g_m);
@@ -162,92 +166,246 @@ fn pollard_rho_dlog(g: &Integer, h: &Integer, p: &u64, n: &Integer, ctx: &mut Co
 
         let mut a = a1 as i128 - a2 as i128;
         a += *p as i128 * (a < 0) as i128;
-        a *= modpow(b, p - 2, *p as i128);
-        a %= *p as i128;
+        let a = mul_mod(a as u64, pow_mod(b as u64, p - 2, *p), *p) as i128;
 
         res_a *= a;
         res_a %= *p as i128;
         res_a -= res_b;
         res_a += *p as i128 * (res_a < 0) as i128;
-        return res_a as u64;
+        return Some(res_a as u64);
     }
+
+    None
 }
 
-/// Calculates the discrete logarithm modulo 2^n.
-fn discrete_log_mod_2pow(g: &Integer, h: &Integer, n: u32) -> Option<Integer> {
-    // println!("discrete log prime power mod 2pow on g = {}, h = {}, n = 2^{}", g, h, n);
-    if n == 1 {
-        return Some(Integer::ONE.clone());
-    }
-    if n == 2 {
-        if g == Integer::ONE {
-            if h == Integer::ONE {
-                return Some(Integer::ONE.clone());
-            } else {
-                return None;
-            }
-        } else {
-            return Some(Integer::ONE.clone());
-        }
+/// g, h and n as machine words, when n < 2^64 (g and h are then reduced modulo n), for the solvers of `word`.
+fn as_words(g: &Integer, h: &Integer, n: &Integer) -> Option<(u64, u64, u64)> {
+    Some((g.to_u64()?, h.to_u64()?, n.to_u64()?))
+}
+
+/// Pollard's rho for a subgroup of prime order p. When n fits in a u64, a single walk runs on machine words (see
+/// `word::rho_u64`). Otherwise, with the `parallel` feature and more than one core, every core runs walks of the
+/// parallel collision search of `parallel_rho_dlog`, and without it `pollard_rho_dlog` runs a single walk.
+fn rho_dlog(g: &Integer, h: &Integer, p: &u64, n: &Integer, ctx: &mut Context) -> Option<u64> {
+    if let Some((g, h, n)) = as_words(g, h, n) {
+        return word::rho_u64(g, h, *p, n);
     }
-    if h == Integer::ONE {
-        return Some(Integer::ZERO);
+    #[cfg(feature = "parallel")]
+    if let Some(threads) = std::thread::available_parallelism().ok().map(|threads| threads.get()).filter(|&t| t > 1) {
+        return parallel_rho::parallel_rho_dlog(g, h, p, n, threads);
     }
-    if g == h {
-        return Some(Integer::ONE.clone());
+    pollard_rho_dlog(g, h, p, n, ctx)
+}
+
+/// Reduces x (in montgomery form, in [0, 2n)) to [0, n), so that equal group elements compare equal.
+fn canonical(x: &Integer, n: &Integer) -> Integer {
+    if x >= n {
+        Integer::from(x - n)
+    } else {
+        x.clone()
     }
+}
 
-    let m = Integer::from(2).pow(&n);
-    let g_is_quad_res = g.is_congruent_2pow(Integer::ONE, 3);
-    let h_is_quad_res = h.is_congruent_2pow(Integer::ONE, 3);
+/// The baby steps of baby-step giant-step for g of order p: a table of g^j for 0 <= j < m, where m = ceil(sqrt(p)),
+/// keyed by their canonical montgomery form, and the giant step g^(-m) in montgomery form.
+/// Returns None if g is not invertible.
+fn baby_steps(g: &Integer, p: &u64, ctx: &mut Context) -> Option<(HashMap<Integer, u64>, Integer)> {
+    let m = (*p as f64).sqrt().ceil() as u64;
+    let mut table: HashMap<Integer, u64> = HashMap::with_capacity(m as usize);
+
+    let g_m = ctx.to_montgomery(g);
+    let mut x = ctx.one();
+    for j in 0..m {
+        table.entry(canonical(&x, &ctx.n)).or_insert(j);
+        x *= ctx.wrap(&g_m);
+    }
+    // x is now g^m
+    ctx.invert_mut(&mut x)?;
+    Some((table, x))
+}
 
-    if g_is_quad_res {
-        if h_is_quad_res {
-            return match discrete_log_prime_power_mod(&2, &n, &m, g, h) {
-                Some((val1, _)) => Some(val1),
-                None => None,
-            };
-        } else {
-            return None;
+/// The giant steps of baby-step giant-step: compares h * g^(-im) against the baby steps of g (see `baby_steps`).
+/// Returns None if h turns out not to be in the subgroup generated by g.
+fn giant_steps(table: &HashMap<Integer, u64>, giant_step: &Integer, h: &Integer, p: &u64, ctx: &mut Context) -> Option<u64> {
+    let m = table.len() as u64;
+    let mut y = ctx.to_montgomery(h);
+    for i in 0..m {
+        if let Some(j) = table.get(&canonical(&y, &ctx.n)) {
+            return Some((i * m + j) % p);
         }
+        y *= ctx.wrap(giant_step);
+    }
+    None
+}
+
+/// Finds x such that g^x = h mod n with baby-step giant-step, assuming g has order p
+/// and h is in the subgroup generated by g.
+/// Returns None if h turns out not to be in the subgroup.
+fn baby_step_giant_step(g: &Integer, h: &Integer, p: &u64, ctx: &mut Context) -> Option<u64> {
+    let (table, giant_step) = baby_steps(g, p, ctx)?;
+    giant_steps(&table, &giant_step, h, p, ctx)
+}
+
+/// The baby steps of a generator of prime order (see `baby_steps`), kept to solve many targets against it.
+#[derive(Clone, Debug)]
+struct BsgsTable {
+    baby_steps: HashMap<Integer, u64>,
+    giant_step: Integer,
+}
+
+/// What `discrete_log` records while solving: the solvers that were used, and the transcript if it was asked for.
+struct Trace {
+    strategies: Vec<DlogStrategy>,
+    transcript: Option<DlogTranscript>,
+}
+
+/// Solves g^x = h mod n = p^k in the subgroup of order p (k >= 2). Its elements are 1 + a * p^(k-1), and
+/// (1 + a * p^(k-1))^x = 1 + a * x * p^(k-1) mod p^k, so x = b / a mod p for h = 1 + b * p^(k-1).
+/// This takes a single division, however large p is. Returns None if h is not in the subgroup.
+fn p_adic_dlog(g: &Integer, h: &Integer, p: &u64, n: &Integer) -> Option<u64> {
+    let step = Integer::from(n / *p);
+    let a = Integer::from(g - 1u32);
+    let b = Integer::from(h - 1u32);
+    if !a.is_divisible(&step) || !b.is_divisible(&step) {
+        return None;
+    }
+    let p = Integer::from(*p);
+    let a = a.div_exact(&step).invert(&p).ok()?;
+    (b.div_exact(&step) * a % &p).to_u64()
+}
+
+/// The solver for a subgroup of prime order p modulo n, picked by the size of p.
+/// When n is a power of p, the subgroup is solved by p-adic lifting (see `p_adic_dlog`). When n is a prime and p is
+/// above `INDEX_CALCULUS_MIN_ORDER` and divides n - 1 once, it is solved with index calculus, whose running time
+/// depends on the size of n rather than on p. Otherwise, subgroups of order up to the `bsgs_limit` of the tuning
+/// profile are solved with baby-step giant-step, which is deterministic and has no retries but stores sqrt(p) group
+/// elements. Larger ones use Pollard's rho.
+fn prime_order_strategy(p: u64, n: &Integer) -> DlogStrategy {
+    let prime = Integer::from(p);
+    if n.is_divisible(&prime) && n.remove_factor_ref(&prime).complete().0 == 1 {
+        DlogStrategy::PAdicLifting
+    } else if p > INDEX_CALCULUS_MIN_ORDER && is_prime(n) && !Integer::from(n - 1u32).is_divisible(&prime.square()) {
+        DlogStrategy::IndexCalculus
+    } else if p <= profile().bsgs_limit {
+        DlogStrategy::BabyStepGiantStep
     } else {
-        if h_is_quad_res {
-            return match discrete_log_prime_power_mod(&2, &n, &m,
-                &g.clone().square().keep_bits(n), h) {
-                None => None,
-                Some(val) => Some(2 * val.0),
-            };
-        } else {
-            if h.is_congruent_2pow(&g, 3) {
-                let new_h = Integer::from(g * h).keep_bits(n);
-                let mut new_g = g.clone().square().keep_bits(n);
-                if new_h == *Integer::ONE {
-                    let mut ord = 1u32;
-                    while new_g != *Integer::ONE {
-                        new_g.square_mut();
-                        new_g.keep_bits_mut(n);
-                        ord *= 2;
-                    }
-                    return Some(Integer::from(2 * ord - 1)); 
-                }
-                return match discrete_log_prime_power_mod(&2, &n, &m, &new_g, &new_h) {
-                    Some((val1, _)) => Some(2 * val1 - 1),
-                    None => None,
-                };
-            } else {
-                return None;
-            }
+        DlogStrategy::PollardRho
+    }
+}
+
+/// Solves g^x = h mod n in a subgroup of prime order p, with the solver of `prime_order_strategy`, or with
+/// baby-step giant-step against table when the baby steps of g are given.
+/// Baby-step giant-step and Pollard's rho run on machine words when n fits in a u64 (see `word`), which is several
+/// times faster than `Integer`s.
+/// The solver that was used (and the subproblem, if a transcript is kept) is recorded in trace.
+fn prime_order_dlog(g: &Integer, h: &Integer, p: &u64, n: &Integer, ctx: &mut Context, table: Option<&BsgsTable>,
+    trace: &mut Trace) -> Option<u64> {
+    let strategy = match table {
+        Some(_) => DlogStrategy::BabyStepGiantStep,
+        None => prime_order_strategy(*p, n),
+    };
+    trace.strategies.push(strategy);
+    let x = match strategy {
+        DlogStrategy::PAdicLifting => p_adic_dlog(g, h, p, n),
+        DlogStrategy::IndexCalculus => index_calculus::index_calculus(g, h, n, &Integer::from(*p)).ok()?.to_u64(),
+        DlogStrategy::BabyStepGiantStep => match (table, as_words(g, h, n)) {
+            (Some(table), _) => giant_steps(&table.baby_steps, &table.giant_step, h, p, ctx),
+            (None, Some((g, h, n))) => word::bsgs_u64(g, h, *p, n),
+            (None, None) => baby_step_giant_step(g, h, p, ctx),
+        },
+        _ => rho_dlog(g, h, p, n, ctx),
+    }?;
+    if let Some(transcript) = &mut trace.transcript {
+        transcript.subproblems.push(DlogSubproblem { modulus: n.clone(), order: *p, generator: g.clone(),
+            target: h.clone(), exponent: x });
+    }
+    Some(x)
+}
+
+/// Writes an odd x as (-1)^a * 5^b mod 2^k, using that (Z/2^kZ)* is the product of {1, -1} and the cyclic group
+/// generated by 5, of order 2^(k-2), for k >= 3. Returns (a, b) with b < 2^(k-2), or b = 0 when k <= 2.
+fn split_mod_2pow(x: &Integer, k: u32) -> (bool, Integer) {
+    // -1 = 1 mod 2, so the sign only shows up from k = 2 on
+    let negative = k >= 2 && x.get_bit(1);
+    let mut t = Integer::from(x.keep_bits_ref(k));
+    if negative {
+        t = (-t).keep_bits(k);
+    }
+    let mut b = Integer::new();
+    if k <= 2 {
+        return (negative, b);
+    }
+
+    // 5^(2^j) = 1 + 2^(j+2) mod 2^(j+3), so b is read off one bit at a time from the bottom,
+    // keeping t = (-1)^a * x * 5^-b = 1 mod 2^(j+2)
+    let mut five_inverse = Integer::from(5).invert(&(Integer::from(1) << k)).unwrap();  // 5^-(2^j)
+    for j in 0..k - 2 {
+        if t.get_bit(j + 2) {
+            b.set_bit(j, true);
+            t *= &five_inverse;
+            t.keep_bits_mut(k);
+        }
+        five_inverse.square_mut();
+        five_inverse.keep_bits_mut(k);
+    }
+    (negative, b)
+}
+
+/// Calculates the discrete logarithm modulo 2^k, for odd g and h.
+/// (Z/2^kZ)* is not cyclic for k >= 3, so instead of Pohlig–Hellman, g and h are split into their -1 and 5-power
+/// components (see split_mod_2pow), and the congruences of the two components are merged.
+/// Returns the solution in [0, order of g), and the order of g.
+fn discrete_log_mod_2pow(g: &Integer, h: &Integer, k: u32) -> Result<(Integer, Integer), DlogError> {
+    let (g_negative, g_exponent) = split_mod_2pow(g, k);
+    let (h_negative, h_exponent) = split_mod_2pow(h, k);
+
+    // the -1 components: x = a_h mod 2 if g has one, otherwise h can't have one either
+    let (x, order) = match (g_negative, h_negative) {
+        (true, _) => (Integer::from(h_negative as u32), Integer::from(2)),
+        (false, false) => (Integer::ZERO, Integer::ONE.clone()),
+        (false, true) => return Err(DlogError::NoSolution),
+    };
+
+    // the 5-power components: b_g * x = b_h mod 2^(k-2). With b_g = 2^t * u for an odd u, 5^b_g has order
+    // 2^(k-2-t), and a solution needs 2^t | b_h.
+    let Some(t) = g_exponent.find_one(0) else {
+        if h_exponent != 0 {
+            return Err(DlogError::NoSolution);
         }
+        return Ok((x, order));
+    };
+    if h_exponent.find_one(0).is_some_and(|s| s < t) {
+        return Err(DlogError::NoSolution);
     }
+    let bits = k - 2 - t;
+    let modulus = Integer::from(1) << bits;
+    let mut residue = (h_exponent >> t) * (g_exponent >> t).invert(&modulus).unwrap();
+    residue.keep_bits_mut(bits);
+
+    let (mut x, order) = chinese_remainder_theorem(x, order, &residue, &modulus).ok_or(DlogError::NoSolution)?;
+    x %= &order;
+    Ok((x, order))
 }
 
 /// Solves for the discrete logarithm mod m where m is a power of an odd prime (to guarantee its a cyclic group)
 /// And with the additional assumption that the order of g is p^e and a solution exists.
-fn discrete_log_prime_power_order(p: &u64, e: &u32, m: &Integer, ctx: &mut Context, mut g: Integer, h: &Integer) -> Integer {
+fn discrete_log_prime_power_order(p: &u64, e: &u32, m: &Integer, ctx: &mut Context, mut g: Integer, h: &Integer,
+    trace: &mut Trace) -> Result<Integer, DlogError> {
     // println!("discrete log prime power order on g = {}, h = {}, n = {} and group order is {}^{}", g, h, m, p, e);
     if g == *h {
-        return Integer::ONE.clone();
+        return Ok(Integer::ONE.clone());
     }
+    let gamma = ctx.pow_mod_standard(&g, &Integer::from(*p).pow(e - 1));
+    g.invert_mut(m).unwrap();  // g = g^-1
+    prime_power_digits(p, e, m, ctx, &g, &gamma, h, None, trace)
+}
+
+/// Solves g^x = h mod m, where g has order p^e, one base p digit of x at a time: the digit k of x is the dlog of
+/// (h * g^-x)^(p^(e - 1 - k)) to the base gamma = g^(p^(e - 1)), of order p, found by `prime_order_dlog` (with the
+/// baby steps of gamma in table, if given). Takes the inverse of g.
+#[allow(clippy::too_many_arguments)]
+fn prime_power_digits(p: &u64, e: &u32, m: &Integer, ctx: &mut Context, g_inverse: &Integer, gamma: &Integer,
+    h: &Integer, table: Option<&BsgsTable>, trace: &mut Trace) -> Result<Integer, DlogError> {
     let mut x: Integer = Integer::ZERO.clone();
     let mut p_pow: Vec<Integer> = vec![Integer::from(*p); (*e + 1) as usize];
     p_pow[0].assign(1);
@@ -259,50 +417,40 @@ fn discrete_log_prime_power_order(p: &u64, e: &u32, m: &Integer, ctx: &mut Conte
         p_pow_i.assign(&*p_pow_1 * &*p_pow_i_1);
     }
 
-    //println!("hi");
     let e_minus_1: usize = (e - 1) as usize;
-    let gamma = Integer::from(g.pow_mod_ref(&p_pow[e_minus_1], &m).unwrap());
-    g.invert_mut(&m).unwrap();  // g = g^-1
-    
-    //println!("gamma: {}, g^-1: {}", gamma, g);
+    let g_inverse = ctx.to_montgomery(g_inverse);
+    let h = ctx.to_montgomery(h);
     let mut h_k = Integer::new();
     for k in 0..*e as usize {
-        h_k.assign(g.pow_mod_ref(&x, &m).unwrap());
-        h_k *= h;
-        h_k.pow_mod_mut(&p_pow[e_minus_1 - k], &m).unwrap();
-        //println!("h_k: {}", h_k);
-        //println!("result: {}, multiplied with {}", pollard_rho_dlog(&gamma, &h_k, &p, &m, &ctx), p_pow[k]);
-        x += Integer::from(pollard_rho_dlog(&gamma, &h_k, &p, &m, ctx)) * &p_pow[k];
+        // in Montgomery form, (g^-x * h)^(p^(e - 1 - k))
+        h_k.assign(ctx.pow_mod(&g_inverse, &x));
+        ctx.mul_assign(&mut h_k, &h);
+        h_k = ctx.pow_mod(&h_k, &p_pow[e_minus_1 - k]);
+        ctx.from_montgomery_mut(&mut h_k);
+        let x_k = prime_order_dlog(gamma, &h_k, p, m, ctx, table, trace).ok_or(DlogError::GaveUp(*p))?;
+        x += Integer::from(x_k) * &p_pow[k];
         x %= Integer::from(&p_pow[*e as usize]);
-        //println!("x: {}", x);
     }
-    // println!("result for: {}^x = {} mod {}: {}", g.invert(m).unwrap(), h, m, x);
-    x 
+    Ok(x)
 }
 
 /// calculates the discrete logarithm: i.e given g, h and m, finds x such that g^x = h mod m, where m = p^e
 /// Assumes 0 < g, h < m, and that the primes in the prime factorization of phi(m) (euler's totient) fits within a u64
-fn discrete_log_prime_power_mod(p: &u64, e: &u32, m: &Integer, g: &Integer, h: &Integer) -> Option<(Integer, Integer)> {
+fn discrete_log_prime_power_mod(p: &u64, e: &u32, m: &Integer, g: &Integer, h: &Integer,
+    trace: &mut Trace) -> Result<(Integer, Integer), DlogError> {
     // println!("discrete log prime power mod on g = {}, h = {}, n = {} (which is {}^{})", g, h, m, p, e);
-    let mut factors: Vec<(u64, u32)> = Vec::new();
-    factors.extend(convert_factors_u64(prime_factorize(&Integer::from(p - 1))).unwrap());
-    if *e != 1 {
-        factors.push((*p, e - 1)); // n = p^(e-1) * (p - 1), so its prime factors are the prime factors of p-1 and p^(e-1)
-    }
-    let phi_m_factors = &factors;
-
-    let phi_m = product(phi_m_factors);
-    //println!("phi({}) = {}", m, phi_m);
-
-    let ord_g = find_order(&m, &phi_m,  &g, phi_m_factors);
+    let phi_m_factors = totient_factorization(*p, *e)?;
+    let ord_g = multiplicative_order_with_factorization(g, m, &phi_m_factors).ok_or(DlogError::NotInvertible)?;
+    let mut ctx = Context::new(m.clone());
 
     if g == h {
-        return Some((Integer::ONE.clone(), ord_g));
+        return Ok((Integer::ONE.clone(), ord_g));
     }
     
     // Check if the order of h divides that of g, otherwise a solution does not exist
-    if !ord_g.is_divisible(&find_order(&m, &phi_m, &h, &phi_m_factors)) {
-        return None;
+    match multiplicative_order_with_factorization(h, m, &phi_m_factors) {
+        Some(ord_h) if ord_g.is_divisible(&ord_h) => {}
+        _ => return Err(DlogError::NoSolution),
     }
 
     // println!("order of {} mod {}^{}: {}", g, p, e, ord_g);
@@ -310,12 +458,7 @@ fn discrete_log_prime_power_mod(p: &u64, e: &u32, m: &Integer, g: &Integer, h: &
     // Break down the Discrete log problem to groups of order p^e because we love cyclic groups
     // Then merge the results using Chinese Remainder Theorem
 
-    factors.clear();
-    //println!("factorizing {}", ord_g);
-    factors.extend(convert_factors_u64(prime_factorize(&ord_g)).unwrap());
-    //println!("prime factors of {}: {:?}", ord_g, factors);
-    let ord_g_factors = &factors;
-    let mut ctx = Context::new(m.clone());
+    let ord_g_factors = &factorize_u64(&ord_g)?;
     let mut value: Integer = Integer::ZERO.clone();
     let mut ord_value = Integer::ONE.clone();
     
@@ -328,17 +471,17 @@ fn discrete_log_prime_power_mod(p: &u64, e: &u32, m: &Integer, g: &Integer, h: &
         m_i.pow_assign(e);
         let mut exponent = ord_g.clone();
         exponent.div_exact_mut(&m_i);
-        g_i.assign(g.pow_mod_ref(&exponent, &m).unwrap());
-        h_i.assign(h.pow_mod_ref(&exponent, &m).unwrap());
+        g_i.assign(ctx.pow_mod_standard(g, &exponent));
+        h_i.assign(ctx.pow_mod_standard(h, &exponent));
         (value, ord_value) = match chinese_remainder_theorem(&value, &ord_value,
-        &discrete_log_prime_power_order(&p, &e, &m, &mut ctx, g_i.clone(), &h_i), &m_i) {
+        &discrete_log_prime_power_order(p, e, m, &mut ctx, g_i.clone(), &h_i, trace)?, &m_i) {
             Some((val1, val2)) => (val1, val2),
-            None => return None,
+            None => return Err(DlogError::NoSolution),
         };
         // println!("prime pow mod result so far: {}^{}, value: {}, order: {}", p, e, value, ord_value);
     }
     // println!("prime pow mod full result: {}^{} = {} mod {}, order {}", g, value, h, m, ord_value);
-    Some((value, ord_value))
+    Ok((value, ord_value))
 }
 
 /// calculates the discrete logarithm: i.e given g, h and n, finds x such that g^x = h mod n.
@@ -346,15 +489,130 @@ fn discrete_log_prime_power_mod(p: &u64, e: &u32, m: &Integer, g: &Integer, h: &
 /// Assumptions: g, h, n positive integers, and that the primes in the prime factorization of
 /// phi(n) (euler's totient) fits within a u64.
 /// (the algorithm would take forever if one of them is greater than a u64 either way)
-pub fn discrete_log(mut g: Integer, mut h: Integer, mut n: Integer) -> Option<(Integer, Integer)> {
+/// 
+/// Returns `DlogError::NoSolution` only when a solution provably does not exist,
+/// the other errors mean the algorithm gave up (see `DlogError`).
+/// 
+/// The problem is split along the structure of n: the part of n that shares factors with g is handled by
+/// direct search, the rest is split into prime power moduli. Each of those is reduced (Pohlig–Hellman)
+/// to subgroups of prime order p, which are solved with baby-step giant-step when p is small, index calculus when
+/// p is a large prime dividing n - 1 for a prime n, and Pollard's rho otherwise. The strategy that was used is
+/// reported in the solution.
+pub fn discrete_log(g: &Integer, h: &Integer, n: &Integer) -> Result<DlogSolution, DlogError> {
+    discrete_log_traced(g.clone(), h.clone(), n.clone(), false)
+}
+
+/// Same as `discrete_log`, taking ownership of g, h and n, so a caller that is done with them (e.g a pipeline that
+/// computed them for this call) doesn't pay for the clones `discrete_log` makes.
+pub fn discrete_log_owned(g: Integer, h: Integer, n: Integer) -> Result<DlogSolution, DlogError> {
+    discrete_log_traced(g, h, n, false)
+}
+
+/// Same as `discrete_log`, but the solution also carries a `DlogTranscript` with the results of the
+/// Pohlig–Hellman subproblems and the congruences merged by CRT, so it can be checked with `verify_dlog` and stored.
+pub fn discrete_log_with_transcript(g: &Integer, h: &Integer, n: &Integer) -> Result<DlogSolution, DlogError> {
+    discrete_log_traced(g.clone(), h.clone(), n.clone(), true)
+}
+
+/// Same as `discrete_log`, for a caller who already knows the factorization of the order of g, or of a multiple of
+/// it such as φ(n) (e.g the published parameters of a protocol), as (q, e) pairs. Neither n nor the order is
+/// factorized, which is what dominates the runtime of `discrete_log` when n or p - 1 has large primes. The order of g
+/// is narrowed down from the factorization, the powers of 2 in n are split off as in `discrete_log`, and the rest of
+/// n is solved with Pohlig–Hellman as a whole. g has to be invertible modulo n.
+///
+/// # Returns
+/// * `Ok(solution)` - The solution, with the order of g as its period.
+/// * `Err(NoSolution)` - h is not a power of g, as far as its order shows. When (Z/nZ)* is not cyclic, h can have an
+///   order dividing that of g without being one of its powers, which can also end in `GaveUp`.
+/// * `Err(NotInvertible)` - g is not coprime to n.
+/// * `Err(WrongOrder(order))` - g^order != 1 for the product of the factorization.
+/// * `Err(FactorTooLarge(q))` - A prime of the factorization does not fit within a u64.
+pub fn discrete_log_with_order(g: &Integer, h: &Integer, n: &Integer, order_factorization: &[(Integer, u32)])
+    -> Result<DlogSolution, DlogError> {
+    let g = g.modulo_ref(n).complete();
+    let h = h.modulo_ref(n).complete();
+    if g.gcd_ref(n).complete() != 1 {
+        return Err(DlogError::NotInvertible);
+    }
+    if h.gcd_ref(n).complete() != 1 {
+        return Err(DlogError::NoSolution);
+    }
+    let mut trace = Trace { strategies: Vec::new(), transcript: None };
+
+    let two_exponent = n.find_one(0).unwrap_or(0);
+    let m = Integer::from(n >> two_exponent);
+    let (mut value, mut ord_value) = discrete_log_mod_2pow(&Integer::from(g.keep_bits_ref(two_exponent)),
+        &Integer::from(h.keep_bits_ref(two_exponent)), two_exponent)?;
+    if two_exponent >= 2 {
+        trace.strategies.push(DlogStrategy::PowerOfTwo);
+    }
+
+    if m > 1 {
+        // the order of g modulo the odd part divides its order modulo n, so the factorization still covers it
+        let factors = convert_factors_u64(order_factorization.to_vec())?;
+        let multiple = product(&factors);
+        if g.pow_mod_ref(&multiple, n).unwrap().complete() != 1 {
+            return Err(DlogError::WrongOrder(multiple));
+        }
+        // g and h modulo the odd part, where the Montgomery arithmetic of ctx applies
+        let mut ctx = Context::new(m.clone());
+        let (g_m, h_m) = (g.modulo_ref(&m).complete(), h.modulo_ref(&m).complete());
+        let ord_g = multiplicative_order_with_factorization(&g_m, &m, order_factorization)
+            .ok_or_else(|| DlogError::WrongOrder(multiple.clone()))?;
+        if ctx.pow_mod_standard(&h_m, &ord_g) != 1 {
+            return Err(DlogError::NoSolution);
+        }
+
+        for (p, _) in factors {
+            let (_, e) = ord_g.remove_factor_ref(&Integer::from(p)).complete();
+            if e == 0 {
+                continue;
+            }
+            let prime_power = Integer::from(p).pow(e);
+            let exponent = ord_g.clone() / &prime_power;
+            let g_i = ctx.pow_mod_standard(&g_m, &exponent);
+            let h_i = ctx.pow_mod_standard(&h_m, &exponent);
+            let x_i = discrete_log_prime_power_order(&p, &e, &m, &mut ctx, g_i, &h_i, &mut trace)?;
+            (value, ord_value) = chinese_remainder_theorem(&value, &ord_value, x_i, prime_power)
+                .ok_or(DlogError::NoSolution)?;
+        }
+    }
+    if g.pow_mod_ref(&value, n).unwrap().complete() != h {
+        return Err(DlogError::NoSolution);
+    }
+
+    let strategy = match trace.strategies.len() {
+        0 => DlogStrategy::Trivial,
+        1 => trace.strategies[0],
+        _ => DlogStrategy::PohligHellman,
+    };
+    Ok(DlogSolution { exponent: value, period: ord_value, strategy, transcript: None })
+}
+
+fn discrete_log_traced(mut g: Integer, mut h: Integer, mut n: Integer, keep_transcript: bool) -> Result<DlogSolution, DlogError> {
+    let mut trace = Trace {
+        strategies: Vec::new(),
+        transcript: keep_transcript.then(|| DlogTranscript {
+            offset: Integer::ZERO,
+            reduced_modulus: Integer::ONE.clone(),
+            reduced_target: Integer::ZERO,
+            congruences: Vec::new(),
+            subproblems: Vec::new(),
+        }),
+    };
+
     // try to convert g, h into elements of (Z/nZ)*
     g %= &n;
     h %= &n;
     if g.is_zero() && h.is_zero() {
-        return Some((Integer::ONE.clone(), Integer::ONE.clone()));
+        if let Some(transcript) = &mut trace.transcript {
+            transcript.offset.assign(1);
+        }
+        return Ok(DlogSolution { exponent: Integer::ONE.clone(), period: Integer::ONE.clone(), strategy: DlogStrategy::Trivial,
+            transcript: trace.transcript });
     }
     if g.is_zero() {
-        return None;
+        return Err(DlogError::NoSolution);
     }
 
     let mut prev_a = Integer::ONE.clone();
@@ -373,12 +631,29 @@ pub fn discrete_log(mut g: Integer, mut h: Integer, mut n: Integer) -> Option<(I
     }
 
     if prev_gcd != h.clone().gcd(&n) {
-        return None;
+        return Err(DlogError::NoSolution);
     }
     if prev_a == h {
-        let factors = convert_factors_u64(prime_factorize(&n)).unwrap();
-        let ord_g = find_order(&n, &product(&factors), &g, &factors);
-        return Some((k, ord_g));
+        // if the gcd with n still grows after k, the later powers never come back to h, so k is the only solution
+        if prev_gcd < new_gcd {
+            if let Some(transcript) = &mut trace.transcript {
+                transcript.offset.assign(&k);
+            }
+            return Ok(DlogSolution { exponent: k, period: Integer::ZERO, strategy: DlogStrategy::Trivial, transcript: trace.transcript });
+        }
+        // otherwise the powers of g from k on repeat with the order of g modulo the part of n that is coprime to g
+        let mut coprime_part = n.clone();
+        let mut common = Integer::from(coprime_part.gcd_ref(&g));
+        while common != 1 {
+            coprime_part.div_exact_mut(&common);
+            common.assign(coprime_part.gcd_ref(&g));
+        }
+        let ord_g = multiplicative_order(&Integer::from(&g % &coprime_part), &coprime_part)
+            .ok_or_else(|| DlogError::Unfactored(coprime_part.clone()))?;
+        if let Some(transcript) = &mut trace.transcript {
+            transcript.offset.assign(&k);
+        }
+        return Ok(DlogSolution { exponent: k, period: ord_g, strategy: DlogStrategy::Trivial, transcript: trace.transcript });
     }
     n.div_exact_mut(&prev_gcd);
     h.div_exact_mut(&prev_gcd);
@@ -386,6 +661,11 @@ pub fn discrete_log(mut g: Integer, mut h: Integer, mut n: Integer) -> Option<(I
     h *= prev_a.invert(&n).unwrap();
     g %= &n;
     h %= &n;
+    if let Some(transcript) = &mut trace.transcript {
+        transcript.offset.assign(&k);
+        transcript.reduced_modulus.assign(&n);
+        transcript.reduced_target.assign(&h);
+    }
 
     // println!("discrete log on g = {}, h = {}, n = {}", g, h, n);
     // handle n has a factor of a power of 2 case before starting.
@@ -396,25 +676,23 @@ pub fn discrete_log(mut g: Integer, mut h: Integer, mut n: Integer) -> Option<(I
     if n.is_even() {
         let two_exponent: u32 = n.find_one(0).unwrap();
         n.shr_assign(two_exponent);
-        let mut temp_g = Integer::new();
-        temp_g.assign(g.keep_bits_ref(two_exponent));
-        // println!("n is even g {}", temp_g);
-        value = match discrete_log_mod_2pow(&temp_g, 
-        &Integer::from(h.keep_bits_ref(two_exponent)), two_exponent) {
-            Some(val) => val,
-            None => return None,
-        };
-        ord_value = Integer::ONE.clone();
-        while temp_g != *Integer::ONE {
-            temp_g.square_mut();
-            temp_g.keep_bits_mut(two_exponent);
-            ord_value *= 2;
+        (value, ord_value) = discrete_log_mod_2pow(&Integer::from(g.keep_bits_ref(two_exponent)),
+            &Integer::from(h.keep_bits_ref(two_exponent)), two_exponent)?;
+        if two_exponent >= 2 {
+            trace.strategies.push(DlogStrategy::PowerOfTwo);
+        }
+        if let Some(transcript) = &mut trace.transcript {
+            transcript.congruences.push(DlogCongruence { modulus: Integer::from(1) << two_exponent,
+                residue: value.clone(), period: ord_value.clone() });
         }
-        value %= &ord_value;
         // println!("n is even value: {}, order: {}", value, ord_value);
     }
 
-    let factors: Vec<(u64, u32)> = convert_factors_u64(prime_factorize(&n)).unwrap();
+    // a power of an odd prime is routed straight to its prime power solver, without factorizing n
+    let factors: Vec<(u64, u32)> = match prime_power(&n) {
+        Some(power) => convert_factors_u64(vec![power])?,
+        None => factorize_u64(&n)?,
+    };
     //println!("factors of n: {:?}", factors);
     let mut m = Integer::new();
     
@@ -423,19 +701,71 @@ pub fn discrete_log(mut g: Integer, mut h: Integer, mut n: Integer) -> Option<(I
         m.pow_assign(e - 1);
         m *= &p;
 
-        let (result, ord_result) = match discrete_log_prime_power_mod(&p, &e, &m,
-            &Integer::from(&g % &m), &Integer::from(&h % &m)) {
-            Some((val1, val2)) => (val1, val2),
-            None => return None,
-        };
+        let (result, ord_result) = discrete_log_prime_power_mod(&p, &e, &m,
+            &Integer::from(&g % &m), &Integer::from(&h % &m), &mut trace)?;
+        if let Some(transcript) = &mut trace.transcript {
+            transcript.congruences.push(DlogCongruence { modulus: m.clone(), residue: result.clone(), period: ord_result.clone() });
+        }
         (value, ord_value) = match chinese_remainder_theorem(&value, &ord_value, &result, &ord_result) {
             Some((val1, val2)) => (val1, val2),
-            None => return None,
+            None => return Err(DlogError::NoSolution),
         };
         // println!("discrete log value so far: {}, order: {}", value, ord_value);
     }
 
-    Some((value + k, ord_value))
+    // if a single prime order subgroup had to be solved, report its solver directly
+    let strategy = match trace.strategies.len() {
+        0 => DlogStrategy::Trivial,
+        1 => trace.strategies[0],
+        _ => DlogStrategy::PohligHellman,
+    };
+    Ok(DlogSolution { exponent: value + k, period: ord_value, strategy, transcript: trace.transcript })
+}
+
+/// Checks a solution of g^x = h mod n: that g^exponent = h and g^(exponent + period) = h mod n.
+/// If the solution has a transcript, every congruence and subproblem in it is checked as well, along with
+/// the CRT recombination (the exponent agrees with every congruence, and their moduli multiply to the reduced modulus).
+/// This only takes modular exponentiations, so it is cheap compared to solving the problem.
+/// It does not check that the period is the smallest one.
+pub fn verify_dlog(g: &Integer, h: &Integer, n: &Integer, solution: &DlogSolution) -> bool {
+    let holds = |base: &Integer, exponent: &Integer, target: &Integer, modulus: &Integer| {
+        match base.pow_mod_ref(exponent, modulus).map(Integer::from) {
+            Some(power) => {
+                let mut target = Integer::from(target % modulus);
+                if target < 0 {
+                    target += modulus;
+                }
+                power == target
+            }
+            None => false,
+        }
+    };
+
+    let shifted = Integer::from(&solution.exponent + &solution.period);
+    if *n < 1 || solution.exponent < 0 || solution.period < 0
+        || !holds(g, &solution.exponent, h, n) || !holds(g, &shifted, h, n) {
+        return false;
+    }
+    let Some(transcript) = &solution.transcript else {
+        return true;
+    };
+
+    let reduced = Integer::from(&solution.exponent - &transcript.offset);
+    let mut product = Integer::from(1);
+    for congruence in &transcript.congruences {
+        let DlogCongruence { modulus, residue, period } = congruence;
+        if !holds(g, residue, &transcript.reduced_target, modulus) || !holds(g, period, &Integer::from(1), modulus)
+            || !Integer::from(&reduced - residue).is_divisible(period) {
+            return false;
+        }
+        product *= modulus;
+    }
+    product == transcript.reduced_modulus
+        && transcript.subproblems.iter().all(|subproblem| {
+            let DlogSubproblem { modulus, order, generator, target, exponent } = subproblem;
+            holds(generator, &Integer::from(*exponent), target, modulus)
+                && holds(generator, &Integer::from(*order), &Integer::from(1), modulus)
+        })
 }
 
 /* 
@@ -484,4 +814,155 @@ fn main() {
     // println!("Of all the testcases, {} of them failed...", failed);
     */
 
-}*/
\ No newline at end of file
+}*/
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_solution_and_no_solution() {
+        let solution = discrete_log(&Integer::from(2), &Integer::from(8), &Integer::from(17)).unwrap();
+        assert_eq!(solution.exponent, 3);
+        assert_eq!(solution.period, 8);
+        assert_eq!(solution.strategy, DlogStrategy::PohligHellman);
+
+        // 2 has order 3 mod 7, but 3 is a generator of (Z/7Z)*, so 3 is not a power of 2
+        let error = discrete_log(&Integer::from(2), &Integer::from(3), &Integer::from(7)).unwrap_err();
+        assert_eq!(error, DlogError::NoSolution);
+        assert!(error.is_unsolvable() && !error.is_retryable());
+        assert!(DlogError::GaveUp(33_554_771).is_retryable() && !DlogError::Unfactored(Integer::from(91)).is_retryable());
+    }
+
+    #[test]
+    fn test_not_coprime_and_trivial_targets() {
+        // the powers of 2 mod 8 are 2, 4, 0, 0, ...: 4 is reached once, so there is no period
+        let solution = discrete_log(&Integer::from(2), &Integer::from(4), &Integer::from(8)).unwrap();
+        assert_eq!((solution.exponent, solution.period), (Integer::from(2), Integer::from(0)));
+        // the powers of 2 mod 12 are 2, 4, 8, 4, 8, ...: from 4 on, they repeat with the order of 2 mod 3
+        let solution = discrete_log(&Integer::from(2), &Integer::from(4), &Integer::from(12)).unwrap();
+        assert_eq!((solution.exponent, solution.period), (Integer::from(2), Integer::from(2)));
+        // and 1 = 2^0 comes before them, never to come back
+        let solution = discrete_log(&Integer::from(2), &Integer::from(1), &Integer::from(12)).unwrap();
+        assert_eq!((solution.exponent, solution.period), (Integer::from(0), Integer::from(0)));
+
+        // h = 1 is g^0, also modulo 4 where 3 has order 2
+        let solution = discrete_log(&Integer::from(3), &Integer::from(1), &Integer::from(4)).unwrap();
+        assert_eq!((solution.exponent, solution.period), (Integer::from(0), Integer::from(2)));
+        // 3 has order 4 mod 20, and 9 = 1 mod 4 goes through the same case of the 2-part
+        let solution = discrete_log(&Integer::from(3), &Integer::from(9), &Integer::from(20)).unwrap();
+        assert_eq!((solution.exponent, solution.period), (Integer::from(2), Integer::from(4)));
+        let solution = discrete_log(&Integer::from(2), &Integer::from(1), &Integer::from(17)).unwrap();
+        assert_eq!((solution.exponent, solution.period), (Integer::from(0), Integer::from(8)));
+    }
+
+    #[test]
+    fn test_strategy_selection() {
+        // 2 has order 11 mod 23
+        let solution = discrete_log(&Integer::from(2), &Integer::from(13), &Integer::from(23)).unwrap();
+        assert_eq!((solution.exponent, solution.period), (Integer::from(7), Integer::from(11)));
+        assert_eq!(solution.strategy, DlogStrategy::BabyStepGiantStep);
+
+        // 67109543 = 2 * 33554771 + 1 is a safe prime, so 4 has the large prime order 33554771
+        let solution = discrete_log(&Integer::from(4), &Integer::from(62103188), &Integer::from(67109543)).unwrap();
+        assert_eq!((solution.exponent, solution.period), (Integer::from(22792476), Integer::from(33554771)));
+        assert_eq!(solution.strategy, DlogStrategy::PollardRho);
+
+        // 4503599627372423 = 2 * 2251799813686211 + 1 is a safe prime, so 25 has an order above 2^48
+        let p = Integer::from(4_503_599_627_372_423u64);
+        let x = Integer::from(1_234_567_890_123u64);
+        let h = Integer::from(25).pow_mod(&x, &p).unwrap();
+        let solution = discrete_log(&Integer::from(25), &h, &p).unwrap();
+        assert_eq!((solution.exponent, solution.period), (x, Integer::from(2_251_799_813_686_211u64)));
+        assert_eq!(solution.strategy, DlogStrategy::IndexCalculus);
+    }
+
+    #[test]
+    fn test_prime_power_modulus() {
+        // the subgroup of order p = 2^61 - 1 mod p^2 would take about 2^30 steps of Pollard's rho
+        let p = (Integer::from(1) << 61u32) - 1u32;
+        let n = Integer::from(p.square_ref());
+        let x = Integer::from(1) << 100u32;
+        let g = Integer::from(&p * 5u32) + 1u32;
+        let h = Integer::from(g.pow_mod_ref(&x, &n).unwrap());
+        let solution = discrete_log_owned(g, h, n.clone()).unwrap();
+        assert_eq!(solution.strategy, DlogStrategy::PAdicLifting);
+        assert_eq!((solution.exponent, &solution.period), (Integer::from(&x % &p), &p));
+
+        let g = Integer::from(37);
+        let h = Integer::from(g.pow_mod_ref(&x, &n).unwrap());
+        let solution = discrete_log_with_transcript(&g, &h, &n).unwrap();
+        assert!(verify_dlog(&g, &h, &n, &solution));
+        assert_eq!(solution.exponent, x % &solution.period);
+    }
+
+    #[test]
+    fn test_mod_2pow() {
+        // every pair of odd g, h against a search through the powers of g
+        for k in 1..=7u32 {
+            let n = 1u64 << k;
+            for g in (1..n).step_by(2) {
+                for h in (1..n).step_by(2) {
+                    let powers: Vec<u64> = std::iter::successors(Some(1), |&x| Some(x * g % n).filter(|&x| x != 1)).collect();
+                    let expected = powers.iter().position(|&x| x == h % n).map(|x| (Integer::from(x), Integer::from(powers.len())));
+                    let result = discrete_log_mod_2pow(&Integer::from(g), &Integer::from(h), k).ok();
+                    assert_eq!(result, expected, "failed for g = {}, h = {}, n = 2^{}", g, h, k);
+                }
+            }
+        }
+
+        let n = Integer::from(1) << 300u32;
+        let x = Integer::from(Integer::u_pow_u(3, 150)) * 7u32;
+        let h = Integer::from(Integer::from(-3).pow_mod_ref(&x, &n).unwrap());
+        let solution = discrete_log(&Integer::from(-3), &h, &n).unwrap();
+        assert_eq!(solution.strategy, DlogStrategy::PowerOfTwo);
+        assert_eq!(solution.period, Integer::from(1) << 298u32);
+        assert_eq!(solution.exponent, x.keep_bits(298));
+        assert_eq!(discrete_log(&Integer::from(5), &Integer::from(3), &n), Err(DlogError::NoSolution));
+    }
+
+    #[test]
+    fn test_known_order() {
+        // (1e9 + 7) * 998244353 * 2^5, with φ = 2^4 * 2 * 500000003 * 2^23 * 7 * 17, which is never factorized
+        let n = Integer::from(1_000_000_007u64 * 998_244_353) << 5u32;
+        let phi = [(2u32, 28u32), (7, 1), (17, 1), (500_000_003, 1)].map(|(q, e)| (Integer::from(q), e));
+        for (g, x) in [(5u32, 123_456_789_012u64), (3, 987_654_321), (17, 1)] {
+            let g = Integer::from(g);
+            let h = Integer::from(g.pow_mod_ref(&Integer::from(x), &n).unwrap());
+            let solution = discrete_log_with_order(&g, &h, &n, &phi).unwrap();
+            assert_eq!(solution, discrete_log(&g, &h, &n).unwrap(), "failed for g = {}", g);
+            // the period is the order of g, as number_theory::order finds it
+            assert_eq!(Some(&solution.period), multiplicative_order(&g, &n).as_ref());
+            assert!(verify_dlog(&g, &h, &n, &solution));
+        }
+
+        let p = Integer::from(1_000_000_007);
+        let phi = [(Integer::from(2), 1), (Integer::from(500_000_003), 1)];
+        assert_eq!(discrete_log_with_order(&Integer::from(4), &Integer::from(5), &p, &phi), Err(DlogError::NoSolution));
+        assert_eq!(discrete_log_with_order(&Integer::from(5), &Integer::from(3), &p, &phi[..1]),
+            Err(DlogError::WrongOrder(Integer::from(2))));
+        assert_eq!(discrete_log_with_order(&Integer::from(6), &Integer::from(3), &Integer::from(9), &phi),
+            Err(DlogError::NotInvertible));
+    }
+
+    #[test]
+    fn test_transcript() {
+        // odd and even moduli, g not coprime to n, and a generator of order 3 mod 4
+        let cases = [(2u64, 8u64, 17u64), (3, 1, 4), (2, 0, 12), (6, 2, 2 * 9 * 5 * 7), (5, 3 * 5 * 5 * 5 * 5, 2 * 3 * 5 * 5 * 7 * 11 * 13), (7, 12345, 1_000_000_007 * 8)];
+        for (g, x, n) in cases {
+            let (g, n) = (Integer::from(g), Integer::from(n));
+            let h = Integer::from(g.pow_mod_ref(&Integer::from(x), &n).unwrap());
+            let solution = discrete_log_with_transcript(&g, &h, &n).unwrap();
+            assert!(solution.transcript.is_some());
+            assert!(verify_dlog(&g, &h, &n, &solution), "failed for g = {}, h = {}, n = {}", g, h, n);
+
+            let mut wrong = solution.clone();
+            wrong.exponent += 1;
+            assert!(!verify_dlog(&g, &h, &n, &wrong) || wrong.period == 1);
+            if let Some(subproblem) = solution.transcript.as_ref().and_then(|t| t.subproblems.first()) {
+                let mut tampered = solution.clone();
+                tampered.transcript.as_mut().unwrap().subproblems[0].exponent = (subproblem.exponent + 1) % subproblem.order;
+                assert!(!verify_dlog(&g, &h, &n, &tampered));
+            }
+        }
+    }
+}
@@ -0,0 +1,125 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::thread;
+
+use rug::Integer;
+
+use crate::montgomery_mod_mult::Context;
+use crate::number_theory::random::{child_seed, random_below_u64, with_seed};
+use crate::number_theory::word::add_mod;
+
+/// The walks pick one of 2^PARTITION_BITS multipliers at each step.
+const PARTITION_BITS: u32 = 5;
+/// About 2^DISTINGUISHED_POINTS_BITS distinguished points are found before two walks collide.
+const DISTINGUISHED_POINTS_BITS: u32 = 10;
+
+/// A distinguished point g^a * h^b of a walk (canonical montgomery form), with a and b.
+type Point = (Integer, u64, u64);
+
+/// One thread of `parallel_rho_dlog`. Each trail starts at g^a for a random a, and takes the steps
+/// x -> x * g^(a_i) * h^(b_i) of an adding walk, where i comes from a hash of x, until x is distinguished (the bits
+/// of mask are 0), which goes to the server before the next trail starts. A trail 16 times longer than the mean
+/// distance between distinguished points is likely caught in a cycle, and is dropped.
+fn walk(g: &Integer, p: u64, n: &Integer, multipliers: &[Point], mask: u64, server: Sender<Point>,
+    cancel: &AtomicBool) {
+    let mut ctx = Context::new(n.clone());
+    let steps: Vec<Integer> = multipliers.iter().map(|(m, _, _)| ctx.to_montgomery(m)).collect();
+    while !cancel.load(Ordering::Relaxed) {
+        let (mut a, mut b) = (random_below_u64(p), 0);
+        let mut x = ctx.to_montgomery(Integer::from(g.pow_mod_ref(&Integer::from(a), n).unwrap()));
+        for _ in 0..16 * (mask + 1) {
+            if x >= ctx.n {
+                x -= &ctx.n;
+            }
+            let bits = x.to_u64_wrapping();
+            if bits & mask == 0 {
+                // the server has stopped listening once it is done
+                if server.send((x, a, b)).is_err() {
+                    return;
+                }
+                break;
+            }
+            let i = (bits.wrapping_mul(0x9E37_79B9_7F4A_7C15) >> (64 - PARTITION_BITS)) as usize;
+            x *= ctx.wrap(&steps[i]);
+            a = add_mod(a, multipliers[i].1, p);
+            b = add_mod(b, multipliers[i].2, p);
+        }
+    }
+}
+
+/// Finds x such that g^x = h mod n, where g has prime order p and h is in the subgroup it generates, with van
+/// Oorschot and Wiener's parallel collision search. Every thread runs trails of the same adding walk from random
+/// starting points (see `walk`) and sends their distinguished points to this thread, the server, which keeps them in
+/// a table. Two trails that meet follow the same path from there, so they end at the same distinguished point, and
+/// the two ways of writing it as g^a * h^b give x. The walks don't depend on each other, so the speedup is close to
+/// the number of threads, and only a few thousand points are kept.
+/// Returns None if no collision came up among 16 times the expected number of distinguished points.
+pub(super) fn parallel_rho_dlog(g: &Integer, h: &Integer, p: &u64, n: &Integer, threads: usize) -> Option<u64> {
+    if *h == 1 {
+        return Some(0);
+    }
+    if g == h {
+        return Some(1);
+    }
+    let p = *p;
+    let multipliers: Vec<Point> = (0..1 << PARTITION_BITS)
+        .map(|_| {
+            let (a, b) = (random_below_u64(p), random_below_u64(p));
+            let m = Integer::from(g.pow_mod_ref(&Integer::from(a), n).unwrap())
+                * Integer::from(h.pow_mod_ref(&Integer::from(b), n).unwrap());
+            (m % n, a, b)
+        })
+        .collect();
+    let root = (p as f64).sqrt();
+    let mask = (1u64 << (root.log2() as u32).saturating_sub(DISTINGUISHED_POINTS_BITS)) - 1;
+    let limit = 16 * (root / (mask + 1) as f64) as usize + 16 * threads;
+
+    let cancel = AtomicBool::new(false);
+    let (server, points) = mpsc::channel();
+    thread::scope(|scope| {
+        for _ in 0..threads {
+            let (server, cancel, seed, multipliers) = (server.clone(), &cancel, child_seed(), &multipliers);
+            scope.spawn(move || with_seed(seed, || walk(g, p, n, multipliers, mask, server, cancel)));
+        }
+        drop(server);
+
+        let mut table: HashMap<Integer, (u64, u64)> = HashMap::new();
+        let mut found = None;
+        for (point, a, b) in points.iter().take(limit) {
+            let Some(&(c, d)) = table.get(&point) else {
+                table.insert(point, (a, b));
+                continue;
+            };
+            // g^a * h^b = g^c * h^d, so x = (c - a) / (b - d) mod p, unless the two trails started together
+            let p = Integer::from(p);
+            let Ok(inverse) = (Integer::from(b) - d).invert(&p) else {
+                continue;
+            };
+            found = ((Integer::from(c) - a) * inverse).modulo(&p).to_u64();
+            break;
+        }
+        cancel.store(true, Ordering::Relaxed);
+        found
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parallel_rho_dlog() {
+        // 67109543 = 2 * 33554771 + 1 and 1e9 + 7 = 2 * 500000003 + 1 are safe primes, so 4 has a large prime order
+        let n = Integer::from(67_109_543);
+        let h = Integer::from(62_103_188);
+        assert_eq!(parallel_rho_dlog(&Integer::from(4), &h, &33_554_771, &n, 4), Some(22_792_476));
+        let n = Integer::from(1_000_000_007);
+        let x = 123_456_789;
+        let h = Integer::from(4).pow_mod(&Integer::from(x), &n).unwrap();
+        for threads in [1, 3] {
+            assert_eq!(parallel_rho_dlog(&Integer::from(4), &h, &500_000_003, &n, threads), Some(x));
+        }
+        assert_eq!(parallel_rho_dlog(&Integer::from(4), &Integer::from(1), &500_000_003, &n, 2), Some(0));
+    }
+}
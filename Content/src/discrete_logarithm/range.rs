@@ -0,0 +1,216 @@
+use std::collections::{hash_map::Entry, HashMap};
+
+use rug::Integer;
+
+use super::{baby_steps, canonical, discrete_log, DlogError, DlogSolution, DlogStrategy};
+use crate::montgomery_mod_mult::Context;
+use crate::number_theory::random::random_u64;
+use crate::number_theory::residues::pow_mod;
+
+/// The memory the baby steps of `discrete_log_in_range` may take, in bytes. Wider ranges use the kangaroo method.
+const RANGE_BSGS_MEMORY: usize = 64 << 20;
+/// The number of times the kangaroo method restarts with new jumps before giving up.
+const KANGAROO_RETRIES: usize = 16;
+
+/// Baby-step giant-step over the exponents in [0, width): the smallest y in the range with g^y = h, if there is one.
+/// g and h are in montgomery form.
+fn range_bsgs(g: &Integer, h: &Integer, width: u64, ctx: &mut Context) -> Option<u64> {
+    let g = ctx.from_montgomery(g);
+    let (table, giant_step) = baby_steps(&g, &width, ctx)?;
+    let m = (width as f64).sqrt().ceil() as u64;
+    let mut y = h.clone();
+    // the giant steps are in increasing order, so the first match is the smallest solution
+    for i in 0..=m {
+        if let Some(j) = table.get(&canonical(&y, &ctx.n)) {
+            let x = i * m + j;
+            return (x < width).then_some(x);
+        }
+        y *= ctx.wrap(&giant_step);
+    }
+    None
+}
+
+/// Reduces x (in montgomery form, in [0, 2n)) to [0, n) in place, see `canonical`.
+fn reduce(x: &mut Integer, n: &Integer) {
+    if *x >= *n {
+        *x -= n;
+    }
+}
+
+/// One run of Pollard's kangaroo method over the exponents in [0, width), with the jumps picked by the salt, in van
+/// Oorschot and Wiener's form with distinguished points. A tame kangaroo starts at g^(width / 2) and a wild one at h,
+/// they take turns jumping, and only the points whose low bits are all zero are remembered, with the distance the
+/// kangaroo had covered. Once one of them lands where the other has been, both follow the same path from there, and
+/// they meet at its next distinguished point. Only a few hundred points are kept, however wide the range.
+/// g and h are in montgomery form.
+fn kangaroo(g: &Integer, h: &Integer, width: u64, salt: u64, ctx: &mut Context) -> Option<u64> {
+    // jumps of 2^i for i < k, with a mean of about sqrt(width) / 2
+    let mean = ((width as f64).sqrt() / 2.0).max(1.0);
+    let mut k = 1;
+    while ((1u128 << k) - 1) as f64 / (k as f64) < mean {
+        k += 1;
+    }
+    let mut jumps = Vec::with_capacity(k);
+    let mut power = g.clone();
+    for _ in 0..k {
+        jumps.push(power.clone());
+        ctx.square_mut(&mut power);
+    }
+    // about 64 distinguished points every sqrt(width) jumps
+    let mask = (1u64 << ((width as f64).sqrt().log2().max(0.0) as u32).saturating_sub(6)) - 1;
+
+    let start = width / 2;
+    let tame = ctx.pow_mod(g, &Integer::from(start));
+    // the tame kangaroo is at g^(start + distance), the wild one at h * g^distance
+    let mut kangaroos = [(tame, start as u128), (h.clone(), 0)];
+    let mut points: HashMap<Integer, (usize, u128)> = HashMap::new();
+    // the kangaroo behind gets to where the other started in at most sqrt(width) jumps, and then lands on one of
+    // its footprints within e.g 5 sqrt(width) / 2 jumps with probability 1 - e^(-5)
+    for _ in 0..(8.0 * mean) as u64 + 4 * (mask + 1) {
+        for (kind, (x, distance)) in kangaroos.iter_mut().enumerate() {
+            reduce(x, &ctx.n);
+            let bits = x.to_u64_wrapping();
+            if bits & mask == 0 {
+                match points.entry(x.clone()) {
+                    Entry::Vacant(entry) => {
+                        entry.insert((kind, *distance));
+                    }
+                    // a kangaroo back on its own path is going around a cycle of a small group
+                    Entry::Occupied(entry) if entry.get().0 == kind => return None,
+                    Entry::Occupied(entry) => {
+                        let (tame, wild) = match kind {
+                            0 => (*distance, entry.get().1),
+                            _ => (entry.get().1, *distance),
+                        };
+                        // g^(y + wild) = g^tame, which only gives y in the range if the order of g is larger than
+                        // the distance between them
+                        let y = tame.checked_sub(wild)?;
+                        return (y < width as u128).then_some(y as u64);
+                    }
+                }
+            }
+            let i = ((bits ^ salt) % k as u64) as usize;
+            *x *= ctx.wrap(&jumps[i]);
+            *distance += 1 << i;
+        }
+    }
+    None
+}
+
+/// Finds x in [low, high) with g^x = h mod n, when the exponent is known to lie in a range, e.g a key with only
+/// 48 random bits. This takes about sqrt(high - low) steps, whatever the order of g, so it can solve problems that
+/// `discrete_log` can't (where the order of g has a large prime factor, or can't be factorized).
+/// Ranges whose baby steps fit in 64 MiB are solved with baby-step giant-step, which returns the smallest solution in
+/// the range. Wider ones use Pollard's kangaroo method, which needs no memory but may return any of the solutions if
+/// there are several. Negative values of low are treated as 0.
+///
+/// The order of g is not computed, so the period of the solution is 0. If g is not invertible modulo n, or n is even,
+/// the problem is left to `discrete_log` and the solutions are restricted to the range afterwards.
+///
+/// # Returns
+/// * `Ok(solution)` - A solution in the range.
+/// * `Err(DlogError::NoSolution)` - There is no solution in the range (certain, except after the kangaroo method).
+/// * `Err(DlogError::RangeTooWide(width))` - high - low does not fit within a u64.
+/// * `Err(DlogError::RangeGaveUp(width))` - The kangaroo method found no solution.
+pub fn discrete_log_in_range(g: &Integer, h: &Integer, n: &Integer, low: &Integer, high: &Integer)
+    -> Result<DlogSolution, DlogError> {
+    solve_in_range(g, h, n, low, high, RANGE_BSGS_MEMORY)
+}
+
+/// The same as `discrete_log_in_range`, but always with Pollard's kangaroo method, for ranges much smaller than the
+/// order of g whose baby steps would still take too much memory, or when the memory has to stay small whatever the
+/// range. About 2 sqrt(high - low) group operations, and a few hundred distinguished points of memory. The kangaroo
+/// method can't tell that there is no solution in the range, so that also ends in `RangeGaveUp`.
+///
+/// # Returns
+/// * `Ok(solution)` - A solution in the range, with a period of 0.
+/// * `Err(DlogError::NoSolution)` - The range is empty, or (when the problem is left to `discrete_log`, see
+///   `discrete_log_in_range`) there is no solution in it.
+/// * `Err(DlogError::RangeTooWide(width))` - high - low does not fit within a u64.
+/// * `Err(DlogError::RangeGaveUp(width))` - The kangaroo method found no solution.
+pub fn discrete_log_bounded(g: &Integer, h: &Integer, n: &Integer, low: &Integer, high: &Integer)
+    -> Result<DlogSolution, DlogError> {
+    solve_in_range(g, h, n, low, high, 0)
+}
+
+/// `discrete_log_in_range`, with baby-step giant-step as long as its baby steps fit in `bsgs_memory` bytes.
+fn solve_in_range(g: &Integer, h: &Integer, n: &Integer, low: &Integer, high: &Integer, bsgs_memory: usize)
+    -> Result<DlogSolution, DlogError> {
+    let low = low.clone().max(Integer::ZERO);
+    if *high <= low || *n < 1 {
+        return Err(DlogError::NoSolution);
+    }
+    let width = Integer::from(high - &low);
+    let width = width.to_u64().ok_or(DlogError::RangeTooWide(width))?;
+
+    if n.is_even() || *n == 1 || Integer::from(g.gcd_ref(n)) != 1 {
+        let solution = discrete_log(g, h, n)?;
+        return solution.in_range(&low, high).next()
+            .map(|exponent| DlogSolution { exponent, period: Integer::ZERO, ..solution })
+            .ok_or(DlogError::NoSolution);
+    }
+
+    // shift the problem to g^y = h * g^(-low) with y in [0, width)
+    let mut ctx = Context::new(n.clone());
+    let mut shift = pow_mod(g, &low, &mut ctx);
+    ctx.to_montgomery_mut(&mut shift);
+    ctx.invert_mut(&mut shift).ok_or(DlogError::NotInvertible)?;
+    let g_m = ctx.to_montgomery(g);
+    let mut h_m = ctx.to_montgomery(Integer::from(h % n));
+    h_m *= ctx.wrap(&shift);
+
+    let entry_size = (n.significant_bits() / 8) as usize + 64;
+    let baby_steps = (width as f64).sqrt().ceil() as usize;
+    let (y, strategy) = if baby_steps.saturating_mul(entry_size) <= bsgs_memory {
+        let y = range_bsgs(&g_m, &h_m, width, &mut ctx).ok_or(DlogError::NoSolution)?;
+        (y, DlogStrategy::BabyStepGiantStep)
+    } else {
+        let y = (0..KANGAROO_RETRIES)
+            .find_map(|_| kangaroo(&g_m, &h_m, width, random_u64(), &mut ctx))
+            .ok_or(DlogError::RangeGaveUp(width))?;
+        (y, DlogStrategy::Kangaroo)
+    };
+    Ok(DlogSolution { exponent: low + y, period: Integer::ZERO, strategy, transcript: None })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_discrete_log_in_range() {
+        let p = Integer::from(1_000_000_007);
+        let x = Integer::from(123_456_789);
+        let h = Integer::from(5).pow_mod(&x, &p).unwrap();
+        let solution = discrete_log_in_range(&Integer::from(5), &h, &p, &Integer::from(123_000_000),
+            &Integer::from(124_000_000)).unwrap();
+        assert_eq!((solution.exponent, solution.strategy), (x, DlogStrategy::BabyStepGiantStep));
+        assert_eq!(discrete_log_in_range(&Integer::from(5), &h, &p, &Integer::from(0), &Integer::from(1000)),
+            Err(DlogError::NoSolution));
+
+        // 3 mod 2^127 - 1 has an order with a 43 bit prime factor, but the exponent is within 2^40 of a known value
+        let m127 = (Integer::from(1) << 127u32) - 1u32;
+        let low = Integer::from(1) << 100u32;
+        let x = Integer::from(&low + 987_654_321_987u64);
+        let h = Integer::from(3).pow_mod(&x, &m127).unwrap();
+        let high = Integer::from(&low + (1u64 << 40));
+        let solution = discrete_log_in_range(&Integer::from(3), &h, &m127, &low, &high).unwrap();
+        assert_eq!(solution.exponent, x);
+
+        let solution = discrete_log_bounded(&Integer::from(3), &h, &m127, &low, &high).unwrap();
+        assert_eq!((solution.exponent, solution.strategy), (x, DlogStrategy::Kangaroo));
+
+        let mut ctx = Context::new(m127.clone());
+        let g_m = ctx.to_montgomery(Integer::from(3));
+        let h_m = ctx.to_montgomery(Integer::from(3).pow_mod(&Integer::from(987_654_321u64), &m127).unwrap());
+        let y = (0..KANGAROO_RETRIES).find_map(|_| kangaroo(&g_m, &h_m, 1 << 32, random_u64(), &mut ctx));
+        assert_eq!(y, Some(987_654_321));
+
+        // 2^x = 8 mod 64 only for x = 3, which discrete_log handles
+        let solution = discrete_log_in_range(&Integer::from(2), &Integer::from(8), &Integer::from(64), &Integer::from(0),
+            &Integer::from(10)).unwrap();
+        assert_eq!(solution.exponent, 3);
+        assert!(matches!(discrete_log_in_range(&Integer::from(3), &Integer::from(1), &m127, &Integer::ZERO, &high),
+            Err(DlogError::RangeTooWide(_))));
+    }
+}
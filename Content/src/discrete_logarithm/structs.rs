@@ -0,0 +1,169 @@
+use std::fmt;
+
+use rug::Integer;
+
+/// A solution to g^x = h mod n. Every x = exponent + period * k is a solution,
+/// where the period is the order of g mod n. The period is 0 when g and n are not coprime and h only
+/// appears among the first powers of g, before they start repeating: then the exponent is the only solution.
+/// It is also 0 for the solutions of `discrete_log_in_range`, which doesn't compute the order of g: then the exponent
+/// is the only solution that is known.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DlogSolution {
+    pub exponent: Integer,
+    pub period: Integer,
+    /// How the solution was found.
+    pub strategy: DlogStrategy,
+    /// The intermediate results, only filled in by `discrete_log_with_transcript`.
+    pub transcript: Option<DlogTranscript>,
+}
+
+/// The solutions of a `DlogSolution` within a range, see `DlogSolution::in_range`: the `count` numbers
+/// `first, first + period, ...`. Iterating yields them in increasing order.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DlogRange {
+    /// The smallest solution not yielded yet.
+    pub first: Integer,
+    pub period: Integer,
+    /// How many solutions are left.
+    pub count: Integer,
+}
+
+/// The intermediate results of a `discrete_log` run, which `verify_dlog` checks without redoing the search.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DlogTranscript {
+    /// The part of the exponent found by direct search when g and n are not coprime (0 otherwise).
+    pub offset: Integer,
+    /// n with the factors shared with g removed. The rest of the problem is solved modulo this number.
+    pub reduced_modulus: Integer,
+    /// The target of the reduced problem: g^(exponent - offset) = reduced_target mod reduced_modulus.
+    pub reduced_target: Integer,
+    /// One congruence per prime power dividing the reduced modulus, merged with CRT into the exponent.
+    pub congruences: Vec<DlogCongruence>,
+    /// The prime order subgroup problems solved by Pohlig–Hellman, in the order they were solved.
+    pub subproblems: Vec<DlogSubproblem>,
+}
+
+/// g^residue = reduced_target mod modulus, where modulus is a prime power and period is the order of g modulo it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DlogCongruence {
+    pub modulus: Integer,
+    pub residue: Integer,
+    pub period: Integer,
+}
+
+/// generator^exponent = target mod modulus, where the generator has prime order `order`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DlogSubproblem {
+    pub modulus: Integer,
+    pub order: u64,
+    pub generator: Integer,
+    pub target: Integer,
+    pub exponent: u64,
+}
+
+/// The algorithm `discrete_log` used to find a solution.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DlogStrategy {
+    /// No group computations were needed (e.g h is a small power of g, or g and n are not coprime and h was reached directly).
+    Trivial,
+    /// A single subgroup of small prime order, solved with baby-step giant-step.
+    BabyStepGiantStep,
+    /// A single subgroup of large prime order, solved with Pollard's rho.
+    PollardRho,
+    /// The modulus is a power of 2 (at least 4), solved by splitting (Z/2^kZ)* into {1, -1} and the powers of 5.
+    PowerOfTwo,
+    /// The modulus is p^k for an odd prime p, and the subgroup of order p was solved directly by p-adic lifting.
+    PAdicLifting,
+    /// The group order was split into several prime order subgroups, and the results were merged with CRT.
+    PohligHellman,
+    /// Pollard's kangaroo method over a range of exponents, see `discrete_log_in_range`.
+    Kangaroo,
+    /// A single subgroup of large prime order, solved with index calculus, see `discrete_log_index_calculus`.
+    IndexCalculus,
+}
+
+/// How `bsgs` stores its baby steps.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BsgsBackend {
+    /// A hash map from each baby step to its exponent.
+    #[default]
+    HashMap,
+    /// A vector of (baby step, exponent) pairs sorted by step and searched by bisection: smaller entries and no
+    /// hashing, at the cost of a sort and a logarithmic lookup.
+    SortedVec,
+}
+
+/// The settings of `bsgs`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BsgsOptions {
+    /// The most memory the baby steps may take, in bytes (64 MiB by default). Below sqrt(order) steps' worth, fewer
+    /// baby steps are taken and more giant steps.
+    pub memory_limit: usize,
+    pub backend: BsgsBackend,
+}
+
+impl Default for BsgsOptions {
+    fn default() -> Self {
+        Self { memory_limit: 64 << 20, backend: BsgsBackend::default() }
+    }
+}
+
+/// Why `discrete_log` did not return a solution.
+/// Only `NoSolution` means that a solution provably does not exist, the other variants mean we gave up.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DlogError {
+    /// h is not in the subgroup generated by g (checked by comparing the orders of g and h), so no solution exists.
+    NoSolution,
+    /// A prime factor of the group order does not fit within a u64.
+    FactorTooLarge(Integer),
+    /// The factorization of this number (the modulus or a group order) could not be completed.
+    Unfactored(Integer),
+    /// The subgroup of this prime order could not be solved (e.g Pollard's rho ran out of retries).
+    GaveUp(u64),
+    /// g is not invertible modulo n, which `DlogContext` needs.
+    NotInvertible,
+    /// The width of the range given to `discrete_log_in_range` does not fit within a u64.
+    RangeTooWide(Integer),
+    /// Pollard's kangaroo method found no solution in a range of this width, after all its retries.
+    RangeGaveUp(u64),
+    /// The factorization given to `in_subgroup` multiplies to this number, which is not the order of g (or, for
+    /// `discrete_log_with_order`, not a multiple of it).
+    WrongOrder(Integer),
+    /// Index calculus found too few relations, or no smooth target, for the subgroup of this prime order.
+    IndexCalculusGaveUp(Integer),
+    /// A point given to `discrete_log_ec` or `EllipticCurve::order` is not on the curve.
+    NotOnCurve,
+}
+
+impl DlogError {
+    /// Whether the error settles the problem: g^x = h has no solution. Every other error leaves it open.
+    pub fn is_unsolvable(&self) -> bool {
+        matches!(self, DlogError::NoSolution)
+    }
+
+    /// Whether the same call can succeed when run again: Pollard's rho, the kangaroo method and the relation search
+    /// of index calculus start from new random points every time. `Unfactored` calls for more effort on the
+    /// factorization instead (which `discrete_log_with_order` can take), and the other errors come from the input.
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, DlogError::GaveUp(_) | DlogError::RangeGaveUp(_) | DlogError::IndexCalculusGaveUp(_))
+    }
+}
+
+impl fmt::Display for DlogError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DlogError::NoSolution => write!(f, "the discrete logarithm does not exist"),
+            DlogError::FactorTooLarge(p) => write!(f, "the prime factor {} is too large for u64", p),
+            DlogError::Unfactored(n) => write!(f, "failed to fully factorize {}", n),
+            DlogError::GaveUp(p) => write!(f, "gave up on the subgroup of order {}", p),
+            DlogError::NotInvertible => write!(f, "the base is not invertible modulo the modulus"),
+            DlogError::RangeTooWide(width) => write!(f, "the range of width {} is too wide", width),
+            DlogError::RangeGaveUp(width) => write!(f, "gave up on the range of width {}", width),
+            DlogError::WrongOrder(order) => write!(f, "{} is not the order of the base", order),
+            DlogError::IndexCalculusGaveUp(q) => write!(f, "index calculus gave up on the subgroup of order {}", q),
+            DlogError::NotOnCurve => write!(f, "the point is not on the curve"),
+        }
+    }
+}
+
+impl std::error::Error for DlogError {}
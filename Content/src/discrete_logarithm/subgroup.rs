@@ -0,0 +1,116 @@
+use rug::{ops::Pow, Complete, Integer};
+
+use super::structs::DlogError;
+use super::discrete_log;
+use crate::number_theory::roots::prime_power;
+
+/// Whether (Z/nZ)* is cyclic, i.e n is 1, 2, 4, p^k or 2p^k for an odd prime p.
+fn is_cyclic_group(n: &Integer) -> bool {
+    if *n <= 4 {
+        return true;
+    }
+    let odd = match n.is_odd() {
+        true => n.clone(),
+        false if n.mod_u(4) == 2 => Integer::from(n >> 1u32),
+        false => return false,
+    };
+    prime_power(&odd).is_some()
+}
+
+/// The order of g, from the factorization of its order, after checking that it is one: g^order = 1, and
+/// g^(order / q) != 1 for every prime q of it.
+fn checked_order(g: &Integer, n: &Integer, order_factorization: &[(Integer, u32)]) -> Result<Integer, DlogError> {
+    let order = order_factorization.iter().fold(Integer::from(1), |acc, (q, e)| acc * q.clone().pow(*e));
+    let power = |exponent: &Integer| Integer::from(g.pow_mod_ref(exponent, n).unwrap());
+    if power(&order) != 1 || order_factorization.iter().any(|(q, _)| power(&Integer::from(&order / q)) == 1) {
+        return Err(DlogError::WrongOrder(order));
+    }
+    Ok(order)
+}
+
+/// Whether h is in the subgroup of (Z/nZ)* generated by g, given the factorization of the order of g (as (q, e)
+/// pairs, e.g from `prime_factorize`). h has to be a power of g, so h^order = 1 is necessary, and it is enough when
+/// (Z/nZ)* is cyclic (n = p^k or 2p^k), which has a single subgroup of each order. Otherwise other subgroups of the
+/// same order exist, so the answer comes from `discrete_log(g, h, n)`.
+///
+/// # Returns
+/// * `Ok(member)` - Whether h is a power of g.
+/// * `Err(NotInvertible)` - g is not coprime to n.
+/// * `Err(WrongOrder(order))` - The factorization is not that of the order of g.
+/// * `Err(error)` - The fallback to `discrete_log` gave up.
+pub fn in_subgroup(h: &Integer, g: &Integer, n: &Integer, order_factorization: &[(Integer, u32)]) -> Result<bool, DlogError> {
+    let g = g.modulo_ref(n).complete();
+    let h = h.modulo_ref(n).complete();
+    if g.gcd_ref(n).complete() != 1 {
+        return Err(DlogError::NotInvertible);
+    }
+    let order = checked_order(&g, n, order_factorization)?;
+    if h.gcd_ref(n).complete() != 1 || h.pow_mod_ref(&order, n).unwrap().complete() != 1 {
+        return Ok(false);
+    }
+    if is_cyclic_group(n) {
+        return Ok(true);
+    }
+    match discrete_log(&g, &h, n) {
+        Ok(_) => Ok(true),
+        Err(DlogError::NoSolution) => Ok(false),
+        Err(error) => Err(error),
+    }
+}
+
+/// Maps h into the subgroup of order `subgroup_order` of a group of order `group_order` in (Z/nZ)*, e.g φ(n) or the
+/// order of a generator, by raising it to the cofactor group_order / subgroup_order. When the subgroup order is a
+/// prime q that divides group_order only once, the result is in the (unique) subgroup of order q, and it is 1 exactly
+/// when h had no component of order q. The small subgroup checks of Diffie–Hellman and Pohlig–Hellman are built on it.
+///
+/// # Returns
+/// * `Ok(h^cofactor mod n)` - The image of h.
+/// * `Err(message)` - The subgroup order does not divide the group order, or h is not invertible modulo n.
+pub fn clear_cofactor(h: &Integer, n: &Integer, group_order: &Integer, subgroup_order: &Integer) -> Result<Integer, String> {
+    if *subgroup_order <= 0 || !group_order.is_divisible(subgroup_order) {
+        return Err(format!("the subgroup order {} does not divide the group order {}", subgroup_order, group_order));
+    }
+    let cofactor = Integer::from(group_order / subgroup_order);
+    h.pow_mod_ref(&cofactor, n)
+        .map(Integer::from)
+        .filter(|_| h.gcd_ref(n).complete() == 1)
+        .ok_or_else(|| format!("{} is not invertible modulo {}", h, n))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::number_theory::multiplicative_order;
+    use crate::prime_factorization::prime_factorize;
+
+    #[test]
+    fn test_in_subgroup() {
+        // p - 1 = 2 * 500000003, and 5 generates the whole group
+        let p = Integer::from(1_000_000_007);
+        let order = |g: &Integer, n: &Integer| prime_factorize(&multiplicative_order(g, n).unwrap()).into_factors();
+        let full = order(&Integer::from(5), &p);
+        assert_eq!(in_subgroup(&Integer::from(123_456), &Integer::from(5), &p, &full), Ok(true));
+
+        // the squares form the subgroup of index 2
+        let g = Integer::from(25);
+        let squares = order(&g, &p);
+        assert_eq!(in_subgroup(&Integer::from(49), &g, &p, &squares), Ok(true));
+        assert_eq!(in_subgroup(&Integer::from(5), &g, &p, &squares), Ok(false));
+        assert_eq!(in_subgroup(&Integer::from(49), &g, &p, &full), Err(DlogError::WrongOrder(Integer::from(&p - 1u32))));
+
+        // (Z/15Z)* = {±1} x {1, 2, 4, 8} is not cyclic: 4 and 11 = -4 both have order 2, but only 4 is a power of 2
+        let n = Integer::from(15);
+        let two = [(Integer::from(2), 2)];
+        assert_eq!(in_subgroup(&Integer::from(4), &Integer::from(2), &n, &two), Ok(true));
+        assert_eq!(in_subgroup(&Integer::from(11), &Integer::from(2), &n, &two), Ok(false));
+        assert_eq!(in_subgroup(&Integer::from(3), &Integer::from(2), &n, &two), Ok(false));
+
+        // into the subgroup of order 500000003, where 5 lands on a generator
+        let q = Integer::from(500_000_003);
+        let cleared = clear_cofactor(&Integer::from(5), &p, &Integer::from(&p - 1u32), &q).unwrap();
+        assert_eq!(in_subgroup(&cleared, &Integer::from(4), &p, &[(q.clone(), 1)]), Ok(true));
+        assert!(cleared != 1 && cleared.clone().pow_mod(&q, &p).unwrap() == 1);
+        assert!(clear_cofactor(&Integer::from(5), &p, &q, &Integer::from(7)).is_err());
+        assert!(clear_cofactor(&Integer::from(6), &n, &Integer::from(8), &Integer::from(2)).is_err());
+    }
+}
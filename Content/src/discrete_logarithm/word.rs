@@ -0,0 +1,105 @@
+use std::collections::HashMap;
+
+use super::RHO_RETRIES;
+use crate::number_theory::random::random_below_u64;
+use crate::number_theory::word::{add_mod, mul_mod, pow_mod};
+
+/// Baby-step giant-step with machine words, for n < 2^64: the same search as `baby_step_giant_step`, with the baby
+/// steps in a table of u64 keys and every product a single u128 multiplication, instead of a `Context` and a table of
+/// `Integer`s. Assumes g has order p, and returns None if h turns out not to be in the subgroup generated by g.
+pub(super) fn bsgs_u64(g: u64, h: u64, p: u64, n: u64) -> Option<u64> {
+    let m = (p as f64).sqrt().ceil() as u64;
+    let mut table: HashMap<u64, u64> = HashMap::with_capacity(m as usize);
+    let mut x = 1 % n;
+    for j in 0..m {
+        table.entry(x).or_insert(j);
+        x = mul_mod(x, g, n);
+    }
+    // g^(-m) = g^(p - m mod p), since g has order p
+    let giant_step = pow_mod(g, (p - m % p) % p, n);
+    let mut y = h;
+    for i in 0..m {
+        if let Some(&j) = table.get(&y) {
+            return Some((i * m + j) % p);
+        }
+        y = mul_mod(y, giant_step, n);
+    }
+    None
+}
+
+/// One step of the walk of `rho_u64`, the same as `update`: x is squared, or multiplied by g or h, depending on x mod 3.
+fn step(x: &mut u64, a: &mut u64, b: &mut u64, g: u64, h: u64, p: u64, n: u64) {
+    match *x % 3 {
+        0 => {
+            *x = mul_mod(*x, *x, n);
+            *a = add_mod(*a, *a, p);
+            *b = add_mod(*b, *b, p);
+        }
+        1 => {
+            *x = mul_mod(*x, g, n);
+            *a = add_mod(*a, 1, p);
+        }
+        _ => {
+            *x = mul_mod(*x, h, n);
+            *b = add_mod(*b, 1, p);
+        }
+    }
+}
+
+/// Pollard's rho with machine words, for n < 2^64 (see `bsgs_u64`). Every walk starts at g^a * h^b for random a and
+/// b, and follows Floyd's cycle finding until it meets itself: g^a1 * h^b1 = g^a2 * h^b2 gives
+/// x = (a1 - a2) / (b2 - b1) mod p. Assumes g has prime order p and h is in the subgroup it generates.
+/// Returns None if no collision was found after `RHO_RETRIES` random walks.
+pub(super) fn rho_u64(g: u64, h: u64, p: u64, n: u64) -> Option<u64> {
+    if h == 1 % n {
+        return Some(0);
+    }
+    if g == h {
+        return Some(1);
+    }
+    let limit = 10 * (p as f64).sqrt() as u64 + 10;
+    for _ in 0..RHO_RETRIES {
+        let (mut a1, mut b1) = (random_below_u64(p), random_below_u64(p));
+        let mut x1 = mul_mod(pow_mod(g, a1, n), pow_mod(h, b1, n), n);
+        let (mut x2, mut a2, mut b2) = (x1, a1, b1);
+        for _ in 0..limit {
+            step(&mut x1, &mut a1, &mut b1, g, h, p, n);
+            step(&mut x2, &mut a2, &mut b2, g, h, p, n);
+            step(&mut x2, &mut a2, &mut b2, g, h, p, n);
+            if x1 == x2 {
+                break;
+            }
+        }
+        let b = add_mod(b2, p - b1, p);
+        if x1 != x2 || b == 0 {
+            continue;
+        }
+        let a = add_mod(a1, p - a2, p);
+        return Some(mul_mod(a, pow_mod(b, p - 2, p), p));
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_word_dlog() {
+        // 4 has the prime order 500000003 modulo 1e9 + 7, and 2 has order 11 modulo 23, with 2^7 = 13
+        let n = 1_000_000_007;
+        let x = 123_456_789;
+        let h = pow_mod(4, x, n);
+        assert_eq!(rho_u64(4, h, 500_000_003, n), Some(x));
+        assert_eq!(bsgs_u64(4, h, 500_000_003, n), Some(x));
+        assert_eq!(bsgs_u64(2, 13, 11, 23), Some(7));
+        assert_eq!(bsgs_u64(2, 5, 11, 23), None);
+
+        // a modulus close to 2^64, where the products overflow a u64: 2^64 - 59 is prime, 2 is a primitive root of it
+        // and 547 divides 2^64 - 60, so 2^((2^64 - 60) / 547) has order 547
+        let n = 18_446_744_073_709_551_557;
+        let g = pow_mod(2, (n - 1) / 547, n);
+        assert_eq!(bsgs_u64(g, pow_mod(g, 500, n), 547, n), Some(500));
+        assert_eq!(rho_u64(g, pow_mod(g, 500, n), 547, n), Some(500));
+    }
+}
@@ -0,0 +1,234 @@
+mod parser;
+pub mod structs;
+
+use std::collections::HashMap;
+
+use rug::{ops::{Pow, RemRounding}, Integer};
+
+pub use parser::{parse, Expr, Op};
+pub use structs::EvalError;
+
+use crate::montgomery_mod_mult::Context;
+use crate::number_theory::{chinese_remainder_theorem, is_prime};
+use crate::number_theory::residues::pow_mod;
+
+/// Evaluates expressions over the integers, such as `3^(2^127-1) mod (2^127-1)`, and keeps variables between them.
+///
+/// Supported syntax, from the loosest to the tightest binding:
+/// * `x mod m` - Evaluates all of x modulo m. Everything inside x is reduced as it is computed, so huge powers are fine.
+/// * `x + y`, `x - y`
+/// * `x * y`, `x / y`, `x % y` - Under a modulus, division multiplies by the inverse. Otherwise it truncates,
+///   and `x % y` is the same as `x mod y`.
+/// * `-x`
+/// * `x ^ y` - Right associative. Exponents are always computed exactly, and a negative exponent inverts x modulo m.
+/// * numbers, variables, `(x)`, and the functions `gcd(a, b)`, `lcm(a, b)`, `inv(a, m)`, `powmod(a, e, m)`,
+///   `crt(a, m, b, n)`, `jacobi(a, n)`, `isprime(n)` (the BPSW test of `number_theory::is_prime`) and (with the
+///   `dlog` feature) `dlog(g, h, n)`.
+///
+/// Results are in [0, m) under a modulus.
+#[derive(Clone, Debug, Default)]
+pub struct Evaluator {
+    variables: HashMap<String, Integer>,
+}
+
+impl Evaluator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set(&mut self, name: &str, value: Integer) {
+        self.variables.insert(name.to_string(), value);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Integer> {
+        self.variables.get(name)
+    }
+
+    /// Evaluates one line, either an expression or an assignment `name = expression`.
+    /// An assignment stores the value and also returns it.
+    pub fn eval(&mut self, input: &str) -> Result<Integer, EvalError> {
+        let (target, expression) = parse(input)?;
+        let value = self.evaluate(&expression, None)?;
+        if let Some(name) = target {
+            self.variables.insert(name, value.clone());
+        }
+        Ok(value)
+    }
+
+    /// Evaluates a parsed expression, reduced modulo `modulus` if there is one.
+    pub fn evaluate(&self, expression: &Expr, modulus: Option<&Integer>) -> Result<Integer, EvalError> {
+        let value = match expression {
+            Expr::Number(value) => value.clone(),
+            Expr::Variable(name) => self.variables.get(name).cloned().ok_or_else(|| EvalError::UnknownName(name.clone()))?,
+            Expr::Neg(x) => -self.evaluate(x, modulus)?,
+            Expr::Binary(Op::Pow, base, exponent) => {
+                let base = self.evaluate(base, modulus)?;
+                let exponent = self.evaluate(exponent, None)?;
+                return power(base, &exponent, modulus);
+            }
+            Expr::Binary(op, x, y) => {
+                let x = self.evaluate(x, modulus)?;
+                let y = self.evaluate(y, modulus)?;
+                match op {
+                    Op::Add => x + y,
+                    Op::Sub => x - y,
+                    Op::Mul => x * y,
+                    Op::Div => match modulus {
+                        Some(m) => x * invert(y, m)?,
+                        None if y == 0 => return Err(EvalError::DivisionByZero),
+                        None => x / y,
+                    },
+                    Op::Pow => unreachable!(),
+                }
+            }
+            Expr::Mod(x, m) => {
+                let m = self.evaluate(m, None)?;
+                if m <= 0 {
+                    return Err(EvalError::InvalidModulus);
+                }
+                self.evaluate(x, Some(&m))?
+            }
+            Expr::Call(name, arguments) => {
+                let arguments = arguments.iter()
+                    .map(|argument| self.evaluate(argument, None))
+                    .collect::<Result<Vec<_>, _>>()?;
+                call(name, arguments)?
+            }
+        };
+        Ok(match modulus {
+            Some(m) => value.rem_euc(m),
+            None => value,
+        })
+    }
+}
+
+/// Evaluates a single expression without variables, see `Evaluator` for the syntax.
+pub fn eval(input: &str) -> Result<Integer, EvalError> {
+    Evaluator::new().eval(input)
+}
+
+/// The inverse of x modulo m, in [0, m).
+fn invert(x: Integer, m: &Integer) -> Result<Integer, EvalError> {
+    if *m <= 0 {
+        return Err(EvalError::InvalidModulus);
+    }
+    x.invert(m).map_err(|_| EvalError::NotInvertible)
+}
+
+/// base^exponent, reduced modulo m if there is one.
+fn power(base: Integer, exponent: &Integer, modulus: Option<&Integer>) -> Result<Integer, EvalError> {
+    let Some(m) = modulus else {
+        let exponent = exponent.to_u32().ok_or(EvalError::ExponentTooLarge)?;
+        return Ok(base.pow(exponent));
+    };
+    if *m == 1 {
+        return Ok(Integer::new());
+    }
+    let base = match exponent.is_negative() {
+        true => invert(base, m)?,
+        false => base,
+    };
+    let exponent = exponent.clone().abs();
+    if m.is_odd() {
+        let mut ctx = Context::new(m.clone());
+        return Ok(pow_mod(&base, &exponent, &mut ctx));
+    }
+    Ok(base.pow_mod(&exponent, m).unwrap())
+}
+
+fn expect_arguments(name: &str, arguments: Vec<Integer>, expected: usize) -> Result<Vec<Integer>, EvalError> {
+    if arguments.len() != expected {
+        return Err(EvalError::Arity { name: name.to_string(), expected, found: arguments.len() });
+    }
+    Ok(arguments)
+}
+
+fn call(name: &str, arguments: Vec<Integer>) -> Result<Integer, EvalError> {
+    let arity = match name {
+        "gcd" | "lcm" | "inv" | "jacobi" => 2,
+        "powmod" => 3,
+        "crt" => 4,
+        "isprime" => 1,
+        #[cfg(feature = "dlog")]
+        "dlog" => 3,
+        _ => return Err(EvalError::UnknownName(name.to_string())),
+    };
+    let mut arguments = expect_arguments(name, arguments, arity)?.into_iter();
+    let mut next = || arguments.next().unwrap();
+
+    match name {
+        "gcd" => Ok(next().gcd(&next())),
+        "lcm" => Ok(next().lcm(&next())),
+        "inv" => {
+            let (a, m) = (next(), next());
+            invert(a, &m)
+        }
+        "powmod" => {
+            let (a, e, m) = (next(), next(), next());
+            if m <= 0 {
+                return Err(EvalError::InvalidModulus);
+            }
+            power(a, &e, Some(&m))
+        }
+        "crt" => {
+            let (a, m, b, n) = (next(), next(), next(), next());
+            if m <= 0 || n <= 0 {
+                return Err(EvalError::InvalidModulus);
+            }
+            chinese_remainder_theorem(a, m, &b, &n)
+                .map(|(x, _)| x)
+                .ok_or_else(|| EvalError::Function("the congruences of crt have no common solution".to_string()))
+        }
+        "jacobi" => {
+            let (a, n) = (next(), next());
+            if n <= 0 || n.is_even() {
+                return Err(EvalError::Function("jacobi needs an odd positive n".to_string()));
+            }
+            Ok(Integer::from(a.jacobi(&n)))
+        }
+        "isprime" => Ok(Integer::from(is_prime(&next()) as u32)),
+        #[cfg(feature = "dlog")]
+        "dlog" => {
+            let (g, h, n) = (next(), next(), next());
+            crate::discrete_logarithm::discrete_log_owned(g, h, n)
+                .map(|solution| solution.exponent)
+                .map_err(|err| EvalError::Function(format!("dlog: {}", err)))
+        }
+        _ => unreachable!(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_eval() {
+        let p = (Integer::from(1) << 127u32) - 1u32;
+        assert_eq!(eval("3^(2^127-1) mod (2^127-1)").unwrap(), 3);
+        assert_eq!(eval("3^(2^127-2) % (2^127-1)").unwrap(), 1);
+        assert_eq!(eval("2^127 - 1").unwrap(), p);
+        assert_eq!(eval("2 + 3*4^2").unwrap(), 50);
+        assert_eq!(eval("-2^2").unwrap(), -4);
+        assert_eq!(eval("2^3^2").unwrap(), 512);
+        assert_eq!(eval("-7 / 2").unwrap(), -3);
+        assert_eq!(eval("3/4 mod 7").unwrap(), 6);
+        assert_eq!(eval("2^-1 mod 1_000_000_007").unwrap(), 500_000_004);
+        assert_eq!(eval("5^100 mod 2^64").unwrap(), Integer::from(5).pow_mod(&Integer::from(100), &(Integer::from(1) << 64u32)).unwrap());
+        assert_eq!(eval("inv(3, 7) + gcd(12, 18) + crt(2, 3, 3, 5)").unwrap(), 5 + 6 + 8);
+        assert_eq!(eval("isprime(2^127-1) * jacobi(2, 7)").unwrap(), 1);
+        // a strong pseudoprime to the bases 2, 3, 5 and 7, a composite above 2^64 and a negative number
+        assert_eq!(eval("isprime(3215031751) + isprime(2^64 + 1) + isprime(-7)").unwrap(), 0);
+
+        let mut evaluator = Evaluator::new();
+        evaluator.eval("x = 2^61 - 1").unwrap();
+        assert_eq!(evaluator.eval("x mod 1000").unwrap(), 951);
+        assert_eq!(evaluator.get("x"), Some(&((Integer::from(1) << 61u32) - 1u32)));
+
+        assert_eq!(eval("1 / 0"), Err(EvalError::DivisionByZero));
+        assert_eq!(eval("2 / 4 mod 6"), Err(EvalError::NotInvertible));
+        assert_eq!(eval("y + 1"), Err(EvalError::UnknownName("y".to_string())));
+        assert_eq!(eval("gcd(1)"), Err(EvalError::Arity { name: "gcd".to_string(), expected: 2, found: 1 }));
+        assert!(matches!(eval("(1 + 2"), Err(EvalError::Syntax(6, _))));
+    }
+}
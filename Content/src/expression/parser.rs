@@ -0,0 +1,209 @@
+use rug::Integer;
+
+use super::EvalError;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Op {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Pow,
+}
+
+/// The syntax tree of an expression.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Expr {
+    Number(Integer),
+    Variable(String),
+    Neg(Box<Expr>),
+    Binary(Op, Box<Expr>, Box<Expr>),
+    /// `x mod m` or `x % m`: x is evaluated modulo m.
+    Mod(Box<Expr>, Box<Expr>),
+    Call(String, Vec<Expr>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Token {
+    Number(Integer),
+    Name(String),
+    Symbol(char),
+}
+
+fn tokenize(input: &str) -> Result<Vec<(usize, Token)>, EvalError> {
+    let mut tokens = Vec::new();
+    let mut chars = input.char_indices().peekable();
+    while let Some(&(start, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c.is_ascii_digit() {
+            let mut end = start;
+            while let Some(&(i, d)) = chars.peek().filter(|(_, d)| d.is_ascii_digit() || *d == '_') {
+                end = i + d.len_utf8();
+                chars.next();
+            }
+            let digits = input[start..end].replace('_', "");
+            tokens.push((start, Token::Number(digits.parse().unwrap())));
+        } else if c.is_alphabetic() || c == '_' {
+            let mut end = start;
+            while let Some(&(i, d)) = chars.peek().filter(|(_, d)| d.is_alphanumeric() || *d == '_') {
+                end = i + d.len_utf8();
+                chars.next();
+            }
+            tokens.push((start, Token::Name(input[start..end].to_string())));
+        } else if "+-*/%^(),=".contains(c) {
+            tokens.push((start, Token::Symbol(c)));
+            chars.next();
+        } else {
+            return Err(EvalError::Syntax(start, format!("unexpected character `{}`", c)));
+        }
+    }
+    Ok(tokens)
+}
+
+/// Recursive descent parser. From the loosest to the tightest binding:
+/// `mod`, then `+ -`, then `* / %`, then unary `-`, then `^` (right associative), then numbers, names,
+/// calls and parentheses.
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    index: usize,
+    end: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.index).map(|(_, token)| token)
+    }
+
+    fn position(&self) -> usize {
+        self.tokens.get(self.index).map_or(self.end, |(position, _)| *position)
+    }
+
+    fn eat_symbol(&mut self, symbol: char) -> bool {
+        if self.peek() == Some(&Token::Symbol(symbol)) {
+            self.index += 1;
+            return true;
+        }
+        false
+    }
+
+    fn expect_symbol(&mut self, symbol: char) -> Result<(), EvalError> {
+        if !self.eat_symbol(symbol) {
+            return Err(EvalError::Syntax(self.position(), format!("expected `{}`", symbol)));
+        }
+        Ok(())
+    }
+
+    fn expression(&mut self) -> Result<Expr, EvalError> {
+        let mut lhs = self.sum()?;
+        while self.peek() == Some(&Token::Name("mod".to_string())) {
+            self.index += 1;
+            lhs = Expr::Mod(Box::new(lhs), Box::new(self.sum()?));
+        }
+        Ok(lhs)
+    }
+
+    fn sum(&mut self) -> Result<Expr, EvalError> {
+        let mut lhs = self.product()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Symbol('+')) => Op::Add,
+                Some(Token::Symbol('-')) => Op::Sub,
+                _ => return Ok(lhs),
+            };
+            self.index += 1;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(self.product()?));
+        }
+    }
+
+    fn product(&mut self) -> Result<Expr, EvalError> {
+        let mut lhs = self.unary()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Symbol('*')) => Op::Mul,
+                Some(Token::Symbol('/')) => Op::Div,
+                Some(Token::Symbol('%')) => {
+                    self.index += 1;
+                    lhs = Expr::Mod(Box::new(lhs), Box::new(self.unary()?));
+                    continue;
+                }
+                _ => return Ok(lhs),
+            };
+            self.index += 1;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(self.unary()?));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, EvalError> {
+        if self.eat_symbol('-') {
+            return Ok(Expr::Neg(Box::new(self.unary()?)));
+        }
+        self.power()
+    }
+
+    fn power(&mut self) -> Result<Expr, EvalError> {
+        let base = self.atom()?;
+        if self.eat_symbol('^') {
+            // the exponent may have a sign of its own, e.g 2^-1
+            return Ok(Expr::Binary(Op::Pow, Box::new(base), Box::new(self.unary()?)));
+        }
+        Ok(base)
+    }
+
+    fn atom(&mut self) -> Result<Expr, EvalError> {
+        let position = self.position();
+        match self.tokens.get(self.index).map(|(_, token)| token.clone()) {
+            Some(Token::Number(value)) => {
+                self.index += 1;
+                Ok(Expr::Number(value))
+            }
+            Some(Token::Name(name)) if name != "mod" => {
+                self.index += 1;
+                if !self.eat_symbol('(') {
+                    return Ok(Expr::Variable(name));
+                }
+                let mut arguments = Vec::new();
+                if !self.eat_symbol(')') {
+                    loop {
+                        arguments.push(self.expression()?);
+                        if self.eat_symbol(')') {
+                            break;
+                        }
+                        self.expect_symbol(',')?;
+                    }
+                }
+                Ok(Expr::Call(name, arguments))
+            }
+            Some(Token::Symbol('(')) => {
+                self.index += 1;
+                let inner = self.expression()?;
+                self.expect_symbol(')')?;
+                Ok(inner)
+            }
+            Some(_) => Err(EvalError::Syntax(position, "expected a number, a name or `(`".to_string())),
+            None => Err(EvalError::Syntax(position, "unexpected end of input".to_string())),
+        }
+    }
+}
+
+/// Parses a line: either an expression, or an assignment `name = expression`.
+/// Returns the name assigned to (if any) and the expression.
+pub fn parse(input: &str) -> Result<(Option<String>, Expr), EvalError> {
+    let tokens = tokenize(input)?;
+    let mut parser = Parser { tokens, index: 0, end: input.len() };
+
+    let mut target = None;
+    if let [(_, Token::Name(name)), (_, Token::Symbol('='))] = parser.tokens.get(..2).unwrap_or_default() {
+        if name == "mod" {
+            return Err(EvalError::Syntax(0, "`mod` can't be assigned to".to_string()));
+        }
+        target = Some(name.clone());
+        parser.index = 2;
+    }
+
+    let expression = parser.expression()?;
+    if parser.index < parser.tokens.len() {
+        return Err(EvalError::Syntax(parser.position(), "unexpected token".to_string()));
+    }
+    Ok((target, expression))
+}
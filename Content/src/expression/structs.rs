@@ -0,0 +1,39 @@
+use std::fmt;
+
+/// Why an expression could not be evaluated.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EvalError {
+    /// The input is not a valid expression. Holds the byte offset of the problem and a description.
+    Syntax(usize, String),
+    /// A variable or function that is not defined.
+    UnknownName(String),
+    /// A function was called with the wrong number of arguments.
+    Arity { name: String, expected: usize, found: usize },
+    DivisionByZero,
+    /// A modulus that is not positive.
+    InvalidModulus,
+    /// A division or negative power modulo m of a number that is not coprime to m.
+    NotInvertible,
+    /// A power without a modulus whose exponent is negative or does not fit within a u32.
+    ExponentTooLarge,
+    /// A library function failed, with the reason.
+    Function(String),
+}
+
+impl fmt::Display for EvalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EvalError::Syntax(position, message) => write!(f, "syntax error at {}: {}", position, message),
+            EvalError::UnknownName(name) => write!(f, "unknown name `{}`", name),
+            EvalError::Arity { name, expected, found } =>
+                write!(f, "{} takes {} arguments, but {} were given", name, expected, found),
+            EvalError::DivisionByZero => write!(f, "division by zero"),
+            EvalError::InvalidModulus => write!(f, "the modulus must be positive"),
+            EvalError::NotInvertible => write!(f, "the value is not invertible modulo the modulus"),
+            EvalError::ExponentTooLarge => write!(f, "exponents without a modulus must fit within a u32 and be non-negative"),
+            EvalError::Function(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for EvalError {}
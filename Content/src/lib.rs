@@ -1,4 +1,12 @@
+pub mod expression;
 pub mod montgomery_mod_mult;
 pub mod number_theory;
+pub mod product_tree;
+pub mod self_test;
+pub mod tuning;
+#[cfg(feature = "factorization")]
 pub mod prime_factorization;
-pub mod discrete_logarithm;
\ No newline at end of file
+#[cfg(feature = "dlog")]
+pub mod discrete_logarithm;
+#[cfg(feature = "factorization")]
+pub mod rsa;
@@ -1,7 +1,9 @@
-use math_algorithms::{discrete_logarithm::discrete_log, prime_factorization::prime_factorize};
-use rug::{
-    integer::IsPrime, rand::RandState, Integer
-};
+use math_algorithms::{discrete_logarithm::{discrete_log, DlogError}, expression::Evaluator,
+    number_theory::{input::MAX_DIGITS, read_integer_file, read_integer as read_integer_from},
+    prime_factorization::{pratt::prime_factorize_certified, prime_factorize_traced, Factorization,
+        FactorizeOptions, Progress},
+    self_test::{self_test, self_test_with_seed}};
+use rug::Integer;
 
 // pub mod montgomery_mod_mult;
 // pub mod number_theory;
@@ -18,62 +20,6 @@ use rug::{
 // use DiscreteLog::discrete_log;
 // use PrimeFactorize::prime_factorize;
 
-/// Generates an n-bit semiprime (product of two primes of similar bit length)
-pub fn generate_semiprime(bits: &u32, rand: &mut RandState<'static>) -> Integer {
-    let half_bits = bits / 2;
-    let remaining_bits = bits - half_bits;
-    
-    loop {
-        // Generate two primes of appropriate sizes
-        let p = generate_prime(half_bits, rand);
-        let q = generate_prime(remaining_bits, rand);
-        
-        let n = p * q;
-        
-        // Ensure we got exactly n bits (sometimes product is n-1 bits)
-        if n.significant_bits() == *bits {
-            return n;
-        }
-    }
-}
-
-/// Helper function to generate a probable prime of specified bit length
-fn generate_prime(bits: u32, rand: &mut RandState) -> Integer {
-    loop {
-        let mut candidate = Integer::from(Integer::random_bits(bits, rand));
-        // Set highest and lowest bits to ensure proper bit length and oddness
-        candidate.set_bit(bits - 1, true);
-        candidate.set_bit(0, true);
-        
-        if candidate.is_probably_prime(30) != IsPrime::No {
-            return candidate;
-        }
-    }
-}
-// /// Generate a random smooth integer: all prime factors are < 2^48
-// /// `bits`: the approximate bit length of the result
-// /// `rand`: random state
-// fn generate_smooth_integer(bits: u32, rand: &mut RandState) -> Integer {
-//     let mut result = Integer::ONE.clone();
-//     let mut total_bits = 0;
-
-//     while total_bits < bits {
-//         // Generate a small prime <= 48 bits
-//         let prime_bits = rand.below(48) + 1; // Random bit size in range [1, 32]
-//         let prime = generate_prime(prime_bits, rand);
-//         // Choose a large exponent to boost its contribution to bit length
-//         let exp = rand.below(30) + 1; // Choose exponent in a reasonable range
-
-//         let power = Integer::from(&prime).pow(exp);
-//         result *= &power;
-
-//         total_bits = result.significant_bits();
-//     }
-
-//     result
-// }
-
-
 use std::io::{self, Write};
 
 fn read_integer(prompt: &str) -> Integer {
@@ -86,7 +32,37 @@ fn read_integer(prompt: &str) -> Integer {
 
 
 fn main() {
-    println!("Enter 1 for prime factorization, 2 for discrete log:");
+    // `self-test [seed]` runs the randomized cross-checks of the crate instead of the menu
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some("self-test") {
+        let report = match args.get(2) {
+            Some(seed) => self_test_with_seed(seed.parse().expect("Invalid seed")),
+            None => self_test(),
+        };
+        print!("{}", report);
+        std::process::exit(if report.passed() { 0 } else { 1 });
+    }
+
+    // `factor [file]` factorizes the integer in the file (or stdin, for no file or `-`), for numbers too large to type
+    if args.get(1).map(String::as_str) == Some("factor") {
+        let n = match args.get(2).map(String::as_str) {
+            None | Some("-") => read_integer_from(io::stdin().lock(), MAX_DIGITS),
+            Some(path) => read_integer_file(path, MAX_DIGITS),
+        };
+        let n = n.unwrap_or_else(|err| {
+            eprintln!("{}", err);
+            std::process::exit(2);
+        });
+        eprintln!("factorizing a {} bit number", n.significant_bits());
+        // the progress goes to stderr as it happens, so that stdout only has the result
+        let progress = Progress::new(|event| eprintln!("{}", event));
+        let options = FactorizeOptions { progress: Some(progress), ..Default::default() };
+        let traced = prime_factorize_traced(&n, &options);
+        println!("{}", traced);
+        std::process::exit(if traced.composites.is_empty() { 0 } else { 1 });
+    }
+
+    println!("Enter 1 for prime factorization, 2 for discrete log, 3 for the expression evaluator:");
     let mut mode_input = String::new();
     io::stdin().read_line(&mut mode_input).unwrap();
     let mode = mode_input.trim();
@@ -94,19 +70,57 @@ fn main() {
     match mode {
         "1" => {
             let n = read_integer("Enter n: ");
-            println!("{:?}", prime_factorize(&n));
+            print!("Print Pratt certificates of the prime factors? (y/n): ");
+            io::stdout().flush().unwrap();
+            let mut answer = String::new();
+            io::stdin().read_line(&mut answer).unwrap();
+            if answer.trim() != "y" {
+                // the progress goes to stderr, so that stdout only has the result
+                let progress = Progress::new(|event| eprintln!("{}", event));
+                let options = FactorizeOptions { progress: Some(progress), ..Default::default() };
+                let traced = prime_factorize_traced(&n, &options);
+                let primes = traced.primes.iter().map(|p| (p.prime.clone(), p.exponent)).collect();
+                println!("{}", Factorization::new(primes));
+                // then every prime with the method that found it, and the time of every method
+                println!("{}", traced);
+            } else {
+                match prime_factorize_certified(&n) {
+                    Some(factors) => for (certificate, exponent) in factors {
+                        println!("{}^{}\n{}\n", certificate.prime, exponent, certificate);
+                    },
+                    None => println!("Failed to fully factorize a p - 1 of the certificates"),
+                }
+            }
         }
         "2" => {
             let g = read_integer("Enter g: ");
             let h = read_integer("Enter h: ");
             let n = read_integer("Enter n: ");
-            match discrete_log(g, h, n) {
-                Some(result) => println!("Discrete log result: {}\n + {}k", result.0, result.1),
-                None => println!("Discrete log does not exist"),
+            match discrete_log(&g, &h, &n) {
+                Ok(result) => println!("Discrete log result: {}\n + {}k", result.exponent, result.period),
+                Err(DlogError::NoSolution) => println!("Discrete log does not exist"),
+                Err(err) if err.is_retryable() => println!("Gave up on the discrete log ({}), try again", err),
+                Err(err) => println!("Failed to compute the discrete log: {}", err),
             };
         }
+        "3" => {
+            println!("Enter expressions such as 3^(2^127-1) mod (2^127-1), or x = ... to set a variable. An empty line exits.");
+            let mut evaluator = Evaluator::new();
+            loop {
+                print!("> ");
+                io::stdout().flush().unwrap();
+                let mut line = String::new();
+                if io::stdin().read_line(&mut line).unwrap() == 0 || line.trim().is_empty() {
+                    break;
+                }
+                match evaluator.eval(line.trim()) {
+                    Ok(value) => println!("{}", value),
+                    Err(err) => println!("{}", err),
+                }
+            }
+        }
         _ => {
-            println!("Invalid choice. Please enter 1 or 2.");
+            println!("Invalid choice. Please enter 1, 2 or 3.");
         }
     }
 
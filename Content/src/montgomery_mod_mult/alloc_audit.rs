@@ -0,0 +1,159 @@
+use std::cell::Cell;
+use std::ffi::c_void;
+use std::ops::Sub;
+use std::sync::OnceLock;
+
+use gmp_mpfr_sys::gmp;
+
+/// The number of calls GMP made to its memory functions, and the bytes it asked for. Every limb of an `Integer` is
+/// allocated through them, so counting them shows whether `Context` operations or the inner loops of ECM reuse
+/// their buffers or grow them: a hot path that doesn't allocate counts zero everywhere.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AllocationCounts {
+    pub allocations: u64,
+    pub reallocations: u64,
+    pub frees: u64,
+    pub bytes: u64, // allocated, and added by reallocations
+}
+
+impl AllocationCounts {
+    /// The allocations and reallocations, the calls a hot path shouldn't make.
+    pub fn total(&self) -> u64 {
+        self.allocations + self.reallocations
+    }
+}
+
+impl Sub for AllocationCounts {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Self {
+            allocations: self.allocations - rhs.allocations,
+            reallocations: self.reallocations - rhs.reallocations,
+            frees: self.frees - rhs.frees,
+            bytes: self.bytes - rhs.bytes,
+        }
+    }
+}
+
+// the counts of the calls made on this thread; GMP calls its memory functions on the thread that does the arithmetic,
+// so the counts of a thread don't see the others
+thread_local! {
+    static COUNTS: Cell<AllocationCounts> = const { Cell::new(AllocationCounts {
+        allocations: 0, reallocations: 0, frees: 0, bytes: 0,
+    }) };
+}
+
+// the memory functions GMP had before `install`, which the counting ones call
+static PREVIOUS: OnceLock<(gmp::allocate_function, gmp::reallocate_function, gmp::free_function)> = OnceLock::new();
+
+fn record(f: impl FnOnce(&mut AllocationCounts)) {
+    // the counts may be gone while the thread exits, and its last integers are freed
+    let _ = COUNTS.try_with(|counts| {
+        let mut c = counts.get();
+        f(&mut c);
+        counts.set(c);
+    });
+}
+
+extern "C" fn allocate(size: usize) -> *mut c_void {
+    record(|c| {
+        c.allocations += 1;
+        c.bytes += size as u64;
+    });
+    PREVIOUS.get().unwrap().0.unwrap()(size)
+}
+
+unsafe extern "C" fn reallocate(ptr: *mut c_void, old_size: usize, new_size: usize) -> *mut c_void {
+    record(|c| {
+        c.reallocations += 1;
+        c.bytes += new_size.saturating_sub(old_size) as u64;
+    });
+    unsafe { PREVIOUS.get().unwrap().1.unwrap()(ptr, old_size, new_size) }
+}
+
+unsafe extern "C" fn free(ptr: *mut c_void, size: usize) {
+    record(|c| c.frees += 1);
+    unsafe { PREVIOUS.get().unwrap().2.unwrap()(ptr, size) }
+}
+
+/// Replaces GMP's memory functions with ones that count their calls and pass them on to the previous ones, once for
+/// the whole process. The integers allocated before are freed by the same allocator, so this can be done at any
+/// time, but not while another thread installs memory functions of its own.
+pub fn install() {
+    PREVIOUS.get_or_init(|| {
+        let (mut alloc, mut realloc, mut free_fn) = (None, None, None);
+        unsafe {
+            gmp::get_memory_functions(&mut alloc, &mut realloc, &mut free_fn);
+            gmp::set_memory_functions(Some(allocate), Some(reallocate), Some(free));
+        }
+        (alloc, realloc, free_fn)
+    });
+}
+
+/// The calls to GMP's memory functions made on this thread since `install`.
+pub fn allocation_counts() -> AllocationCounts {
+    COUNTS.with(Cell::get)
+}
+
+/// Runs f, and returns its result with the calls to GMP's memory functions it made on this thread.
+pub fn count_allocations<R>(f: impl FnOnce() -> R) -> (R, AllocationCounts) {
+    install();
+    let before = allocation_counts();
+    let result = f();
+    (result, allocation_counts() - before)
+}
+
+#[cfg(test)]
+mod tests {
+    use rug::Integer;
+
+    use super::*;
+    use crate::montgomery_mod_mult::Context;
+
+    #[test]
+    fn test_count_allocations() {
+        let n: Integer = Integer::from(Integer::u_pow_u(2, 521)) - 1;
+        let mut ctx = Context::new(n.clone());
+        let mut a = ctx.to_montgomery(Integer::from(3));
+        let b = ctx.to_montgomery(Integer::from(5));
+
+        // a temporary integer is allocated and freed (how many calls GMP makes for it is its own business)
+        let (bits, counts) = count_allocations(|| Integer::from(&n * 2u32).significant_bits());
+        assert_eq!(bits, 522);
+        assert!(counts.allocations > 0 && counts.frees == counts.allocations);
+
+        // once a and the scratch buffers of the context have grown to the size of n, multiplications don't allocate:
+        // a long run makes no more calls than a short one
+        let mut run = |iterations: usize| count_allocations(|| {
+            for _ in 0..iterations {
+                a *= ctx.wrap(&b);
+                ctx.square_mut(&mut a);
+                a += ctx.wrap(&b);
+            }
+        }).1;
+        let warm_up = run(10);
+        let baseline = run(10);
+        assert!(baseline.total() <= warm_up.total());
+        assert!(run(1000).total() <= baseline.total());
+
+        // neither do the PRAC chains of ECM stage 1, once their buffers have grown
+        #[cfg(feature = "ecm")]
+        {
+            use crate::prime_factorization::ecm::{prac, suyama::suyama_curve, MontgomeryPoint, PracChains};
+
+            let (x, a24) = suyama_curve(11, &n).unwrap();
+            let mut point = MontgomeryPoint { X: ctx.to_montgomery(&x), Z: ctx.one() };
+            let a24 = ctx.to_montgomery(&a24);
+            let chains = PracChains::new([(2, 10), (3, 6), (5, 4), (7, 3), (11, 2), (101, 1), (1009, 1)]);
+            let mut stage1 = || {
+                for &(p, multiplier) in &chains.primes {
+                    prac(&mut point, p, multiplier, &a24, &mut ctx);
+                }
+            };
+            let baseline = count_allocations(&mut stage1).1;
+            let (_, counts) = count_allocations(|| (0..10).for_each(|_| stage1()));
+            assert!(counts.total() <= baseline.total());
+        }
+    }
+}
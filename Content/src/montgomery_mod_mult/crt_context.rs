@@ -0,0 +1,250 @@
+use rug::{ops::Pow, Complete, Integer};
+
+use super::Context;
+use crate::number_theory::is_prime;
+
+/// One prime power p^e of the modulus of a `CrtContext`.
+#[derive(Debug, Clone)]
+struct Component {
+    p: Integer,
+    modulus: Integer,
+    /// The Montgomery context of p^e, or None for a power of 2, whose residues are kept in standard form
+    /// (Montgomery reduction needs an odd modulus).
+    ctx: Option<Context>,
+    /// φ(p^e), which the exponents of units are reduced by.
+    phi: Integer,
+    /// The inverse of the product of the moduli before this one, modulo this one (Garner's coefficient).
+    coefficient: Integer,
+}
+
+impl Component {
+    /// Brings a value of [0, 2 p^e) down to [0, p^e), so equal residues compare equal.
+    fn canonical(&self, mut x: Integer) -> Integer {
+        if x >= self.modulus {
+            x -= &self.modulus;
+        }
+        x
+    }
+
+    /// The residue of x in the form of this component.
+    fn enter(&mut self, x: &Integer) -> Integer {
+        let x = x.modulo_ref(&self.modulus).complete();
+        match &mut self.ctx {
+            Some(ctx) => {
+                let x = ctx.to_montgomery(x);
+                self.canonical(x)
+            }
+            None => x,
+        }
+    }
+
+    /// The residue x mod p^e in standard form.
+    fn leave(&mut self, x: &Integer) -> Integer {
+        match &mut self.ctx {
+            Some(ctx) => ctx.from_montgomery(x),
+            None => x.clone(),
+        }
+    }
+
+    fn one(&mut self) -> Integer {
+        match &mut self.ctx {
+            Some(ctx) => {
+                let one = ctx.one();
+                self.canonical(one)
+            }
+            None => Integer::from(1),
+        }
+    }
+
+    fn mul(&mut self, a: &Integer, b: &Integer) -> Integer {
+        match &mut self.ctx {
+            Some(ctx) => {
+                let product = ctx.mul(a, b);
+                self.canonical(product)
+            }
+            None => (a * b).complete() % &self.modulus,
+        }
+    }
+
+    fn invert(&mut self, a: &Integer) -> Option<Integer> {
+        match &mut self.ctx {
+            Some(ctx) => {
+                let mut inverse = a.clone();
+                ctx.invert_mut(&mut inverse)?;
+                Some(self.canonical(inverse))
+            }
+            None => a.invert_ref(&self.modulus).map(Integer::from),
+        }
+    }
+
+    /// a^k for k >= 0. The exponent of a unit is reduced modulo φ(p^e) first.
+    fn pow(&mut self, a: &Integer, k: &Integer) -> Integer {
+        let k = match a.is_divisible(&self.p) {
+            true => k.clone(),
+            false => k.modulo_ref(&self.phi).complete(),
+        };
+        let mut result = self.one();
+        for i in (0..k.significant_bits()).rev() {
+            result = self.mul(&result, &result);
+            if k.get_bit(i) {
+                result = self.mul(&result, a);
+            }
+        }
+        result
+    }
+}
+
+/// An element of Z/nZ in a `CrtContext`: its residues modulo the prime powers of n, in the order of the factorization
+/// the context was built from, each in the Montgomery form of its component and in [0, p^e). Elements of the same
+/// context can be compared directly.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CrtElement(pub Vec<Integer>);
+
+/// Arithmetic modulo an n whose factorization is known, as Z/nZ = Z/p1^e1 × Z/p2^e2 × ...: every element is kept as
+/// its residues modulo the prime powers, which are multiplied with a Montgomery context each, and the result is put
+/// back together with Garner's algorithm (`from_crt`). Products of k limbs cost about k^2, so splitting n into parts
+/// of k / c limbs makes multiplications about c times cheaper, and exponents of units only need to be reduced modulo
+/// φ(p^e) instead of φ(n). This pays off for long exponentiations, e.g k-th roots or discrete logs modulo a composite.
+#[derive(Debug, Clone)]
+pub struct CrtContext {
+    pub n: Integer,
+    components: Vec<Component>,
+}
+
+impl CrtContext {
+    /// Builds the context of n = p1^e1 p2^e2 ... from its factorization.
+    ///
+    /// # Returns
+    /// * `Ok(context)` - The context.
+    /// * `Err(message)` - The factorization is empty, has an exponent of 0, a number that is not prime, or a repeated prime.
+    pub fn new(factorization: &[(Integer, u32)]) -> Result<Self, String> {
+        if factorization.is_empty() {
+            return Err("the factorization has no primes".to_string());
+        }
+        let mut n = Integer::from(1);
+        let mut components: Vec<Component> = Vec::with_capacity(factorization.len());
+        for (p, e) in factorization {
+            if *e == 0 {
+                return Err(format!("the prime {} has an exponent of 0", p));
+            }
+            if !is_prime(p) {
+                return Err(format!("{} is not a prime", p));
+            }
+            if components.iter().any(|component| component.p == *p) {
+                return Err(format!("the prime {} appears more than once", p));
+            }
+            let modulus = p.clone().pow(*e);
+            let phi = Integer::from(&modulus / p) * Integer::from(p - 1u32);
+            let coefficient = Integer::from(&n % &modulus).invert(&modulus).unwrap();
+            let ctx = p.is_odd().then(|| Context::new(modulus.clone()));
+            n *= &modulus;
+            components.push(Component { p: p.clone(), modulus, ctx, phi, coefficient });
+        }
+        Ok(CrtContext { n, components })
+    }
+
+    /// The prime powers p^e of n, in the order of the residues of the elements.
+    pub fn moduli(&self) -> Vec<&Integer> {
+        self.components.iter().map(|component| &component.modulus).collect()
+    }
+
+    /// The element of x (any integer, reduced modulo n).
+    pub fn to_crt(&mut self, x: &Integer) -> CrtElement {
+        CrtElement(self.components.iter_mut().map(|component| component.enter(x)).collect())
+    }
+
+    /// The residues of x in standard form, x mod p^e for every component.
+    pub fn residues(&mut self, x: &CrtElement) -> Vec<Integer> {
+        self.components.iter_mut().zip(&x.0).map(|(component, r)| component.leave(r)).collect()
+    }
+
+    /// The integer in [0, n) of x, with Garner's algorithm: each step adds the product of the previous moduli times
+    /// the correction for the next residue.
+    pub fn from_crt(&mut self, x: &CrtElement) -> Integer {
+        let mut value = Integer::new();
+        let mut product = Integer::from(1);
+        for (component, r) in self.components.iter_mut().zip(&x.0) {
+            let r = component.leave(r);
+            let correction = ((r - &value) * &component.coefficient).modulo(&component.modulus);
+            value += correction * &product;
+            product *= &component.modulus;
+        }
+        value
+    }
+
+    pub fn one(&mut self) -> CrtElement {
+        CrtElement(self.components.iter_mut().map(Component::one).collect())
+    }
+
+    pub fn add(&self, a: &CrtElement, b: &CrtElement) -> CrtElement {
+        CrtElement(self.components.iter().zip(a.0.iter().zip(&b.0))
+            .map(|(component, (x, y))| component.canonical((x + y).complete()))
+            .collect())
+    }
+
+    pub fn sub(&self, a: &CrtElement, b: &CrtElement) -> CrtElement {
+        CrtElement(self.components.iter().zip(a.0.iter().zip(&b.0))
+            .map(|(component, (x, y))| {
+                let difference = (x - y).complete();
+                if difference < 0 { difference + &component.modulus } else { difference }
+            })
+            .collect())
+    }
+
+    pub fn mul(&mut self, a: &CrtElement, b: &CrtElement) -> CrtElement {
+        CrtElement(self.components.iter_mut().zip(a.0.iter().zip(&b.0))
+            .map(|(component, (x, y))| component.mul(x, y))
+            .collect())
+    }
+
+    /// a^k for k >= 0, component by component.
+    pub fn pow(&mut self, a: &CrtElement, k: &Integer) -> CrtElement {
+        assert!(*k >= 0, "the exponent must not be negative");
+        CrtElement(self.components.iter_mut().zip(&a.0).map(|(component, x)| component.pow(x, k)).collect())
+    }
+
+    /// The inverse of a, or None if a is not coprime to n.
+    pub fn invert(&mut self, a: &CrtElement) -> Option<CrtElement> {
+        self.components.iter_mut().zip(&a.0).map(|(component, x)| component.invert(x)).collect::<Option<_>>().map(CrtElement)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rug::rand::RandState;
+
+    #[test]
+    fn test_crt_context() {
+        let mersenne = (Integer::from(1) << 61u32) - 1u32;
+        let factorization = [(Integer::from(2), 5), (Integer::from(3), 4), (Integer::from(1_000_003), 1), (mersenne, 2)];
+        let mut ctx = CrtContext::new(&factorization).unwrap();
+        let n = ctx.n.clone();
+        assert_eq!(ctx.moduli().len(), 4);
+
+        let mut rand = RandState::new();
+        for _ in 0..200 {
+            let a = Integer::from(n.random_below_ref(&mut rand));
+            let b = Integer::from(n.random_below_ref(&mut rand));
+            let k = Integer::from(Integer::random_bits(300, &mut rand));
+            let (x, y) = (ctx.to_crt(&a), ctx.to_crt(&b));
+            assert_eq!(ctx.from_crt(&x), a);
+            let (sum, difference, product, power) = (ctx.add(&x, &y), ctx.sub(&x, &y), ctx.mul(&x, &y), ctx.pow(&x, &k));
+            assert_eq!(ctx.from_crt(&sum), Integer::from(&a + &b) % &n);
+            assert_eq!(ctx.from_crt(&difference), Integer::from(&a - &b).modulo(&n));
+            assert_eq!(ctx.from_crt(&product), Integer::from(&a * &b) % &n);
+            assert_eq!(ctx.from_crt(&power), a.clone().pow_mod(&k, &n).unwrap());
+            let inverse = ctx.invert(&x);
+            assert_eq!(inverse.map(|inverse| ctx.from_crt(&inverse)), a.invert(&n).ok());
+        }
+        // 2^5 * 3^4 * ... divides 6^5, so a non-unit power goes to 0 in the first two components
+        let six = ctx.to_crt(&Integer::from(6));
+        let power = ctx.pow(&six, &Integer::from(5));
+        assert_eq!(ctx.residues(&power)[..2], [0, 0]);
+        let one = ctx.one();
+        assert_eq!(ctx.mul(&six, &one), six);
+
+        assert!(CrtContext::new(&[(Integer::from(15), 1)]).is_err());
+        assert!(CrtContext::new(&[(Integer::from(3), 1), (Integer::from(3), 2)]).is_err());
+    }
+}
@@ -1,8 +1,15 @@
+#[cfg(feature = "alloc-audit")]
+pub mod alloc_audit;
 pub mod benchmark;
+pub mod crt_context;
 pub mod montgomery_impl;
 pub mod montgomery_traits;
+pub mod recoding;
 
+#[cfg(feature = "alloc-audit")]
+pub use alloc_audit::{allocation_counts, count_allocations, AllocationCounts};
 pub use benchmark::benchmark_montgomery;
+pub use crt_context::{CrtContext, CrtElement};
 pub use montgomery_impl::Context;
 pub use montgomery_traits::{MontgomeryOwned, MontgomeryRef, WrapWithCtx};
 
@@ -1,12 +1,24 @@
 use std::ops::{AddAssign, MulAssign, ShrAssign, SubAssign};
 
 use rug::{
-    Assign, Integer,
+    Assign, Complete, Integer,
     ops::{NegAssign, SubFrom},
 };
 
+use super::recoding::fixed_windows;
 use super::WrapWithCtx;
 
+/// The width of the windows of `pow_mod` and `multi_pow` for exponents of that many bits.
+fn window_width(bits: u32) -> u32 {
+    match bits {
+        0..=16 => 1,
+        17..=64 => 3,
+        65..=256 => 4,
+        257..=1024 => 5,
+        _ => 6,
+    }
+}
+
 /// Montgomery multiplication context holding precomputed constants
 /// for efficient modular arithmetic operations.
 ///
@@ -165,6 +177,40 @@ impl Context {
         Some(())
     }
 
+    /// Inverts every value in place, in Montgomery form, with a single modular inversion (Montgomery's trick): the
+    /// prefix products v0 * ... * vi are kept, their last one is inverted, and walking back, each inverse is
+    /// (v0 * ... * vi)^-1 * (v0 * ... * v(i-1)), for 3 multiplications per value instead of an inversion.
+    ///
+    /// # Returns
+    /// * `Ok(())` - Every value was replaced by its inverse.
+    /// * `Err(i)` - values[i] is the first value not coprime to n, so its gcd with n is a factor of n (n itself when
+    ///   values[i] = 0 mod n). The values are left unchanged.
+    pub fn invert_batch(&mut self, values: &mut [Integer]) -> Result<(), usize> {
+        let Some(last) = values.len().checked_sub(1) else {
+            return Ok(());
+        };
+        // prefix[i] = v0 * ... * vi
+        let mut prefix: Vec<Integer> = Vec::with_capacity(values.len());
+        prefix.push(values[0].clone());
+        for value in &values[1..] {
+            let product = self.mul(&prefix[prefix.len() - 1], value);
+            prefix.push(product);
+        }
+
+        let mut inverse = prefix.pop().unwrap();
+        if self.invert_mut(&mut inverse).is_none() {
+            return Err(values.iter().position(|value| value.gcd_ref(&self.n).complete() != 1).unwrap());
+        }
+        // inverse = (v0 * ... * vi)^-1, so vi^-1 = inverse * prefix[i - 1]
+        for i in (1..=last).rev() {
+            let value_inverse = self.mul(&inverse, &prefix[i - 1]);
+            self.mul_assign(&mut inverse, &values[i]);
+            values[i] = value_inverse;
+        }
+        values[0] = inverse;
+        Ok(())
+    }
+
     /// Squares a number in Montgomery form.
     #[inline]
     pub fn square<X: Into<Integer>>(&mut self, x: X) -> Integer {
@@ -200,6 +246,83 @@ impl Context {
         self.reduce_mut(a);
     }
 
+    /// Raises a number in Montgomery form to the power exponent >= 0, with the result in Montgomery form, by the
+    /// fixed window (2^k-ary) method (see `recoding::fixed_windows`): the powers x^0, ..., x^(2^k - 1) are tabulated,
+    /// and the exponent takes a squaring per bit and a multiplication per nonzero digit of k bits. k grows with the
+    /// exponent, from 1 (square-and-multiply) up to 6 above 1024 bits, where the 64 table entries cost little next to
+    /// the digits they save. Everything stays in Montgomery form, with the constants of the context, so code that keeps
+    /// a context for its modulus doesn't go back and forth through `Integer::pow_mod`.
+    pub fn pow_mod(&mut self, base: &Integer, exponent: &Integer) -> Integer {
+        let k = window_width(exponent.significant_bits());
+        let digits = fixed_windows(exponent, k);
+        let Some((&top, rest)) = digits.split_last() else {
+            return self.one();
+        };
+        // table[i] = base^i
+        let mut table = Vec::with_capacity(1 << k);
+        table.push(self.one());
+        for i in 1..1 << k {
+            let power = self.mul(&table[i - 1], base);
+            table.push(power);
+        }
+
+        let mut result = table[top].clone();
+        for &digit in rest.iter().rev() {
+            for _ in 0..k {
+                self.square_mut(&mut result);
+            }
+            if digit != 0 {
+                self.mul_assign(&mut result, &table[digit]);
+            }
+        }
+        result
+    }
+
+    /// `pow_mod` on a number in standard form in [0, n), with the result in standard form in [0, n).
+    pub fn pow_mod_standard(&mut self, base: &Integer, exponent: &Integer) -> Integer {
+        let base = self.to_montgomery(base);
+        let power = self.pow_mod(&base, exponent);
+        self.from_montgomery(power)
+    }
+
+    /// The product of base^exponent over the terms, in Montgomery form (exponents >= 0), with interleaved fixed
+    /// windows (Shamir's trick, see `recoding::fixed_windows`): every base gets a table of x^0, ..., x^(2^k - 1), and
+    /// the windows of all the exponents are read together, so the squarings are shared. For t exponents of b bits,
+    /// that is b squarings and about t * b / k multiplications, instead of t * b squarings for t separate
+    /// exponentiations. The empty product is 1.
+    pub fn multi_pow(&mut self, terms: &[(&Integer, &Integer)]) -> Integer {
+        let bits = terms.iter().map(|(_, exponent)| exponent.significant_bits()).max().unwrap_or(0);
+        let k = window_width(bits);
+        let mut tables = Vec::with_capacity(terms.len());
+        for (base, _) in terms {
+            let mut table = Vec::with_capacity(1 << k);
+            table.push(self.one());
+            for i in 1..1 << k {
+                let power = self.mul(&table[i - 1], *base);
+                table.push(power);
+            }
+            tables.push(table);
+        }
+
+        let digits: Vec<Vec<usize>> = terms.iter().map(|(_, exponent)| fixed_windows(exponent, k)).collect();
+        let windows = bits.div_ceil(k) as usize;
+        let mut result = self.one();
+        for window in (0..windows).rev() {
+            if window + 1 < windows {
+                for _ in 0..k {
+                    self.square_mut(&mut result);
+                }
+            }
+            for (digits, table) in digits.iter().zip(&tables) {
+                let digit = digits.get(window).copied().unwrap_or(0);
+                if digit != 0 {
+                    self.mul_assign(&mut result, &table[digit]);
+                }
+            }
+        }
+        result
+    }
+
     /// Add by 1 in Montgomery Form.
     #[inline]
     pub fn increment<X: Into<Integer>>(&mut self, x: X) -> Integer {
@@ -378,6 +501,7 @@ impl Context {
         self.r_cubed_mod_n.shr_assign(self.r_bit_length);
     }
 
+    #[cfg(feature = "factorization")]
     pub(crate) fn assign(&mut self, other: &Context) {
         self.n.assign(&other.n);
         self.n2.assign(&other.n2);
@@ -0,0 +1,87 @@
+use rug::Integer;
+
+/// The bits of k below its leading one, from the most significant down: the steps of a left-to-right ladder that
+/// starts from the base itself (square-and-multiply, the Montgomery ladder of a curve, a Lucas chain), where a 1 also
+/// multiplies by the base or moves the pair of the ladder up by one. Empty for k <= 1.
+pub fn ladder_bits(k: &Integer) -> impl Iterator<Item = bool> + '_ {
+    (0..k.significant_bits().saturating_sub(1)).rev().map(|i| k.get_bit(i))
+}
+
+/// `ladder_bits` of a machine word.
+pub fn ladder_bits_u64(k: u64) -> impl Iterator<Item = bool> {
+    (0..(u64::BITS - k.leading_zeros()).saturating_sub(1)).rev().map(move |i| (k >> i) & 1 == 1)
+}
+
+/// The digits of k >= 0 in base 2^w, least significant first: k is the sum of digits[i] * 2^(wi), with one digit per
+/// w bits of k (none for k = 0). The fixed window (2^w-ary) method reads them from the top, with w squarings and a
+/// multiplication by a tabulated power per digit, and the same digits of several exponents can share the squarings.
+pub fn fixed_windows(k: &Integer, w: u32) -> Vec<usize> {
+    assert!((1..usize::BITS).contains(&w), "the window width must be in [1, {})", usize::BITS);
+    (0..k.significant_bits().div_ceil(w))
+        .map(|window| (0..w).rev().fold(0, |digit, i| digit << 1 | k.get_bit(window * w + i) as usize))
+        .collect()
+}
+
+/// The sliding windows of k >= 0, most significant first: (position, digit) pairs with odd digits below 2^w such that
+/// k is the sum of digit * 2^position. Every window starts at a 1 and ends at the last 1 among the next w bits, and
+/// the zeros in between are skipped, so only the odd powers of the base have to be tabulated (half as many as for
+/// fixed windows), and there is about one window per w + 1 bits.
+pub fn sliding_windows(k: &Integer, w: u32) -> Vec<(u32, usize)> {
+    assert!((1..usize::BITS).contains(&w), "the window width must be in [1, {})", usize::BITS);
+    let mut windows = Vec::new();
+    let mut top = k.significant_bits();
+    while top > 0 {
+        if !k.get_bit(top - 1) {
+            top -= 1;
+            continue;
+        }
+        let mut bottom = top.saturating_sub(w);
+        while !k.get_bit(bottom) {
+            bottom += 1;
+        }
+        let digit = (bottom..top).rev().fold(0, |digit, i| digit << 1 | k.get_bit(i) as usize);
+        windows.push((bottom, digit));
+        top = bottom;
+    }
+    windows
+}
+
+/// The width-w non-adjacent form of k >= 0 (wNAF, for w >= 2), least significant first: k is the sum of
+/// digits[i] * 2^i, where every nonzero digit is odd and below 2^(w - 1) in absolute value, and any w consecutive
+/// digits hold at most one nonzero digit. About one digit in w + 1 is nonzero, against one in two for the binary
+/// digits, which pays off in groups where inverses are free, such as points on a curve: a scalar multiplication takes
+/// a doubling per digit and an addition or subtraction of one of the 2^(w - 2) odd multiples per nonzero digit.
+/// w = 2 is the plain NAF.
+pub fn naf(k: &Integer, w: u32) -> Vec<i32> {
+    assert!((2..=16).contains(&w), "the window width must be in [2, 16]");
+    assert!(*k >= 0, "only k >= 0 can be recoded");
+    let modulus = 1i32 << w;
+    let bits = k.significant_bits();
+    let mut digits = Vec::with_capacity(bits as usize + 1);
+    // the digits are read straight from the bits of k instead of subtracting them from a copy, which would take
+    // quadratic time for the scalars of ECM stage 1. A negative digit leaves a carry into the bit after its window.
+    let mut carry = 0;
+    let mut i = 0;
+    while i < bits || carry != 0 {
+        let bit = k.get_bit(i) as i32 + carry;
+        if bit & 1 == 0 {
+            digits.push(0);
+            carry = bit >> 1;
+            i += 1;
+            continue;
+        }
+        let window = (0..w).fold(carry, |window, j| window + ((k.get_bit(i + j) as i32) << j));
+        let mut digit = window & (modulus - 1);
+        if digit >= modulus / 2 {
+            digit -= modulus;
+        }
+        digits.push(digit);
+        digits.extend(std::iter::repeat_n(0, w as usize - 1));
+        carry = (window - digit) >> w;
+        i += w;
+    }
+    while digits.last() == Some(&0) {
+        digits.pop();
+    }
+    digits
+}
@@ -163,3 +163,104 @@ fn test_in_place_operations() {
         assert_eq!(diff_result, diff_expected, "Subtraction in test_all failed");
     }
 }
+
+#[test]
+fn test_pow_mod() {
+    // exponents of every size, so that every window width is used, against Integer::pow_mod
+    let mut modulus = random_below(&(Integer::from(1) << 600u32));
+    modulus.set_bit(0, true);
+    let mut ctx = Context::new(modulus.clone());
+    for bits in [0u32, 1, 5, 16, 17, 64, 65, 200, 256, 257, 1024, 1025, 2000] {
+        for _ in 0..10 {
+            let a = random_below(&modulus);
+            let exponent = random_below(&(Integer::from(1) << bits));
+            let mont_a = ctx.to_montgomery(&a);
+            let result = ctx.pow_mod(&mont_a, &exponent);
+            let expected = a.clone().pow_mod(&exponent, &modulus).unwrap();
+            assert_eq!(ctx.from_montgomery(result), expected, "Exponentiation failed for a={} e={}", a, exponent);
+            assert_eq!(ctx.pow_mod_standard(&a, &exponent), expected, "Exponentiation failed for a={} e={}", a, exponent);
+        }
+    }
+}
+
+#[test]
+fn test_multi_pow() {
+    // products of up to 4 powers with exponents of different sizes, against separate exponentiations
+    let mut modulus = random_below(&(Integer::from(1) << 300u32));
+    modulus.set_bit(0, true);
+    let mut ctx = Context::new(modulus.clone());
+    for count in 0..5 {
+        for bits in [1u32, 20, 100, 300, 1100] {
+            let terms: Vec<(Integer, Integer)> = (0..count)
+                .map(|i| (random_below(&modulus), random_below(&(Integer::from(1) << (bits >> i)))))
+                .collect();
+            let mont_terms: Vec<(Integer, &Integer)> = terms.iter().map(|(a, e)| (ctx.to_montgomery(a), e)).collect();
+            let refs: Vec<(&Integer, &Integer)> = mont_terms.iter().map(|(a, e)| (a, *e)).collect();
+            let result = ctx.multi_pow(&refs);
+            let expected = terms.iter().fold(Integer::from(1) % &modulus, |product, (a, e)| {
+                product * a.clone().pow_mod(e, &modulus).unwrap() % &modulus
+            });
+            assert_eq!(ctx.from_montgomery(result), expected, "Multi-exponentiation failed for {:?}", terms);
+        }
+    }
+}
+
+#[test]
+fn test_invert_batch() {
+    // every value times its inverse is 1, and a value sharing the factor 101 of the modulus is reported
+    let modulus = Integer::from(101) * ((Integer::from(1) << 61u32) - 1u32);
+    let mut ctx = Context::new(modulus.clone());
+    let values: Vec<Integer> = (0..50).map(|_| random_below(&modulus)).filter(|v| !v.is_divisible_u(101))
+        .map(|v| ctx.to_montgomery(&v)).collect();
+    let mut inverses = values.clone();
+    ctx.invert_batch(&mut inverses).unwrap();
+    for (value, inverse) in values.iter().zip(&inverses) {
+        let product = ctx.mul(value, inverse);
+        assert_eq!(ctx.from_montgomery(product), 1, "Batch inversion failed for {}", ctx.from_montgomery(value));
+    }
+    assert_eq!(ctx.invert_batch(&mut []), Ok(()));
+
+    // the values are left as they were
+    let mut with_factor = values.clone();
+    with_factor[17] = ctx.to_montgomery(Integer::from(202));
+    let before = with_factor.clone();
+    assert_eq!(ctx.invert_batch(&mut with_factor), Err(17));
+    assert_eq!(with_factor, before);
+    assert_eq!(Integer::from(with_factor[17].gcd_ref(&modulus)), 101);
+}
+
+#[test]
+fn test_recoding() {
+    use super::recoding::{fixed_windows, ladder_bits, ladder_bits_u64, naf, sliding_windows};
+
+    // every recoding of random scalars adds back up to them, with digits of the promised form
+    for bits in [0u32, 1, 2, 7, 64, 65, 300] {
+        for _ in 0..20 {
+            let k = random_below(&(Integer::from(2) << bits));
+            for w in 1..=6 {
+                let fixed = fixed_windows(&k, w);
+                let sum = fixed.iter().rev().fold(Integer::new(), |sum, &digit| (sum << w) + digit);
+                assert_eq!(sum, k);
+                let sliding = sliding_windows(&k, w);
+                assert!(sliding.iter().all(|&(_, digit)| digit % 2 == 1 && digit < 1 << w));
+                let sum: Integer = sliding.iter().map(|&(position, digit)| Integer::from(digit) << position).sum();
+                assert_eq!(sum, k);
+            }
+            for w in 2..=6 {
+                let digits = naf(&k, w);
+                let sum = digits.iter().rev().fold(Integer::new(), |sum, &digit| (sum << 1) + digit);
+                assert_eq!(sum, k);
+                for (i, &digit) in digits.iter().enumerate().filter(|(_, digit)| **digit != 0) {
+                    assert!(digit % 2 != 0 && digit.abs() < 1 << (w - 1));
+                    assert!(digits[i + 1..].iter().take(w as usize - 1).all(|&next| next == 0));
+                }
+            }
+            let ladder = ladder_bits(&k).fold(Integer::from(1), |sum, bit| (sum << 1) + bit as u32);
+            assert_eq!(ladder, k.clone().max(Integer::from(1)));
+        }
+    }
+    // 7 = 8 - 1 in NAF, and the ladder of a word leaves out its leading one
+    assert_eq!(naf(&Integer::from(7), 2), [-1, 0, 0, 1]);
+    assert_eq!(ladder_bits_u64(0b1011).collect::<Vec<_>>(), [false, true, true]);
+    assert_eq!(ladder_bits_u64(1).count() + ladder_bits_u64(0).count(), 0);
+}
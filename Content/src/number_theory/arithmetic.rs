@@ -0,0 +1,131 @@
+use super::generate_primes::stored_primes;
+
+/// Evaluates a multiplicative function f on every n in [a, b) with a segmented sieve over the window.
+/// `apply(value, p, e)` multiplies f(p^e) into value. Every n in the window is split by the stored primes up to
+/// sqrt(b), and what is left of n afterwards is 1 or a single prime above sqrt(b).
+fn multiplicative_range<T, F>(a: u64, b: u64, one: T, apply: F) -> Vec<T>
+where
+    T: Clone,
+    F: Fn(&mut T, u64, u32),
+{
+    assert!(a >= 1, "the range must start at 1 or above");
+    if b <= a {
+        return Vec::new();
+    }
+    let primes = stored_primes();
+    let largest = *primes.last().unwrap() as u64;
+    assert!(b - 1 <= largest * largest, "the range must end below {}", largest * largest + 1);
+
+    let mut rest: Vec<u64> = (a..b).collect();
+    let mut values = vec![one; (b - a) as usize];
+    for &p in primes {
+        let p = p as u64;
+        if p * p >= b {
+            break;
+        }
+        let mut multiple = a.div_ceil(p) * p;
+        while multiple < b {
+            let i = (multiple - a) as usize;
+            let mut e = 0;
+            while rest[i].is_multiple_of(p) {
+                rest[i] /= p;
+                e += 1;
+            }
+            apply(&mut values[i], p, e);
+            multiple += p;
+        }
+    }
+
+    for (value, q) in values.iter_mut().zip(rest) {
+        if q > 1 {
+            apply(value, q, 1);
+        }
+    }
+    values
+}
+
+/// Euler's totient phi(n) for every n in [a, b). Needs 1 <= a and b <= 2.5e7^2.
+pub fn phi_range(a: u64, b: u64) -> Vec<u64> {
+    multiplicative_range(a, b, 1, |value, p, e| *value *= (p - 1) * p.pow(e - 1))
+}
+
+/// The Möbius function mu(n) for every n in [a, b). Needs 1 <= a and b <= 2.5e7^2.
+pub fn mu_range(a: u64, b: u64) -> Vec<i8> {
+    multiplicative_range(a, b, 1, |value, _, e| *value = if e == 1 { -*value } else { 0 })
+}
+
+/// The sum of divisors sigma(n) for every n in [a, b). Needs 1 <= a and b <= 2.5e7^2.
+pub fn sigma_range(a: u64, b: u64) -> Vec<u64> {
+    // sigma(p^e) = 1 + p + ... + p^e, computed without the (p^(e+1) - 1) / (p - 1) overflow
+    multiplicative_range(a, b, 1, |value, p, e| *value *= (0..=e).fold(0, |sum, _| sum * p + 1))
+}
+
+/// The values of a `*_range` function on [1, limit], indexed by n, with the default value in front for n = 0.
+fn up_to<T: Default>(limit: u32, range: fn(u64, u64) -> Vec<T>) -> Vec<T> {
+    let mut values = vec![T::default()];
+    values.extend(range(1, limit as u64 + 1));
+    values
+}
+
+/// Euler's totient phi(n) for every n up to limit, indexed by n (with phi(0) = 0): `phi_range(1, limit + 1)`.
+pub fn phi_sieve(limit: u32) -> Vec<u64> {
+    up_to(limit, phi_range)
+}
+
+/// The Möbius function mu(n) for every n up to limit, indexed by n (with mu(0) = 0): `mu_range(1, limit + 1)`.
+pub fn mu_sieve(limit: u32) -> Vec<i8> {
+    up_to(limit, mu_range)
+}
+
+/// The sum of divisors sigma(n) for every n up to limit, indexed by n (with sigma(0) = 0): `sigma_range(1, limit + 1)`.
+pub fn sigma_sieve(limit: u32) -> Vec<u64> {
+    up_to(limit, sigma_range)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// (phi, mu, sigma) of n by trial division.
+    fn naive(mut n: u64) -> (u64, i8, u64) {
+        let (mut phi, mut mu, mut sigma) = (1, 1, 1);
+        let mut p = 2;
+        while n > 1 {
+            if p * p > n {
+                p = n;
+            }
+            let mut e = 0;
+            while n.is_multiple_of(p) {
+                n /= p;
+                e += 1;
+            }
+            if e > 0 {
+                phi *= (p - 1) * p.pow(e - 1);
+                mu = if e == 1 { -mu } else { 0 };
+                sigma *= (((p as u128).pow(e + 1) - 1) / (p as u128 - 1)) as u64;
+            }
+            p += 1;
+        }
+        (phi, mu, sigma)
+    }
+
+    #[test]
+    fn test_ranges() {
+        for (a, b) in [(1, 3000), (1_000_000_000_000, 1_000_000_000_030)] {
+            let (phi, mu, sigma) = (phi_range(a, b), mu_range(a, b), sigma_range(a, b));
+            for n in a..b {
+                let i = (n - a) as usize;
+                assert_eq!((phi[i], mu[i], sigma[i]), naive(n), "failed for n = {}", n);
+            }
+        }
+        assert!(phi_range(5, 5).is_empty());
+
+        let limit = 100_000;
+        let (phi, mu, sigma) = (phi_sieve(limit), mu_sieve(limit), sigma_sieve(limit));
+        assert_eq!(phi[1..], phi_range(1, limit as u64 + 1));
+        assert_eq!(mu[1..], mu_range(1, limit as u64 + 1));
+        assert_eq!(sigma[1..], sigma_range(1, limit as u64 + 1));
+        assert_eq!((phi[0], mu[0], sigma[0]), (0, 0, 0));
+        assert_eq!((phi_sieve(1), mu_sieve(0)), (vec![0, 1], vec![0]));
+    }
+}
@@ -0,0 +1,50 @@
+use rug::Integer;
+
+use crate::product_tree::ProductTree;
+
+/// For every modulus N_i, the gcd of N_i with the product of all the other moduli (Bernstein's batch gcd), e.g to find
+/// the RSA moduli of a set of keys that share a prime. The product P of all moduli comes from a product tree, and the
+/// remainder tree of P modulo the squares of the moduli gives (P mod N_i^2) / N_i = (P / N_i) mod N_i, so the whole
+/// set takes a quasi-linear number of operations instead of the gcds of all the pairs.
+///
+/// A result of 1 means N_i shares no prime with the others, and a result of N_i means all of its primes are shared
+/// (e.g N_i appears twice, or each of its primes is in a different modulus), so the pairwise gcds of those few moduli
+/// are needed to split them. The results are in the order of the moduli. Panics if a modulus is 0.
+pub fn batch_gcd(moduli: &[Integer]) -> Vec<Integer> {
+    let tree = ProductTree::new(moduli);
+    let remainders = tree.remainders_squared(&tree.product());
+    moduli.iter()
+        .zip(remainders)
+        .map(|(n, r)| r.div_exact(n).gcd(n))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_batch_gcd() {
+        let primes: Vec<Integer> = (0..8u32).map(|i| (Integer::from(1_000_003) * (i + 1)).next_prime()).collect();
+        let moduli = vec![
+            Integer::from(&primes[0] * &primes[1]),
+            Integer::from(&primes[2] * &primes[3]),
+            Integer::from(&primes[1] * &primes[4]),
+            Integer::from(&primes[5] * &primes[6]),
+            Integer::from(&primes[4] * &primes[7]),
+            Integer::from(&primes[2] * &primes[3]),
+        ];
+        let expected = [&primes[1], &moduli[1], &moduli[2], &Integer::from(1), &primes[4], &moduli[5]];
+        assert_eq!(batch_gcd(&moduli).iter().collect::<Vec<_>>(), expected);
+
+        // the same as the pairwise gcds
+        let pairwise: Vec<Integer> = moduli.iter().enumerate()
+            .map(|(i, n)| moduli.iter().enumerate()
+                .filter(|&(j, _)| j != i)
+                .fold(Integer::from(1), |acc, (_, m)| Integer::from(acc.lcm_ref(&m.gcd_ref(n).into()))))
+            .collect();
+        assert_eq!(batch_gcd(&moduli), pairwise);
+        assert!(batch_gcd(&[]).is_empty());
+        assert_eq!(batch_gcd(&moduli[..1]), [1]);
+    }
+}
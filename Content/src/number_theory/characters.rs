@@ -0,0 +1,138 @@
+use std::f64::consts::PI;
+
+use rug::Integer;
+
+use super::word::pow_mod;
+
+/// An element of Z[ζ_k] as its coefficients on 1, ζ_k, ..., ζ_k^(k-1), where ζ_k = e^(2πi/k).
+/// The coefficients are not reduced by the cyclotomic polynomial, so the representation is not unique
+/// (e.g 1 + ζ_k + ... + ζ_k^(k-1) = 0), but sums and products are exact.
+pub type Cyclotomic = Vec<Integer>;
+
+/// The distinct prime factors of n, by trial division.
+fn prime_divisors(mut n: u64) -> Vec<u64> {
+    let mut primes = Vec::new();
+    let mut q = 2;
+    while q * q <= n {
+        if n.is_multiple_of(q) {
+            primes.push(q);
+            while n.is_multiple_of(q) {
+                n /= q;
+            }
+        }
+        q += 1;
+    }
+    if n > 1 {
+        primes.push(n);
+    }
+    primes
+}
+
+/// The smallest primitive root modulo the prime p.
+pub fn primitive_root(p: u64) -> u64 {
+    if p == 2 {
+        return 1;
+    }
+    let divisors = prime_divisors(p - 1);
+    (2..p).find(|&g| divisors.iter().all(|q| pow_mod(g, (p - 1) / q, p) != 1)).unwrap()
+}
+
+/// The character of order k that the sums below are taken over: χ(g^j) = ζ_k^j for the smallest primitive root g
+/// modulo p, and χ(0) = 0. Returns the table of j mod k for every x in [0, p), with index 0 unused.
+fn character_table(p: u64, k: u32) -> Vec<u32> {
+    assert!(p > 2 && (p - 1).is_multiple_of(k as u64) && k >= 2, "k must be at least 2 and divide p - 1");
+    let g = primitive_root(p);
+    let mut table = vec![0u32; p as usize];
+    let mut x = 1u64;
+    for j in 0..p - 1 {
+        table[x as usize] = (j % k as u64) as u32;
+        x = x * g % p;
+    }
+    table
+}
+
+/// The product of two elements of Z[ζ_k].
+pub fn cyclotomic_mul(x: &Cyclotomic, y: &Cyclotomic) -> Cyclotomic {
+    let k = x.len();
+    let mut product = vec![Integer::new(); k];
+    for (i, a) in x.iter().enumerate() {
+        for (j, b) in y.iter().enumerate() {
+            product[(i + j) % k] += Integer::from(a * b);
+        }
+    }
+    product
+}
+
+/// The complex value (re, im) of an element of Z[ζ_k].
+pub fn cyclotomic_value(x: &Cyclotomic) -> (f64, f64) {
+    let k = x.len() as f64;
+    x.iter().enumerate().fold((0.0, 0.0), |(re, im), (j, c)| {
+        let angle = 2.0 * PI * j as f64 / k;
+        (re + c.to_f64() * angle.cos(), im + c.to_f64() * angle.sin())
+    })
+}
+
+/// The Jacobi sum J(χ^a, χ^b) = Σ χ^a(x) χ^b(1 - x) over x in Z/pZ, where χ is the character of order k
+/// that sends the smallest primitive root to ζ_k. This takes O(p) time and memory, so p should be small.
+/// Panics unless p is an odd prime and k >= 2 divides p - 1.
+pub fn jacobi_sum(p: u64, k: u32, a: u32, b: u32) -> Cyclotomic {
+    let table = character_table(p, k);
+    let mut sum = vec![Integer::new(); k as usize];
+    // χ(0) = 0, so x = 0 and x = 1 don't contribute
+    for x in 2..p {
+        let j = (a as u64 * table[x as usize] as u64 + b as u64 * table[(p + 1 - x) as usize] as u64) % k as u64;
+        sum[j as usize] += 1;
+    }
+    sum
+}
+
+/// The Gauss sum g(χ^a) = Σ χ^a(x) e^(2πix/p) over x in Z/pZ as a complex number (re, im), with χ as in `jacobi_sum`.
+/// For a nontrivial character, |g(χ^a)|^2 = p. Panics unless p is an odd prime and k >= 2 divides p - 1.
+pub fn gauss_sum(p: u64, k: u32, a: u32) -> (f64, f64) {
+    let table = character_table(p, k);
+    (1..p).fold((0.0, 0.0), |(re, im), x| {
+        let angle = 2.0 * PI * ((a as u64 * table[x as usize] as u64 % k as u64) as f64 / k as f64 + x as f64 / p as f64);
+        (re + angle.cos(), im + angle.sin())
+    })
+}
+
+/// The k-th power of the Gauss sum of χ (as in `jacobi_sum`), exactly, which unlike the Gauss sum itself lies in Z[ζ_k]:
+/// g(χ)^k = χ(-1) p J(χ, χ) J(χ, χ^2) ... J(χ, χ^(k-2)). Panics unless p is an odd prime and k >= 2 divides p - 1.
+pub fn gauss_sum_power(p: u64, k: u32) -> Cyclotomic {
+    let mut power = vec![Integer::new(); k as usize];
+    // χ(-1) = ζ_k^((p - 1) / 2)
+    power[((p - 1) / 2 % k as u64) as usize] = Integer::from(p);
+    for b in 1..k - 1 {
+        power = cyclotomic_mul(&power, &jacobi_sum(p, k, 1, b));
+    }
+    power
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_character_sums() {
+        for (p, k) in [(7u64, 3u32), (13, 4), (31, 6), (41, 5), (101, 2)] {
+            // |J(χ^a, χ^b)|^2 = p when χ^a, χ^b and χ^(a + b) are nontrivial
+            for a in 1..k {
+                for b in 1..k - a {
+                    let (re, im) = cyclotomic_value(&jacobi_sum(p, k, a, b));
+                    assert!((re * re + im * im - p as f64).abs() < 1e-6, "failed for p = {}, k = {}, a = {}, b = {}", p, k, a, b);
+                }
+            }
+
+            let (re, im) = gauss_sum(p, k, 1);
+            assert!((re * re + im * im - p as f64).abs() < 1e-6);
+            // g(χ)^k from the Jacobi sums against the power of the complex value
+            let (abs, arg) = ((re * re + im * im).sqrt().powi(k as i32), im.atan2(re) * k as f64);
+            let (expected_re, expected_im) = cyclotomic_value(&gauss_sum_power(p, k));
+            assert!((abs * arg.cos() - expected_re).abs() < 1e-6 && (abs * arg.sin() - expected_im).abs() < 1e-6,
+                "failed for p = {}, k = {}", p, k);
+        }
+        // for the Legendre symbol, g^2 = (-1/p) p
+        assert_eq!(gauss_sum_power(13, 2), vec![Integer::from(13), Integer::new()]);
+        assert_eq!(primitive_root(998_244_353), 3);
+    }
+}
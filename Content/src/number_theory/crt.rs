@@ -101,6 +101,59 @@ pub fn chinese_remainder_theorem_mut(a: &mut Integer, m: &mut Integer, b: &Integ
 }
 
 
+/// Accumulates congruences x ≡ a (mod m) one at a time, keeping the combined solution up to date.
+///
+/// Starts from x ≡ 0 (mod 1), which every integer satisfies. Moduli don't need to be coprime;
+/// a congruence that contradicts the ones pushed so far is rejected, and the accumulator is left unchanged.
+#[derive(Clone, Debug)]
+pub struct CrtAccumulator {
+    residue: Integer,
+    modulus: Integer,
+}
+
+impl Default for CrtAccumulator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CrtAccumulator {
+    /// Creates an accumulator holding x ≡ 0 (mod 1).
+    pub fn new() -> Self {
+        Self { residue: Integer::ZERO, modulus: Integer::ONE.clone() }
+    }
+
+    /// Adds the congruence x ≡ a (mod m).
+    ///
+    /// # Returns
+    /// * `Some(())` - The congruence was merged into the solution.
+    /// * `None` - The congruence contradicts the ones pushed so far, nothing was changed.
+    pub fn push(&mut self, a: &Integer, m: &Integer) -> Option<()> {
+        chinese_remainder_theorem_mut(&mut self.residue, &mut self.modulus, a, m)
+    }
+
+    /// The current solution x, in the range [0, modulus).
+    pub fn residue(&self) -> &Integer {
+        &self.residue
+    }
+
+    /// The modulus of the current solution (the lcm of all moduli pushed so far).
+    pub fn modulus(&self) -> &Integer {
+        &self.modulus
+    }
+
+    /// Consumes the accumulator, returning (x, M).
+    pub fn into_inner(self) -> (Integer, Integer) {
+        (self.residue, self.modulus)
+    }
+
+    /// Resets the accumulator to x ≡ 0 (mod 1), keeping the allocated buffers.
+    pub fn clear(&mut self) {
+        self.residue.assign(0);
+        self.modulus.assign(1);
+    }
+}
+
 
 #[cfg(test)]
 mod tests {
@@ -130,6 +183,21 @@ mod tests {
                 "CRT returned None but solution exists for a={a}, m={m}, b={b}, n={n}");
         }
     }
+    #[test]
+    fn test_crt_accumulator() {
+        let mut acc = CrtAccumulator::new();
+        assert!(acc.push(&Integer::from(2), &Integer::from(6)).is_some());
+        assert!(acc.push(&Integer::from(5), &Integer::from(9)).is_some());
+        assert_eq!((acc.residue(), acc.modulus()), (&Integer::from(14), &Integer::from(18)));
+
+        // x ≡ 2 (mod 6) forces x to be even, so this conflicts and must not change the state
+        assert!(acc.push(&Integer::from(1), &Integer::from(4)).is_none());
+        assert_eq!((acc.residue(), acc.modulus()), (&Integer::from(14), &Integer::from(18)));
+
+        assert!(acc.push(&Integer::from(3), &Integer::from(5)).is_some());
+        assert_eq!(acc.into_inner(), (Integer::from(68), Integer::from(90)));
+    }
+
     #[test]
     fn test_crt() {
         let mut rng = RandState::new();
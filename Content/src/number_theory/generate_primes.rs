@@ -1,3 +1,7 @@
+use std::{borrow::Cow, thread};
+
+use once_cell::sync::OnceCell;
+
 
 /**
  * I got the implementation from https://github.com/kth-competitive-programming/kactl/blob/main/content/number-theory/FastEratosthenes.h
@@ -19,51 +23,166 @@
  * Se https://github.com/kth-competitive-programming/kactl/pull/166#discussion_r408354338
  */
 
-/// Generate a vector of all primes up to 2.5e7
-pub fn generate_primes() -> Vec<u32> {
-    const LIM: usize = 25_000_000;
-    let s = (LIM as f64).sqrt().round() as usize;
-    let r = LIM / 2;
-    let reserve = ((LIM as f64) / (LIM as f64).ln() * 1.1).ceil() as usize;
-    let mut primes: Vec<u32> = Vec::with_capacity(reserve);
-    primes.push(2);
-    let mut sieve = vec![false; s + 1];
-    let mut cp: Vec<(u32, usize)> = Vec::new();
-    for i in (3..=s).step_by(2) {
-        if !sieve[i] {
-            let idx = i * i / 2;
-            cp.push((i as u32, idx));
-            for j in ((i * i)..=s).step_by(2 * i) {
-                sieve[j] = true;
+/// The primes of `generate_primes` and `stored_primes` go up to this bound.
+pub const PRIME_LIMIT: u32 = 25_000_000;
+/// Odd numbers per segment of the sieve: one bit each, so a segment is 32 KiB and stays in the L1 cache.
+const SEGMENT: usize = 1 << 18;
+
+/// The primes in [low, limit], in increasing order, passed through convert. The odd primes up to the square root of
+/// the limit are sieved first, then the range of odd numbers is cut into one run of whole segments per thread, and
+/// every thread crosses off the odd multiples of those primes in a bit-packed segment of `SEGMENT` odd numbers at a
+/// time. The memory stays small and the limit can go beyond 2^32.
+fn sieve<T: Send>(low: u64, limit: u64, convert: impl Fn(u64) -> T + Sync) -> Vec<T> {
+    let mut primes = Vec::new();
+    if limit < 2 || low > limit {
+        return primes;
+    }
+    if low <= 2 {
+        primes.push(convert(2));
+    }
+    // the index i stands for the odd number 2i + 1
+    let (start, end) = ((low / 2).max(1), (limit - 1) / 2);
+    if start > end {
+        return primes;
+    }
+    let base = base_primes(limit.isqrt());
+
+    let segments = (end - start) / SEGMENT as u64 + 1;
+    let threads = thread::available_parallelism().map_or(1, |threads| threads.get() as u64).min(segments);
+    let run = segments.div_ceil(threads) * SEGMENT as u64;
+    let runs: Vec<Vec<T>> = thread::scope(|scope| {
+        let handles: Vec<_> = (0..threads)
+            .map(|t| start + t * run)
+            .take_while(|&from| from <= end)
+            .map(|from| {
+                let (base, convert) = (&base, &convert);
+                scope.spawn(move || sieve_run(from, (from + run - 1).min(end), base, convert))
+            })
+            .collect();
+        handles.into_iter().map(|handle| handle.join().unwrap()).collect()
+    });
+    runs.into_iter().for_each(|run| primes.extend(run));
+    primes
+}
+
+/// The odd primes up to s: a plain sieve up to the square root of s, then `sieve_run` with these.
+fn base_primes(s: u64) -> Vec<u64> {
+    if s < 3 {
+        return Vec::new();
+    }
+    let root = s.isqrt() as usize;
+    let mut composite = vec![false; root + 1];
+    let mut small: Vec<u64> = Vec::new();
+    for i in (3..=root).step_by(2) {
+        if !composite[i] {
+            small.push(i as u64);
+            for j in ((i * i)..=root).step_by(2 * i) {
+                composite[j] = true;
             }
         }
     }
+    sieve_run(1, (s - 1) / 2, &small, &|p| p)
+}
 
-    let mut block = vec![false; s];
-    let mut l = 1;
-    while l <= r {
-        let block_size = if l + s - 1 <= r { s } else { r - l + 1 };
-        block.fill(false);
-        for &mut (p, ref mut idx) in cp.iter_mut() {
-            if *idx < l {
-                let diff = l - *idx;
-                *idx += ((diff + p as usize - 1) / p as usize) * p as usize;
-            }
+/// The primes whose index is in [from, to], for base primes that include every odd prime up to the square root of the
+/// last one, sieved one bit-packed segment at a time. Each base prime resumes where it stopped in the previous segment.
+fn sieve_run<T>(from: u64, to: u64, base: &[u64], convert: &impl Fn(u64) -> T) -> Vec<T> {
+    // the index of the next odd multiple of each prime, 2i + 1 = 0 mod p, from `from` on but nothing below p^2
+    let mut next: Vec<u64> = base.iter()
+        .map(|&p| (from + ((p - 1) / 2 + p - from % p) % p).max(p * p / 2))
+        .collect();
+    let mut words = vec![0u64; SEGMENT / 64];
+    let mut primes = Vec::new();
+    let mut l = from;
+    while l <= to {
+        let block_size = SEGMENT.min((to - l + 1) as usize);
+        words.fill(0);
+        for (&p, idx) in base.iter().zip(next.iter_mut()) {
             let mut i = *idx;
-            while i < l + block_size {
-                block[i - l] = true;
-                i += p as usize;
+            while i < l + block_size as u64 {
+                let bit = (i - l) as usize;
+                words[bit / 64] |= 1 << (bit % 64);
+                i += p;
             }
             *idx = i;
         }
 
-        for i in 0..block_size {
-            if !block[i] {
-                primes.push(((l + i) * 2 + 1) as u32);
+        for (w, &word) in words[..block_size.div_ceil(64)].iter().enumerate() {
+            let mut left = !word;
+            while left != 0 {
+                let bit = w * 64 + left.trailing_zeros() as usize;
+                if bit >= block_size {
+                    break;
+                }
+                primes.push(convert((l + bit as u64) * 2 + 1));
+                left &= left - 1;
             }
         }
-        l += s;
+        l += SEGMENT as u64;
     }
-
     primes
-}
\ No newline at end of file
+}
+
+/// Generate a vector of all primes up to limit, with a segmented sieve that works beyond 2^32.
+pub fn generate_primes_up_to(limit: u64) -> Vec<u64> {
+    sieve(0, limit, |p| p)
+}
+
+/// The primes in [a, b], in increasing order. Only the interval is sieved, by the primes up to the square root of b,
+/// so a window far out (b up to about 1e15, where the base primes take a few dozen MB) costs little more than its
+/// length, e.g to study prime gaps or count the primes of windows.
+pub fn primes_in_range(a: u64, b: u64) -> Vec<u64> {
+    sieve(a, b, |p| p)
+}
+
+/// Generate a vector of all primes up to `PRIME_LIMIT` (2.5e7)
+pub fn generate_primes() -> Vec<u32> {
+    sieve(0, PRIME_LIMIT as u64, |p| p as u32)
+}
+
+static PRIMES: OnceCell<Vec<u32>> = OnceCell::new();
+
+/// The primes up to 2.5e7, generated on first use and shared by everything that needs them
+/// (prime_factorize uses the same list).
+pub fn stored_primes() -> &'static Vec<u32> {
+    PRIMES.get_or_init(generate_primes)
+}
+
+/// The primes up to limit: a slice of `stored_primes` if it reaches that far, and a list of their own, sieved with
+/// `generate_primes_up_to`, above `PRIME_LIMIT`.
+pub fn stored_primes_up_to(limit: u32) -> Cow<'static, [u32]> {
+    match limit <= PRIME_LIMIT {
+        true => {
+            let primes = stored_primes();
+            Cow::Borrowed(&primes[..primes.partition_point(|&p| p <= limit)])
+        }
+        false => Cow::Owned(generate_primes_up_to(limit as u64).into_iter().map(|p| p as u32).collect()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::number_theory::is_prime_u64;
+
+    #[test]
+    fn test_generate_primes() {
+        let primes = stored_primes();
+        assert_eq!(primes.len(), 1_565_927);
+        assert_eq!(&primes[..10], [2, 3, 5, 7, 11, 13, 17, 19, 23, 29]);
+        assert_eq!(*primes.last().unwrap(), 24_999_983);
+        assert!(generate_primes_up_to(1).is_empty());
+        assert_eq!(generate_primes_up_to(2), [2]);
+        assert_eq!(generate_primes_up_to(300_001), (0..=300_001).filter(|&n| is_prime_u64(n)).collect::<Vec<_>>());
+        assert_eq!(stored_primes_up_to(100).len(), 25);
+
+        // windows around 2^32 and 1e15, against the Miller–Rabin test, with segments cut in the middle
+        for low in [(1u64 << 32) - 1000, 1_000_000_000_000_000 - 77_777] {
+            let high = low + 3 * SEGMENT as u64 + 12_345;
+            assert_eq!(primes_in_range(low, high), (low..=high).filter(|&n| is_prime_u64(n)).collect::<Vec<_>>());
+        }
+        assert_eq!(primes_in_range(0, 10), [2, 3, 5, 7]);
+        assert_eq!(primes_in_range(3, 3), [3]);
+        assert!(primes_in_range(24, 28).is_empty() && primes_in_range(10, 5).is_empty());
+    }
+}
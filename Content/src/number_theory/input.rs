@@ -0,0 +1,122 @@
+use std::{fmt, fs::File, io::{self, BufRead, BufReader}, path::Path};
+
+use rug::Integer;
+
+/// The default limit of `read_integer` on the digits of its input, far beyond anything that can be factorized, but
+/// small enough that a wrong file is rejected before it fills the memory.
+pub const MAX_DIGITS: usize = 1_000_000;
+
+/// Why an integer could not be read.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ReadIntegerError {
+    /// Reading the input failed.
+    Io(io::ErrorKind),
+    /// A byte that is not a digit of the base or a separator, with its offset from the start of the input.
+    InvalidDigit { position: usize, byte: u8 },
+    /// The input has no digits.
+    Empty,
+    /// The input has more than this many digits.
+    TooLarge(usize),
+}
+
+impl fmt::Display for ReadIntegerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReadIntegerError::Io(kind) => write!(f, "could not read the input: {}", kind),
+            ReadIntegerError::InvalidDigit { position, byte } => {
+                write!(f, "invalid digit {:?} at byte {}", char::from(*byte), position)
+            }
+            ReadIntegerError::Empty => write!(f, "the input has no digits"),
+            ReadIntegerError::TooLarge(limit) => write!(f, "the input has more than {} digits", limit),
+        }
+    }
+}
+
+impl std::error::Error for ReadIntegerError {}
+
+impl From<io::Error> for ReadIntegerError {
+    fn from(err: io::Error) -> Self {
+        ReadIntegerError::Io(err.kind())
+    }
+}
+
+/// Reads a non-negative integer of up to `max_digits` digits from `reader`, e.g a file or stdin: in decimal, or in
+/// hexadecimal after `0x`. The digits may be split by whitespace and backslashes, as numbers wrapped over several
+/// lines are (with or without line continuations), and the input holds nothing else. The input is read and checked
+/// a buffer at a time, so a file that is too large or isn't a number is rejected at the first digit too many or the
+/// first byte that doesn't belong, and the digits are converted at once at the end, which GMP does in subquadratic
+/// time.
+pub fn read_integer(mut reader: impl BufRead, max_digits: usize) -> Result<Integer, ReadIntegerError> {
+    let mut digits = Vec::new();
+    let mut radix = 10;
+    let mut position = 0;
+    loop {
+        let buffer = reader.fill_buf()?;
+        if buffer.is_empty() {
+            break;
+        }
+        for &byte in buffer {
+            match byte {
+                b'x' | b'X' if radix == 10 && digits == b"0" => {
+                    radix = 16;
+                    digits.clear();
+                }
+                _ if byte.is_ascii_whitespace() || byte == b'\\' => {}
+                _ if char::from(byte).is_digit(radix) => {
+                    if digits.len() == max_digits {
+                        return Err(ReadIntegerError::TooLarge(max_digits));
+                    }
+                    digits.push(byte);
+                }
+                _ => return Err(ReadIntegerError::InvalidDigit { position, byte }),
+            }
+            position += 1;
+        }
+        let length = buffer.len();
+        reader.consume(length);
+    }
+    if digits.is_empty() {
+        return Err(ReadIntegerError::Empty);
+    }
+    // every byte is a digit of the radix
+    Ok(Integer::from(Integer::parse_radix(&digits, radix as i32).unwrap()))
+}
+
+/// `read_integer` on the contents of a file.
+pub fn read_integer_file(path: impl AsRef<Path>, max_digits: usize) -> Result<Integer, ReadIntegerError> {
+    read_integer(BufReader::new(File::open(path)?), max_digits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_integer() {
+        let read = |input: &str| read_integer(input.as_bytes(), 40);
+        assert_eq!(read("  1234567890123\n"), Ok(Integer::from(1_234_567_890_123u64)));
+        // wrapped over lines, with and without continuations
+        assert_eq!(read("12345\\\n67890\r\n123 45"), Ok(Integer::from(123_456_789_012_345u64)));
+        assert_eq!(read("0x1F_ff"), Err(ReadIntegerError::InvalidDigit { position: 4, byte: b'_' }));
+        assert_eq!(read("0XdeadBEEF\n"), Ok(Integer::from(0xdead_beefu64)));
+        assert_eq!(read("0"), Ok(Integer::ZERO));
+        assert_eq!(read(" \n"), Err(ReadIntegerError::Empty));
+        assert_eq!(read("0x"), Err(ReadIntegerError::Empty));
+        assert_eq!(read("-5"), Err(ReadIntegerError::InvalidDigit { position: 0, byte: b'-' }));
+        assert_eq!(read(&"9".repeat(41)), Err(ReadIntegerError::TooLarge(40)));
+
+        // a number of 5000 digits, read through a small buffer from a file
+        let n = Integer::from(Integer::u_pow_u(7, 5916)) - 1u32;
+        let decimal = n.to_string();
+        assert_eq!(decimal.len(), 5000);
+        let wrapped: Vec<_> =
+            decimal.as_bytes().chunks(80).map(|line| String::from_utf8_lossy(line) + "\\\n").collect();
+        let path = std::env::temp_dir().join(format!("read_integer_{}.txt", std::process::id()));
+        std::fs::write(&path, wrapped.concat()).unwrap();
+        assert_eq!(read_integer_file(&path, MAX_DIGITS), Ok(n));
+        let file = BufReader::with_capacity(64, File::open(&path).unwrap());
+        assert_eq!(read_integer(file, 4999), Err(ReadIntegerError::TooLarge(4999)));
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(read_integer_file(&path, MAX_DIGITS), Err(ReadIntegerError::Io(io::ErrorKind::NotFound))));
+    }
+}
@@ -1,9 +1,55 @@
+#[cfg(feature = "sieve")]
+pub mod arithmetic;
+pub mod batch_gcd;
+pub mod characters;
 pub mod crt;
+#[cfg(feature = "sieve")]
 pub mod generate_primes;
+pub mod input;
+#[cfg(feature = "factorization")]
+pub mod order;
+#[cfg(feature = "sieve")]
+pub mod pseudoprimes;
+#[cfg(feature = "sieve")]
+pub mod prime_pi;
+pub mod primality;
+pub mod random;
+pub mod residues;
+pub mod roots;
+pub mod spf;
+#[cfg(feature = "factorization")]
+pub mod squares;
+#[cfg(feature = "factorization")]
+pub mod totient;
+pub(crate) mod word;
 
+#[cfg(feature = "sieve")]
+pub use self::arithmetic::{mu_range, mu_sieve, phi_range, phi_sieve, sigma_range, sigma_sieve};
+pub use self::batch_gcd::batch_gcd;
+pub use self::characters::{gauss_sum, gauss_sum_power, jacobi_sum, primitive_root, Cyclotomic};
 pub use self::crt::chinese_remainder_theorem;
 pub use self::crt::chinese_remainder_theorem_mut;
-pub use self::generate_primes::generate_primes;
+pub use self::crt::CrtAccumulator;
+#[cfg(feature = "sieve")]
+pub use self::generate_primes::{generate_primes, generate_primes_up_to, primes_in_range, stored_primes};
+pub use self::input::{read_integer, read_integer_file, ReadIntegerError};
+#[cfg(feature = "factorization")]
+pub use self::order::{
+    carmichael_lambda_factorization, find_generator, multiplicative_order, multiplicative_order_with_factorization,
+};
+#[cfg(feature = "sieve")]
+pub use self::pseudoprimes::{pseudoprime_statistics, Pseudoprime, PseudoprimeSample, PseudoprimeStats};
+#[cfg(feature = "sieve")]
+pub use self::prime_pi::{nth_prime, prime_pi};
+pub use self::primality::{bpsw, is_prime, is_prime_u64, lucas_lehmer, next_prime, pepin, prev_prime, proth};
+pub use self::random::{random_source, set_random_source, RandomSource};
+pub use self::residues::{euler_criterion, is_power_residue, solovay_strassen, strong_lucas_probable_prime, strong_probable_prime};
+pub use self::roots::{iroot_rem, isqrt_rem, prime_power, RootRem};
+pub use self::spf::{factorize_small, factorize_with_spf, smallest_prime_factor, spf_sieve, SPF_LIMIT};
+#[cfg(feature = "factorization")]
+pub use self::squares::{r2, r4, sum_of_two_squares};
+#[cfg(feature = "factorization")]
+pub use self::totient::totient_inverse;
 
 // to use:
 // let buffer = get_buffer();
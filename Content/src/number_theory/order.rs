@@ -0,0 +1,173 @@
+use rug::{ops::Pow, Complete, Integer};
+
+use super::is_prime;
+use crate::prime_factorization::prime_factorize;
+
+/// The prime factorization of n from `prime_factorize`, with a cofactor it left over taken as a prime if it is one.
+/// Fails with the cofactor if it is composite.
+pub(crate) fn full_factorization(n: &Integer) -> Result<Vec<(Integer, u32)>, Integer> {
+    let factorization = prime_factorize(n);
+    let rest = n / factorization.value();
+    let mut factors = factorization.into_factors();
+    if rest > 1 {
+        if !is_prime(&rest) {
+            return Err(rest);
+        }
+        factors.push((rest, 1));
+    }
+    factors.sort();
+    Ok(factors)
+}
+
+/// The prime factorization of Carmichael's function λ(n), the exponent of (Z/nZ)*, i.e the smallest m with a^m = 1
+/// for every a coprime to n. It is the lcm of the λ(p^e) of the prime powers of n, which are p^(e-1) (p - 1) for an
+/// odd p, and 1, 2 and 2^(e-2) for 2, 4 and 2^e with e >= 3, so both n and the p - 1 of its primes are factorized.
+/// None if n < 1, or if one of them can't be fully factorized.
+pub fn carmichael_lambda_factorization(n: &Integer) -> Option<Vec<(Integer, u32)>> {
+    if *n < 1 {
+        return None;
+    }
+    // the largest exponent of every prime among the λ(p^e)
+    let mut lambda: Vec<(Integer, u32)> = Vec::new();
+    let mut include = |q: Integer, e: u32| match lambda.iter_mut().find(|(p, _)| *p == q) {
+        Some((_, f)) => *f = (*f).max(e),
+        None => lambda.push((q, e)),
+    };
+    for (p, e) in full_factorization(n).ok()? {
+        if p == 2 {
+            if e >= 2 {
+                include(p, if e == 2 { 1 } else { e - 2 });
+            }
+            continue;
+        }
+        for (q, f) in full_factorization(&Integer::from(&p - 1u32)).ok()? {
+            include(q, f);
+        }
+        if e > 1 {
+            include(p, e - 1);
+        }
+    }
+    lambda.sort();
+    Some(lambda)
+}
+
+/// The order of a modulo n, the smallest k >= 1 with a^k = 1 mod n, given the prime factorization of a multiple of
+/// it such as λ(n) or φ(n) (e.g from `carmichael_lambda_factorization`, or known in advance). Starting from the
+/// multiple M, each prime q is divided out of it for as long as a^(M / q) stays 1, which takes a number of
+/// exponentiations in the number of primes of M, counted with multiplicity.
+///
+/// # Returns
+/// * `Some(order)` - The order of a.
+/// * `None` - n < 1, a is not coprime to n, or a^M != 1 for the product M of the factorization.
+pub fn multiplicative_order_with_factorization(a: &Integer, n: &Integer, factorization: &[(Integer, u32)])
+    -> Option<Integer> {
+    if *n < 1 || a.gcd_ref(n).complete() != 1 {
+        return None;
+    }
+    let one = Integer::from(1) % n;
+    let power = |exponent: &Integer| a.pow_mod_ref(exponent, n).unwrap().complete();
+    let mut order = factorization.iter().fold(Integer::from(1), |order, (q, e)| order * q.clone().pow(*e));
+    if power(&order) != one {
+        return None;
+    }
+    for (q, e) in factorization {
+        for _ in 0..*e {
+            let smaller = Integer::from(&order / q);
+            if power(&smaller) != one {
+                break;
+            }
+            order = smaller;
+        }
+    }
+    Some(order)
+}
+
+/// The order of a modulo n, the smallest k >= 1 with a^k = 1 mod n, from the factorization of λ(n) (see
+/// `carmichael_lambda_factorization`), which is where the time goes for large n.
+///
+/// # Returns
+/// * `Some(order)` - The order of a.
+/// * `None` - n < 1, a is not coprime to n, or n or the p - 1 of one of its primes couldn't be fully factorized.
+pub fn multiplicative_order(a: &Integer, n: &Integer) -> Option<Integer> {
+    if *n < 1 || a.gcd_ref(n).complete() != 1 {
+        return None;
+    }
+    multiplicative_order_with_factorization(a, n, &carmichael_lambda_factorization(n)?)
+}
+
+/// The smallest generator of (Z/nZ)*, when the group is cyclic, which is for n = 1, 2, 4, p^k and 2p^k with an odd
+/// prime p (then λ(n) = φ(n)). A candidate g generates the group when g^(λ(n) / q) != 1 for every prime q of λ(n),
+/// and there are φ(φ(n)) of them, so the search ends quickly. For a prime n this is a primitive root, as
+/// `characters::primitive_root` finds for a u64 by trial division.
+///
+/// # Returns
+/// * `Some(generator)` - The smallest generator, 0 for n = 1.
+/// * `None` - n < 1, (Z/nZ)* is not cyclic, or n or the p - 1 of its odd prime couldn't be fully factorized.
+pub fn find_generator(n: &Integer) -> Option<Integer> {
+    if *n < 1 {
+        return None;
+    }
+    if *n <= 2 {
+        return Some(Integer::from(n - 1u32));
+    }
+    let factors = full_factorization(n).ok()?;
+    let cyclic = match factors.as_slice() {
+        [(two, e)] if *two == 2 => *e == 2,
+        [(two, 1), _] if *two == 2 => true,
+        [(p, _)] => *p != 2,
+        _ => false,
+    };
+    if !cyclic {
+        return None;
+    }
+    let lambda = carmichael_lambda_factorization(n)?;
+    let order = lambda.iter().fold(Integer::from(1), |order, (q, e)| order * q.clone().pow(*e));
+    let cofactors: Vec<Integer> = lambda.iter().map(|(q, _)| Integer::from(&order / q)).collect();
+    let mut g = Integer::from(2);
+    loop {
+        if g.gcd_ref(n).complete() == 1 && cofactors.iter().all(|c| g.pow_mod_ref(c, n).unwrap().complete() != 1) {
+            return Some(g);
+        }
+        g += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_multiplicative_order() {
+        // against the powers of a, for every a coprime to n
+        for n in 1..200u32 {
+            for a in (0..n).filter(|a| Integer::from(*a).gcd_u(n) == 1) {
+                let order = (1..=n).find(|&k| Integer::from(a).pow_mod(&Integer::from(k), &Integer::from(n)).unwrap()
+                    == Integer::from(1) % n).unwrap();
+                assert_eq!(multiplicative_order(&Integer::from(a), &Integer::from(n)), Some(Integer::from(order)),
+                    "failed for a = {}, n = {}", a, n);
+            }
+        }
+        let lambda = |n: u32| carmichael_lambda_factorization(&Integer::from(n)).unwrap().iter()
+            .fold(Integer::from(1), |acc, (q, e)| acc * q.clone().pow(*e)).to_u64().unwrap();
+        assert_eq!((lambda(561), lambda(1 << 10), lambda(1), lambda(4)), (80, 256, 1, 2));
+
+        // 2 has order 127 modulo 2^127 - 1, and -1 has order 2 modulo any n > 2
+        let m127 = (Integer::from(1) << 127u32) - 1u32;
+        assert_eq!(multiplicative_order(&Integer::from(2), &m127), Some(Integer::from(127)));
+        let phi = [(Integer::from(2), 1), (Integer::from(500_000_003), 1)];
+        let p = Integer::from(1_000_000_007);
+        assert_eq!(multiplicative_order_with_factorization(&(p.clone() - 1u32), &p, &phi), Some(Integer::from(2)));
+        assert_eq!(multiplicative_order_with_factorization(&Integer::from(5), &p, &phi[..1]), None);
+        assert_eq!(multiplicative_order(&Integer::from(6), &Integer::from(9)), None);
+
+        // a generator has order φ(n), and there are none modulo 8 or 15
+        for n in [3u32, 4, 9, 10, 25, 50, 243, 998_244_353] {
+            let g = find_generator(&Integer::from(n)).unwrap();
+            let factors = carmichael_lambda_factorization(&Integer::from(n)).unwrap();
+            let phi = factors.iter().fold(Integer::from(1), |acc, (q, e)| acc * q.clone().pow(*e));
+            assert_eq!(multiplicative_order(&g, &Integer::from(n)), Some(phi), "failed for n = {}", n);
+        }
+        assert_eq!(find_generator(&Integer::from(998_244_353)), Some(Integer::from(3)));
+        assert_eq!((find_generator(&Integer::from(8)), find_generator(&Integer::from(15))), (None, None));
+    }
+}
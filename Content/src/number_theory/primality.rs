@@ -0,0 +1,278 @@
+use once_cell::sync::OnceCell;
+use rug::Integer;
+
+use super::residues::{self, strong_lucas_probable_prime, strong_probable_prime};
+use super::word::{mul_mod, pow_mod};
+use crate::montgomery_mod_mult::Context;
+
+/// Odd primes that `is_prime` and `is_prime_u64` divide by before the probable prime tests.
+const SMALL_PRIMES: [u64; 24] = [3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83,
+    89, 97];
+/// Bases for which the strong probable prime test has no pseudoprime below 2^64 (found by Jim Sinclair).
+const MILLER_RABIN_BASES: [u64; 7] = [2, 325, 9375, 28178, 450_775, 9_780_504, 1_795_265_022];
+/// `next_prime` and `prev_prime` sieve their windows by the odd primes below this bound, and search below it directly.
+const SIEVE_LIMIT: u32 = 1 << 12;
+/// Odd numbers in each window of `next_prime` and `prev_prime`.
+const WINDOW: usize = 1 << 12;
+static SIEVE_PRIMES: OnceCell<Vec<u32>> = OnceCell::new();
+
+/// Whether n is prime, with the Miller–Rabin test to the bases of `MILLER_RABIN_BASES`, which is exact below 2^64.
+pub fn is_prime_u64(n: u64) -> bool {
+    if n < 2 {
+        return false;
+    }
+    if let Some(&p) = SMALL_PRIMES.iter().find(|&&p| n.is_multiple_of(p)) {
+        return n == p;
+    }
+    if n.is_multiple_of(2) {
+        return n == 2;
+    }
+    // n - 1 = d * 2^s
+    let s = (n - 1).trailing_zeros();
+    let d = (n - 1) >> s;
+    MILLER_RABIN_BASES.iter().all(|&base| {
+        let base = base % n;
+        if base == 0 {
+            return true;
+        }
+        let mut x = pow_mod(base, d, n);
+        if x == 1 || x == n - 1 {
+            return true;
+        }
+        for _ in 1..s {
+            x = mul_mod(x, x, n);
+            if x == n - 1 {
+                return true;
+            }
+        }
+        false
+    })
+}
+
+/// The Baillie–PSW test of the odd n > 2: the strong probable prime test to base 2, then the strong Lucas test with
+/// Selfridge's parameters. The two fail on very different composites, and no composite that passes both is known
+/// (there is none below 2^64).
+///
+/// # Returns
+/// * `false` - n is composite (certain).
+/// * `true` - n is prime, or a BPSW pseudoprime (none is known).
+pub fn bpsw(n: &Integer) -> bool {
+    let mut ctx = Context::new(n.clone());
+    strong_probable_prime(&Integer::from(2), &mut ctx) && strong_lucas_probable_prime(n)
+}
+
+/// Whether the Mersenne number 2^p - 1 is prime, with the Lucas–Lehmer test: for an odd prime p, 2^p - 1 is prime
+/// exactly when s_(p - 2) = 0, where s_0 = 4 and s_(i + 1) = s_i^2 - 2 mod 2^p - 1. The reduction modulo 2^p - 1 is a
+/// shift and an addition, so the test costs about p squarings and no divisions.
+pub fn lucas_lehmer(p: u32) -> bool {
+    if p == 2 {
+        return true;
+    }
+    if !is_prime_u64(p as u64) {
+        return false;
+    }
+    let m = (Integer::from(1) << p) - 1u32;
+    let mut s = Integer::from(4);
+    for _ in 0..p - 2 {
+        s.square_mut();
+        s -= 2u32;
+        // x = (x >> p) * 2^p + (x & m) = (x >> p) + (x & m) mod 2^p - 1, and the sum is below 2m
+        s = Integer::from(&s >> p) + (s & &m);
+        if s >= m {
+            s -= &m;
+        }
+    }
+    s == 0
+}
+
+/// Whether the Proth number N = k * 2^s + 1 is prime, for an odd k < 2^s, with Proth's theorem: for an a with Jacobi
+/// symbol (a/N) = -1, N is prime exactly when a^((N - 1) / 2) = -1 mod N. Such an a is found among the first odd
+/// numbers unless N is a square, so the answer is exact and costs one exponentiation. Panics if k is not an odd number
+/// below 2^s.
+pub fn proth(k: &Integer, s: u32) -> bool {
+    assert!(k.is_odd() && *k > 0 && k.significant_bits() <= s, "k must be odd and below 2^s");
+    let n = (Integer::from(k << s)) + 1u32;
+    if n.is_perfect_square() {
+        return false;
+    }
+    let mut a = Integer::from(3);
+    loop {
+        match a.jacobi(&n) {
+            -1 => break,
+            0 => return a == n,
+            _ => a += 2,
+        }
+    }
+    let n_minus_1 = Integer::from(&n - 1u32);
+    let mut ctx = Context::new(n);
+    residues::pow_mod(&a, &(n_minus_1.clone() >> 1u32), &mut ctx) == n_minus_1
+}
+
+/// Whether the Fermat number F_m = 2^(2^m) + 1 is prime, with Pépin's test: F_m is prime exactly when
+/// 3^((F_m - 1) / 2) = -1 mod F_m (for m >= 1, while F_0 = 3 is prime).
+pub fn pepin(m: u32) -> bool {
+    if m == 0 {
+        return true;
+    }
+    let n = (Integer::from(1) << (1u32 << m)) + 1u32;
+    let n_minus_1 = Integer::from(&n - 1u32);
+    let mut ctx = Context::new(n);
+    residues::pow_mod(&Integer::from(3), &(n_minus_1.clone() >> 1u32), &mut ctx) == n_minus_1
+}
+
+/// Whether n is prime: `is_prime_u64` below 2^64, which is exact, and trial division by the primes below 100 then
+/// `bpsw` above. This is the test the factorization pipeline runs on every factor; unlike `is_probably_prime(20)`
+/// it runs no random Miller–Rabin rounds on top of BPSW, so a prime costs one exponentiation and one Lucas sequence.
+/// Above 2^64, numbers of a special form get their exact test instead: `lucas_lehmer` for 2^p - 1, `pepin` for
+/// 2^(2^m) + 1 and `proth` for the other k * 2^s + 1 with k < 2^s.
+pub fn is_prime(n: &Integer) -> bool {
+    if let Some(small) = n.to_u64() {
+        return is_prime_u64(small);
+    }
+    if *n < 0 || n.is_even() {
+        return false;
+    }
+    let n_plus_1 = Integer::from(n + 1u32);
+    if n_plus_1.is_power_of_two() {
+        return lucas_lehmer(n_plus_1.find_one(0).unwrap());
+    }
+    let n_minus_1 = Integer::from(n - 1u32);
+    let s = n_minus_1.find_one(0).unwrap();
+    let k = n_minus_1 >> s;
+    if k == 1 {
+        return s.is_power_of_two() && pepin(s.trailing_zeros());
+    }
+    if SMALL_PRIMES.iter().any(|&p| n.is_divisible_u(p as u32)) {
+        return false;
+    }
+    match k.significant_bits() <= s {
+        true => proth(&k, s),
+        false => bpsw(n),
+    }
+}
+
+/// Marks which of the `WINDOW` odd numbers base, base ± 2, base ± 4, ... (upwards or downwards) have a prime factor
+/// below `SIEVE_LIMIT`, for an odd base whose window lies entirely above `SIEVE_LIMIT`.
+fn sieve_window(base: &Integer, upwards: bool) -> Vec<bool> {
+    let primes = SIEVE_PRIMES.get_or_init(|| (3..SIEVE_LIMIT).filter(|&p| is_prime_u64(p as u64)).collect());
+    let mut composite = vec![false; WINDOW];
+    for &p in primes {
+        // base ± 2i = 0 mod p, and (p + 1) / 2 is the inverse of 2
+        let r = base.mod_u(p);
+        let twice = if upwards { (p - r) % p } else { r };
+        let first = (twice as u64 * (p as u64 + 1) / 2 % p as u64) as usize;
+        composite.iter_mut().skip(first).step_by(p as usize).for_each(|c| *c = true);
+    }
+    composite
+}
+
+/// The smallest prime above n. Windows of odd numbers past n are sieved by the primes below `SIEVE_LIMIT`, and only
+/// the numbers left (about one odd number in seven) run `is_prime`.
+pub fn next_prime(n: &Integer) -> Integer {
+    if *n < SIEVE_LIMIT {
+        let start = n.to_u64().map_or(2, |n| n + 1);
+        return Integer::from((start..).find(|&m| is_prime_u64(m)).unwrap());
+    }
+    let mut base = Integer::from(n + 1u32);
+    if base.is_even() {
+        base += 1;
+    }
+    loop {
+        let composite = sieve_window(&base, true);
+        for i in (0..WINDOW).filter(|&i| !composite[i]) {
+            let candidate = Integer::from(&base + 2 * i as u32);
+            if is_prime(&candidate) {
+                return candidate;
+            }
+        }
+        base += 2 * WINDOW as u32;
+    }
+}
+
+/// The largest prime below n, found like in `next_prime` with windows going downwards, or None if n <= 2.
+pub fn prev_prime(n: &Integer) -> Option<Integer> {
+    let mut top = Integer::from(n - 1u32);
+    // the last window ends above SIEVE_LIMIT
+    let threshold = SIEVE_LIMIT as usize + 2 * WINDOW;
+    if top > threshold {
+        if top.is_even() {
+            top -= 1;
+        }
+        while top > threshold {
+            let composite = sieve_window(&top, false);
+            for i in (0..WINDOW).filter(|&i| !composite[i]) {
+                let candidate = Integer::from(&top - 2 * i as u32);
+                if is_prime(&candidate) {
+                    return Some(candidate);
+                }
+            }
+            top -= 2 * WINDOW as u32;
+        }
+    }
+    let top = top.to_u64()?;
+    (2..=top).rev().find(|&m| is_prime_u64(m)).map(Integer::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rug::integer::IsPrime;
+
+    #[test]
+    fn test_is_prime() {
+        // the strong pseudoprimes to several bases at once, and the largest primes below 2^64 and 2^32
+        assert!(!is_prime_u64(3_215_031_751) && !is_prime_u64(3_825_123_056_546_413_051));
+        assert!(is_prime_u64(18_446_744_073_709_551_557) && is_prime_u64(4_294_967_291));
+        assert!(!is_prime_u64(0) && !is_prime_u64(1) && is_prime_u64(2) && is_prime_u64(97));
+
+        // strong pseudoprimes to base 2 (2047 = 23 * 89 is the smallest), which the Lucas test catches
+        assert!(!bpsw(&Integer::from(2047)) && !bpsw(&Integer::from(3_215_031_751u64)));
+        // 2^89 - 1 and 2^127 - 1 are prime, their product and the square of the first are not
+        let m89 = (Integer::from(1) << 89u32) - 1u32;
+        let m127 = (Integer::from(1) << 127u32) - 1u32;
+        assert!(is_prime(&m89) && is_prime(&m127) && bpsw(&m127));
+        assert!(!is_prime(&Integer::from(&m89 * &m127)) && !is_prime(&Integer::from(&m89 * &m89)));
+        assert!(!is_prime(&Integer::from(-7)) && !is_prime(&Integer::from(1)) && is_prime(&Integer::from(2)));
+        // agrees with GMP just above 2^64, where the u64 path stops
+        let mut n = (Integer::from(1) << 64u32) + 1u32;
+        for _ in 0..2000 {
+            assert_eq!(is_prime(&n), n.is_probably_prime(30) != IsPrime::No, "failed for n = {}", n);
+            n += 2;
+        }
+
+        // agrees with GMP across window boundaries, in both directions, from below 2^12 to above 2^64
+        for start in [Integer::from(-10), Integer::from(0), Integer::from(4000), Integer::from(12_000),
+            Integer::from(u64::MAX - 100), (Integer::from(1) << 200u32) - 5u32] {
+            let mut up = start.clone();
+            for _ in 0..50 {
+                let next = next_prime(&up);
+                assert_eq!(next, up.clone().max(Integer::from(1)).next_prime(), "failed after {}", up);
+                up = next;
+            }
+            let mut down = up;
+            while down > start.clone().max(Integer::from(2)) {
+                let prev = prev_prime(&down).unwrap();
+                assert_eq!(next_prime(&prev), down);
+                down = prev;
+            }
+        }
+        assert_eq!(prev_prime(&Integer::from(3)), Some(Integer::from(2)));
+        assert_eq!(prev_prime(&Integer::from(2)), None);
+        // the special forms, against GMP
+        let exponents: Vec<u32> = (2..200).filter(|&p| lucas_lehmer(p)).collect();
+        assert_eq!(exponents, [2, 3, 5, 7, 13, 17, 19, 31, 61, 89, 107, 127]);
+        assert!(is_prime(&((Integer::from(1) << 521u32) - 1u32)) && !is_prime(&((Integer::from(1) << 523u32) - 1u32)));
+        assert!((1..=4).all(pepin) && !(5..=9).any(pepin));
+        assert!(!is_prime(&((Integer::from(1) << 96u32) + 1u32)));
+        for s in [66, 70, 189, 201] {
+            for k in (1..200).step_by(2) {
+                let n = (Integer::from(k) << s) + 1u32;
+                assert_eq!(proth(&Integer::from(k), s), n.is_probably_prime(30) != IsPrime::No, "failed for {}", n);
+                assert_eq!(is_prime(&n), proth(&Integer::from(k), s));
+            }
+        }
+        // 2^89 - 1 is prime
+        let m89 = (Integer::from(1) << 89u32) - 1u32;
+        assert_eq!(prev_prime(&next_prime(&m89)), Some(m89));
+    }
+}
@@ -0,0 +1,80 @@
+use super::generate_primes::primes_in_range;
+
+/// Numbers sieved at a time when `nth_prime` walks from its estimate to the prime.
+const NTH_PRIME_WINDOW: u64 = 1 << 20;
+
+/// The number of primes up to x, with Lucy_Hedgehog's method (a form of Meissel–Lehmer): S(v), the count of the
+/// numbers in [2, v] that no prime below p divides, is only needed at the values v = x / i, of which there are about
+/// 2 sqrt(x). Going over the primes p up to sqrt(x), S(v) loses the numbers whose smallest prime is p, which is
+/// S(v / p) - S(p - 1) for every v >= p^2, and ends as π(v). Runs in O(x^(3/4)) time and O(sqrt(x)) memory, so
+/// x = 1e12 takes about a second.
+pub fn prime_pi(x: u64) -> u64 {
+    if x < 2 {
+        return 0;
+    }
+    let r = x.isqrt();
+    // small[v] = S(v) for v <= r, and large[i] = S(x / i) for i <= r
+    let mut small: Vec<u64> = (0..=r).map(|v| v.saturating_sub(1)).collect();
+    let mut large: Vec<u64> = (0..=r).map(|i| x.checked_div(i).map_or(0, |v| v - 1)).collect();
+    for p in 2..=r {
+        if small[p as usize] == small[p as usize - 1] {
+            continue;
+        }
+        let below = small[p as usize - 1];
+        let square = p * p;
+        for i in 1..=r.min(x / square) {
+            let d = i * p;
+            let s = if d <= r { large[d as usize] } else { small[(x / d) as usize] };
+            large[i as usize] -= s - below;
+        }
+        for v in (square..=r).rev() {
+            small[v as usize] -= small[(v / p) as usize] - below;
+        }
+    }
+    large[1]
+}
+
+/// The n-th prime (the first is 2). Starts from Dusart's lower bound n (ln n + ln ln n - 1), which is within a
+/// fraction of a percent of the prime for large n, counts the primes up to it with `prime_pi`, and sieves windows of
+/// `NTH_PRIME_WINDOW` numbers from there until it has n of them. Panics if n is 0.
+pub fn nth_prime(n: u64) -> u64 {
+    assert!(n >= 1, "the primes are counted from 1");
+    let mut x = match n < 6 {
+        true => 1,
+        false => {
+            let ln = (n as f64).ln();
+            (n as f64 * (ln + ln.ln() - 1.0)) as u64
+        }
+    };
+    let mut count = prime_pi(x);
+    loop {
+        let primes = primes_in_range(x + 1, x + NTH_PRIME_WINDOW);
+        if count + primes.len() as u64 >= n {
+            return primes[(n - count - 1) as usize];
+        }
+        count += primes.len() as u64;
+        x += NTH_PRIME_WINDOW;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::number_theory::stored_primes;
+
+    #[test]
+    fn test_prime_pi() {
+        let powers = [0, 4, 25, 168, 1229, 9592, 78_498, 664_579, 5_761_455, 50_847_534, 455_052_511];
+        for (k, &count) in powers.iter().enumerate() {
+            assert_eq!(prime_pi(10u64.pow(k as u32)), count, "failed for 10^{}", k);
+        }
+        assert_eq!(prime_pi(1_000_000_000_000), 37_607_912_018);
+        assert_eq!((prime_pi(0), prime_pi(2), prime_pi(3), prime_pi(24)), (0, 1, 2, 9));
+
+        let primes = stored_primes();
+        for n in (1..100).chain([1000, 12_345, 1_565_927]) {
+            assert_eq!(nth_prime(n), primes[n as usize - 1] as u64, "failed for n = {}", n);
+        }
+        assert_eq!(nth_prime(100_000_000), 2_038_074_743);
+    }
+}
@@ -0,0 +1,191 @@
+use rug::{integer::IsPrime, Integer};
+
+use super::generate_primes::stored_primes;
+use super::random::random_bits;
+use super::residues::{strong_lucas_probable_prime, strong_probable_prime};
+use crate::montgomery_mod_mult::Context;
+
+// odd numbers sieved at a time by `pseudoprime_statistics` over a range
+const SIEVE_BLOCK: usize = 1 << 16;
+// Miller–Rabin rounds (on top of BPSW) of rug's test, for the numbers that pass all the tests but are too large for
+// the sieve to settle
+const CONFIRMATION_ROUNDS: u32 = 30;
+
+/// The numbers that `pseudoprime_statistics` goes through. Only the odd ones above 3 are tested.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PseudoprimeSample {
+    /// Every number in [low, high).
+    Range { low: Integer, high: Integer },
+    /// `count` random odd numbers of exactly `bits` bits (drawn from `random_source`).
+    Random { bits: u32, count: u64 },
+}
+
+/// A composite that passed at least one of the tests of a `pseudoprime_statistics` run.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Pseudoprime {
+    pub n: Integer,
+    /// The bases of the run that n is a strong pseudoprime to.
+    pub strong_bases: Vec<u32>,
+    /// Whether n is a strong Lucas pseudoprime (with Selfridge's parameters).
+    pub lucas: bool,
+}
+
+/// The counts of a `pseudoprime_statistics` run.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PseudoprimeStats {
+    /// The odd numbers above 3 that were looked at.
+    pub tested: u64,
+    pub primes: u64,
+    pub composites: u64,
+    /// The strong pseudoprimes to each base of the run, in the order of the bases.
+    pub strong: Vec<u64>,
+    /// The strong pseudoprimes to all the bases at once.
+    pub strong_all: u64,
+    pub lucas: u64,
+    /// The composites that pass the strong test to every base and the Lucas test, i.e BPSW when the bases are [2].
+    pub strong_all_and_lucas: u64,
+}
+
+impl PseudoprimeStats {
+    /// Runs the tests on the composite n and counts it, returning the `Pseudoprime` if it passed any test.
+    fn record(&mut self, n: &Integer, bases: &[u32], ctx: &mut Context) -> Option<Pseudoprime> {
+        self.composites += 1;
+        ctx.change_mod(n);
+        let strong_bases: Vec<u32> = bases.iter()
+            .copied()
+            .filter(|&base| strong_probable_prime(&Integer::from(base), ctx))
+            .collect();
+        let lucas = strong_lucas_probable_prime(n);
+        for (count, base) in self.strong.iter_mut().zip(bases) {
+            *count += strong_bases.contains(base) as u64;
+        }
+        let all = strong_bases.len() == bases.len();
+        self.strong_all += all as u64;
+        self.lucas += lucas as u64;
+        self.strong_all_and_lucas += (all && lucas) as u64;
+        (!strong_bases.is_empty() || lucas).then(|| Pseudoprime { n: n.clone(), strong_bases, lucas })
+    }
+}
+
+/// Whether the odd n > 3, which no sieving prime divides, is composite: any failed test proves it, and the numbers
+/// that pass every test are settled by rug's test.
+fn survivor_is_composite(n: &Integer, bases: &[u32], ctx: &mut Context) -> bool {
+    ctx.change_mod(n);
+    !bases.iter().all(|&base| strong_probable_prime(&Integer::from(base), ctx))
+        || !strong_lucas_probable_prime(n)
+        || n.is_probably_prime(CONFIRMATION_ROUNDS) == IsPrime::No
+}
+
+/// Counts the strong pseudoprimes to each of the bases and the strong Lucas pseudoprimes among the odd numbers of
+/// the sample, calling `callback` on every composite that passes at least one of the tests, as soon as it is found.
+/// The tests are `strong_probable_prime` (with Montgomery multiplication) and `strong_lucas_probable_prime`.
+///
+/// Over a range, a segmented sieve with `stored_primes` finds the composites, so the tests only run on them. Up to
+/// the square of the largest stored prime (about 6e14) this tells the primes apart exactly; above it the numbers
+/// left by the sieve go through the tests, and the ones that pass all of them are confirmed with rug's test.
+/// A random sample has no sieve, so every number is tested that way.
+pub fn pseudoprime_statistics(sample: &PseudoprimeSample, bases: &[u32], mut callback: impl FnMut(&Pseudoprime))
+    -> PseudoprimeStats {
+    let mut stats = PseudoprimeStats { strong: vec![0; bases.len()], ..Default::default() };
+    let mut ctx = Context::new(Integer::from(5));
+
+    match sample {
+        PseudoprimeSample::Random { bits, count } => {
+            for _ in 0..*count {
+                let mut n = random_bits(*bits);
+                n.set_bit(bits.saturating_sub(1), true);
+                n.set_bit(0, true);
+                if n <= 3 {
+                    continue;
+                }
+                stats.tested += 1;
+                if !survivor_is_composite(&n, bases, &mut ctx) {
+                    stats.primes += 1;
+                } else if let Some(pseudoprime) = stats.record(&n, bases, &mut ctx) {
+                    callback(&pseudoprime);
+                }
+            }
+        }
+        PseudoprimeSample::Range { low, high } => {
+            let primes = stored_primes();
+            let largest = Integer::from(*primes.last().unwrap());
+            let exact = Integer::from(largest.square_ref()) >= *high;
+            // the first odd number above 3 in the range
+            let mut start = Integer::from(low.max(&Integer::from(5)));
+            start.set_bit(0, true);
+            let mut marks = vec![false; SIEVE_BLOCK];
+
+            while start < *high {
+                // the block holds start, start + 2, ..., below high
+                let length = (Integer::from(high - &start) + 1u32) >> 1u32;
+                let length = length.to_usize().map_or(SIEVE_BLOCK, |length| length.min(SIEVE_BLOCK));
+                let end = Integer::from(&start + 2 * length as u64);
+                marks[..length].fill(false);
+                for &p in primes.iter().skip(1) {
+                    let p = p as u64;
+                    if end <= p * p {
+                        break;
+                    }
+                    // the first index i with p | start + 2i, using 1/2 = (p + 1) / 2 mod p
+                    let r = start.mod_u(p as u32) as u64;
+                    let mut i = (p - r) % p * p.div_ceil(2) % p;
+                    if start <= p && Integer::from(&start + 2 * i) == p {
+                        i += p;
+                    }
+                    while (i as usize) < length {
+                        marks[i as usize] = true;
+                        i += p;
+                    }
+                }
+
+                for (i, &marked) in marks[..length].iter().enumerate() {
+                    let n = Integer::from(&start + 2 * i as u64);
+                    stats.tested += 1;
+                    if !marked && (exact || !survivor_is_composite(&n, bases, &mut ctx)) {
+                        stats.primes += 1;
+                    } else if let Some(pseudoprime) = stats.record(&n, bases, &mut ctx) {
+                        callback(&pseudoprime);
+                    }
+                }
+                start = end;
+            }
+        }
+    }
+    stats
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pseudoprime_statistics() {
+        let sample = PseudoprimeSample::Range { low: Integer::new(), high: Integer::from(100_000) };
+        let mut found = Vec::new();
+        let stats = pseudoprime_statistics(&sample, &[2, 3], |pseudoprime| found.push(pseudoprime.clone()));
+
+        // 9590 primes below 1e5, without 2 and 3
+        assert_eq!((stats.tested, stats.primes, stats.composites), (49_998, 9590, 40_408));
+        let strong_2: Vec<Integer> = found.iter().filter(|p| p.strong_bases.contains(&2)).map(|p| p.n.clone()).collect();
+        assert_eq!(strong_2[..5], [2047, 3277, 4033, 4681, 8321]);
+        assert_eq!((stats.strong[0], strong_2.len()), (16, 16));
+        let lucas: Vec<Integer> = found.iter().filter(|p| p.lucas).map(|p| p.n.clone()).collect();
+        assert_eq!(lucas[..5], [5459, 5777, 10877, 16109, 18971]);
+        assert_eq!(stats.lucas, 12);
+        // the smallest strong pseudoprime to both 2 and 3 is 1373653, and BPSW has none
+        assert_eq!((stats.strong_all, stats.strong_all_and_lucas), (0, 0));
+
+        // around 2^64, beyond the exact sieve, the tests agree with rug's
+        let low = Integer::from(u64::MAX) - 2000u32;
+        let high = Integer::from(&low + 4000u32);
+        let range = pseudoprime_statistics(&PseudoprimeSample::Range { low: low.clone(), high: high.clone() }, &[2], |_| {});
+        let expected = (0..2000u32).map(|i| Integer::from(&low + 2 * i))
+            .filter(|n| n.is_probably_prime(30) != IsPrime::No)
+            .count() as u64;
+        assert_eq!((range.tested, range.primes), (2000, expected));
+
+        let random = pseudoprime_statistics(&PseudoprimeSample::Random { bits: 64, count: 1000 }, &[2], |_| {});
+        assert_eq!(random.tested, 1000);
+        assert_eq!(random.primes + random.composites, 1000);
+    }
+}
@@ -0,0 +1,347 @@
+use std::{cell::RefCell, fmt, sync::{atomic::{AtomicU64, Ordering}, Arc, RwLock}};
+
+use rand::{rngs::StdRng, RngCore, SeedableRng};
+use rug::{integer::Order, rand::RandState, Complete, Integer};
+
+use super::primality::{is_prime, next_prime};
+
+/// Where the randomized algorithms of the crate (Pollard's rho, the ECM curves, Solovay–Strassen, ...) get their
+/// random numbers from, see `set_random_source`.
+#[derive(Clone, Default)]
+pub enum RandomSource {
+    /// Every thread seeds its own generator from the operating system. This is the default.
+    #[default]
+    Entropy,
+    /// Every thread starts its own generator from this seed, so runs on a single thread can be repeated exactly.
+    Seeded(u64),
+    /// Every number is drawn from this function, e.g on targets without an operating system source of entropy.
+    Custom(Arc<dyn Fn() -> u64 + Send + Sync>),
+}
+
+impl fmt::Debug for RandomSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RandomSource::Entropy => write!(f, "Entropy"),
+            RandomSource::Seeded(seed) => write!(f, "Seeded({})", seed),
+            RandomSource::Custom(_) => write!(f, "Custom(..)"),
+        }
+    }
+}
+
+static SOURCE: RwLock<RandomSource> = RwLock::new(RandomSource::Entropy);
+/// Bumped by every `set_random_source`, so the threads know they have to start their generators over.
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+enum Generator {
+    Std(Box<StdRng>),
+    Custom(Arc<dyn Fn() -> u64 + Send + Sync>),
+}
+
+thread_local! {
+    static GENERATOR: RefCell<Option<(u64, Generator)>> = const { RefCell::new(None) };
+    /// The generator of the innermost `with_seed` running on this thread, which takes over from the source.
+    static SCOPED: RefCell<Option<Box<StdRng>>> = const { RefCell::new(None) };
+}
+
+/// Puts back the generator that was scoped before a `with_seed`, even if its function panics.
+struct ScopeGuard(Option<Box<StdRng>>);
+
+impl Drop for ScopeGuard {
+    fn drop(&mut self) {
+        SCOPED.with(|cell| *cell.borrow_mut() = self.0.take());
+    }
+}
+
+/// Sets the source of all the random numbers drawn by the crate from now on, on every thread.
+pub fn set_random_source(source: RandomSource) {
+    *SOURCE.write().unwrap() = source;
+    GENERATION.fetch_add(1, Ordering::Release);
+}
+
+/// The source set by `set_random_source`.
+pub fn random_source() -> RandomSource {
+    SOURCE.read().unwrap().clone()
+}
+
+/// Runs f with every random number drawn on this thread coming from a generator started from the seed, whatever the
+/// source is, so a run can be replayed exactly: e.g a factorization, whose Pollard's rho walks and ECM curves are
+/// the same from one run to the next. Nothing changes with None. The threads f spawns should start from
+/// `child_seed()`. Calls can be nested, the innermost seed wins.
+pub fn with_seed<R>(seed: Option<u64>, f: impl FnOnce() -> R) -> R {
+    let Some(seed) = seed else {
+        return f();
+    };
+    let previous = SCOPED.with(|cell| cell.borrow_mut().replace(Box::new(StdRng::seed_from_u64(seed))));
+    let _guard = ScopeGuard(previous);
+    f()
+}
+
+/// The seed that a thread spawned inside a `with_seed` should pass to its own `with_seed`, drawn from the current
+/// generator, so that its numbers are replayed too (they differ from thread to thread). None outside of `with_seed`.
+/// Draw the seeds on the spawning thread, in a fixed order.
+pub fn child_seed() -> Option<u64> {
+    SCOPED.with(|cell| cell.borrow_mut().as_mut().map(|rng| rng.next_u64()))
+}
+
+/// A uniformly random u64 from the current source, or from the generator of `with_seed` inside one.
+pub fn random_u64() -> u64 {
+    if let Some(x) = child_seed() {
+        return x;
+    }
+    GENERATOR.with(|cell| {
+        let mut cell = cell.borrow_mut();
+        let generation = GENERATION.load(Ordering::Acquire);
+        if cell.as_ref().is_none_or(|(seen, _)| *seen != generation) {
+            let generator = match &*SOURCE.read().unwrap() {
+                RandomSource::Entropy => Generator::Std(Box::new(StdRng::from_rng(&mut rand::rng()))),
+                RandomSource::Seeded(seed) => Generator::Std(Box::new(StdRng::seed_from_u64(*seed))),
+                RandomSource::Custom(f) => Generator::Custom(f.clone()),
+            };
+            *cell = Some((generation, generator));
+        }
+        match &mut cell.as_mut().unwrap().1 {
+            Generator::Std(rng) => rng.next_u64(),
+            Generator::Custom(f) => f(),
+        }
+    })
+}
+
+/// A uniformly random number in [0, bound). Panics if the bound is 0.
+pub fn random_below_u64(bound: u64) -> u64 {
+    assert!(bound > 0, "the bound must be positive");
+    // reject the top values that would make the smaller residues more likely
+    let zone = u64::MAX - (u64::MAX - bound + 1) % bound;
+    loop {
+        let x = random_u64();
+        if x <= zone {
+            return x % bound;
+        }
+    }
+}
+
+/// A uniformly random integer of at most `bits` bits.
+pub fn random_bits(bits: u32) -> Integer {
+    let words: Vec<u64> = (0..bits.div_ceil(64)).map(|_| random_u64()).collect();
+    let mut x = Integer::from_digits(&words, Order::Lsf);
+    x.keep_bits_mut(bits);
+    x
+}
+
+/// A uniformly random integer in [0, bound). Panics if the bound is not positive.
+pub fn random_below(bound: &Integer) -> Integer {
+    assert!(*bound > 0, "the bound must be positive");
+    let bits = bound.significant_bits();
+    loop {
+        let x = random_bits(bits);
+        if x < *bound {
+            return x;
+        }
+    }
+}
+
+/// A `RandState` seeded from the current source, for the functions of rug (and of this crate) that take one.
+pub fn rand_state() -> RandState<'static> {
+    let mut state = RandState::new();
+    state.seed(&random_bits(128));
+    state
+}
+
+/// How `generate_semiprime` shares the bits of the modulus between its two primes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SemiprimeBalance {
+    /// Both primes have half of the bits (the larger one gets the odd bit), like RSA moduli.
+    #[default]
+    Balanced,
+    /// The smaller prime has this many bits, and the other one the rest, e.g to test the methods whose running time
+    /// depends on the smallest factor (Pollard's rho, ECM).
+    Unbalanced(u32),
+}
+
+/// A random probable prime of exactly `bits` bits, drawn from rand (which `rand_state` seeds from the crate's source).
+/// Panics if bits < 2.
+pub fn generate_prime(bits: u32, rand: &mut RandState) -> Integer {
+    assert!(bits >= 2, "a prime has at least 2 bits");
+    loop {
+        let mut candidate = Integer::from(Integer::random_bits(bits, rand));
+        // the top bit for the length, the bottom one for oddness (3 is the only 2 bit candidate)
+        candidate.set_bit(bits - 1, true);
+        candidate.set_bit(0, true);
+        if is_prime(&candidate) {
+            return candidate;
+        }
+    }
+}
+
+/// A random product of two probable primes with exactly `bits` bits, split between them as set by balance, for tests
+/// and benchmarks of the factorization methods. Panics if either prime would have fewer than 2 bits.
+pub fn generate_semiprime(bits: u32, balance: SemiprimeBalance, rand: &mut RandState) -> Integer {
+    let small_bits = match balance {
+        SemiprimeBalance::Balanced => bits / 2,
+        SemiprimeBalance::Unbalanced(small_bits) => small_bits,
+    };
+    assert!(small_bits >= 2 && bits >= small_bits + 2, "both primes need at least 2 bits");
+    loop {
+        let n = generate_prime(small_bits, rand) * generate_prime(bits - small_bits, rand);
+        // the product of a and b bit numbers has a + b - 1 or a + b bits
+        if n.significant_bits() == bits {
+            return n;
+        }
+    }
+}
+
+/// A random safe prime of exactly `bits` bits, a p with (p - 1) / 2 also prime, so the multiplicative group modulo p
+/// has a subgroup of prime order (p - 1) / 2, as Diffie–Hellman and ElGamal groups want. Both are probable primes.
+/// Panics if bits < 3.
+pub fn generate_safe_prime(bits: u32, rand: &mut RandState) -> Integer {
+    assert!(bits >= 3, "a safe prime has at least 3 bits");
+    loop {
+        let mut q = Integer::from(Integer::random_bits(bits - 1, rand));
+        q.set_bit(bits - 2, true);
+        q.set_bit(0, true);
+        // for q = 1 mod 3, 3 divides 2q + 1
+        if q.mod_u(3) == 1 || !is_prime(&q) {
+            continue;
+        }
+        let p = Integer::from(&q << 1u32) + 1u32;
+        if is_prime(&p) {
+            return p;
+        }
+    }
+}
+
+/// A random strong prime of exactly `bits` bits, built the way FIPS 186-4 (B.3.6) builds RSA primes: p - 1 has a prime
+/// factor p1 and p + 1 a prime factor p2, both of bits / 4 bits, so that neither Pollard's p - 1 nor Williams' p + 1
+/// splits a product of such primes. p is the first probable prime with p = 1 mod 2p1 and p = -1 mod p2 from a random
+/// start. Panics if bits < 16.
+pub fn generate_strong_prime(bits: u32, rand: &mut RandState) -> Integer {
+    assert!(bits >= 16, "a strong prime has at least 16 bits");
+    let aux_bits = bits / 4;
+    let p1 = generate_prime(aux_bits, rand);
+    let p2 = loop {
+        let p2 = generate_prime(aux_bits, rand);
+        if p2 != p1 {
+            break p2;
+        }
+    };
+    let two_p1 = Integer::from(&p1 << 1u32);
+    let step = Integer::from(&two_p1 * &p2);
+    // the residue with r = 1 mod 2p1 and r = -1 mod p2
+    let r = Integer::from(p2.invert_ref(&two_p1).unwrap()) * &p2
+        - Integer::from(two_p1.invert_ref(&p2).unwrap()) * &two_p1;
+    let limit = Integer::from(1) << bits;
+    loop {
+        let mut x = Integer::from(Integer::random_bits(bits, rand));
+        x.set_bit(bits - 1, true);
+        let mut p = Integer::from(&r - &x).modulo(&step) + x;
+        while p < limit {
+            if is_prime(&p) {
+                return p;
+            }
+            p += &step;
+        }
+    }
+}
+
+/// A random integer of exactly `bits` bits whose prime factors are all at most smoothness_bound, with its
+/// factorization (in increasing order), e.g for the order of a group whose discrete logs Pohlig–Hellman can solve.
+/// Each prime gets a random bit length first, so small and large primes both show up, and is then drawn from rand
+/// among the primes of that length that still fit in the remaining bits. Panics if bits is 0 or the bound is below 2.
+pub fn random_smooth(bits: u32, smoothness_bound: &Integer, rand: &mut RandState) -> (Integer, Vec<(Integer, u32)>) {
+    assert!(bits > 0, "an integer has at least 1 bit");
+    assert!(*smoothness_bound >= 2, "the smoothness bound must be at least 2");
+    let mut n = Integer::from(1);
+    let mut primes: Vec<Integer> = Vec::new();
+    while n.significant_bits() < bits {
+        // a prime below 2^room keeps n within bits bits, and 2 always fits
+        let room = bits - n.significant_bits() + 1;
+        let cap = smoothness_bound.clone().min((Integer::from(1) << room) - 1u32);
+        let prime_bits = 2 + rand.below(cap.significant_bits() - 1);
+        let low = Integer::from(1) << (prime_bits - 1);
+        let high = cap.min((Integer::from(1) << prime_bits) - 1u32);
+        let x = ((&high - &low).complete() + 1u32).random_below(rand) + &low;
+        let p = if is_prime(&x) { x } else { next_prime(&x) };
+        if p > high || Integer::from(&n * &p).significant_bits() > bits {
+            continue;
+        }
+        n *= &p;
+        primes.push(p);
+    }
+
+    primes.sort();
+    let mut factors: Vec<(Integer, u32)> = Vec::new();
+    for p in primes {
+        match factors.last_mut() {
+            Some((q, exponent)) if *q == p => *exponent += 1,
+            _ => factors.push((p, 1)),
+        }
+    }
+    (n, factors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rug::{integer::IsPrime, ops::Pow};
+
+    #[test]
+    fn test_random_source() {
+        // every draw runs on a new thread, whose generator starts from the seed
+        let draw = || std::thread::spawn(|| (random_u64(), random_bits(100), random_below(&Integer::from(1000))))
+            .join().unwrap();
+        set_random_source(RandomSource::Seeded(7));
+        let first = draw();
+        assert_eq!(draw(), first);
+        assert!(first.1.significant_bits() <= 100 && first.2 < 1000);
+
+        // a Weyl sequence, since the other tests may draw from it too
+        let counter = Arc::new(AtomicU64::new(0));
+        let next = counter.clone();
+        set_random_source(RandomSource::Custom(Arc::new(move || {
+            next.fetch_add(1, Ordering::Relaxed).wrapping_mul(0x9e37_79b9_7f4a_7c15)
+        })));
+        let (x, y) = std::thread::spawn(|| (random_u64(), random_u64())).join().unwrap();
+        assert_ne!(x, y);
+        assert!(counter.load(Ordering::Relaxed) >= 2);
+        assert_eq!(format!("{:?}", random_source()), "Custom(..)");
+
+        set_random_source(RandomSource::Entropy);
+        assert!((0..100).all(|_| random_below_u64(3) < 3));
+
+        // a scoped seed overrides the source, and its child seeds replay the spawned threads
+        let run = || with_seed(Some(11), || {
+            let seed = child_seed();
+            let child = std::thread::spawn(move || with_seed(seed, || random_bits(64))).join().unwrap();
+            (random_u64(), child, with_seed(Some(12), random_u64), random_u64())
+        });
+        let first = run();
+        assert_eq!(run(), first);
+        assert_ne!(first.0, first.3);
+        assert_eq!(child_seed(), None);
+        assert_eq!(with_seed(None, || 1), 1);
+
+        let mut rand = rand_state();
+        let p = generate_prime(64, &mut rand);
+        assert!(p.significant_bits() == 64 && p.is_probably_prime(30) != IsPrime::No);
+        assert_eq!(generate_prime(2, &mut rand), 3);
+        for (balance, small_bits) in [(SemiprimeBalance::Balanced, 20), (SemiprimeBalance::Unbalanced(12), 12)] {
+            let n = generate_semiprime(41, balance, &mut rand).to_u64().unwrap();
+            assert_eq!(64 - n.leading_zeros(), 41);
+            let p = (3..).step_by(2).find(|p| n.is_multiple_of(*p)).unwrap();
+            assert_eq!(64 - p.leading_zeros(), small_bits);
+            assert!(Integer::from(n / p).is_probably_prime(30) != IsPrime::No);
+        }
+        let p = generate_safe_prime(128, &mut rand);
+        assert!(p.significant_bits() == 128 && Integer::from(&p >> 1u32).is_probably_prime(30) != IsPrime::No);
+        assert_eq!(generate_safe_prime(3, &mut rand), 7);
+        let p = generate_strong_prime(256, &mut rand);
+        assert!(p.significant_bits() == 256 && p.is_probably_prime(30) != IsPrime::No);
+
+        for (bits, bound) in [(300, 1000u32), (64, 2), (20, 1_000_000), (1, 7)] {
+            let (n, factors) = random_smooth(bits, &Integer::from(bound), &mut rand);
+            assert_eq!(n.significant_bits(), bits);
+            assert_eq!(factors.iter().fold(Integer::from(1), |acc, (p, e)| acc * p.clone().pow(*e)), n);
+            assert!(factors.windows(2).all(|pair| pair[0].0 < pair[1].0));
+            assert!(factors.iter().all(|(p, _)| *p <= bound && p.is_probably_prime(30) != IsPrime::No));
+        }
+    }
+}
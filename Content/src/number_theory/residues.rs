@@ -0,0 +1,203 @@
+use rug::{ops::{DivFrom, RemRounding}, Assign, Integer};
+
+use super::random::random_below;
+use crate::montgomery_mod_mult::Context;
+
+/// Computes base^exponent mod n using Montgomery multiplication (see `Context::pow_mod`), where n is the modulus of ctx.
+/// The result is in standard form and in the range [0, n).
+pub(crate) fn pow_mod(base: &Integer, exponent: &Integer, ctx: &mut Context) -> Integer {
+    let mut b = Integer::from(base % &ctx.n);
+    if b.is_negative() {
+        b += &ctx.n;
+    }
+    ctx.to_montgomery_mut(&mut b);
+    let result = ctx.pow_mod(&b, exponent);
+    ctx.from_montgomery(result)
+}
+
+/// Solovay–Strassen probabilistic primality test.
+/// For `rounds` random bases a, checks that a^((n-1)/2) = (a/n) mod n, where (a/n) is the Jacobi symbol.
+/// A composite n passes a single round with probability at most 1/2.
+///
+/// # Returns
+/// * `false` - n is composite (certain).
+/// * `true` - n is probably prime.
+pub fn solovay_strassen(n: &Integer, rounds: u32) -> bool {
+    if *n < 2 {
+        return false;
+    }
+    if *n < 4 {
+        return true;
+    }
+    if n.is_even() {
+        return false;
+    }
+
+    let mut ctx = Context::new(n.clone());
+    let n_minus_1 = Integer::from(n - 1);
+    let exponent = Integer::from(&n_minus_1 >> 1);
+    let bound = Integer::from(n - 3);
+    let mut a = Integer::new();
+
+    for _ in 0..rounds {
+        // a is a random integer in [2, n - 2]
+        a.assign(random_below(&bound));
+        a += 2;
+
+        let jacobi = a.jacobi(n);
+        if jacobi == 0 {
+            return false;  // gcd(a, n) > 1
+        }
+
+        let x = pow_mod(&a, &exponent, &mut ctx);
+        let expected_one = jacobi == 1;
+        if (expected_one && x != 1) || (!expected_one && x != n_minus_1) {
+            return false;
+        }
+    }
+
+    true
+}
+
+/// Strong probable prime test (one Miller–Rabin round) of the odd n > 2 to the given base: with n - 1 = d * 2^s and
+/// d odd, n passes if a^d = 1 or a^(d * 2^r) = -1 mod n for some r < s. ctx must have n as its modulus, so a run over
+/// many bases (or many n, with `change_mod`) reuses it.
+///
+/// # Returns
+/// * `false` - n is composite (certain).
+/// * `true` - n is a strong probable prime to the base: a prime, or a strong pseudoprime to it.
+pub fn strong_probable_prime(base: &Integer, ctx: &mut Context) -> bool {
+    let n = ctx.n.clone();
+    let n_minus_1 = Integer::from(&n - 1);
+    let s = n_minus_1.find_one(0).unwrap_or(0);
+    let mut x = pow_mod(base, &Integer::from(&n_minus_1 >> s), ctx);
+    if x == 1 || x == n_minus_1 {
+        return true;
+    }
+    for _ in 1..s {
+        x.square_mut();
+        x %= &n;
+        if x == n_minus_1 {
+            return true;
+        }
+    }
+    false
+}
+
+/// Strong Lucas probable prime test of the odd n > 2, with Selfridge's parameters: D is the first of 5, -7, 9, -11, ...
+/// with (D/n) = -1, P = 1 and Q = (1 - D) / 4. With n + 1 = d * 2^s and d odd, n passes if U_d = 0 or
+/// V_(d * 2^r) = 0 mod n for some r < s. Together with the strong test to base 2 this is BPSW, which has no known
+/// pseudoprime.
+///
+/// # Returns
+/// * `false` - n is composite (certain).
+/// * `true` - n is a strong Lucas probable prime: a prime, or a strong Lucas pseudoprime.
+pub fn strong_lucas_probable_prime(n: &Integer) -> bool {
+    // no D has (D/n) = -1 for a square
+    if n.is_perfect_square() {
+        return false;
+    }
+    let mut d = Integer::from(5);
+    loop {
+        match d.jacobi(n) {
+            -1 => break,
+            0 if Integer::from(d.abs_ref()) != *n => return false,
+            _ => {}
+        }
+        // 5, -7, 9, -11, ...
+        d = -d;
+        d += if d.is_negative() { -2 } else { 2 };
+    }
+    let q = Integer::from(1 - &d) >> 2u32;
+    let reduce = |x: Integer| x.rem_euc(n);
+    // x / 2 mod n
+    let halve = |x: Integer| match x.is_odd() {
+        true => (x + n) >> 1,
+        false => x >> 1,
+    };
+
+    let n_plus_1 = Integer::from(n + 1);
+    let s = n_plus_1.find_one(0).unwrap_or(0);
+    let exponent = Integer::from(&n_plus_1 >> s);
+    // U_k, V_k and Q^k, from k = 1 doubling (and incrementing) along the bits of the exponent
+    let (mut u, mut v, mut q_k) = (Integer::from(1), Integer::from(1), reduce(q.clone()));
+    for i in (0..exponent.significant_bits() - 1).rev() {
+        u = reduce(u * &v);
+        v = reduce(Integer::from(v.square_ref()) - Integer::from(&q_k * 2));
+        q_k = reduce(q_k.square());
+        if exponent.get_bit(i) {
+            // U_(k+1) = (P U_k + V_k) / 2 and V_(k+1) = (D U_k + P V_k) / 2, with P = 1
+            (u, v) = (halve(reduce(Integer::from(&u + &v))), halve(reduce(Integer::from(&d * &u) + &v)));
+            q_k = reduce(q_k * &q);
+        }
+    }
+    if u == 0 || v == 0 {
+        return true;
+    }
+    for _ in 1..s {
+        // V_2k = V_k^2 - 2 Q^k
+        v = reduce(Integer::from(v.square_ref()) - Integer::from(&q_k * 2));
+        if v == 0 {
+            return true;
+        }
+        q_k = reduce(q_k.square());
+    }
+    false
+}
+
+/// Euler's criterion: returns whether a is a quadratic residue modulo the odd prime p,
+/// i.e a^((p-1)/2) = 1 mod p. Multiples of p count as residues (they are squares of 0).
+pub fn euler_criterion(a: &Integer, p: &Integer) -> bool {
+    is_power_residue(a, 2, p)
+}
+
+/// Generalized Euler's criterion: returns whether a is a k-th power residue modulo the prime p,
+/// i.e whether x^k = a mod p has a solution. Since (Z/pZ)* is cyclic, this holds exactly when
+/// a^((p-1)/gcd(k, p-1)) = 1 mod p. Multiples of p count as residues (they are k-th powers of 0).
+pub fn is_power_residue(a: &Integer, k: u32, p: &Integer) -> bool {
+    if a.is_divisible(p) || *p == 2 {
+        return true;
+    }
+
+    let p_minus_1 = Integer::from(p - 1);
+    let mut exponent = Integer::from(k);
+    exponent.gcd_mut(&p_minus_1);
+    exponent.div_from(&p_minus_1);  // exponent = (p-1) / gcd(k, p-1)
+
+    let mut ctx = Context::new(p.clone());
+    pow_mod(a, &exponent, &mut ctx) == 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rug::integer::IsPrime;
+
+    #[test]
+    fn test_solovay_strassen() {
+        for n in 0..10_000u32 {
+            let n = Integer::from(n);
+            assert_eq!(solovay_strassen(&n, 20), n.is_probably_prime(30) != IsPrime::No, "failed for n = {}", n);
+        }
+        // Carmichael numbers fool the Fermat test, but not Solovay–Strassen
+        for n in [561u32, 1105, 1729, 2465, 2821, 6601, 8911] {
+            assert!(!solovay_strassen(&Integer::from(n), 20));
+        }
+    }
+
+    #[test]
+    fn test_power_residue() {
+        let p = Integer::from(101);
+        for k in 1..12u32 {
+            let mut residues = [false; 101];
+            for x in 0..101u32 {
+                residues[(Integer::from(x).pow_mod(&Integer::from(k), &p).unwrap()).to_usize().unwrap()] = true;
+            }
+            for a in 0..101u32 {
+                assert_eq!(is_power_residue(&Integer::from(a), k, &p), residues[a as usize], "failed for a = {}, k = {}", a, k);
+            }
+        }
+        assert!(euler_criterion(&Integer::from(4), &p));
+        assert!(!euler_criterion(&Integer::from(2), &p));  // 101 = 5 mod 8
+    }
+}
@@ -0,0 +1,82 @@
+use rug::{Complete, Integer};
+
+use super::is_prime;
+
+/// The floor of the k-th root of n and what is left over, n = root^k + remainder.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RootRem {
+    pub root: Integer,
+    pub remainder: Integer,
+}
+
+impl RootRem {
+    /// Whether n is exactly root^k.
+    pub fn is_exact(&self) -> bool {
+        self.remainder == 0
+    }
+
+    /// The root, if n is exactly root^k.
+    pub fn exact_root(self) -> Option<Integer> {
+        self.is_exact().then_some(self.root)
+    }
+}
+
+/// The floor of the square root of n, with the remainder n - root^2. Panics if n is negative.
+pub fn isqrt_rem(n: &Integer) -> RootRem {
+    assert!(*n >= 0, "cannot take the square root of a negative number");
+    let (root, remainder) = n.sqrt_rem_ref().complete();
+    RootRem { root, remainder }
+}
+
+/// The k-th root of n rounded towards zero, with the remainder n - root^k (which has the sign of n).
+/// Panics if k is 0, or if k is even and n is negative.
+pub fn iroot_rem(n: &Integer, k: u32) -> RootRem {
+    assert!(k > 0, "cannot take the 0-th root");
+    assert!(k % 2 == 1 || *n >= 0, "cannot take an even root of a negative number");
+    let (root, remainder) = n.root_rem_ref(k).complete();
+    RootRem { root, remainder }
+}
+
+/// Writes n as p^k for a prime p, if n is a prime power (k = 1 when n is prime).
+pub fn prime_power(n: &Integer) -> Option<(Integer, u32)> {
+    if *n < 2 {
+        return None;
+    }
+    // the largest k whose root is exact leaves a base that is not a perfect power itself
+    let (base, k) = n.is_perfect_power()
+        .then(|| (2..n.significant_bits()).rev().find_map(|k| {
+            let r = iroot_rem(n, k);
+            r.is_exact().then_some((r.root, k))
+        }))
+        .flatten()
+        .unwrap_or((n.clone(), 1));
+    is_prime(&base).then_some((base, k))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rug::ops::Pow;
+
+    #[test]
+    fn test_roots() {
+        let r = isqrt_rem(&Integer::from(1_000_001));
+        assert_eq!((r.root.to_u32(), r.remainder.to_u32(), r.is_exact()), (Some(1000), Some(1), false));
+        assert!(isqrt_rem(&Integer::from(144)).is_exact());
+        assert_eq!(isqrt_rem(&Integer::from(144)).exact_root(), Some(Integer::from(12)));
+        assert_eq!(isqrt_rem(&Integer::from(145)).exact_root(), None);
+
+        let n = Integer::from(3).pow(40u32);
+        let r = iroot_rem(&n, 8);
+        assert_eq!((r.is_exact(), r.root), (true, Integer::from(243)));
+        let r = iroot_rem(&(n - 1u32), 8);
+        assert_eq!((r.is_exact(), r.root), (false, Integer::from(242)));
+        let r = iroot_rem(&Integer::from(-30), 3);
+        assert_eq!((r.root, r.remainder), (Integer::from(-3), Integer::from(-3)));
+
+        assert_eq!(prime_power(&(Integer::from(1) << 100u32)), Some((Integer::from(2), 100)));
+        assert_eq!(prime_power(&Integer::from(3).pow(40u32)), Some((Integer::from(3), 40)));
+        assert_eq!(prime_power(&Integer::from(1_000_000_007)), Some((Integer::from(1_000_000_007), 1)));
+        assert_eq!(prime_power(&Integer::from(36)), None);
+    }
+}
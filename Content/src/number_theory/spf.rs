@@ -0,0 +1,124 @@
+use once_cell::sync::OnceCell;
+
+/// Numbers below this bound are factorized with table lookups.
+pub const SPF_LIMIT: u32 = 1 << 20;
+
+/// table[n] is the smallest prime factor of the composite n, and 0 for primes, 0 and 1.
+/// Composites below 2^20 have a prime factor below 2^10, so u16 entries are enough (2 MiB in total).
+static SPF_TABLE: OnceCell<Vec<u16>> = OnceCell::new();
+
+fn spf_table() -> &'static [u16] {
+    SPF_TABLE.get_or_init(|| {
+        let limit = SPF_LIMIT as usize;
+        let mut table = vec![0u16; limit];
+        let mut p = 2;
+        while p * p < limit {
+            if table[p] == 0 {
+                for multiple in (p * p..limit).step_by(p) {
+                    if table[multiple] == 0 {
+                        table[multiple] = p as u16;
+                    }
+                }
+            }
+            p += 1;
+        }
+        table
+    })
+}
+
+/// Returns the smallest prime factor of n, or None if n < 2 or n >= SPF_LIMIT.
+/// The table is built on first use.
+pub fn smallest_prime_factor(n: u32) -> Option<u32> {
+    if !(2..SPF_LIMIT).contains(&n) {
+        return None;
+    }
+    match spf_table()[n as usize] {
+        0 => Some(n),
+        p => Some(p as u32),
+    }
+}
+
+/// Factorizes n >= 1 by dividing out smallest(n), the smallest prime factor of what is left, until 1 remains. The
+/// primes are in increasing order.
+fn factorize_with(mut n: u32, smallest: impl Fn(u32) -> u32) -> Vec<(u32, u32)> {
+    let mut factors: Vec<(u32, u32)> = Vec::new();
+    while n > 1 {
+        let p = smallest(n);
+        match factors.last_mut() {
+            Some((q, exponent)) if *q == p => *exponent += 1,
+            _ => factors.push((p, 1)),
+        }
+        n /= p;
+    }
+    factors
+}
+
+/// Factorizes n < SPF_LIMIT with O(log n) table lookups. The primes are in increasing order.
+/// Returns None if n is 0 or n >= SPF_LIMIT.
+pub fn factorize_small(n: u32) -> Option<Vec<(u32, u32)>> {
+    if n == 0 || n >= SPF_LIMIT {
+        return None;
+    }
+    Some(factorize_with(n, |n| smallest_prime_factor(n).unwrap()))
+}
+
+/// The smallest prime factor of every n up to limit, for factoring many numbers below ~1e8 with `factorize_with_spf`
+/// (the shared table of `smallest_prime_factor` stops at `SPF_LIMIT`). The linear sieve sets every entry once:
+/// each n is crossed off as p * m, with p its smallest prime factor, from the m with smallest prime factor >= p.
+/// Entries 0 and 1 are 0, and the table takes 4 * (limit + 1) bytes.
+pub fn spf_sieve(limit: u32) -> Vec<u32> {
+    let mut spf = vec![0u32; limit as usize + 1];
+    let mut primes: Vec<u32> = Vec::new();
+    for n in 2..=limit as usize {
+        if spf[n] == 0 {
+            spf[n] = n as u32;
+            primes.push(n as u32);
+        }
+        for &p in &primes {
+            let multiple = n * p as usize;
+            if p > spf[n] || multiple > limit as usize {
+                break;
+            }
+            spf[multiple] = p;
+        }
+    }
+    spf
+}
+
+/// Factorizes n with a table from `spf_sieve`, with O(log n) lookups. The primes are in increasing order.
+/// Returns None if n is 0 or beyond the table.
+pub fn factorize_with_spf(n: u32, spf: &[u32]) -> Option<Vec<(u32, u32)>> {
+    if n == 0 || n as usize >= spf.len() {
+        return None;
+    }
+    Some(factorize_with(n, |n| spf[n as usize]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_factorize_small() {
+        for n in (1..5000).chain(SPF_LIMIT - 5000..SPF_LIMIT) {
+            let factors = factorize_small(n).unwrap();
+            assert_eq!(factors.iter().map(|&(p, e)| p.pow(e)).product::<u32>(), n);
+            for &(p, _) in &factors {
+                assert!((2..p).take_while(|d| d * d <= p).all(|d| p % d != 0), "{} is not prime", p);
+            }
+        }
+        assert_eq!(factorize_small(0), None);
+        assert_eq!(factorize_small(SPF_LIMIT), None);
+        assert_eq!(smallest_prime_factor(1_048_573), Some(1_048_573));  // largest prime below 2^20
+
+        // a table of its own beyond SPF_LIMIT agrees with the shared one below it
+        let spf = spf_sieve(3_000_000);
+        for n in (1..5000).chain(SPF_LIMIT - 5000..SPF_LIMIT) {
+            assert_eq!(factorize_with_spf(n, &spf), factorize_small(n));
+        }
+        assert_eq!(factorize_with_spf(2_999_999, &spf), Some(vec![(2_999_999, 1)]));
+        assert_eq!(factorize_with_spf(3_000_000, &spf), Some(vec![(2, 6), (3, 1), (5, 6)]));
+        assert_eq!(factorize_with_spf(3_000_001, &spf), None);
+        assert_eq!(factorize_with_spf(0, &spf), None);
+    }
+}
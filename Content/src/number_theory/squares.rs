@@ -0,0 +1,156 @@
+use rug::{ops::Pow, Assign, Integer};
+
+use super::residues::pow_mod;
+use crate::montgomery_mod_mult::Context;
+use crate::prime_factorization::prime_factorize;
+
+/// Writes a prime p = 1 mod 4 as a^2 + b^2 with a > b > 0 (Cornacchia's algorithm): finds a square root x of -1
+/// mod p, then runs the Euclidean algorithm on p and x until the remainder drops below sqrt(p).
+fn prime_two_squares(p: &Integer) -> (Integer, Integer) {
+    let mut ctx = Context::new(p.clone());
+    let quarter = Integer::from(p - 1u32) >> 2u32;
+    // c^((p - 1) / 4) is a square root of -1 exactly when c is a quadratic non-residue
+    let mut c = Integer::from(2);
+    while c.jacobi(p) != -1 {
+        c += 1;
+    }
+    let mut x = pow_mod(&c, &quarter, &mut ctx);
+
+    let root = Integer::from(p.sqrt_ref());
+    let mut a = p.clone();
+    while x > root {
+        let r = Integer::from(&a % &x);
+        a.assign(&x);
+        x.assign(r);
+    }
+    let b = Integer::from(p - x.square_ref()).sqrt();
+    if x > b { (x, b) } else { (b, x) }
+}
+
+/// Writes n as a^2 + b^2 with a >= b >= 0, from the factorization of n: every prime p = 1 mod 4 is split with
+/// `prime_two_squares` and the results are multiplied as Gaussian integers (a + bi)(c + di).
+///
+/// # Returns
+/// * `Some((a, b))` - A decomposition of n.
+/// * `None` - n is negative, or a prime p = 3 mod 4 divides n to an odd power, so n is not a sum of two squares.
+pub fn sum_of_two_squares(n: &Integer) -> Option<(Integer, Integer)> {
+    if *n < 0 {
+        return None;
+    }
+    if *n == 0 {
+        return Some((Integer::new(), Integer::new()));
+    }
+
+    let (mut a, mut b) = (Integer::from(1), Integer::new());
+    let multiply = |a: &mut Integer, b: &mut Integer, c: &Integer, d: &Integer| {
+        let real = Integer::from(&*a * c) - Integer::from(&*b * d);
+        *b = Integer::from(&*a * d) + Integer::from(&*b * c);
+        *a = real;
+    };
+    for (p, e) in prime_factorize(n) {
+        if p.is_congruent_u(3, 4) {
+            if e % 2 == 1 {
+                return None;
+            }
+            let power = Integer::from((&p).pow(e / 2));
+            a *= &power;
+            b *= &power;
+            continue;
+        }
+        let (c, d) = match p == 2 {
+            true => (Integer::from(1), Integer::from(1)),
+            false => prime_two_squares(&p),
+        };
+        for _ in 0..e {
+            multiply(&mut a, &mut b, &c, &d);
+        }
+    }
+
+    a.abs_mut();
+    b.abs_mut();
+    if a < b {
+        std::mem::swap(&mut a, &mut b);
+    }
+    Some((a, b))
+}
+
+/// The number of ways to write n as a^2 + b^2, counting signs and order, from the factorization of n:
+/// r2(n) = 4 * product of (e + 1) over the primes p^e = 1 mod 4 dividing n, and 0 if a prime p = 3 mod 4
+/// divides n to an odd power.
+pub fn r2(n: &Integer) -> Integer {
+    if *n <= 0 {
+        return Integer::from((*n == 0) as u32);
+    }
+    let mut count = Integer::from(4);
+    for (p, e) in prime_factorize(n) {
+        if p.is_congruent_u(1, 4) {
+            count *= e + 1;
+        } else if p.is_congruent_u(3, 4) && e % 2 == 1 {
+            return Integer::new();
+        }
+    }
+    count
+}
+
+/// The number of ways to write n as a sum of four squares, counting signs and order, with Jacobi's formula:
+/// r4(n) = 8 * the sum of the divisors of n that are not multiples of 4. That is 8 sigma(n) for odd n,
+/// and 24 sigma(m) for n = 2^k m with k >= 1 and m odd.
+pub fn r4(n: &Integer) -> Integer {
+    if *n <= 0 {
+        return Integer::from((*n == 0) as u32);
+    }
+    let mut sigma = Integer::from(1);
+    for (p, e) in prime_factorize(n) {
+        if p == 2 {
+            continue;
+        }
+        // 1 + p + ... + p^e
+        let mut term = Integer::from(1);
+        for _ in 0..e {
+            term *= &p;
+            term += 1;
+        }
+        sigma *= term;
+    }
+    match n.is_even() {
+        true => sigma * 24,
+        false => sigma * 8,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_squares() {
+        // r2 by counting the pairs, and r4 as the convolution of r2 with itself
+        let roots = -18..=18i64;
+        let mut two = [0usize; 300];
+        for (a, b) in roots.clone().flat_map(|a| roots.clone().map(move |b| (a, b))) {
+            if a * a + b * b < 300 {
+                two[(a * a + b * b) as usize] += 1;
+            }
+        }
+        for n in 0..300usize {
+            let four: usize = (0..=n).map(|k| two[k] * two[n - k]).sum();
+            let two = two[n];
+            let n = Integer::from(n);
+            assert_eq!(r2(&n), two, "r2 failed for n = {}", n);
+            assert_eq!(r4(&n), four, "r4 failed for n = {}", n);
+
+            let decomposition = sum_of_two_squares(&n);
+            assert_eq!(decomposition.is_some(), two > 0, "failed for n = {}", n);
+            if let Some((a, b)) = decomposition {
+                assert!(a >= b && b >= 0 && Integer::from(a.square_ref()) + Integer::from(b.square_ref()) == n);
+            }
+        }
+
+        // primes = 1 mod 4, and 9
+        let n = Integer::from(1_000_000_009u64) * Integer::from(998_244_353u64) * 9u32 * 2u32;
+        let (a, b) = sum_of_two_squares(&n).unwrap();
+        assert_eq!(Integer::from(a.square_ref()) + Integer::from(b.square_ref()), n);
+        assert_eq!(r2(&n), 16);
+        assert_eq!(sum_of_two_squares(&Integer::from(3 * 5)), None);
+    }
+}
@@ -0,0 +1,71 @@
+use rug::Integer;
+
+use super::is_prime;
+use crate::prime_factorization::prime_factorize;
+
+/// Extends `n` (whose totient is m / rest) with powers of the primes below primes[end], collecting every n that
+/// reaches the totient m into `results`. Taking the primes in decreasing order makes every n come out once.
+fn extend(rest: &Integer, end: usize, n: &Integer, primes: &[Integer], results: &mut Vec<Integer>) {
+    if *rest == 1 {
+        results.push(n.clone());
+    }
+    for i in (0..end).rev() {
+        let p = &primes[i];
+        let p_minus_1 = Integer::from(p - 1u32);
+        if !rest.is_divisible(&p_minus_1) {
+            continue;
+        }
+        // phi(p^k) = (p - 1) p^(k - 1)
+        let mut rest = Integer::from(rest / &p_minus_1);
+        let mut n = Integer::from(n * p);
+        loop {
+            extend(&rest, i, &n, primes, results);
+            if !rest.is_divisible(p) {
+                break;
+            }
+            rest /= p;
+            n *= p;
+        }
+    }
+}
+
+/// Finds every n with phi(n) = m, in increasing order. The primes that can divide such an n are the p = d + 1 for
+/// the divisors d of m, so m is factorized, and n is built up from these primes one prime power at a time.
+/// Returns an empty vector when there is no such n (for m = 0 and every odd m > 1, among others).
+pub fn totient_inverse(m: &Integer) -> Vec<Integer> {
+    if *m < 1 {
+        return Vec::new();
+    }
+    let mut primes: Vec<Integer> = prime_factorize(m).divisors()
+        .map(|d| d + 1u32)
+        .filter(is_prime)
+        .collect();
+    primes.sort();
+
+    let mut results = Vec::new();
+    extend(m, primes.len(), &Integer::from(1), &primes, &mut results);
+    results.sort();
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::number_theory::phi_range;
+
+    #[test]
+    fn test_totient_inverse() {
+        // phi(n) >= sqrt(n / 2), so every n with phi(n) <= 300 is below 2 * 300^2
+        let phi = phi_range(1, 2 * 300 * 300 + 1);
+        for m in 0..=300u64 {
+            let expected: Vec<Integer> = (1..=phi.len() as u64).filter(|&n| phi[n as usize - 1] == m).map(Integer::from).collect();
+            assert_eq!(totient_inverse(&Integer::from(m)), expected, "failed for m = {}", m);
+        }
+
+        // the n with phi(n) = 2^32 are 2^a times a product of Fermat primes 3, 5, 17, 257, 65537
+        let m = Integer::from(1u64 << 32);
+        let results = totient_inverse(&m);
+        assert_eq!(results.len(), 32);
+        assert_eq!(results.last(), Some(&Integer::from(3u64 * 5 * 17 * 257 * 65537 * 4)));
+    }
+}
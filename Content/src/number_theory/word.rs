@@ -0,0 +1,46 @@
+#[cfg(feature = "factorization")]
+use num_traits::PrimInt;
+
+/// The greatest common divisor of a and b by Euclid's algorithm, for any primitive integer type.
+#[cfg(feature = "factorization")]
+pub(crate) fn gcd<T: PrimInt>(mut a: T, mut b: T) -> T {
+    while b != T::zero() {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+/// a * b mod n, through a u128.
+#[inline]
+pub(crate) fn mul_mod(a: u64, b: u64, n: u64) -> u64 {
+    (a as u128 * b as u128 % n as u128) as u64
+}
+
+/// a + b mod n, for a, b < n.
+#[cfg(feature = "dlog")]
+#[inline]
+pub(crate) fn add_mod(a: u64, b: u64, n: u64) -> u64 {
+    let (sum, overflow) = a.overflowing_add(b);
+    if overflow || sum >= n { sum.wrapping_sub(n) } else { sum }
+}
+
+/// a - b mod n, for a, b < n.
+#[cfg(feature = "dlog")]
+#[inline]
+pub(crate) fn sub_mod(a: u64, b: u64, n: u64) -> u64 {
+    if a >= b { a - b } else { a.wrapping_sub(b).wrapping_add(n) }
+}
+
+/// base^e mod n by square-and-multiply, for any base and n >= 1.
+pub(crate) fn pow_mod(mut base: u64, mut e: u64, n: u64) -> u64 {
+    let mut result = 1 % n;
+    base %= n;
+    while e > 0 {
+        if e & 1 == 1 {
+            result = mul_mod(result, base, n);
+        }
+        base = mul_mod(base, base, n);
+        e >>= 1;
+    }
+    result
+}
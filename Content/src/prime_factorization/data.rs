@@ -1,117 +1,492 @@
+use std::borrow::Cow;
+use std::fmt;
+
 use once_cell::sync::OnceCell;
-use rug::Integer;
+use rug::{ops::Pow, Integer};
 
-use crate::number_theory::generate_primes;
+use crate::montgomery_mod_mult::recoding::ladder_bits;
+use crate::number_theory::generate_primes::stored_primes_up_to;
+#[cfg(test)]
+use crate::number_theory::stored_primes;
+use crate::tuning::profile;
 
-use super::ecm::suyama::generate_parameters;
+#[cfg(feature = "ecm")]
+use super::ecm::{prac::PracChains, suyama::generate_parameters};
+#[cfg(feature = "ecm")]
+use super::structs::{CurveFamily, EcmRound};
 
 
 pub static ITERATIONS: usize = 200;
 pub static SIZE: usize = 128;
 pub static BOUNDS1: (usize, usize) = (50_000, 50 * 50_000);
-pub static BOUNDS2: (usize, usize) = (500_000, 50 * 500_000);
-pub static BLOCK_SIZE_1: usize = 2000;
-pub static BLOCK_SIZE_2: usize = 5000;
+pub static CURVE_BATCH: usize = 4;  // number of curves that go through ECM phase 1 together
+// number of points ECM phase 2 precomputes per curve, one per value of a Stage2Plan
+pub const STAGE2_TABLE_SIZE: usize = 2000;
+// the t-levels of the ECM schedule (see `ecm_schedule`): a level's round finds a factor of that many digits with
+// probability about 1 - 1/e. The B1 are those of GMP-ECM's table, the curves are scaled up for the smaller B2 and capped
+// at ITERATIONS, so the levels above t25 are somewhat weaker than their name.
+#[cfg(feature = "ecm")]
+pub static ECM_LEVELS: [(u32, EcmRound); 5] = [
+    (20, EcmRound { curves: 90, B1: 11_000, B2: 550_000 }),
+    (25, EcmRound { curves: 200, B1: 50_000, B2: 2_500_000 }),
+    (30, EcmRound { curves: 200, B1: 250_000, B2: 12_500_000 }),
+    (35, EcmRound { curves: 200, B1: 1_000_000, B2: 25_000_000 }),
+    (40, EcmRound { curves: 200, B1: 3_000_000, B2: 25_000_000 }),
+];
+// the prime modulo which GMP-ECM computes the CHECKSUM of a line of its save files (see `EcmCheckpoint::to_gmp_ecm`)
+#[cfg(feature = "ecm")]
+pub static GMP_ECM_CHECKSUM_MODULUS: u64 = 4_294_967_291;
+// the width of the wNAF of the stage 1 scalar on Edwards curves (see `edwards_phase1`): 2^(w - 2) odd multiples of the
+// point are tabulated, and there is an addition every w + 1 doublings on average
+#[cfg(feature = "ecm")]
+pub static EDWARDS_NAF_WIDTH: u32 = 4;
+// the ratios r / k that start the PRAC chain of a prime k in ECM stage 1 (see `prac`): the golden ratio and the
+// others GMP-ECM tries, of which every prime gets the one with the cheapest chain
+#[cfg(feature = "ecm")]
+pub static PRAC_MULTIPLIERS: [f64; 10] = [
+    0.618_033_988_749_894_9, 0.723_606_797_749_978_9, 0.580_178_728_295_464_1, 0.632_839_806_088_706_3,
+    0.612_429_949_509_495, 0.620_181_980_807_415_7, 0.617_214_616_534_403_9, 0.618_347_119_656_228,
+    0.617_889_199_871_893, 0.618_062_124_200_396_4,
+];
+// the cost in multiplications of a differential addition and of a doubling, to compare PRAC chains
+#[cfg(feature = "ecm")]
+pub static PRAC_COSTS: (u32, u32) = (6, 5);
+// the highest t-level of the schedule that prime_factorize runs
+#[cfg(feature = "ecm")]
+pub static ECM_SCHEDULE_LEVEL: u32 = 35;
+// a composite of d digits is given the levels up to d / 2 + ECM_EXTRA_DIGITS, two levels past the one aimed at its
+// largest possible smallest factor
+pub static ECM_EXTRA_DIGITS: u32 = 10;
+// (B1, B2) of the Pollard p - 1 pass that prime_factorize runs between Pollard's rho and ECM
+pub static P_MINUS_1_BOUNDS: (u32, u32) = (1_000_000, 25_000_000);
+// (B1, B2) of the Williams p + 1 pass that prime_factorize runs after p - 1, and its starting values, whose seed^2 - 4
+// (5, 12 and 32) have different squarefree parts, so that one of them is likely to catch a smooth p + 1
+pub static P_PLUS_1_BOUNDS: (u32, u32) = (300_000, 25_000_000);
+pub static P_PLUS_1_SEEDS: [u32; 3] = [3, 4, 6];
+// block sizes of the stage 2 walk of p - 1 and p + 1 (see `stage2::lucas_stage2`): the largest one up to B1 is used
+pub static LUCAS_BLOCK_SIZES: [usize; 5] = [2310, 210, 30, 6, 2];
+// composites below 2^SQUFOF_BITS are split with SQUFOF instead of Pollard's rho in prime_factorize
+pub static SQUFOF_BITS: u32 = 62;
+// composites of 50 to HART_LEHMAN_BITS bits get HART_STEPS steps of Hart's one line factoring and Lehman's method
+// up to k = LEHMAN_MAX_K in prime_factorize: after SQUFOF fails below 2^SQUFOF_BITS, and before Fermat's method and
+// Pollard's rho above it
+pub static HART_LEHMAN_MIN_BITS: u32 = 50;
+pub static HART_LEHMAN_BITS: u32 = 70;
+pub static HART_STEPS: u64 = 1 << 14;
+pub static LEHMAN_MAX_K: u64 = 1 << 12;
+// steps of Fermat's method that prime_factorize tries on every composite above 2^SQUFOF_BITS before Pollard's rho
+pub static FERMAT_STEPS: u64 = 4096;
+// rounds of is_probably_prime when verifying a prime found by prime_factorize: GMP runs BPSW, then reps - 24 Miller-Rabin
+// tests with random bases
+pub static VERIFY_REPS: u32 = 40;
+// Pollard's rho doubles the length of its cycle RHO_MAX_ITERATIONS times (up to 2^18 steps) before a walk gives up,
+// and takes a gcd every RHO_BATCH_SIZE steps
+pub static RHO_MAX_ITERATIONS: u32 = 19;
+pub static RHO_BATCH_SIZE: u64 = 4096;
+// budgets of the methods raced against each other in race_find_factor
+pub static RACE_RHO_ATTEMPTS: u32 = 16;
+pub static RACE_P_MINUS_1_B1: u32 = 1_000_000;
+pub static RACE_ECM_CURVES: usize = 32;
+// prove_prime proves primes of at least ECPP_MIN_BITS bits with ECPP instead of Pocklington's theorem, whose n - 1
+// would have to be half factored
+pub static ECPP_MIN_BITS: u32 = 160;
+// generate_provable_prime draws primes of up to PROVABLE_PRIME_DIRECT_BITS bits directly and proves them by factoring
+// p - 1, and builds the larger ones on a proven prime of about half their size
+pub static PROVABLE_PRIME_DIRECT_BITS: u32 = 32;
+// ECPP removes the primes up to ECPP_SMOOTH_BOUND from the order of every curve, and hopes for a prime cofactor
+pub static ECPP_SMOOTH_BOUND: u32 = 1 << 20;
+// the chain of ECPP ends at a prime of at most ECPP_LAST_BITS bits, whose p - 1 is factored at once for Pocklington
+pub static ECPP_LAST_BITS: u32 = 64;
+// prime_factorize runs the Pollard–Strassen method on the composites of up to STRASSEN_MAX_BITS bits that are left at
+// the end, with polynomials of degree at most STRASSEN_MAX_DEGREE
+#[cfg(feature = "ecm")]
+pub static STRASSEN_MAX_BITS: u32 = 90;
+#[cfg(feature = "ecm")]
+pub static STRASSEN_MAX_DEGREE: u64 = 1 << 16;
+// ECPP tries the discriminants -D of class number up to ECPP_MAX_CLASS_NUMBER for D up to ECPP_MAX_DISCRIMINANT, the
+// smallest class numbers first, until it has ECPP_CANDIDATES orders with a prime cofactor
+pub static ECPP_MAX_DISCRIMINANT: i64 = 5000;
+pub static ECPP_MAX_CLASS_NUMBER: usize = 20;
+pub static ECPP_CANDIDATES: usize = 8;
+// the quadratic sieve: (digits, factor base size, M) for composites of up to that many digits, which sieve over
+// [-M, M), the odd squarefree multipliers k that choose_multiplier picks from, the primes of the factor base it
+// doesn't sieve with (their logs are made up for by QS_TOLERANCE), the slack of the threshold in multiples of the log2
+// of the largest prime, and the relations it collects beyond the size of the factor base
+#[cfg(feature = "qs")]
+pub static QS_PARAMETERS: [(u32, usize, usize); 14] = [
+    (24, 100, 16_384), (30, 200, 16_384), (36, 400, 32_768), (42, 800, 32_768), (48, 1400, 32_768),
+    (54, 2200, 65_536), (60, 3500, 65_536), (66, 5000, 98_304), (72, 7000, 131_072), (78, 9500, 163_840),
+    (84, 13_000, 196_608), (90, 17_000, 262_144), (96, 21_000, 327_680), (102, 25_000, 393_216),
+];
+#[cfg(feature = "qs")]
+pub static QS_MULTIPLIERS: [u32; 24] =
+    [1, 3, 5, 7, 11, 13, 15, 17, 19, 21, 23, 29, 31, 33, 35, 37, 39, 41, 43, 47, 51, 53, 55, 57];
+#[cfg(feature = "qs")]
+pub static QS_SIEVE_START: usize = 8;
+#[cfg(feature = "qs")]
+pub static QS_TOLERANCE: f64 = 2.0;
+#[cfg(feature = "qs")]
+pub static QS_EXTRA_RELATIONS: usize = 64;
+// the large primes of the quadratic sieve: a value that splits over the factor base apart from a prime below
+// QS_LARGE_PRIME_MULTIPLIER times its largest prime is kept as a partial relation, and from
+// QS_DOUBLE_LARGE_PRIME_DIGITS digits on, so is one apart from two such primes, with the threshold lowered by
+// QS_DOUBLE_LARGE_PRIME_TOLERANCE more times the log2 of the largest prime to let them through
+#[cfg(feature = "qs")]
+pub static QS_LARGE_PRIME_MULTIPLIER: u64 = 64;
+#[cfg(feature = "qs")]
+pub static QS_DOUBLE_LARGE_PRIME_DIGITS: u32 = 72;
+#[cfg(feature = "qs")]
+pub static QS_DOUBLE_LARGE_PRIME_TOLERANCE: f64 = 0.4;
+// prime_factorize hands the composites of up to QS_MAX_DIGITS digits that ECM couldn't split to the quadratic sieve
+#[cfg(feature = "qs")]
+pub static QS_MAX_DIGITS: u32 = 90;
+
+/// The largest bound the primes of `get_data` have to reach: the trial division bound of the tuning profile, and the
+/// B2 of the p - 1 pass and of every ECM level (with the `ecm` feature). A `FactorizerBuilder` can't go beyond it.
+pub fn prime_limit() -> u32 {
+    let limit = profile().trial_division_bound.max(P_MINUS_1_BOUNDS.1).max(RACE_P_MINUS_1_B1);
+    #[cfg(feature = "ecm")]
+    let limit = ECM_LEVELS.iter().map(|(_, round)| round.B2).fold(BOUNDS1.1, usize::max).max(limit as usize) as u32;
+    limit
+}
 
+/// The curve parameters are only generated with the `ecm` feature.
 pub struct PrimeFactorizeData {
-    pub primes: Vec<u32>,
-    pub gaps1: (Vec<usize>, Vec<usize>),
-    pub s1: Vec<bool>,
+    /// The primes up to `prime_limit()`.
+    pub primes: Cow<'static, [u32]>,
+    #[cfg(feature = "ecm")]
     pub params1: [(u32, u32); ITERATIONS],
-    pub gaps2: (Vec<usize>, Vec<usize>),
-    pub s2: Vec<bool>,
-    pub params2: [(u32, u32); ITERATIONS]
 }
 
 pub static DATA: OnceCell<PrimeFactorizeData> = OnceCell::new();
 
+#[cfg(feature = "ecm")]
 pub fn get_data() -> &'static PrimeFactorizeData {
     DATA.get_or_init(|| {
-        let primes = generate_primes();
-        let gaps1 = calculate_gaps(&primes, BLOCK_SIZE_1, BOUNDS1.1 as u32);
-        let s1 = find_s(BOUNDS1.0 as u64, &primes);
-        let gaps2 = calculate_gaps(&primes, BLOCK_SIZE_2, BOUNDS2.1 as u32);
-        let s2 = find_s(BOUNDS2.0 as u64, &primes);
-        let params1 = generate_parameters();
-        let params2 = generate_parameters();
-
-        PrimeFactorizeData {
-            primes,
-            gaps1,
-            s1,
-            params1,
-            gaps2,
-            s2,
-            params2
-        }
+        PrimeFactorizeData { primes: stored_primes_up_to(prime_limit()), params1: generate_parameters() }
     })
 }
 
-fn calculate_gaps(primes: &Vec<u32>, block_size: usize, B2: u32) -> (Vec<usize>, Vec<usize>) {
-    static INF: usize = 1_000_000;
+/// The tables of one ECM round: the stage 1 scalar and the plan of stage 2.
+#[cfg(feature = "ecm")]
+pub struct EcmTables {
+    pub round: EcmRound,
+    pub s: Vec<bool>,
+    /// The same scalar as the PRAC chains of its prime factors, which stage 1 of the Montgomery curves follows.
+    pub chains: PracChains,
+    /// The full stage 1 scalar of the previous round, whose curves this round continues. None for the first round.
+    pub resume_from: Option<Vec<bool>>,
+    /// The chains of `resume_from`.
+    pub resume_chains: Option<PracChains>,
+    /// Must reach the B2 of the round, with a block size of at most its B1.
+    pub stage2: Stage2,
+    /// The t-level of the round when it comes from `ecm_schedule`: the round is skipped once the composites left are
+    /// too small to need it. None for rounds that always run.
+    pub level: Option<u32>,
+    /// The family of the curves, Suyama's curves unless the factorizer asks for another one.
+    pub family: CurveFamily,
+}
 
-    let half_block_size = block_size / 2;
-    let mut values: Vec<usize> = Vec::with_capacity(half_block_size);  // it actually should have size phi(block_size)/2 + 1
-    let mut index: Vec<usize> = vec![0; half_block_size + 1]; 
+/// How ECM stage 2 reaches the primes in (B1, B2].
+#[cfg(feature = "ecm")]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Stage2 {
+    /// Walks over the primes one by one, which needs all of them up to B2 from the sieve.
+    Primes(Stage2Plan),
+    /// Evaluates a polynomial at the giant steps, which covers every number in (B1, B2] at once.
+    Polyeval(PolyevalPlan),
+}
 
-    // mark all the multiples of 2 and 5 as not needed, because their gcd with the block size != 1
-    for i in (0..half_block_size).step_by(2) {
-        index[i] = INF;
-    }
-    
-    for i in (5..half_block_size).step_by(10) {
-        index[i] = INF;
+/// The plan of the polynomial evaluation stage 2 (Montgomery's FFT extension) for a block size and a bound B2.
+/// For each curve, the x coordinates of jQ for the same values of j as a `Stage2Plan` are the roots of a polynomial F,
+/// and F is evaluated at the x coordinates of the giant steps iDQ, where D is the block size, for every multiple iD
+/// within half a block of (B1, B2]. Every number c ± j in that range (every prime, in particular) is then covered
+/// by a difference x(iDQ) - x(jQ). The giant steps are multiplied into F a chunk of deg F at a time, so stage 2 costs
+/// about (B2 - B1) / φ(D) polynomial products of degree φ(D) / 2 instead of a multiplication per prime, and needs no
+/// primes at all: B2 can go far beyond the sieve.
+#[cfg(feature = "ecm")]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PolyevalPlan {
+    pub block_size: usize,
+    pub B2: u64,
+    /// The values of j, in increasing order.
+    pub values: Vec<usize>,
+}
+
+/// The tables of stage 2 for a block size and a bound B2. Stage 2 walks over the multiples c of the block size
+/// and pairs each prime p in (B1, B2] with the nearest one: p = c ± j, where j is odd, coprime to the block size and
+/// at most half of it. For each curve (or starting value of p - 1 and p + 1) it precomputes jQ for all these j, so a
+/// prime only costs a multiplication, see `stage2::Stage2Walk`.
+/// Plans depend neither on n nor on the curves, so one can be built once and used for any number of factorizations.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Stage2Plan {
+    pub block_size: usize,
+    pub B2: u32,
+    /// The values of j, in increasing order.
+    pub values: Vec<usize>,
+    /// For every prime up to B2 (in the order of `stored_primes`), the index in values of its j.
+    /// The primes that divide the block size have no j, and get usize::MAX.
+    pub gaps: Vec<usize>,
+}
+
+/// Builds the tables of a sequence of ECM rounds, where every round continues the curves of the one before it.
+/// The first round uses the first block size of the tuning profile, the later ones the second.
+/// The rounds must have increasing values of B1 (see `FactorizerBuilder::build`). With `polyeval`, stage 2 evaluates
+/// polynomials (see `PolyevalPlan`) instead of walking over the primes.
+#[cfg(feature = "ecm")]
+pub fn ecm_tables(rounds: &[EcmRound], primes: &[u32], polyeval: bool) -> Vec<EcmTables> {
+    let profile = profile();
+    let mut previous_B1 = 1;
+    rounds.iter().enumerate().map(|(i, round)| {
+        let block_size = if i == 0 { profile.ecm_block_size_1 } else { profile.ecm_block_size_2 };
+        let tables = EcmTables {
+            round: *round,
+            s: find_s(previous_B1, round.B1 as u64, primes),
+            chains: find_chains(previous_B1, round.B1 as u64, primes),
+            resume_from: (i > 0).then(|| find_s(1, previous_B1, primes)),
+            resume_chains: (i > 0).then(|| find_chains(1, previous_B1, primes)),
+            stage2: match polyeval {
+                true => Stage2::Polyeval(PolyevalPlan::new(block_size, round.B2 as u64).unwrap()),
+                false => Stage2::Primes(Stage2Plan::new(block_size, round.B2 as u32, primes).unwrap()),
+            },
+            level: None,
+            family: CurveFamily::Suyama,
+        };
+        previous_B1 = round.B1 as u64;
+        tables
+    }).collect()
+}
+
+/// The ECM rounds that the schedule runs on a composite of `digits` digits: the levels of `ECM_LEVELS` from t20 up to
+/// digits / 2 + `ECM_EXTRA_DIGITS`, in increasing order, but at most up to t`max_level`.
+/// The smallest factor of the composite has at most digits / 2 digits, so higher levels would be wasted on it.
+#[cfg(feature = "ecm")]
+pub fn ecm_schedule(digits: u32, max_level: u32) -> Vec<EcmRound> {
+    ECM_LEVELS.iter()
+        .filter(|&&(level, _)| level <= max_level && level <= digits / 2 + ECM_EXTRA_DIGITS)
+        .map(|&(_, round)| round)
+        .collect()
+}
+
+#[cfg(not(feature = "ecm"))]
+pub fn get_data() -> &'static PrimeFactorizeData {
+    DATA.get_or_init(|| PrimeFactorizeData { primes: stored_primes_up_to(prime_limit()) })
+}
+
+/// The values of j for a block size: the odd j up to half of it that are coprime to it. Fails if the block size is not
+/// even, or if there are more of them than the `STAGE2_TABLE_SIZE` points that are precomputed per curve.
+fn stage2_values(block_size: usize) -> Result<Vec<usize>, String> {
+    if block_size < 2 || !block_size.is_multiple_of(2) {
+        return Err("the block size must be even".to_string());
     }
-    
-    for i in 1..half_block_size {
-        if index[i] == 0 {
-            index[i] = values.len();
-            values.push(i);
-        }
+    let values: Vec<usize> = (1..=block_size / 2).step_by(2)
+        .filter(|&j| Integer::from(j).gcd_u(block_size as u32) == 1)
+        .collect();
+    if values.len() > STAGE2_TABLE_SIZE {
+        return Err(format!("the block size {} needs {} points, more than {}", block_size, values.len(), STAGE2_TABLE_SIZE));
     }
+    Ok(values)
+}
 
-    let mut gaps: Vec<usize> = Vec::with_capacity(primes.len());
+impl Stage2Plan {
+    /// Builds the plan for the primes up to B2, which must all be in `primes`.
+    /// The block size must be even, and small enough that its values of j fit in the `STAGE2_TABLE_SIZE` points
+    /// that are precomputed per curve (every block size up to 8000 does). When the plan is used, the block size must
+    /// also be at most B1, so that the walk starts past the first block and the primes in (B1, B2] don't divide it.
+    pub fn new(block_size: usize, B2: u32, primes: &[u32]) -> Result<Self, String> {
+        let values = stage2_values(block_size)?;
+        let half_block_size = block_size / 2;
 
-    let mut multiple = 0;
-    for &p in primes {
-        if p > B2 {
-            break;
+        // index[j] is the index of j in values
+        let mut index = vec![usize::MAX; half_block_size + 1];
+        for (i, &j) in values.iter().enumerate() {
+            index[j] = i;
         }
 
-        while multiple + (block_size as u32) < p {
-            multiple += block_size as u32;
-        }
-        
-        let mut v = p - multiple;
-        if v > half_block_size as u32 {
-            v = block_size as u32 - v;
+        let mut gaps: Vec<usize> = Vec::with_capacity(primes.partition_point(|&p| p <= B2));
+        let mut multiple = 0;
+        for &p in primes {
+            if p > B2 {
+                break;
+            }
+
+            while multiple + (block_size as u32) < p {
+                multiple += block_size as u32;
+            }
+
+            let mut v = p - multiple;
+            if v > half_block_size as u32 {
+                v = block_size as u32 - v;
+            }
+
+            gaps.push(index[v as usize]);
         }
 
-        gaps.push(index[v as usize]);
+        Ok(Self { block_size, B2, values, gaps })
+    }
+}
+
+#[cfg(feature = "ecm")]
+impl PolyevalPlan {
+    /// Builds the plan for the bound B2, with the same conditions on the block size as `Stage2Plan::new`.
+    /// Larger block sizes make fewer but larger polynomials, which pays off for a large B2.
+    pub fn new(block_size: usize, B2: u64) -> Result<Self, String> {
+        Ok(Self { block_size, B2, values: stage2_values(block_size)? })
     }
+}
 
-    (values, gaps)
+/// Three lines: `block_size B2`, then the values and the gaps separated by spaces, where `-` is a prime without j.
+impl fmt::Display for Stage2Plan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} {}", self.block_size, self.B2)?;
+        let values: Vec<String> = self.values.iter().map(usize::to_string).collect();
+        writeln!(f, "{}", values.join(" "))?;
+        let gaps: Vec<String> = self.gaps.iter()
+            .map(|&gap| if gap == usize::MAX { "-".to_string() } else { gap.to_string() })
+            .collect();
+        writeln!(f, "{}", gaps.join(" "))
+    }
 }
 
-fn find_s(B1: u64, primes: &Vec<u32>) -> Vec<bool> {
-    let mut s: Integer = Integer::ONE.clone();
-    // For each prime, compute the highest power pᵉ with pᵉ ≤ B₁ and multiply s by pᵉ.
-    for p in primes {
-        let mut p_pow = *p as u64;  
-        if p_pow > B1 {
-            break;
+impl Stage2Plan {
+    /// Reads a plan written with `to_string`. The tables are checked to be consistent with each other, so a corrupted
+    /// plan is rejected instead of making stage 2 panic, but not recomputed: that is what the plan saves.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut lines = text.lines();
+        let mut next = |what: &str| lines.next().ok_or_else(|| format!("the plan has no {} line", what));
+        let header: Vec<&str> = next("header")?.split_whitespace().collect();
+        let [block_size, B2] = header[..] else {
+            return Err("the header must be `block_size B2`".to_string());
+        };
+        let block_size: usize = block_size.parse().map_err(|_| format!("invalid block size {}", block_size))?;
+        let B2: u32 = B2.parse().map_err(|_| format!("invalid B2 {}", B2))?;
+        let values = next("values")?.split_whitespace()
+            .map(|value| value.parse::<usize>().map_err(|_| format!("invalid value {}", value)))
+            .collect::<Result<Vec<_>, _>>()?;
+        let gaps = next("gaps")?.split_whitespace()
+            .map(|gap| match gap {
+                "-" => Ok(usize::MAX),
+                _ => gap.parse::<usize>().map_err(|_| format!("invalid gap {}", gap)),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if block_size < 2 || !block_size.is_multiple_of(2) || values.len() > STAGE2_TABLE_SIZE
+            || values.iter().any(|&j| j % 2 == 0 || j > block_size / 2) || !values.is_sorted() {
+            return Err("the values don't match the block size".to_string());
         }
-        while p_pow * (*p as u64) <= B1 {
-            p_pow *= *p as u64;
+        if gaps.iter().any(|&gap| gap != usize::MAX && gap >= values.len()) {
+            return Err("a gap is out of the range of the values".to_string());
         }
-        s *= p_pow;
+        Ok(Self { block_size, B2, values, gaps })
     }
+}
+
+/// The stage 1 scalar of ECM and Pollard's p − 1 for the bound B1: the product of the largest powers pᵉ ≤ B1 of all
+/// primes p ≤ B1. `primes` must contain all the primes up to B1, like `stored_primes` does for B1 up to 2.5e7.
+///
+/// # Returns
+/// * `(s, bits)` - The scalar, and its bits from the most significant one down, without the leading one (the form
+///   that the ladder of ECM stage 1 walks through).
+pub fn stage1_exponent(B1: u64, primes: &[u32]) -> (Integer, Vec<bool>) {
+    let s = stage1_multiplier(1, B1, primes);
+    let bits = ladder_bits(&s).collect();
+    (s, bits)
+}
+
+/// The part of the stage 1 scalar for B1 that is not already covered by the one for B0 ≤ B1.
+fn stage1_multiplier(B0: u64, B1: u64, primes: &[u32]) -> Integer {
+    let mut s: Integer = Integer::ONE.clone();
+    for (p, e) in stage1_powers(B0, B1, primes) {
+        s *= Integer::from(p).pow(e);
+    }
+    s
+}
+
+/// The prime powers (p, e) of `stage1_multiplier`, in increasing order of p, without the ones with e = 0.
+fn stage1_powers(B0: u64, B1: u64, primes: &[u32]) -> impl Iterator<Item = (u32, u32)> + '_ {
+    // For each prime, the exponent of the highest power pᵉ ≤ B₁, minus the part already covered by B₀
+    let exponent = |p: u64, B: u64| (1..).take_while(|&e| p.checked_pow(e).is_some_and(|q| q <= B)).last().unwrap_or(0);
+    primes.iter()
+        .take_while(move |&&p| p as u64 <= B1)
+        .map(move |&p| (p, exponent(p as u64, B1) - exponent(p as u64, B0)))
+        .filter(|&(_, e)| e > 0)
+}
+
+/// The bits of s (without the leading one) such that multiplying the stage 1 result for B0 by s gives the one
+/// for B1. B0 = 1 gives the full stage 1 scalar.
+#[cfg(feature = "ecm")]
+pub(super) fn find_s(B0: u64, B1: u64, primes: &[u32]) -> Vec<bool> {
+    ladder_bits(&stage1_multiplier(B0, B1, primes)).collect()
+}
 
-    let n = s.significant_bits() - 1;
-    let mut s_bits: Vec<bool> = Vec::with_capacity(n as usize);
-    for i in (0..n).rev() {
-        s_bits.push(s.get_bit(i));
+/// The PRAC chains that take the stage 1 result for B0 to the one for B1, like `find_s`.
+#[cfg(feature = "ecm")]
+pub(super) fn find_chains(B0: u64, B1: u64, primes: &[u32]) -> PracChains {
+    PracChains::new(stage1_powers(B0, B1, primes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stage1_exponent() {
+        let primes = stored_primes();
+        // 2^3 * 3^2 * 5 * 7 = 2520
+        let (s, bits) = stage1_exponent(10, primes);
+        assert_eq!(s, 2520);
+        assert_eq!(bits, [false, false, true, true, true, false, true, true, false, false, false]);
+        assert_eq!(stage1_exponent(1, primes), (Integer::from(1), vec![]));
+        // the scalar for B1 = 100 is a multiple of the one for 10, and of p - 1 for every 100-powersmooth p - 1 such as 96
+        let (big, _) = stage1_exponent(100, primes);
+        assert!(big.is_divisible(&s));
+        assert!(big.is_divisible(&Integer::from(97 - 1)));
     }
 
-    s_bits
-}
\ No newline at end of file
+    #[cfg(feature = "ecm")]
+    #[test]
+    fn test_stage2_plan() {
+        use std::sync::atomic::AtomicBool;
+        use crate::prime_factorization::ecm::ecm_curves;
+
+        let primes = stored_primes();
+        // 2310 = 2 * 3 * 5 * 7 * 11, so phi(2310) / 2 = 240 values of j
+        let plan = Stage2Plan::new(2310, 1_000_000, primes).unwrap();
+        assert_eq!(plan.values.len(), 240);
+        assert_eq!(&plan.values[..3], [1, 13, 17]);
+        assert_eq!(Stage2Plan::parse(&plan.to_string()), Ok(plan.clone()));
+
+        // the default block size still gives the odd j that are not multiples of 5
+        let default_plan = Stage2Plan::new(2000, 100_000, primes).unwrap();
+        assert_eq!(default_plan.values.len(), 400);
+        assert!(Stage2Plan::new(2001, 100_000, primes).is_err());
+        assert!(Stage2Plan::new(9998, 100_000, primes).is_err());  // 2499 values
+        assert!(Stage2Plan::parse("2310 1000000\n1 2\n0 1").is_err());
+
+        let n = Integer::from(1_073_741_789u64) * 2_147_483_629u64;
+        let tables = EcmTables {
+            round: EcmRound { curves: 100, B1: 10_000, B2: 1_000_000 },
+            s: stage1_exponent(10_000, primes).1,
+            chains: find_chains(1, 10_000, primes),
+            resume_from: None,
+            resume_chains: None,
+            stage2: Stage2::Primes(plan),
+            level: None,
+            family: CurveFamily::Suyama,
+        };
+        let mut g = Integer::new();
+        assert_eq!(ecm_curves(&n, get_data(), &tables, 100, &AtomicBool::new(false), &mut g), Some(()));
+        assert!(g == 1_073_741_789u64 || g == 2_147_483_629u64);
+
+        // the same curves with a polynomial evaluation stage 2, with B2 beyond the primes of the sieve
+        let tables = EcmTables { stage2: Stage2::Polyeval(PolyevalPlan::new(2310, 30_000_000).unwrap()), ..tables };
+        assert_eq!(ecm_curves(&n, get_data(), &tables, 100, &AtomicBool::new(false), &mut g), Some(()));
+        assert!(g == 1_073_741_789u64 || g == 2_147_483_629u64);
+
+        // a 40 digit composite gets the levels up to t30, a 100 digit one all of them up to the maximum,
+        // and one of 18 digits is left to the other methods
+        assert_eq!(ecm_schedule(40, 35), ECM_LEVELS[..3].iter().map(|&(_, round)| round).collect::<Vec<_>>());
+        assert_eq!(ecm_schedule(100, 35).len(), 4);
+        assert!(ecm_schedule(18, 35).is_empty());
+    }
+}
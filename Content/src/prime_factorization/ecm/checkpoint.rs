@@ -0,0 +1,375 @@
+#![allow(non_snake_case)]
+use std::{fmt, fs, io, path::Path, sync::atomic::{AtomicBool, Ordering}};
+
+use rug::{Assign, Integer};
+
+use crate::montgomery_mod_mult::Context;
+use crate::number_theory::random::random_below_u64;
+use super::super::data::{find_chains, get_data, prime_limit, PolyevalPlan, Stage2, Stage2Plan};
+use super::super::data::{GMP_ECM_CHECKSUM_MODULUS, LUCAS_BLOCK_SIZES};
+use super::super::{CurveFamily, EcmCurve, CURVE_BATCH};
+use super::suyama::suyama_curve;
+use super::{ecm_iteration, ecm_phase1, MontgomeryPoint, Phase2};
+
+/// A curve of an `EcmCheckpoint`: the sigma of Suyama's parameterization, which defines the curve and its starting
+/// point, the bound B1 that stage 1 has reached, and the x coordinate of the point after stage 1 (Z = 1).
+/// A curve with B1 = 1 is still at its starting point.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CurveCheckpoint {
+    pub sigma: u64,
+    pub B1: u64,
+    pub X: Integer,
+    a24: Integer,
+}
+
+/// The progress of ECM on n, which can be saved to a file and resumed later (like the `-save` and `-resume` files of
+/// GMP-ECM): stage 1 can be continued to a larger B1 without starting the curves over, and stage 2 can be run on
+/// the saved points with any B2.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EcmCheckpoint {
+    pub n: Integer,
+    pub curves: Vec<CurveCheckpoint>,
+}
+
+impl CurveCheckpoint {
+    /// The point after stage 1 and a24, in the Montgomery form of ctx.
+    fn load(&self, ctx: &mut Context) -> (MontgomeryPoint, Integer) {
+        let X = ctx.to_montgomery(&self.X);
+        (MontgomeryPoint { X, Z: ctx.r_mod_n.clone() }, ctx.to_montgomery(&self.a24))
+    }
+}
+
+impl EcmCheckpoint {
+    /// Starts a curve for every sigma (each greater than 5) on an odd n > 1.
+    ///
+    /// ## Returns
+    /// * `Ok(checkpoint)` - The curves, at their starting points.
+    /// * `Err(d)` - Setting up a curve found the divisor d of n (see `suyama_curve`).
+    pub fn new(n: &Integer, sigmas: impl IntoIterator<Item = u64>) -> Result<Self, Integer> {
+        assert!(*n > 1 && n.is_odd(), "ECM needs an odd n > 1");
+        let curves = sigmas.into_iter()
+            .map(|sigma| {
+                assert!(sigma > 5, "sigma must be greater than 5");
+                let (X, a24) = suyama_curve(sigma, n)?;
+                Ok::<_, Integer>(CurveCheckpoint { sigma, B1: 1, X, a24 })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { n: n.clone(), curves })
+    }
+
+    /// Starts `count` curves with random values of sigma below 2^32, see `new`.
+    pub fn random(n: &Integer, count: usize) -> Result<Self, Integer> {
+        Self::new(n, (0..count).map(|_| 6 + random_below_u64(u32::MAX as u64 - 5)))
+    }
+
+    /// Runs stage 1 of every curve up to B1, continuing from the bound it has already reached, CURVE_BATCH curves at
+    /// a time. B1 can be at most `prime_limit()`.
+    ///
+    /// The checkpoint stays valid when the run stops early: the curves of a batch are only updated once it is done,
+    /// so a cancelled run (checked between batches) can be saved and continued later. A curve that reaches the point
+    /// at infinity modulo n itself can't find anything anymore, and is removed.
+    ///
+    /// ## Returns
+    /// * `Some((d, curve))` - A non-trivial factor of n and the curve that found it (with B2 = B1), which is left
+    ///   where it was.
+    /// * `None` - No factor was found, or the run was cancelled.
+    pub fn stage1(&mut self, B1: u64, cancel: &AtomicBool) -> Option<(Integer, EcmCurve)> {
+        assert!(B1 <= prime_limit() as u64, "B1 can be at most {}", prime_limit());
+        let primes = &get_data().primes;
+        let mut ctx = Context::new(self.n.clone());
+
+        // the curves that reached the same bound share the scalar that takes them to B1
+        let mut pending: Vec<(u64, usize)> = self.curves.iter().enumerate()
+            .filter(|(_, curve)| curve.B1 < B1)
+            .map(|(i, curve)| (curve.B1, i))
+            .collect();
+        pending.sort_unstable();
+        let mut dead = Vec::new();
+        let mut g = Integer::new();
+        for group in pending.chunk_by(|a, b| a.0 == b.0) {
+            let chains = find_chains(group[0].0, B1, primes);
+            for indices in group.chunks(CURVE_BATCH) {
+                if cancel.load(Ordering::Relaxed) {
+                    return None;
+                }
+                let mut batch: Vec<_> = indices.iter().map(|&(_, i)| self.curves[i].load(&mut ctx)).collect();
+                ecm_phase1(&mut ctx, &mut batch, &chains);
+
+                for (&(_, i), (P, _)) in indices.iter().zip(&batch) {
+                    g.assign(ctx.from_montgomery(&P.Z));
+                    if g.invert_mut(&self.n).is_err() {
+                        g.gcd_mut(&self.n);
+                        if g != self.n {
+                            let sigma = self.curves[i].sigma;
+                            return Some((g, EcmCurve { family: CurveFamily::Suyama, sigma, B1, B2: B1 }));
+                        }
+                        dead.push(i);
+                        continue;
+                    }
+                    let curve = &mut self.curves[i];
+                    curve.X.assign(ctx.from_montgomery(&P.X) * &g);
+                    curve.X %= &self.n;
+                    curve.B1 = B1;
+                }
+            }
+        }
+
+        dead.sort_unstable();
+        for i in dead.into_iter().rev() {
+            self.curves.remove(i);
+        }
+        None
+    }
+
+    /// Runs stage 2 up to B2 on every curve that has been through stage 1. B2 can go past `prime_limit()`, stage 2
+    /// then evaluates polynomials (see `PolyevalPlan`) instead of walking over the primes.
+    ///
+    /// ## Returns
+    /// * `Some((d, curve))` - A non-trivial factor of n and the curve that found it.
+    /// * `None` - No factor was found, or the run was cancelled (checked between curves).
+    pub fn stage2(&self, B2: u64, cancel: &AtomicBool) -> Option<(Integer, EcmCurve)> {
+        let primes = &get_data().primes;
+        let mut ctx = Context::new(self.n.clone());
+        let end = primes.partition_point(|&p| p as u64 <= B2);
+        let mut g = Integer::new();
+
+        // the block size depends on B1, so the plans are built for each block size used
+        let mut plans: Vec<(usize, Stage2)> = Vec::new();
+        for curve in self.curves.iter().filter(|curve| curve.B1 >= 2 && curve.B1 < B2) {
+            if cancel.load(Ordering::Relaxed) {
+                return None;
+            }
+            let block_size = LUCAS_BLOCK_SIZES.into_iter().find(|&size| size as u64 <= curve.B1).unwrap_or(2);
+            if !plans.iter().any(|(size, _)| *size == block_size) {
+                let stage2 = match B2 <= prime_limit() as u64 {
+                    true => Stage2::Primes(Stage2Plan::new(block_size, B2 as u32, primes).ok()?),
+                    false => Stage2::Polyeval(PolyevalPlan::new(block_size, B2).ok()?),
+                };
+                plans.push((block_size, stage2));
+            }
+            let (_, stage2) = plans.iter().find(|(size, _)| *size == block_size).unwrap();
+
+            let (mut Q, a24) = curve.load(&mut ctx);
+            let start = primes.partition_point(|&p| (p as u64) < curve.B1);
+            let phase2 = Phase2 { B1: curve.B1 as usize, stage2, primes, start, end };
+            ecm_iteration(&mut ctx, &self.n, &phase2, &mut Q, &a24, &mut g);
+            if g != 1 && g != self.n {
+                return Some((g, EcmCurve { family: CurveFamily::Suyama, sigma: curve.sigma, B1: curve.B1, B2 }));
+            }
+        }
+        None
+    }
+
+    /// Reads a checkpoint written with `to_string`. The curves are set up again from their sigma, and their points
+    /// are only checked to be reduced modulo n: a corrupted point makes the curve useless, but not wrong.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut lines = text.lines().enumerate()
+            .map(|(number, line)| (number + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'));
+        let n = match lines.next() {
+            Some((_, line)) => line.strip_prefix("n ")
+                .and_then(|n| n.trim().parse::<Integer>().ok())
+                .ok_or("the first line must be `n <n>`")?,
+            None => return Err("the checkpoint is empty".to_string()),
+        };
+        if n <= 1 || n.is_even() {
+            return Err("n must be odd and greater than 1".to_string());
+        }
+
+        let mut curves = Vec::new();
+        for (number, line) in lines {
+            let values: Vec<&str> = line.split_whitespace().collect();
+            let ["curve", sigma, B1, X] = values[..] else {
+                return Err(format!("line {}: expected `curve <sigma> <B1> <X>`", number));
+            };
+            let invalid = |what: &str| format!("line {}: invalid {}", number, what);
+            let sigma: u64 = sigma.parse().ok().filter(|&sigma| sigma > 5).ok_or_else(|| invalid("sigma"))?;
+            let B1: u64 = B1.parse().ok().filter(|&B1| B1 >= 1).ok_or_else(|| invalid("B1"))?;
+            let X: Integer = X.parse().ok().filter(|X: &Integer| *X >= 0 && *X < n).ok_or_else(|| invalid("X"))?;
+            let (start, a24) = suyama_curve(sigma, &n)
+                .map_err(|d| format!("line {}: the curve of sigma {} has the factor {} of n", number, sigma, d))?;
+            if B1 == 1 && X != start {
+                return Err(format!("line {}: X is not the starting point of the curve", number));
+            }
+            curves.push(CurveCheckpoint { sigma, B1, X, a24 });
+        }
+        Ok(Self { n, curves })
+    }
+
+    /// Reads a checkpoint file.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let text = fs::read_to_string(path)?;
+        Self::parse(&text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Writes the checkpoint to a file, in the format read by `load`.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        fs::write(path, self.to_string())
+    }
+
+    /// The curves in the format of GMP-ECM's save files, one line per curve, e.g
+    /// `METHOD=ECM; PARAM=0; SIGMA=1234; B1=50000; N=...; X=0x...; CHECKSUM=...; PROGRAM=math_algorithms 0.1.0;`.
+    /// `ecm -resume` continues them: the curves are its PARAM=0 curves, and X is the same coordinate.
+    pub fn to_gmp_ecm(&self) -> String {
+        self.curves.iter()
+            .map(|curve| format!("METHOD=ECM; PARAM=0; SIGMA={}; B1={}; N={}; X={:#x}; CHECKSUM={}; PROGRAM={} {};\n",
+                curve.sigma, curve.B1, self.n, curve.X, gmp_ecm_checksum(curve, &self.n),
+                env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION")))
+            .collect()
+    }
+
+    /// Reads the lines of a GMP-ECM save file, which can hold curves on several numbers, into a checkpoint for each
+    /// number (in the order they first appear). Only ECM curves given by their sigma with PARAM=0 can be read, and
+    /// a line whose CHECKSUM doesn't match is rejected. Unknown fields (WHO, TIME, ...) are ignored.
+    pub fn parse_gmp_ecm(text: &str) -> Result<Vec<Self>, String> {
+        let mut checkpoints: Vec<Self> = Vec::new();
+        for (number, line) in text.lines().enumerate().map(|(number, line)| (number + 1, line.trim())) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fields: Vec<(&str, &str)> = line.split(';')
+                .map(str::trim)
+                .filter(|field| !field.is_empty())
+                .map(|field| field.split_once('=').map(|(key, value)| (key.trim(), value.trim())))
+                .collect::<Option<_>>()
+                .ok_or_else(|| format!("line {}: expected `KEY=value;` fields", number))?;
+            let field = |key: &str| fields.iter().find(|(k, _)| *k == key).map(|&(_, value)| value);
+            let required = |key: &str| field(key).ok_or_else(|| format!("line {}: no {}", number, key));
+            let invalid = |key: &str| format!("line {}: invalid {}", number, key);
+
+            if required("METHOD")? != "ECM" {
+                return Err(format!("line {}: only ECM curves can be read", number));
+            }
+            if field("PARAM").is_some_and(|param| param != "0") {
+                return Err(format!("line {}: only PARAM=0 (Suyama's) curves can be read", number));
+            }
+            let sigma: u64 = required("SIGMA")?.parse().ok().filter(|&sigma| sigma > 5)
+                .ok_or_else(|| invalid("SIGMA"))?;
+            let B1: u64 = required("B1")?.parse().ok().filter(|&B1| B1 >= 1).ok_or_else(|| invalid("B1"))?;
+            let n: Integer = required("N")?.parse().ok().filter(|n: &Integer| *n > 1 && n.is_odd())
+                .ok_or_else(|| invalid("N"))?;
+            let X = required("X")?;
+            let X = match X.strip_prefix("0x").or_else(|| X.strip_prefix("0X")) {
+                Some(hex) => Integer::from_str_radix(hex, 16).ok(),
+                None => X.parse().ok(),
+            };
+            let X = X.filter(|X| *X >= 0 && *X < n).ok_or_else(|| invalid("X"))?;
+            let a24 = suyama_curve(sigma, &n)
+                .map_err(|d| format!("line {}: the curve of sigma {} has the factor {} of N", number, sigma, d))?
+                .1;
+            let curve = CurveCheckpoint { sigma, B1, X, a24 };
+            if field("CHECKSUM").is_some_and(|checksum| checksum.parse().ok() != Some(gmp_ecm_checksum(&curve, &n))) {
+                return Err(format!("line {}: the checksum doesn't match", number));
+            }
+
+            match checkpoints.iter_mut().find(|checkpoint| checkpoint.n == n) {
+                Some(checkpoint) => checkpoint.curves.push(curve),
+                None => checkpoints.push(Self { n, curves: vec![curve] }),
+            }
+        }
+        Ok(checkpoints)
+    }
+
+    /// Reads a GMP-ECM save file, see `parse_gmp_ecm`.
+    pub fn load_gmp_ecm<P: AsRef<Path>>(path: P) -> io::Result<Vec<Self>> {
+        let text = fs::read_to_string(path)?;
+        Self::parse_gmp_ecm(&text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Writes the curves to a file that GMP-ECM can resume, see `to_gmp_ecm`.
+    pub fn save_gmp_ecm<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        fs::write(path, self.to_gmp_ecm())
+    }
+}
+
+/// The CHECKSUM of a line of a GMP-ECM save file: B1 times the residues of sigma, N and X and PARAM + 1 (1 here)
+/// modulo `GMP_ECM_CHECKSUM_MODULUS`.
+fn gmp_ecm_checksum(curve: &CurveCheckpoint, n: &Integer) -> u64 {
+    let modulus = GMP_ECM_CHECKSUM_MODULUS;
+    [curve.sigma % modulus, n.mod_u(modulus as u32) as u64, curve.X.mod_u(modulus as u32) as u64]
+        .into_iter()
+        .fold(curve.B1 % modulus, |checksum, value| (checksum as u128 * value as u128 % modulus as u128) as u64)
+}
+
+/// The line `n <n>`, then a line `curve <sigma> <B1> <X>` for every curve.
+impl fmt::Display for EcmCheckpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "n {}", self.n)?;
+        for curve in &self.curves {
+            writeln!(f, "curve {} {} {}", curve.sigma, curve.B1, curve.X)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::number_theory::random::with_seed;
+    use crate::prime_factorization::ecm::suyama::suyama_parameterization;
+    use crate::prime_factorization::ITERATIONS;
+
+    #[test]
+    fn test_checkpoint() {
+        // the single curve matches the batched parameterization of the pipeline
+        let n = Integer::from(1_000_000_007u64) * 998_244_353u64;
+        let mut ctx = Context::new(n.clone());
+        let params: Vec<(u32, u32)> = (0..ITERATIONS as u32).map(|i| ((i + 6) * (i + 6) - 5, 4 * (i + 6))).collect();
+        let mut curves = vec![Default::default(); ITERATIONS];
+        suyama_parameterization(&mut ctx, &params, &mut curves).unwrap();
+        for (sigma, (P, a24)) in (6..).zip(&curves) {
+            let (X, expected_a24) = suyama_curve(sigma, &n).unwrap();
+            assert_eq!(ctx.from_montgomery(&P.X), X);
+            assert_eq!(ctx.from_montgomery(a24), expected_a24);
+        }
+        // sigma = 6 gives u = 31, so the denominators are not invertible modulo a multiple of 31
+        let mut ctx = Context::new(Integer::from(1_000_000_007u64) * 31u32);
+        assert_eq!(suyama_parameterization(&mut ctx, &params, &mut curves), Err(Integer::from(31)));
+
+        // stage 1 in two steps, through a saved checkpoint, gives the same points as a single run
+        let n = ((Integer::from(1) << 127) - 1u32) * ((Integer::from(1) << 89) - 1u32);
+        let never = AtomicBool::new(false);
+        let mut direct = EcmCheckpoint::new(&n, 100..110).unwrap();
+        assert_eq!(direct.stage1(2000, &never), None);
+        let mut resumed = EcmCheckpoint::new(&n, 100..110).unwrap();
+        assert_eq!(resumed.stage1(500, &never), None);
+        let mut resumed = EcmCheckpoint::parse(&resumed.to_string()).unwrap();
+        assert!(resumed.curves.iter().all(|curve| curve.B1 == 500));
+        assert_eq!(resumed.stage1(2000, &never), None);
+        assert_eq!(resumed, direct);
+
+        // a cancelled run leaves the curves where they were
+        let cancelled = AtomicBool::new(true);
+        assert_eq!(resumed.stage1(100_000, &cancelled), None);
+        assert_eq!(resumed, direct);
+
+        // enough curves find a 40 bit factor after stage 2
+        let p = Integer::from(1_000_000_000_039u64);
+        let n = (Integer::from(1) << 127) - 1u32;
+        let n = n * &p;
+        let factor = with_seed(Some(3762), || {
+            let mut checkpoint = EcmCheckpoint::random(&n, 40).unwrap();
+            checkpoint.stage1(5000, &never).or_else(|| checkpoint.stage2(500_000, &never))
+        });
+        assert_eq!(factor.map(|(factor, _)| factor).as_ref(), Some(&p));
+
+        for invalid in ["", "n 10\n", "n 15\ncurve 5 1 0\n", "n 15\ncurve 7 1 15\n", "n 1000003\ncurve 7 1 3\n"] {
+            assert!(EcmCheckpoint::parse(invalid).is_err());
+        }
+
+        // GMP-ECM save files, with the fields GMP-ECM adds and without the checksum
+        let lines = direct.to_gmp_ecm();
+        assert_eq!(EcmCheckpoint::parse_gmp_ecm(&lines).unwrap(), vec![direct.clone()]);
+        let other = EcmCheckpoint::new(&p, [7]).unwrap();
+        let text = format!("# saved\n{}{}", other.to_gmp_ecm(), lines.replace("PROGRAM=", "WHO=x@y; PROGRAM="));
+        assert_eq!(EcmCheckpoint::parse_gmp_ecm(&text).unwrap(), vec![other, direct.clone()]);
+        let curve = &direct.curves[0];
+        let line = format!("METHOD=ECM; SIGMA={}; B1=2000; N={}; X={};", curve.sigma, direct.n, curve.X);
+        assert_eq!(EcmCheckpoint::parse_gmp_ecm(&line).unwrap()[0].curves, vec![curve.clone()]);
+        let first = lines.lines().next().unwrap();
+        for invalid in [first.replace("SIGMA=100", "SIGMA=101"), first.replace("PARAM=0", "PARAM=1"),
+            first.replace("METHOD=ECM", "METHOD=P-1"), first.replace("; X=", "; Y=")] {
+            assert!(EcmCheckpoint::parse_gmp_ecm(&invalid).is_err());
+        }
+    }
+}
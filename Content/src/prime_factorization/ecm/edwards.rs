@@ -0,0 +1,372 @@
+#![allow(non_snake_case)]
+
+use std::cell::RefCell;
+
+use rug::{ops::Pow, Assign, Integer};
+
+use crate::montgomery_mod_mult::{recoding::{ladder_bits_u64, naf}, Context};
+use crate::prime_factorization::data::EDWARDS_NAF_WIDTH;
+
+use super::MontgomeryPoint;
+
+/// A point of an Edwards curve x^2 + y^2 = 1 + dx^2y^2 in extended coordinates (X : Y : Z : T), where x = X / Z,
+/// y = Y / Z and T = XY / Z. The neutral element is (0 : 1 : 1 : 0) and the negative of (X : Y : Z : T) is
+/// (-X : Y : Z : -T).
+#[derive(Clone, Debug, Default)]
+pub struct EdwardsPoint {
+    pub X: Integer,
+    pub Y: Integer,
+    pub Z: Integer,
+    pub T: Integer,
+}
+
+impl EdwardsPoint {
+    pub fn assign(&mut self, other: &EdwardsPoint) {
+        self.X.assign(&other.X);
+        self.Y.assign(&other.Y);
+        self.Z.assign(&other.Z);
+        self.T.assign(&other.T);
+    }
+}
+
+/// An Edwards curve of ECM with its point, in the Montgomery form of a context. a24 belongs to the Montgomery curve
+/// that the Edwards curve is birational to, By^2 = x^3 + Ax^2 + x with A = 2(1 + d) / (1 - d) and B = 4 / (1 - d),
+/// on which stage 2 runs: a24 = (A + 2) / 4 = 1 / (1 - d).
+#[derive(Clone, Debug, Default)]
+pub struct EdwardsCurve {
+    pub P: EdwardsPoint,
+    pub d: Integer,
+    pub a24: Integer,
+}
+
+impl EdwardsCurve {
+    /// The point of the curve on the Montgomery curve, with x = (1 + y) / (1 - y), that is (Z + Y : Z - Y), and its
+    /// a24. The neutral element maps to the point at infinity.
+    pub fn to_montgomery(&self, curve: &mut (MontgomeryPoint, Integer), ctx: &mut Context) {
+        let (Q, a24) = curve;
+        Q.X.assign(&self.P.Z);
+        Q.X += ctx.wrap(&self.P.Y);
+        Q.Z.assign(&self.P.Z);
+        Q.Z -= ctx.wrap(&self.P.Y);
+        a24.assign(&self.a24);
+    }
+}
+
+/// The start or the stage 1 residue of an Edwards curve: the point in affine coordinates and the curve, in standard
+/// form (not in Montgomery form) modulo `modulus`, so that the curve can be continued modulo any divisor of it.
+/// A modulus of 0 means there is no residue.
+#[derive(Clone, Debug, Default)]
+pub struct EdwardsResidue {
+    pub x: Integer,
+    pub y: Integer,
+    pub d: Integer,
+    pub a24: Integer,
+    pub modulus: Integer,
+}
+
+impl EdwardsResidue {
+    pub fn clear(&mut self) {
+        self.modulus.assign(0);
+    }
+
+    /// Whether the residue can be continued modulo m.
+    pub fn is_valid_mod(&self, m: &Integer) -> bool {
+        self.modulus != 0 && self.modulus.is_divisible(m)
+    }
+
+    /// Saves the point of the curve, where ctx has `modulus` as its modulus. If Z is not invertible the residue is
+    /// cleared instead.
+    pub fn save(&mut self, curve: &EdwardsCurve, modulus: &Integer, ctx: &mut Context) {
+        let Ok(z) = ctx.from_montgomery(&curve.P.Z).invert(modulus) else {
+            self.clear();
+            return;
+        };
+        self.x.assign(ctx.from_montgomery(&curve.P.X) * &z % modulus);
+        self.y.assign(ctx.from_montgomery(&curve.P.Y) * z % modulus);
+        self.d.assign(ctx.from_montgomery(&curve.d));
+        self.a24.assign(ctx.from_montgomery(&curve.a24));
+        self.modulus.assign(modulus);
+    }
+
+    /// Loads the residue into the curve, where ctx has a divisor of `self.modulus` as its modulus.
+    pub fn load(&self, curve: &mut EdwardsCurve, ctx: &mut Context) {
+        let EdwardsCurve { P, d, a24 } = curve;
+        for (value, standard) in [(&mut P.X, &self.x), (&mut P.Y, &self.y), (d, &self.d), (a24, &self.a24)] {
+            value.assign(standard % &ctx.n);
+            ctx.to_montgomery_mut(value);
+        }
+        P.Z.assign(&ctx.r_mod_n);
+        P.T.assign(&P.X);
+        P.T *= ctx.wrap(&P.Y);
+    }
+}
+
+/// The k-th curve (k >= 2) of a family of Edwards curves x^2 + y^2 = 1 + dx^2y^2 with a rational point of order 12
+/// and a point of infinite order, modulo n, as the start of a residue.
+///
+/// The curves with a point of order 12 are d = (s^2 + 1)^3 (3s^2 - 1) / (16s^6), on which
+/// y = (s^3 - 3s^2 - s - 1) / (2s(s^2 + 1)) has a rational x when -(s^4 - 8s^3 + 2s^2 + 1) is a square v^2, with
+/// x = -4s^3 / ((s^2 + 1)v). The quartic is birational to the elliptic curve V^2 = U^3 + 4U^2 + 5U + 1 of rank 1,
+/// with s = (V - 1) / m and v = 2(U^3 - 5U + 2V - 2) / m^2 for m = V - 2U - 1, so every multiple (U, V) = k(0, 1)
+/// gives a curve (k = 1 gives s = 0).
+///
+/// Like Suyama's curves, the order of the curves modulo any prime is divisible by 12, but the whole torsion
+/// subgroup is rational, which makes a higher power of 2 more likely.
+///
+/// ## Returns
+/// * `Ok(residue)` - The starting point and the curve modulo n.
+/// * `Err(d)` - The gcd of n with a denominator, when it is not 1.
+pub fn edwards_curve(k: u64, n: &Integer) -> Result<EdwardsResidue, Integer> {
+    assert!(k >= 2, "the curves start at k = 2");
+    let invert = |value: Integer| value.invert(n).map_err(|value| value.gcd(n));
+
+    // k(0, 1) by double-and-add, in affine coordinates
+    let (mut U, mut V) = (Integer::new(), Integer::from(1));
+    for bit in ladder_bits_u64(k) {
+        let lambda = (U.clone().square() * 3u32 + U.clone() * 8u32 + 5u32) * invert(V.clone() * 2u32)?;
+        let next = (lambda.clone().square() - 4u32 - U.clone() * 2u32).modulo(n);
+        V = (lambda * (U - &next) - V).modulo(n);
+        U = next;
+        if bit {
+            let lambda = (V.clone() - 1u32) * invert(U.clone())?;
+            let next = (lambda.clone().square() - 4u32 - &U).modulo(n);
+            V = (lambda * (U - &next) - V).modulo(n);
+            U = next;
+        }
+    }
+
+    let m = invert(V.clone() - U.clone() * 2u32 - 1u32)?;
+    let s = ((V.clone() - 1u32) * &m).modulo(n);
+    let v = ((U.clone().pow(3) - U * 5u32 + V * 2u32 - 2u32) * 2u32 * m.square()).modulo(n);
+
+    // d = N / D, and the denominators of x and y divide D, so a single inversion of D(D - N) is enough
+    let w = (s.clone().square() + 1u32).modulo(n);
+    let D = (s.clone().pow(6) * 16u32 * &w * &v).modulo(n);
+    let N = (w.pow(4) * (s.clone().square() * 3u32 - 1u32) * &v).modulo(n);
+    let difference = (D.clone() - &N).modulo(n);
+    let inverse = invert(D.clone() * &difference)?;
+    let D_inverse = (difference.clone() * &inverse).modulo(n);
+
+    Ok(EdwardsResidue {
+        x: (-s.clone().pow(9) * 64u32 * &D_inverse).modulo(n),
+        y: ((s.clone().pow(3) - s.clone().square() * 3u32 - &s - 1u32) * s.pow(5) * 8u32 * v * D_inverse).modulo(n),
+        d: (N * difference * &inverse).modulo(n),
+        a24: (D.square() * inverse).modulo(n),
+        modulus: n.clone(),
+    })
+}
+
+thread_local! {
+    static BUFFER: RefCell<[Integer; 4]> = RefCell::new(std::array::from_fn(|_| Integer::new()));
+}
+
+struct Buffer;
+
+impl Buffer {
+    fn get_mut<F, R>(f: F) -> R
+    where
+        F: FnOnce(&mut [Integer; 4]) -> R,
+    {
+        BUFFER.with(|cell| f(&mut cell.borrow_mut()))
+    }
+}
+
+/// Edwards point doubling (3M + 4S): given a point P, calculates 2P and stores the result in P. T is only needed by
+/// an addition, so it is only computed with `with_T` (1M).
+fn point_double(P: &mut EdwardsPoint, with_T: bool, ctx: &mut Context) {
+    Buffer::get_mut(|[a, b, c, e]| {
+        a.assign(&P.X);
+        ctx.square_mut(a);          // a = X^2
+        b.assign(&P.Y);
+        ctx.square_mut(b);          // b = Y^2
+        e.assign(&P.X);
+        *e += ctx.wrap(&P.Y);
+        ctx.square_mut(e);
+        *e -= ctx.wrap(&*a);
+        *e -= ctx.wrap(&*b);        // e = 2XY
+        c.assign(&P.Z);
+        ctx.square_mut(c);          // c = Z^2
+
+        P.Z.assign(&*a);
+        P.Z += ctx.wrap(&*b);       // g = X^2 + Y^2, kept in Z
+        *a -= ctx.wrap(&*b);        // h = X^2 - Y^2
+        P.X.assign(&P.Z);
+        P.X -= ctx.wrap(&*c);
+        P.X -= ctx.wrap(&*c);       // f = g - 2Z^2, kept in X
+
+        if with_T {
+            P.T.assign(&*e);
+            P.T *= ctx.wrap(&*a);   // T = eh
+        }
+        P.Y.assign(&P.Z);
+        P.Y *= ctx.wrap(&*a);       // Y = gh
+        b.assign(&P.X);
+        P.X *= ctx.wrap(&*e);       // X = ef
+        P.Z *= ctx.wrap(&*b);       // Z = fg
+    });
+}
+
+/// Edwards point addition (9M): given two points P and Q, calculates P + Q and stores the result in P. T is only
+/// needed by another addition, so it is only computed with `with_T` (1M).
+fn point_add(P: &mut EdwardsPoint, Q: &EdwardsPoint, d: &Integer, with_T: bool, ctx: &mut Context) {
+    Buffer::get_mut(|[a, b, c, e]| {
+        a.assign(&P.X);
+        *a *= ctx.wrap(&Q.X);       // a = X1 X2
+        b.assign(&P.Y);
+        *b *= ctx.wrap(&Q.Y);       // b = Y1 Y2
+        c.assign(&P.T);
+        *c *= ctx.wrap(&Q.T);
+        *c *= ctx.wrap(d);          // c = d T1 T2
+        e.assign(&P.X);
+        *e += ctx.wrap(&P.Y);
+        P.X.assign(&Q.X);
+        P.X += ctx.wrap(&Q.Y);
+        *e *= ctx.wrap(&P.X);
+        *e -= ctx.wrap(&*a);
+        *e -= ctx.wrap(&*b);        // e = X1 Y2 + Y1 X2
+        P.Z *= ctx.wrap(&Q.Z);      // Z1 Z2
+
+        P.X.assign(&P.Z);
+        P.X -= ctx.wrap(&*c);       // f = Z1 Z2 - c, kept in X
+        P.Z += ctx.wrap(&*c);       // g = Z1 Z2 + c, kept in Z
+        *b -= ctx.wrap(&*a);        // h = Y1 Y2 - X1 X2
+
+        if with_T {
+            P.T.assign(&*e);
+            P.T *= ctx.wrap(&*b);   // T = eh
+        }
+        P.Y.assign(&P.Z);
+        P.Y *= ctx.wrap(&*b);       // Y = gh
+        a.assign(&P.X);
+        P.X *= ctx.wrap(&*e);       // X = ef
+        P.Z *= ctx.wrap(&*a);       // Z = fg
+    });
+}
+
+/// ECM stage 1 on Edwards curves: calculates sP for the point P of every curve, with the width-w NAF of s
+/// (w = EDWARDS_NAF_WIDTH) and a table of ±P, ±3P, ..., ±(2^(w - 1) - 1)P per curve. A doubling costs 3M + 4S and an
+/// addition 10M, about every w + 1 doublings, against the 6M + 4S per bit of the Montgomery ladder.
+pub fn edwards_phase1(ctx: &mut Context, curves: &mut [EdwardsCurve], s: &Integer) {
+    let digits = naf(s, EDWARDS_NAF_WIDTH);
+    let Some((top, digits)) = digits.split_last() else {
+        return;
+    };
+    let size = 1 << (EDWARDS_NAF_WIDTH - 2);
+    for EdwardsCurve { P, d, .. } in curves.iter_mut() {
+        let mut P2 = P.clone();
+        point_double(&mut P2, true, ctx);
+        let mut table = Vec::with_capacity(size);
+        table.push(P.clone());
+        for i in 1..size {
+            let mut Q = table[i - 1].clone();
+            point_add(&mut Q, &P2, d, true, ctx);
+            table.push(Q);
+        }
+        let negatives: Vec<EdwardsPoint> = table.iter().map(|Q| {
+            let mut R = Q.clone();
+            for value in [&mut R.X, &mut R.T] {
+                let Q_value = std::mem::take(value);
+                *value -= ctx.wrap(&Q_value);
+            }
+            R
+        }).collect();
+
+        P.assign(&table[*top as usize / 2]);
+        for &digit in digits.iter().rev() {
+            // only the doubling before an addition needs T: an addition is always followed by a doubling
+            point_double(P, digit != 0, ctx);
+            if digit != 0 {
+                let Q = match digit > 0 {
+                    true => &table[digit as usize / 2],
+                    false => &negatives[digit.unsigned_abs() as usize / 2],
+                };
+                point_add(P, Q, d, false, ctx);
+            }
+        }
+    }
+}
+
+/// The scalar whose `ladder_bits` are bits, e.g. the stage 1 scalar of `EcmTables`.
+pub fn ladder_scalar(bits: &[bool]) -> Integer {
+    let mut k = Integer::new();
+    k.set_bit(bits.len() as u32, true);
+    for (i, _) in bits.iter().rev().enumerate().filter(|(_, bit)| **bit) {
+        k.set_bit(i as u32, true);
+    }
+    k
+}
+
+/// Stage 1 of a batch of curves of `ecm_trial` on Edwards curves: the j-th curve is the `edwards_curve` of ks[j]
+/// modulo n, which ctx has as its modulus, or the continuation of residues[j] if the round resumes and the residue
+/// is valid modulo n (curves without one are run through the previous scalar first). The residues are updated, and
+/// the points and curves of stage 2 are written to the Montgomery curves. A curve whose parameters aren't invertible
+/// gets a point with a Z of their gcd with n instead.
+pub(super) fn edwards_stage1(ctx: &mut Context, n: &Integer, ks: &[u64], residues: &mut [EdwardsResidue],
+    s: &Integer, resume_from: Option<&Integer>, curves: &mut [(MontgomeryPoint, Integer)]) {
+    let mut edwards = EdwardsCurve::default();
+    for ((&k, residue), curve) in ks.iter().zip(residues.iter_mut()).zip(curves.iter_mut()) {
+        let fresh = resume_from.is_none() || !residue.is_valid_mod(n);
+        if fresh {
+            match edwards_curve(k, n) {
+                Ok(start) => *residue = start,
+                Err(g) => {
+                    residue.clear();
+                    curve.0.X.assign(&ctx.r_mod_n);
+                    curve.0.Z.assign(g);
+                    continue;
+                }
+            }
+        }
+        residue.load(&mut edwards, ctx);
+        if let (true, Some(s_previous)) = (fresh, resume_from) {
+            edwards_phase1(ctx, std::slice::from_mut(&mut edwards), s_previous);
+        }
+        edwards_phase1(ctx, std::slice::from_mut(&mut edwards), s);
+        residue.save(&edwards, n, ctx);
+        edwards.to_montgomery(curve, ctx);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prime_factorization::ecm::scalar_mul;
+
+    #[test]
+    fn test_edwards_curve() {
+        let p = Integer::from(100_003);
+        let mut ctx = Context::new(p.clone());
+        for k in 2..8 {
+            let residue = edwards_curve(k, &p).unwrap();
+            let EdwardsResidue { x, y, d, a24, .. } = &residue;
+            let (x2, y2) = (x.clone().square(), y.clone().square());
+            assert_eq!((x2.clone() + &y2 - 1u32 - d.clone() * &x2 * &y2).modulo(&p), 0);
+            assert_eq!((a24.clone() * (1u32 - d.clone())).modulo(&p), 1);
+
+            // the order of the Montgomery curve By^2 = x^3 + Ax^2 + x, with A = 4 a24 - 2 and B = 4 / (1 - d), is
+            // divisible by 12
+            let A = Integer::from(a24 * 4u32) - 2u32;
+            let twist = (1u32 - d.clone()).modulo(&p).jacobi(&p);
+            let order = 1 + (0..100_003u32).map(|x| {
+                let rhs = (Integer::from(x).square() + &A * x + 1u32) * x;
+                1 + twist * rhs.modulo(&p).jacobi(&p)
+            }).sum::<i32>();
+            assert_eq!(order % 12, 0, "k = {k}");
+
+            // stage 1 on the Edwards curve agrees with the Montgomery ladder on the Montgomery curve
+            let mut curve = EdwardsCurve::default();
+            residue.load(&mut curve, &mut ctx);
+            let mut start = (MontgomeryPoint::default(), Integer::new());
+            curve.to_montgomery(&mut start, &mut ctx);
+            let s = Integer::from(3u32).pow(40) * 1_000_121u32;
+            edwards_phase1(&mut ctx, std::slice::from_mut(&mut curve), &s);
+            let mut end = (MontgomeryPoint::default(), Integer::new());
+            curve.to_montgomery(&mut end, &mut ctx);
+            let expected = scalar_mul(&start.0, &s, &start.1, &mut ctx);
+            let (X, Z) = (ctx.from_montgomery(&end.0.X), ctx.from_montgomery(&end.0.Z));
+            let (X_expected, Z_expected) = (ctx.from_montgomery(&expected.X), ctx.from_montgomery(&expected.Z));
+            assert_eq!((X * Z_expected).modulo(&p), (X_expected * Z).modulo(&p), "k = {k}");
+        }
+        assert_eq!(ladder_scalar(&[false, true, true]), 11);
+    }
+}
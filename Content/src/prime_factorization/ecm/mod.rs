@@ -1,12 +1,25 @@
 #![allow(non_snake_case, dead_code)]
-use crate::montgomery_mod_mult::Context;
-use std::cell::RefCell;
-use rug::integer::IsPrime;
+use crate::montgomery_mod_mult::{recoding::ladder_bits_u64, Context};
+use crate::number_theory::{is_prime, isqrt_rem};
+use std::{cell::RefCell, cmp::min, sync::atomic::{AtomicBool, Ordering}};
 use rug::{Integer, Assign};
-use super::structs::{Factor, FixedVec};
-use super::{BLOCK_SIZE_1, BLOCK_SIZE_2, BOUNDS1, ITERATIONS, SIZE};
-
+use super::structs::{Budget, CurveFamily, EcmCurve, FactorEvent, FactorQueue, GrowVec, Method};
+use super::{push_small_factors, CURVE_BATCH, ITERATIONS, SIZE};
+use super::data::{EcmTables, PolyevalPlan, PrimeFactorizeData, Stage2, STAGE2_TABLE_SIZE};
+use super::stage2::{Stage2Group, Stage2Walk};
+use super::structs::EcmRound;
+use suyama::suyama_parameterization;
+
+pub mod checkpoint;
+pub mod edwards;
+pub mod prac;
+pub(super) mod poly;
+pub mod subgroup;
 pub mod suyama;
+pub use checkpoint::{CurveCheckpoint, EcmCheckpoint};
+pub use edwards::{edwards_curve, edwards_phase1, EdwardsCurve, EdwardsPoint, EdwardsResidue};
+pub use prac::{prac, PracChains};
+pub use subgroup::{clear_cofactor_point, point_in_subgroup, scalar_mul};
 
 #[derive(Clone, Debug, Default)]
 pub struct MontgomeryPoint {
@@ -22,6 +35,74 @@ impl MontgomeryPoint {
     }
 }
 
+/// The point of a curve after stage 1, normalized to Z = 1 and kept in standard form, so that a later pass with
+/// a larger B1 can continue the curve modulo any divisor of `modulus` instead of starting over.
+/// A modulus of 0 means there is no residue.
+#[derive(Clone, Debug, Default)]
+pub struct Stage1Residue {
+    pub X: Integer,
+    pub a24: Integer,
+    pub modulus: Integer,
+}
+
+impl Stage1Residue {
+    pub fn clear(&mut self) {
+        self.modulus.assign(0);
+    }
+
+    /// Whether the residue can be continued modulo m.
+    fn is_valid_mod(&self, m: &Integer) -> bool {
+        self.modulus != 0 && self.modulus.is_divisible(m)
+    }
+
+    /// Saves the point of the curve, where ctx has `modulus` as its modulus. If Z is not invertible the residue is
+    /// cleared instead (gcd(Z, modulus) is a factor, which phase 2 picks up).
+    fn save(&mut self, curve: &(MontgomeryPoint, Integer), modulus: &Integer, ctx: &mut Context) {
+        BufferIntegers::get_mut(|z, _, _| {
+            z.assign(ctx.from_montgomery(&curve.0.Z));
+            if z.invert_mut(modulus).is_err() {
+                self.clear();
+                return;
+            }
+            self.X.assign(ctx.from_montgomery(&curve.0.X));
+            self.X *= &*z;
+            self.X %= modulus;
+            self.a24.assign(ctx.from_montgomery(&curve.1));
+            self.modulus.assign(modulus);
+        });
+    }
+
+    /// Loads the residue into the curve, where ctx has a divisor of `self.modulus` as its modulus.
+    fn load(&self, curve: &mut (MontgomeryPoint, Integer), ctx: &mut Context) {
+        let (P, a24) = curve;
+        P.X.assign(&self.X % &ctx.n);
+        ctx.to_montgomery_mut(&mut P.X);
+        P.Z.assign(&ctx.r_mod_n);
+        a24.assign(&self.a24 % &ctx.n);
+        ctx.to_montgomery_mut(a24);
+    }
+}
+
+/// What `ecm_trial` keeps from one round to the next: the curves, the residues of both curve families, and the
+/// context of the number the curves were last run on.
+pub struct EcmState {
+    pub curves: [(MontgomeryPoint, Integer); ITERATIONS],
+    pub residues: [Stage1Residue; ITERATIONS],
+    pub edwards_residues: [EdwardsResidue; ITERATIONS],
+    pub ctx: Context,
+}
+
+impl Default for EcmState {
+    fn default() -> Self {
+        Self {
+            curves: std::array::from_fn(|_| (MontgomeryPoint::default(), Integer::new())),
+            residues: std::array::from_fn(|_| Stage1Residue::default()),
+            edwards_residues: std::array::from_fn(|_| EdwardsResidue::default()),
+            ctx: Context::new(Integer::ONE.clone()),
+        }
+    }
+}
+
 thread_local! {
     static BUFFER_INTEGERS: RefCell<(Integer, Integer, Integer)> =
         RefCell::new((Integer::new(), Integer::new(), Integer::new()));
@@ -43,22 +124,27 @@ impl BufferIntegers {
 
 /// Montgomery point doubling: given a point P (in projective coordinates), calculates 2P and stores the result in P.
 fn point_double(P: &mut MontgomeryPoint, a24: &Integer, ctx: &mut Context) {
+    x_double(&mut P.X, &mut P.Z, a24, ctx);
+}
+
+/// Coordinate-level doubling, so that callers keeping X and Z in separate arrays can double in place.
+fn x_double(X: &mut Integer, Z: &mut Integer, a24: &Integer, ctx: &mut Context) {
     BufferIntegers::get_mut(|a, b, _| {
-        a.assign(&P.X);
-        *a += ctx.wrap(&P.Z);
-        ctx.square_mut(a);      // a = (P.X + P.Z)^2
-        b.assign(&P.X);
-        *b -= ctx.wrap(&P.Z);
-        ctx.square_mut(b);      // b = (P.X - P.Z)^2
-
-        P.X.assign(&*a);
-        P.X *= ctx.wrap(&*b);   // P.X = a * b
+        a.assign(&*X);
+        *a += ctx.wrap(&*Z);
+        ctx.square_mut(a);      // a = (X + Z)^2
+        b.assign(&*X);
+        *b -= ctx.wrap(&*Z);
+        ctx.square_mut(b);      // b = (X - Z)^2
+
+        X.assign(&*a);
+        *X *= ctx.wrap(&*b);    // X = a * b
         *a -= ctx.wrap(&*b);    // a = a - b
-        P.Z.assign(&*a);   // P.Z = a
+        Z.assign(&*a);          // Z = a
         
         *a *= ctx.wrap(a24);
         *a += ctx.wrap(&*b);
-        P.Z *= ctx.wrap(&*a);  // P.Z = a * (b + a24 * a) 
+        *Z *= ctx.wrap(&*a);    // Z = a * (b + a24 * a) 
     });
 }
 
@@ -67,27 +153,32 @@ fn point_double(P: &mut MontgomeryPoint, a24: &Integer, ctx: &mut Context) {
 /// and stores the result in P. It is required that R = P - Q (= Q - P).
 /// If R.Z != 1, multiply the returned point's X coordinate by R.Z.
 fn point_add(P: &mut MontgomeryPoint, Q: &MontgomeryPoint, R: &MontgomeryPoint, ctx: &mut Context) {
+    x_add(&mut P.X, &mut P.Z, &Q.X, &Q.Z, &R.X, ctx);
+}
+
+/// Coordinate-level differential addition: (X : Z) += (QX : QZ), where RX is the X coordinate of the difference.
+fn x_add(X: &mut Integer, Z: &mut Integer, QX: &Integer, QZ: &Integer, RX: &Integer, ctx: &mut Context) {
     BufferIntegers::get_mut(|a, b, z| {
-        a.assign(&P.X);
-        b.assign(&P.X);
-        *a += ctx.wrap(&P.Z);    // a = P.X + P.Z
-        *b -= ctx.wrap(&P.Z);    // b = P.X - P.Z
-        z.assign(&Q.X);
-        *z -= ctx.wrap(&Q.Z);
-        *a *= ctx.wrap(&*z);     // a = (Q.X - Q.Z) * (P.X + P.Z)
+        a.assign(&*X);
+        b.assign(&*X);
+        *a += ctx.wrap(&*Z);     // a = X + Z
+        *b -= ctx.wrap(&*Z);     // b = X - Z
+        z.assign(QX);
+        *z -= ctx.wrap(QZ);
+        *a *= ctx.wrap(&*z);     // a = (QX - QZ) * (X + Z)
         
-        z.assign(&Q.X);
-        *z += ctx.wrap(&Q.Z);
-        *b *= ctx.wrap(&*z);     // b = (Q.X + Q.Z) * (P.X - P.Z)
+        z.assign(QX);
+        *z += ctx.wrap(QZ);
+        *b *= ctx.wrap(&*z);     // b = (QX + QZ) * (X - Z)
         
-        P.X.assign(&*a);
-        P.X += ctx.wrap(&*b);
-        ctx.square_mut(&mut P.X);  // P.X = (a + b)^2
+        X.assign(&*a);
+        *X += ctx.wrap(&*b);
+        ctx.square_mut(X);       // X = (a + b)^2
         
-        P.Z.assign(&*a);
-        P.Z -= ctx.wrap(&*b);
-        ctx.square_mut(&mut P.Z);
-        P.Z *= ctx.wrap(&R.X);     // P.Z = R.X (a - b)^2
+        Z.assign(&*a);
+        *Z -= ctx.wrap(&*b);
+        ctx.square_mut(Z);
+        *Z *= ctx.wrap(RX);      // Z = RX (a - b)^2
     });
 }
 
@@ -118,8 +209,8 @@ fn montgomery_ladder(P0: &mut MontgomeryPoint, Q0: &mut MontgomeryPoint, s: u32,
         P.assign(&*P0);
         point_double(P, &a24, ctx);
 
-        for i in (0..(31 - s.leading_zeros())).rev() {
-            if (s >> i) & 1 != 0 {
+        for bit in ladder_bits_u64(s as u64) {
+            if bit {
                 point_add(Q, P, P0, ctx);
                 Q.X *= ctx.wrap(&P0.Z);
                 point_double(P, a24, ctx);
@@ -136,33 +227,22 @@ fn montgomery_ladder(P0: &mut MontgomeryPoint, Q0: &mut MontgomeryPoint, s: u32,
 }
 
 
-/// ECM Phase 1. We calculate s*P (s has been calculated beforehand).
-fn ecm_phase1(ctx: &mut Context, P0: &mut MontgomeryPoint, a24: &Integer, s: &Vec<bool>) {
-    // Montgomery ladder for scalar multiplication.
-    // Given a point P, compute [s]P. In this ladder, the difference between the two
-    // running points is always the initial P.
-    BufferPoints::get_mut(|P, Q| {
-        Q.assign(&*P0);
-        P.assign(&*P0);
-        point_double(P, a24, ctx);
-        
-        for b in s {
-            if *b {
-                point_add(Q, P, P0, ctx);
-                point_double(P, a24, ctx);
-            } else {
-                point_add(P, Q, P0, ctx);
-                point_double(Q, a24, ctx);
-            }
+/// ECM Phase 1. We calculate s*P for up to CURVE_BATCH curves at once, where s is the product of the primes of the
+/// chains (calculated beforehand): the points are multiplied by one prime at a time, with the PRAC chain of the prime
+/// (see `prac`), which takes fewer operations than the Montgomery ladder over the bits of s. The curves go through
+/// the primes in lockstep: every curve is multiplied by a prime before moving on to the next prime. The results are
+/// stored back into the curves' points.
+fn ecm_phase1(ctx: &mut Context, curves: &mut [(MontgomeryPoint, Integer)], chains: &PracChains) {
+    for &(p, multiplier) in &chains.primes {
+        for (P, a24) in curves.iter_mut() {
+            prac(P, p, multiplier, a24, ctx);
         }
-
-        P0.assign(&*Q);
-    });
+    }
 }
 
 /// Precomputes jQ0 where j is odd, storing the results in the table.
 /// Give the values of j in the values vector.
-fn precompute_gaps(Q0: &mut MontgomeryPoint, Q2: &MontgomeryPoint, table: &mut [MontgomeryPoint; 2000], ctx: &mut Context, values: &Vec<usize>) {
+fn precompute_gaps(Q0: &mut MontgomeryPoint, Q2: &MontgomeryPoint, table: &mut [MontgomeryPoint; STAGE2_TABLE_SIZE], ctx: &mut Context, values: &Vec<usize>) {
     BufferPoints::get_mut(|P, Q| {
         let mut index = 0;
         let mut j = 1;
@@ -184,7 +264,7 @@ fn precompute_gaps(Q0: &mut MontgomeryPoint, Q2: &MontgomeryPoint, table: &mut [
 
 
 thread_local! {
-    static PHASE_2_BUFFER: RefCell<([MontgomeryPoint; 2000], MontgomeryPoint, MontgomeryPoint, MontgomeryPoint)> =
+    static PHASE_2_BUFFER: RefCell<([MontgomeryPoint; STAGE2_TABLE_SIZE], MontgomeryPoint, MontgomeryPoint, MontgomeryPoint)> =
         RefCell::new((
             std::array::from_fn(|_| (MontgomeryPoint::default())),
             MontgomeryPoint::default(),
@@ -198,7 +278,7 @@ struct Phase2Buffer;
 impl Phase2Buffer {
     fn get_mut<F, R>(f: F) -> R
     where
-        F: FnOnce(&mut [MontgomeryPoint; 2000], &mut MontgomeryPoint, &mut MontgomeryPoint, &mut MontgomeryPoint) -> R,
+        F: FnOnce(&mut [MontgomeryPoint; STAGE2_TABLE_SIZE], &mut MontgomeryPoint, &mut MontgomeryPoint, &mut MontgomeryPoint) -> R,
     {
         PHASE_2_BUFFER.with(|cell| {
             let (table, Q2, R_prev, R) = &mut *cell.borrow_mut();
@@ -208,71 +288,150 @@ impl Phase2Buffer {
 }
 
 
-fn ecm_iteration(ctx: &mut Context, n: &Integer, B1: usize, block_size: usize, Q: &mut MontgomeryPoint, a24: &Integer,
-    primes: &Vec<u32>, start: usize, end: usize, gaps: &Vec<usize>, values: &Vec<usize>, s: &Vec<bool>, g: &mut Integer) {
-    ecm_phase1(ctx, Q, a24, &s);
-    g.assign(Q.Z.gcd_ref(n));
-    if g != Integer::ONE && g != n {
-        return;
+/// The affine x coordinates X / Z of the points, in standard form, with a single inversion modulo n (see
+/// `Context::invert_batch`). If one of the Z is not invertible, its gcd with n is returned as the error instead.
+fn affine_x(points: &[MontgomeryPoint], n: &Integer, ctx: &mut Context) -> Result<Vec<Integer>, Integer> {
+    let mut inverses: Vec<Integer> = points.iter().map(|P| P.Z.clone()).collect();
+    if let Err(i) = ctx.invert_batch(&mut inverses) {
+        return Err(points[i].Z.gcd_ref(n).into());
     }
+    Ok(points.iter().zip(inverses).map(|(P, inverse)| {
+        let x = ctx.mul(inverse, &P.X);
+        ctx.from_montgomery(x)
+    }).collect())
+}
 
-    g.assign(&ctx.r_mod_n);  // g = 1 in montgomery form
-
-    let half_block_size = block_size / 2;
+/// ECM phase 2 by polynomial evaluation, see `PolyevalPlan`. Q should be the point after phase 1.
+/// The x coordinates of jQ are the roots of F, the giant steps iDQ are multiplied in chunks of deg F into
+/// H = prod (X - x(iDQ)) mod F, and at the end the product of H over the roots of F is the product of all the
+/// differences x(iDQ) - x(jQ). Its gcd with n is stored in g.
+fn ecm_polyeval(ctx: &mut Context, n: &Integer, B1: usize, plan: &PolyevalPlan, Q: &mut MontgomeryPoint, a24: &Integer,
+    g: &mut Integer) {
+    let PolyevalPlan { block_size, B2, values } = plan;
     Phase2Buffer::get_mut(|table, Q2, R_prev, R| {
         Q2.assign(&*Q);
-        point_double(Q2, a24, ctx);  // Q2 = 2Q0 (duh)
-        
+        point_double(Q2, a24, ctx);
         R.assign(&*Q);
-        // precompute the gaps between any prime and the block
         precompute_gaps(Q, Q2, table, ctx, values);
-
         Q.assign(&*R);
-        montgomery_ladder(Q, Q2, block_size as u32, a24, ctx);  // Q = block_size Q
 
-        let mut c = ((B1 + half_block_size) / block_size) as i32;
+        let roots = match affine_x(&table[..values.len()], n, ctx) {
+            Ok(roots) => roots,
+            Err(d) => return g.assign(d),
+        };
+        let tree = poly::product_tree(&roots, n);
+        let F = &tree.last().unwrap()[0];
+        let inverse = poly::reversed_inverse(F, n);
+
+        montgomery_ladder(Q, Q2, *block_size as u32, a24, ctx);  // Q = DQ
+        // the multiples iD within half a block of (B1, B2]
+        let first = (B1 / block_size) as u64;
+        let last = B2 / *block_size as u64 + 1;
         Q2.assign(&*Q);
-        // R = the starting location: [(B1 + block_size/2) / block_size] Q
-        // Q2 = 1 block behind the starting location: [(B1 + block_size/2) / block_size - 1] Q
-        montgomery_ladder(Q2, R, c as u32 - 1, a24, ctx);
-
-        c *= block_size as i32;  // c = the scalar of R: R = cQ (before we multiplied Q by block_size)
-        let mut index = start;
+        montgomery_ladder(Q2, R, first as u32, a24, ctx);  // Q2 = first * DQ, R = (first + 1) * DQ
 
-        for &gap in &gaps[start..end] {
-            let mut distance = primes[index] as i32 - c;  // the "distance" between our point and the next prime
-            while distance > half_block_size as i32 {
+        let mut H: poly::Poly = vec![Integer::from(1)];
+        let mut giants: Vec<MontgomeryPoint> = Vec::with_capacity(values.len());
+        giants.push(Q2.clone());
+        for i in first + 1..=last {
+            if i > first + 1 {
                 R_prev.assign(Q2);
                 Q2.assign(R);
-                point_add(R, Q, R_prev, ctx);  // move to the next block
+                point_add(R, Q, R_prev, ctx);  // move to the next giant step
                 R.X *= ctx.wrap(&R_prev.Z);
-                
-                distance -= block_size as i32;
-                c += block_size as i32;
             }
-
-            BufferIntegers::get_mut(|x, y, _| {    
-                x.assign(&R.X);
-                *x *= ctx.wrap(&table[gap].Z);
-                y.assign(&R.Z);
-                *y *= ctx.wrap(&table[gap].X);
-                
-                *x -= ctx.wrap(&*y);
-                *g *= ctx.wrap(&*x);  // g *= R.X * table[gap].Z - table[gap].X * R.Z
-            });
-            
-            if primes[index] % block_size as u32 > half_block_size as u32 {
-                index += 1;
+            giants.push(R.clone());
+            if giants.len() < values.len() && i < last {
                 continue;
             }
 
-            index += 1;
+            let xs = match affine_x(&giants, n, ctx) {
+                Ok(xs) => xs,
+                Err(d) => return g.assign(d),
+            };
+            giants.clear();
+            let mut G = poly::product_tree(&xs, n).pop().unwrap().swap_remove(0);
+            // G mod F = G - F when both are monic of the same degree
+            if G.len() == F.len() {
+                G.pop();
+                for (c, f) in G.iter_mut().zip(F) {
+                    *c -= f;
+                    if *c < 0 {
+                        *c += n;
+                    }
+                }
+            }
+            H = poly::rem_with_inverse(&poly::mul(&H, &G, n), F, &inverse, n);
         }
-        
+
+        g.assign(poly::evaluate_product(&H, &tree, n));
         g.gcd_mut(n);
     });
 }
 
+/// The phase 2 run on every curve of a round: the plan, the bound B1 of phase 1, and the primes the plan covers
+/// (`primes[start..end]`), which are only needed by a `Stage2::Primes` plan.
+struct Phase2<'a> {
+    B1: usize,
+    stage2: &'a Stage2,
+    primes: &'a [u32],
+    start: usize,
+    end: usize,
+}
+
+/// Checks the result of phase 1 for a single curve, then runs phase 2 on it. Q should be the point after phase 1.
+/// The gcd of the accumulated product with n is stored in g.
+fn ecm_iteration(ctx: &mut Context, n: &Integer, phase2: &Phase2, Q: &mut MontgomeryPoint, a24: &Integer,
+    g: &mut Integer) {
+    let &Phase2 { B1, stage2, primes, start, end } = phase2;
+    g.assign(Q.Z.gcd_ref(n));
+    if g != Integer::ONE && g != n {
+        return;
+    }
+    let plan = match stage2 {
+        Stage2::Primes(plan) => plan,
+        Stage2::Polyeval(plan) => return ecm_polyeval(ctx, n, B1, plan, Q, a24, g),
+    };
+
+    g.assign(&ctx.r_mod_n);  // g = 1 in montgomery form
+    let curve = &mut Curve { a24, ctx };
+    let mut walk = Stage2Walk::new(curve, Q, plan, B1 as u32);
+    walk.multiply(curve, primes, start..end, g);
+    g.gcd_mut(n);
+}
+
+/// A curve for `Stage2Walk`, whose points are kept in projective X : Z coordinates.
+struct Curve<'a> {
+    a24: &'a Integer,
+    ctx: &'a mut Context,
+}
+
+impl Stage2Group for Curve<'_> {
+    type Element = MontgomeryPoint;
+
+    fn double(&mut self, P: &mut MontgomeryPoint) {
+        point_double(P, self.a24, self.ctx);
+    }
+
+    fn add(&mut self, P: &mut MontgomeryPoint, Q: &MontgomeryPoint, difference: &MontgomeryPoint) {
+        point_add(P, Q, difference, self.ctx);
+        P.X *= self.ctx.wrap(&difference.Z);
+    }
+
+    fn multiply_difference(&mut self, product: &mut Integer, P: &MontgomeryPoint, Q: &MontgomeryPoint) {
+        let ctx = &mut *self.ctx;
+        BufferIntegers::get_mut(|x, y, _| {
+            x.assign(&P.X);
+            *x *= ctx.wrap(&Q.Z);
+            y.assign(&P.Z);
+            *y *= ctx.wrap(&Q.X);
+
+            *x -= ctx.wrap(&*y);
+            *product *= ctx.wrap(&*x);  // product *= P.X * Q.Z - Q.X * P.Z
+        });
+    }
+}
+
 fn print_curve(curve: &(MontgomeryPoint, Integer), ctx: &mut Context) {
     println!("Curve: X: {}, Z: {}, a24: {}", ctx.from_montgomery(&curve.0.X), ctx.from_montgomery(&curve.0.Z), ctx.from_montgomery(&curve.1));
 }
@@ -298,58 +457,66 @@ impl Buffer {
     }
 }
 
-/// Given bounds B1 and B2, it runs 200 iterations of ECM (both phase 1 and 2).
+/// Runs one round of ECM (both phase 1 and 2), with the bounds and number of curves (at most ITERATIONS) in tables.
 /// Any prime factors found will be inserted into the prime_factors vector.
 /// Insert the number to be factorised in the temporary_factors vector.
-pub fn ecm_trial(n: &Integer, ctx_n: &mut Context, B1: usize, B2: usize, params: &[(u32, u32)], curves: &mut [(MontgomeryPoint, Integer); ITERATIONS],
-    s: &Vec<bool>, temporary_factors: &mut FixedVec<Factor, SIZE>, prime_factors: &mut FixedVec<Integer, SIZE>,
-    primes: &Vec<u32>, gaps: &Vec<usize>, values: &Vec<usize>) {
-    let block_size = if B1 == BOUNDS1.0 {
-        BLOCK_SIZE_1
-    } else {
-        BLOCK_SIZE_2
-    };
-    let print_curve_parameters = true;  // set to true to print the curve parameters
+/// Phase 1 is run on CURVE_BATCH curves at a time, after which each curve of the batch goes through phase 2.
+/// The point of every curve after phase 1 is saved in residues. The curves must have been built from the parameters
+/// of data, which give the sigma of a curve that finds a factor. When a curve finds a factor, the curves after it in
+/// its batch are not lost: they are run again from their starting points on the next composite.
+///
+/// If the round has a `resume_from` scalar, the curves continue from the residues of the previous round, which used
+/// that scalar, so s only has to cover the prime powers between the two values of B1. Curves without a
+/// usable residue are run through the previous scalar first.
+///
+/// With a `CurveFamily::Edwards` round, stage 1 runs on the Edwards curves of the same parameters instead (see
+/// `edwards_stage1`), with their residues in edwards_residues, and the curves only receive the points of stage 2.
+///
+/// The budget is checked between batches of curves: once it runs out, the numbers that are not factorized yet
+/// are left in temporary_factors.
+pub fn ecm_trial(n: &Integer, state: &mut EcmState, tables: &EcmTables, temporary_factors: &mut FactorQueue,
+    prime_factors: &mut GrowVec<Integer, SIZE>, data: &PrimeFactorizeData, budget: &Budget) {
+    let EcmState { curves, residues, edwards_residues, ctx: ctx_n } = state;
+    let EcmTables {
+        round: EcmRound { curves: curve_count, B1, B2 }, s, chains, resume_from, resume_chains, stage2, family, ..
+    } = tables;
+    let (curve_count, B1, B2, family) = (*curve_count, *B1, *B2, *family);
+    let primes = &data.primes;
+    // the Edwards curves multiply by the scalars themselves rather than walking over their bits
+    let edwards_scalars = (family == CurveFamily::Edwards)
+        .then(|| (edwards::ladder_scalar(s), resume_from.as_deref().map(edwards::ladder_scalar)));
 
     let start = primes.partition_point(|&x| x < B1 as u32);
     let end = primes.partition_point(|&x| x <= B2 as u32);
+    let phase2 = Phase2 { B1, stage2, primes, start, end };
     Buffer::get_mut(|result| {
         let mut i = 0;
-        while i < ITERATIONS && !temporary_factors.is_empty() {
-            let curve = &mut curves[i];
-            // print_curve(&curve, ctx_n);
-            i += 1;
-            
+        while i < curve_count && !temporary_factors.is_empty() && !budget.is_exhausted() {
             let factor = temporary_factors.top();
+            // remove the prime factors from other iterations of ECM that also divide the current value
+            factor.remove_primes(prime_factors);
             let curval = &mut factor.n;
-            let index = &mut factor.idx;
             let ctx = &mut factor.ctx;
             
-            // check if we have found a prime factor from other iterations of ECM that also divides the current value 
-            for idx in *index..prime_factors.len() {
-                let p = prime_factors.get(idx);
-                while curval.is_divisible(p) {
-                        curval.div_exact_mut(p);
-                }
-            }
-            
             if *curval == 1 {
-                temporary_factors.dec();
-                i -= 1;  // reuse the current curve
+                temporary_factors.pop();
                 continue;
             }
 
-            *index = prime_factors.len();  // we have tested division up to this point
-
-            while curval.is_perfect_square() {
-                curval.sqrt_mut();
+            while *curval > 1 && let Some(root) = isqrt_rem(curval).exact_root() {
+                curval.assign(root);
             }
             
-            if curval.is_probably_prime(20) != IsPrime::No {
+            // small cofactors are split with table lookups instead of more curves
+            if push_small_factors(curval, prime_factors) {
+                temporary_factors.pop();
+                continue;
+            }
+
+            if is_prime(curval) {
                 prime_factors.next().assign(&*curval);
                 prime_factors.inc();
-                temporary_factors.dec();
-                i -= 1;  // reuse the current curve
+                temporary_factors.pop();
                 continue;
             }
             
@@ -358,47 +525,88 @@ pub fn ecm_trial(n: &Integer, ctx_n: &mut Context, B1: usize, B2: usize, params:
             if ctx.n != *curval {
                 ctx.change_mod(curval);
             }
+
+            let batch_start = i;
+            let batch = &mut curves[batch_start..min(i + CURVE_BATCH, curve_count)];
+            i += batch.len();
             
-            // change the curve to the new modulus if necessary
-            if curval != n {
+            // change the curves to the new modulus if necessary
+            if curval != n && edwards_scalars.is_none() {
                 // println!("changing curve to {}", curval);
-                ctx_n.from_montgomery_mut(&mut curve.0.X);
-                ctx_n.from_montgomery_mut(&mut curve.0.Z);
-                ctx_n.from_montgomery_mut(&mut curve.1);
-                curve.0.X %= &*curval;
-                curve.0.Z %= &*curval;
-                curve.1 %= &*curval;
-                ctx.to_montgomery_mut(&mut curve.0.X);
-                ctx.to_montgomery_mut(&mut curve.0.Z);
-                ctx.to_montgomery_mut(&mut curve.1);
+                for curve in batch.iter_mut() {
+                    ctx_n.from_montgomery_mut(&mut curve.0.X);
+                    ctx_n.from_montgomery_mut(&mut curve.0.Z);
+                    ctx_n.from_montgomery_mut(&mut curve.1);
+                    curve.0.X %= &*curval;
+                    curve.0.Z %= &*curval;
+                    curve.1 %= &*curval;
+                    ctx.to_montgomery_mut(&mut curve.0.X);
+                    ctx.to_montgomery_mut(&mut curve.0.Z);
+                    ctx.to_montgomery_mut(&mut curve.1);
+                }
             }
     
+            if let Some((s, s_previous)) = &edwards_scalars {
+                let ks: Vec<u64> = data.params1[batch_start..i].iter().map(|&(_, v)| v as u64 / 4).collect();
+                let residues = &mut edwards_residues[batch_start..i];
+                edwards::edwards_stage1(ctx, curval, &ks, residues, s, s_previous.as_ref(), batch);
+            } else if let Some(chains_previous) = resume_chains {
+                for (j, curve) in batch.iter_mut().enumerate() {
+                    let residue = &mut residues[batch_start + j];
+                    if !residue.is_valid_mod(curval) {
+                        // the curve was run on another composite, so it starts over. This is rare enough that
+                        // these curves don't need to be batched.
+                        ecm_phase1(ctx, std::slice::from_mut(curve), chains_previous);
+                        residue.save(curve, curval, ctx);
+                        if !residue.is_valid_mod(curval) {
+                            continue;
+                        }
+                    }
+                    residue.load(curve, ctx);
+                }
+            }
+
             // println!("current: {}", curval);
-            ecm_iteration(ctx, curval, B1, block_size, &mut curve.0, &curve.1, &primes, start, end, &gaps, &values, &s, result);
-            
-            
-            // if *result != 1 {
-            //     println!("found: {}", result);
-            //     println!("\n");
-            //     print_curve(curve, ctx);
-            // }
-    
-            if result == Integer::ONE || result == curval {
-                // the current curve failed to find a factor
-                // println!("failed: {}", result);
-                continue;
+            if edwards_scalars.is_none() {
+                ecm_phase1(ctx, batch, chains);
+                for (j, curve) in batch.iter().enumerate() {
+                    residues[batch_start + j].save(curve, curval, ctx);
+                }
+            }
+
+            // stop at the first curve of the batch that finds a factor
+            let mut found = None;
+            for (j, curve) in batch.iter_mut().enumerate() {
+                ecm_iteration(ctx, curval, &phase2, &mut curve.0, &curve.1, result);
+                
+                if result != Integer::ONE && result != curval {
+                    found = Some(batch_start + j);
+                    break;
+                }
             }
 
-            if print_curve_parameters {
-                println!("Bounds: {} {}", B1, B2);
-                println!("DATA: {}, {}", params[i - 1].0, params[i - 1].1);
-                println!("result: {}, curval: {}", result, curval);
+            budget.report(|| FactorEvent::EcmProgress { curves: i, total: curve_count, B1 });
+            let Some(index) = found else {
+                // none of the curves in the batch found a factor
+                continue;
+            };
+            budget.report(|| FactorEvent::FactorFound {
+                method: Method::Ecm, n: curval.clone(), factor: result.clone()
+            });
+            // the parameters are (sigma^2 - 5, 4 sigma)
+            let curve = EcmCurve { family, sigma: data.params1[index].1 as u64 / 4, B1: B1 as u64, B2: B2 as u64 };
+            budget.report(|| FactorEvent::EcmCurveFound { curve, factor: result.clone() });
+            // the curves after it in the batch start over on what is left, like the ones of later batches
+            if edwards_scalars.is_none() {
+                reset_curves(ctx_n, &data.params1[index + 1..i], &mut curves[index + 1..i]);
             }
+            residues[index + 1..i].iter_mut().for_each(Stage1Residue::clear);
+            edwards_residues[index + 1..i].iter_mut().for_each(EdwardsResidue::clear);
+            i = index + 1;
             // don't update the ctx, leave that to before calling ecm_iteration
             curval.div_exact_mut(result);
 
-            temporary_factors.next().update_n_and_index(&*result, prime_factors.len());
-            temporary_factors.inc();
+            temporary_factors.push(result, prime_factors.len());
 
             let len = temporary_factors.len();
             if len > 1 && temporary_factors.get(len - 2).n < temporary_factors.get(len - 1).n {
@@ -406,4 +614,105 @@ pub fn ecm_trial(n: &Integer, ctx_n: &mut Context, B1: usize, B2: usize, params:
             }
         }
     })
-}
\ No newline at end of file
+}
+
+/// Puts the curves back at the starting points of Suyama's parameterization for their parameters, in Montgomery form
+/// modulo the modulus of ctx_n, as `suyama_parameterization` leaves them.
+fn reset_curves(ctx_n: &mut Context, params: &[(u32, u32)], curves: &mut [(MontgomeryPoint, Integer)]) {
+    for (&(_, v), (P, a24)) in params.iter().zip(curves) {
+        // the parameters are (sigma^2 - 5, 4 sigma), whose denominators were already inverted for the batch
+        if let Ok((X, a)) = suyama::suyama_curve(v as u64 / 4, &ctx_n.n) {
+            P.X.assign(ctx_n.to_montgomery(&X));
+            P.Z.assign(&ctx_n.r_mod_n);
+            a24.assign(ctx_n.to_montgomery(&a));
+        }
+    }
+}
+
+/// Runs up to `curves` random curves of ECM on n, with stage 1 up to B1 (at most `prime_limit()`) and stage 2 up to
+/// B2, CURVE_BATCH curves at a time, and stops at the first factor. Unlike `ecm_trial`, it keeps all its state to
+/// itself (see `EcmCheckpoint` to save the curves or to know which one found the factor).
+///
+/// ## Returns
+/// * `Some(d)` - A proper factor of n (2 if n is even).
+/// * `None` - n is at most 3 or prime, or none of the curves found a factor.
+pub fn factor(n: &Integer, B1: u64, B2: u64, curves: usize) -> Option<Integer> {
+    if *n > 3 && n.is_even() {
+        return Some(Integer::from(2));
+    }
+    if *n <= 3 || is_prime(n) {
+        return None;
+    }
+    let never = AtomicBool::new(false);
+    for count in (0..curves).step_by(CURVE_BATCH).map(|start| min(CURVE_BATCH, curves - start)) {
+        let mut checkpoint = match EcmCheckpoint::random(n, count) {
+            Ok(checkpoint) => checkpoint,
+            Err(d) => return Some(d),
+        };
+        let found = checkpoint.stage1(B1, &never).or_else(|| checkpoint.stage2(B2, &never));
+        if let Some((d, _)) = found {
+            return Some(d);
+        }
+    }
+    None
+}
+
+/// Runs the first `count` curves of an ECM round (e.g with the tables of the first round of the default `Factorizer`,
+/// the t20 level with B1 = 11e3 and B2 = 50 * B1) on n, CURVE_BATCH curves at a time.
+/// Unlike ecm_trial, it stops at the first non-trivial factor, which is stored in g, and it checks cancel
+/// between curves so it can be raced against other methods.
+pub fn ecm_curves(n: &Integer, data: &PrimeFactorizeData, tables: &EcmTables, count: usize, cancel: &AtomicBool,
+    g: &mut Integer) -> Option<()> {
+    let primes = &data.primes;
+    let (B1, B2) = (tables.round.B1, tables.round.B2);
+    let start = primes.partition_point(|&x| x < B1 as u32);
+    let end = primes.partition_point(|&x| x <= B2 as u32);
+    let phase2 = Phase2 { B1, stage2: &tables.stage2, primes, start, end };
+
+    let mut ctx = Context::new(n.clone());
+    let mut curves: Vec<(MontgomeryPoint, Integer)> = vec![Default::default(); ITERATIONS];
+    if let Err(d) = suyama_parameterization(&mut ctx, &data.params1, &mut curves) {
+        if d == *n {
+            return None;
+        }
+        g.assign(d);
+        return Some(());
+    }
+
+    for batch in curves[..min(count, ITERATIONS)].chunks_mut(CURVE_BATCH) {
+        if cancel.load(Ordering::Relaxed) {
+            return None;
+        }
+        ecm_phase1(&mut ctx, batch, &tables.chains);
+        for curve in batch.iter_mut() {
+            if cancel.load(Ordering::Relaxed) {
+                return None;
+            }
+            ecm_iteration(&mut ctx, n, &phase2, &mut curve.0, &curve.1, g);
+            if *g != 1 && *g != *n {
+                return Some(());
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::number_theory::random::with_seed;
+
+    #[test]
+    fn test_factor() {
+        let p = Integer::from(1_000_000_000_039u64);
+        let q = Integer::from(1_000_000_000_061u64);
+        let n = Integer::from(&p * &q) * ((Integer::from(1) << 89) - 1u32);
+        let d = with_seed(Some(3764), || factor(&n, 5000, 500_000, 100)).unwrap();
+        assert!(d > 1 && d < n && n.is_divisible(&d));
+
+        assert_eq!(factor(&Integer::from(2 * 1_000_003), 5000, 500_000, 10), Some(Integer::from(2)));
+        for n in [Integer::from(1), Integer::from(3), p] {
+            assert_eq!(factor(&n, 5000, 500_000, 10), None);
+        }
+    }
+}
@@ -0,0 +1,173 @@
+use rug::{integer::Order, Integer};
+
+/// A polynomial over Z/nZ, as its coefficients from the constant one up, each in [0, n).
+pub type Poly = Vec<Integer>;
+
+/// Writes the coefficients of a into the limbs of a single integer, `limbs` limbs per coefficient.
+fn pack(a: &[Integer], limbs: usize) -> Integer {
+    let mut digits = vec![0u64; a.len() * limbs];
+    for (slot, c) in digits.chunks_mut(limbs).zip(a) {
+        c.write_digits(slot, Order::Lsf);
+    }
+    Integer::from_digits(&digits, Order::Lsf)
+}
+
+/// The product of a and b (Kronecker substitution): both are packed into integers with slots wide enough for the
+/// coefficients of the product, so a single multiplication of GMP (which switches to FFT for large operands) does
+/// the work, and the coefficients are read back from the slots.
+pub fn mul(a: &[Integer], b: &[Integer], n: &Integer) -> Poly {
+    if a.is_empty() || b.is_empty() {
+        return Vec::new();
+    }
+    // every coefficient of the product is a sum of at most min(len) products below n^2
+    let bits = 2 * n.significant_bits() + usize::BITS - a.len().min(b.len()).leading_zeros();
+    let limbs = bits.div_ceil(64) as usize;
+    let product = pack(a, limbs) * pack(b, limbs);
+    let mut digits = vec![0u64; product.significant_digits::<u64>()];
+    product.write_digits(&mut digits, Order::Lsf);
+
+    let len = a.len() + b.len() - 1;
+    (0..len).map(|i| {
+        let slot = &digits[(i * limbs).min(digits.len())..((i + 1) * limbs).min(digits.len())];
+        Integer::from_digits(slot, Order::Lsf) % n
+    }).collect()
+}
+
+/// The product of a and b modulo X^k, with exactly k coefficients.
+fn mul_low(a: &[Integer], b: &[Integer], k: usize, n: &Integer) -> Poly {
+    let mut product = mul(&a[..a.len().min(k)], &b[..b.len().min(k)], n);
+    product.resize(k, Integer::new());
+    product
+}
+
+/// The inverse of f modulo X^k, for f with constant coefficient 1, with Newton's iteration g = g(2 - fg).
+fn inverse_series(f: &[Integer], k: usize, n: &Integer) -> Poly {
+    let mut g = vec![Integer::from(1)];
+    let mut precision = 1;
+    while precision < k {
+        precision = (2 * precision).min(k);
+        let mut e = mul_low(f, &g, precision, n);
+        for c in e.iter_mut() {
+            if *c != 0 {
+                *c = Integer::from(n - &*c);
+            }
+        }
+        e[0] += 2;
+        e[0] %= n;
+        g = mul_low(&g, &e, precision, n);
+    }
+    g
+}
+
+/// The remainder of a divided by the monic f, with fewer coefficients than f. The quotient is found from the
+/// reversed polynomials, rev(q) = rev(a) / rev(f) mod X^(deg a - deg f + 1), where rev(f) starts with 1.
+pub fn rem(a: &[Integer], f: &[Integer], n: &Integer) -> Poly {
+    let d = f.len() - 1;
+    if a.len() <= d {
+        return a.to_vec();
+    }
+    let quotient_len = a.len() - d;
+    let reversed_f: Poly = f.iter().rev().cloned().collect();
+    let inverse = inverse_series(&reversed_f, quotient_len, n);
+    rem_with_inverse(a, f, &inverse, n)
+}
+
+/// `rem`, with the inverse of rev(f) given to enough precision, so that dividing by the same f many times only
+/// computes it once.
+pub fn rem_with_inverse(a: &[Integer], f: &[Integer], inverse: &[Integer], n: &Integer) -> Poly {
+    let d = f.len() - 1;
+    if a.len() <= d {
+        return a.to_vec();
+    }
+    let quotient_len = a.len() - d;
+    debug_assert!(inverse.len() >= quotient_len, "the inverse is not precise enough for the dividend");
+    let reversed_a: Poly = a.iter().rev().take(quotient_len).cloned().collect();
+    let mut quotient = mul_low(&reversed_a, inverse, quotient_len, n);
+    quotient.reverse();
+
+    let qf = mul(&quotient, &f[..d], n);
+    a[..d].iter().zip(qf.iter().chain(std::iter::repeat(&Integer::ZERO))).map(|(x, y)| {
+        let mut r = Integer::from(x - y);
+        if r < 0 {
+            r += n;
+        }
+        r
+    }).collect()
+}
+
+/// The inverse of the reversed monic f to the precision that `rem_with_inverse` needs for dividends with fewer
+/// than 2 * deg f coefficients.
+pub fn reversed_inverse(f: &[Integer], n: &Integer) -> Poly {
+    let reversed_f: Poly = f.iter().rev().cloned().collect();
+    inverse_series(&reversed_f, f.len() - 1, n)
+}
+
+/// The product tree of the polynomials X - r for the roots r: the first level holds them, and every level above
+/// holds the products of pairs of the one below (an odd one out is carried up as it is). The last level is the product
+/// of all of them.
+pub fn product_tree(roots: &[Integer], n: &Integer) -> Vec<Vec<Poly>> {
+    let leaves: Vec<Poly> = roots.iter().map(|r| {
+        let constant = if *r == 0 { Integer::new() } else { Integer::from(n - r) };
+        vec![constant, Integer::from(1)]
+    }).collect();
+    let mut tree = vec![leaves];
+    while tree.last().unwrap().len() > 1 {
+        let next = tree.last().unwrap().chunks(2).map(|pair| match pair {
+            [a, b] => mul(a, b, n),
+            _ => pair[0].clone(),
+        }).collect();
+        tree.push(next);
+    }
+    tree
+}
+
+/// The values h(r) modulo n at the roots r of a product tree, in the order of the roots. h is reduced down the tree
+/// (a remainder tree), so every leaf X - r is left with the constant h(r).
+pub fn evaluate(h: &[Integer], tree: &[Vec<Poly>], n: &Integer) -> Vec<Integer> {
+    let mut remainders = vec![h.to_vec()];
+    for level in tree.iter().rev() {
+        remainders = level.iter().enumerate().map(|(i, f)| rem(&remainders[i / 2], f, n)).collect();
+    }
+    remainders.into_iter().map(|r| r.into_iter().next().unwrap_or_default()).collect()
+}
+
+/// The product of h(r) over the roots r of a product tree, modulo n (see `evaluate`).
+pub fn evaluate_product(h: &[Integer], tree: &[Vec<Poly>], n: &Integer) -> Integer {
+    let mut product = Integer::from(1);
+    for value in evaluate(h, tree, n) {
+        if value == 0 {
+            return value;
+        }
+        product *= value;
+        product %= n;
+    }
+    product
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_poly() {
+        let n = Integer::from(1_000_000_007);
+        let roots: Vec<Integer> = (1..=37u32).map(|r| Integer::from(r * r * 1009 + 7)).collect();
+        let tree = product_tree(&roots, &n);
+        let f = &tree.last().unwrap()[0];
+        assert_eq!(f.len(), 38);
+
+        // h(r) = r^2 + 3 for every root, compared to the values computed directly
+        let h = vec![Integer::from(3), Integer::new(), Integer::from(1)];
+        let expected = roots.iter().fold(Integer::from(1), |acc, r| acc * (Integer::from(r * r) + 3) % &n);
+        assert_eq!(evaluate_product(&h, &tree, &n), expected);
+        assert_eq!(evaluate(&h, &tree, &n)[3], Integer::from(&roots[3] * &roots[3]) + 3);
+
+        // a large h reduced by f first, with a precomputed inverse
+        let big: Poly = (0..70u32).map(|i| Integer::from(i * 7919 + 11)).collect();
+        let inverse = reversed_inverse(f, &n);
+        let reduced = rem_with_inverse(&mul(&big[..37], &big[33..], &n), f, &inverse, &n);
+        assert_eq!(reduced, rem(&mul(&big[..37], &big[33..], &n), f, &n));
+        let value = |p: &[Integer], x: &Integer| p.iter().rev().fold(Integer::new(), |acc, c| (acc * x + c) % &n);
+        assert_eq!(value(&reduced, &roots[5]), value(&big[..37], &roots[5]) * value(&big[33..], &roots[5]) % &n);
+    }
+}
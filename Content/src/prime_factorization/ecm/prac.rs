@@ -0,0 +1,257 @@
+#![allow(non_snake_case)]
+
+use std::cell::RefCell;
+use std::mem::swap;
+
+use rug::Integer;
+
+use crate::montgomery_mod_mult::Context;
+use crate::prime_factorization::data::{PRAC_COSTS, PRAC_MULTIPLIERS};
+
+use super::{x_add, x_double, MontgomeryPoint};
+
+/// The prime factors of a stage 1 scalar with multiplicity, in the order `ecm_phase1` multiplies by them, each with
+/// the index in `PRAC_MULTIPLIERS` of the ratio that gives it the cheapest chain (see `best_multiplier`). 2 and 3
+/// don't need one: they are a doubling and a tripling.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PracChains {
+    pub primes: Vec<(u32, u8)>,
+}
+
+impl PracChains {
+    /// The chains of the prime powers (p, e), the scalar being the product of all the p^e.
+    pub fn new(powers: impl IntoIterator<Item = (u32, u32)>) -> Self {
+        let primes = powers.into_iter()
+            .flat_map(|(p, e)| std::iter::repeat_n((p, best_multiplier(p as u64)), e as usize))
+            .collect();
+        Self { primes }
+    }
+}
+
+/// The first two steps of the chain of k from the ratio v: d = k - r and e = 2r - k for r = round(kv), the
+/// differences after the first doubling and addition, or None if r is not in (k / 2, k).
+fn start(k: u64, v: f64) -> Option<(u64, u64)> {
+    let r = (k as f64 * v + 0.5) as u64;
+    (2 * r > k && r < k).then(|| (k - r, 2 * r - k))
+}
+
+/// The cost of the PRAC chain of k from the ratio v, with the costs of `PRAC_COSTS`, or u32::MAX if v doesn't give
+/// one. It follows the same rules as `prac`, without the points.
+pub fn lucas_cost(k: u64, v: f64) -> u32 {
+    let (add, double) = PRAC_COSTS;
+    let Some((mut d, mut e)) = start(k, v) else {
+        return u32::MAX;
+    };
+    let mut cost = add + double;
+    while d != e {
+        if d < e {
+            swap(&mut d, &mut e);
+        }
+        cost += match () {
+            _ if d - e <= e / 4 && (d + e) % 3 == 0 => {
+                d = (2 * d - e) / 3;
+                e = (e - d) / 2;
+                3 * add
+            }
+            _ if d - e <= e / 4 && (d - e) % 6 == 0 => {
+                d = (d - e) / 2;
+                add + double
+            }
+            _ if d <= 4 * e => {
+                d -= e;
+                add
+            }
+            _ if (d + e) % 2 == 0 => {
+                d = (d - e) / 2;
+                add + double
+            }
+            _ if d % 2 == 0 => {
+                d /= 2;
+                add + double
+            }
+            _ if d % 3 == 0 => {
+                d = d / 3 - e;
+                3 * add + double
+            }
+            _ if (d + e) % 3 == 0 => {
+                d = (d - 2 * e) / 3;
+                3 * add + double
+            }
+            _ if (d - e) % 3 == 0 => {
+                d = (d - e) / 3;
+                3 * add + double
+            }
+            _ => {
+                e /= 2;
+                add + double
+            }
+        };
+    }
+    cost
+}
+
+/// The index in `PRAC_MULTIPLIERS` of the ratio that gives the cheapest PRAC chain for k >= 5.
+pub fn best_multiplier(k: u64) -> u8 {
+    (0..PRAC_MULTIPLIERS.len()).min_by_key(|&i| lucas_cost(k, PRAC_MULTIPLIERS[i])).unwrap() as u8
+}
+
+thread_local! {
+    static BUFFER: RefCell<[MontgomeryPoint; 5]> = RefCell::new(std::array::from_fn(|_| MontgomeryPoint::default()));
+}
+
+struct Buffer;
+
+impl Buffer {
+    fn get_mut<F, R>(f: F) -> R
+    where
+        F: FnOnce(&mut [MontgomeryPoint; 5]) -> R,
+    {
+        BUFFER.with(|cell| f(&mut cell.borrow_mut()))
+    }
+}
+
+/// R += Q, where D = R - Q. Unlike the additions of the ladder, D may have any Z.
+fn add(R: &mut MontgomeryPoint, Q: &MontgomeryPoint, D: &MontgomeryPoint, ctx: &mut Context) {
+    x_add(&mut R.X, &mut R.Z, &Q.X, &Q.Z, &D.X, ctx);
+    R.X *= ctx.wrap(&D.Z);
+}
+
+/// R = P + Q, where D = P - Q.
+fn sum(R: &mut MontgomeryPoint, P: &MontgomeryPoint, Q: &MontgomeryPoint, D: &MontgomeryPoint, ctx: &mut Context) {
+    R.assign(P);
+    add(R, Q, D, ctx);
+}
+
+fn double(P: &mut MontgomeryPoint, a24: &Integer, ctx: &mut Context) {
+    x_double(&mut P.X, &mut P.Z, a24, ctx);
+}
+
+/// P = 3P, with T as a temporary.
+fn triple(P: &mut MontgomeryPoint, T: &mut MontgomeryPoint, a24: &Integer, ctx: &mut Context) {
+    T.assign(P);
+    double(T, a24, ctx);
+    add(T, P, P, ctx);
+    swap(P, T);
+}
+
+/// A = kA for a prime k, with Montgomery's PRAC chain from the ratio of index `multiplier` in `PRAC_MULTIPLIERS`.
+/// The chain keeps three points A = dP, B = eP and C = A - B, and shrinks d and e with the first rule of
+/// Montgomery's table that applies, each a few differential additions and doublings, until d = e, where A + B is kP.
+/// For k = 2 and k = 3, A is doubled or tripled.
+pub fn prac(A: &mut MontgomeryPoint, k: u32, multiplier: u8, a24: &Integer, ctx: &mut Context) {
+    if k == 2 {
+        return double(A, a24, ctx);
+    }
+    Buffer::get_mut(|[B, C, T, U, V]| {
+        if k == 3 {
+            return triple(A, T, a24, ctx);
+        }
+        let (mut d, mut e) = start(k as u64, PRAC_MULTIPLIERS[multiplier as usize])
+            .expect("the multipliers give a chain for every prime k >= 5");
+        B.assign(A);
+        C.assign(A);
+        double(A, a24, ctx);
+        while d != e {
+            if d < e {
+                swap(&mut d, &mut e);
+                swap(A, B);
+            }
+            if d - e <= e / 4 && (d + e) % 3 == 0 {
+                d = (2 * d - e) / 3;
+                e = (e - d) / 2;
+                sum(T, A, B, C, ctx);   // T = A + B
+                sum(U, T, A, B, ctx);   // U = T + A
+                add(B, T, A, ctx);      // B = B + T
+                swap(A, U);
+            } else if d - e <= e / 4 && (d - e) % 6 == 0 {
+                d = (d - e) / 2;
+                add(B, A, C, ctx);      // B = B + A
+                double(A, a24, ctx);
+            } else if d <= 4 * e {
+                d -= e;
+                sum(T, B, A, C, ctx);   // T = B + A, then (B, T, C) = (T, C, B)
+                swap(B, T);
+                swap(T, C);
+            } else if (d + e) % 2 == 0 {
+                d = (d - e) / 2;
+                add(B, A, C, ctx);      // B = B + A
+                double(A, a24, ctx);
+            } else if d % 2 == 0 {
+                d /= 2;
+                add(C, A, B, ctx);      // C = C + A
+                double(A, a24, ctx);
+            } else if d % 3 == 0 {
+                d = d / 3 - e;
+                T.assign(A);
+                double(T, a24, ctx);    // T = 2A
+                sum(U, A, B, C, ctx);   // U = A + B
+                sum(V, T, A, A, ctx);
+                swap(A, V);             // A = 3A
+                add(T, U, C, ctx);      // T = T + U, then (C, B, T) = (B, T, C)
+                swap(C, B);
+                swap(B, T);
+            } else if (d + e) % 3 == 0 {
+                d = (d - 2 * e) / 3;
+                sum(T, A, B, C, ctx);   // T = A + B
+                sum(U, T, A, B, ctx);
+                swap(B, U);             // B = T + A
+                triple(A, T, a24, ctx);
+            } else if (d - e) % 3 == 0 {
+                d = (d - e) / 3;
+                sum(T, A, B, C, ctx);   // T = A + B
+                add(C, A, B, ctx);      // C = C + A
+                swap(B, T);
+                triple(A, T, a24, ctx);
+            } else {
+                e /= 2;
+                add(C, B, A, ctx);      // C = C + B
+                double(B, a24, ctx);
+            }
+        }
+        add(A, B, C, ctx);
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::number_theory::stored_primes;
+    use crate::prime_factorization::ecm::{scalar_mul, suyama::suyama_curve};
+
+    #[test]
+    fn test_prac() {
+        let n = Integer::from(1_000_000_007u64) * 998_244_353u64;
+        let mut ctx = Context::new(n.clone());
+        let (X, a24) = suyama_curve(11, &n).unwrap();
+        let P = MontgomeryPoint { X: ctx.to_montgomery(&X), Z: ctx.one() };
+        let a24 = ctx.to_montgomery(&a24);
+        let same = |P: &MontgomeryPoint, Q: &MontgomeryPoint, ctx: &mut Context| {
+            let (X, Z) = (ctx.from_montgomery(&P.X), ctx.from_montgomery(&P.Z));
+            (X * ctx.from_montgomery(&Q.Z) - Z * ctx.from_montgomery(&Q.X)).is_divisible(&n)
+        };
+
+        // every multiplier that gives a chain gives kP, from a point with Z != 1 too
+        let mut Q = P.clone();
+        double(&mut Q, &a24, &mut ctx);
+        for &k in stored_primes().iter().take(200).chain(&[1_000_003, 24_999_983]) {
+            for multiplier in 0..PRAC_MULTIPLIERS.len() as u8 {
+                if k > 3 && start(k as u64, PRAC_MULTIPLIERS[multiplier as usize]).is_none() {
+                    continue;
+                }
+                let mut R = Q.clone();
+                prac(&mut R, k, multiplier, &a24, &mut ctx);
+                assert!(same(&R, &scalar_mul(&Q, &Integer::from(k), &a24, &mut ctx), &mut ctx), "k = {k}");
+            }
+        }
+
+        // the chains of a scalar multiply by all of it, and cost less than the ladder
+        let chains = PracChains::new([(2, 3), (3, 2), (5, 1), (7, 1), (101, 2)]);
+        let mut R = P.clone();
+        for &(p, multiplier) in &chains.primes {
+            prac(&mut R, p, multiplier, &a24, &mut ctx);
+        }
+        assert!(same(&R, &scalar_mul(&P, &Integer::from(2520u32 * 101 * 101), &a24, &mut ctx), &mut ctx));
+        let (add, double) = PRAC_COSTS;
+        assert!(lucas_cost(1_000_003, PRAC_MULTIPLIERS[0]) < 19 * (add + double));
+    }
+}
@@ -0,0 +1,107 @@
+use rug::Integer;
+
+use super::{x_add, x_double, MontgomeryPoint};
+use crate::montgomery_mod_mult::{recoding::ladder_bits, Context};
+
+/// Whether P is the point at infinity, Z = 0 mod n.
+pub fn is_infinity(P: &MontgomeryPoint, ctx: &Context) -> bool {
+    P.Z.is_divisible(&ctx.n)
+}
+
+/// [k]P on the curve By^2 = x^3 + Ax^2 + x, with the Montgomery ladder over the bits of k: R0 = [j]P and
+/// R1 = [j + 1]P, whose difference is always P. The coordinates of P and a24 = (A + 2) / 4 are in the Montgomery form
+/// of ctx, like the curves of ECM, and so is the result. Unlike the ladder of stage 1, k is any k >= 0 (k = 0 gives
+/// the point at infinity, (1 : 0)), and P may have Z != 1 or be a point of order 2, (0 : 1), whose differences
+/// x-only additions can't use.
+pub fn scalar_mul(P: &MontgomeryPoint, k: &Integer, a24: &Integer, ctx: &mut Context) -> MontgomeryPoint {
+    let infinity = MontgomeryPoint { X: ctx.one(), Z: Integer::new() };
+    if *k == 0 || is_infinity(P, ctx) {
+        return infinity;
+    }
+    if P.X.is_divisible(&ctx.n) {
+        return if k.is_odd() { P.clone() } else { infinity };
+    }
+
+    let mut R0 = P.clone();
+    let mut R1 = P.clone();
+    x_double(&mut R1.X, &mut R1.Z, a24, ctx);
+    for bit in ladder_bits(k) {
+        // the differential additions leave X scaled by 1 / Z of the difference
+        let (sum, double) = match bit {
+            true => (&mut R0, &mut R1),
+            false => (&mut R1, &mut R0),
+        };
+        x_add(&mut sum.X, &mut sum.Z, &double.X, &double.Z, &P.X, ctx);
+        sum.X *= ctx.wrap(&P.Z);
+        x_double(&mut double.X, &mut double.Z, a24, ctx);
+    }
+    R0
+}
+
+/// Maps P into the subgroup of points whose order divides group_order / cofactor, by multiplying it by the cofactor.
+/// With #E = cofactor * q for a prime q that does not divide the cofactor, the result is in the subgroup of order q,
+/// and it is the point at infinity exactly when P had no component of order q. Same forms as `scalar_mul`.
+pub fn clear_cofactor_point(P: &MontgomeryPoint, cofactor: &Integer, a24: &Integer, ctx: &mut Context) -> MontgomeryPoint {
+    scalar_mul(P, cofactor, a24, ctx)
+}
+
+/// Whether [N]P is the point at infinity, where N is the product of the (q, e) pairs of order_factorization, i.e
+/// whether P is in the N-torsion E[N]. For an N that divides #E only once per prime (e.g a prime order subgroup whose
+/// square does not divide #E), that is the single subgroup of order N. Same forms as `scalar_mul`.
+pub fn point_in_subgroup(P: &MontgomeryPoint, order_factorization: &[(Integer, u32)], a24: &Integer, ctx: &mut Context)
+    -> bool {
+    let mut Q = P.clone();
+    for (q, e) in order_factorization {
+        for _ in 0..*e {
+            Q = scalar_mul(&Q, q, a24, ctx);
+        }
+    }
+    is_infinity(&Q, ctx)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prime_factorization::prime_factorize;
+
+    #[test]
+    fn test_point_subgroups() {
+        // y^2 = x^3 + 7x^2 + x over F_p, with its points counted directly
+        let (p, a) = (10_007u32, 7u32);
+        let n = Integer::from(p);
+        let rhs = |x: u32| Integer::from(x) * (Integer::from(x) * (x + a) + 1u32) % p;
+        let order = (0..p).fold(Integer::from(p + 1), |acc, x| acc + rhs(x).jacobi(&n));
+        let factorization = prime_factorize(&order);
+        let (q, e) = factorization.factors().last().unwrap().clone();
+        assert_eq!(e, 1);
+
+        let mut ctx = Context::new(n.clone());
+        let a24 = ctx.to_montgomery((Integer::from(a + 2) * Integer::from(4).invert(&n).unwrap()) % &n);
+        let mut tested = 0;
+        for x in (1..p).filter(|&x| rhs(x).jacobi(&n) == 1).take(50) {
+            let P = MontgomeryPoint { X: ctx.to_montgomery(Integer::from(x)), Z: ctx.one() };
+            assert!(point_in_subgroup(&P, factorization.factors(), &a24, &mut ctx));
+            let cleared = clear_cofactor_point(&P, &Integer::from(&order / &q), &a24, &mut ctx);
+            assert!(point_in_subgroup(&cleared, &[(q.clone(), 1)], &a24, &mut ctx));
+            tested += !is_infinity(&cleared, &ctx) as u32;
+        }
+        assert!(tested > 40);
+
+        // [k]P against repeated additions of P, from a projective P with Z != 1
+        let P = MontgomeryPoint { X: ctx.to_montgomery(Integer::from(6)), Z: ctx.to_montgomery(Integer::from(3)) };
+        let affine = |R: &MontgomeryPoint, ctx: &mut Context| {
+            let z = ctx.from_montgomery(&R.Z) % &n;
+            (z != 0).then(|| ctx.from_montgomery(&R.X) * z.invert(&n).unwrap() % &n)
+        };
+        let mut previous = scalar_mul(&P, &Integer::from(1), &a24, &mut ctx);
+        let mut current = scalar_mul(&P, &Integer::from(2), &a24, &mut ctx);
+        assert_eq!(affine(&previous, &mut ctx), Some(Integer::from(2)));
+        for k in 3..40u32 {
+            let mut next = current.clone();
+            x_add(&mut next.X, &mut next.Z, &P.X, &P.Z, &previous.X, &mut ctx);
+            next.X *= ctx.wrap(&previous.Z);
+            assert_eq!(affine(&next, &mut ctx), affine(&scalar_mul(&P, &Integer::from(k), &a24, &mut ctx), &mut ctx));
+            (previous, current) = (current, next);
+        }
+    }
+}
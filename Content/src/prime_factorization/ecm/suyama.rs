@@ -2,21 +2,17 @@
 
 use std::{cell::RefCell, cmp::max};
 
-use rug::{Assign, Integer};
+use rug::{ops::Pow, Assign, Integer};
 
-use rand::{rng, Rng};
-
-use crate::{montgomery_mod_mult::Context, prime_factorization::ITERATIONS};
+use crate::{montgomery_mod_mult::Context, number_theory::random::random_u64, prime_factorization::ITERATIONS};
 
 use super::MontgomeryPoint;
 
 pub fn generate_parameters() -> [(u32, u32); ITERATIONS] {
-    let mut rng = rng();
-
     let mut params: [(u32, u32); ITERATIONS] = std::array::from_fn(|_| (0, 0));
 
     for item in params.iter_mut() {
-        let sigma: u16 = rng.random();
+        let sigma = random_u64() as u16;
         item.0 = sigma.into();
         item.0 = max(item.0, 6);  // sigma must be > 5
         item.1 = 4 * item.0;
@@ -27,6 +23,28 @@ pub fn generate_parameters() -> [(u32, u32); ITERATIONS] {
     params
 }
 
+/// The curve and starting point of Suyama's parameterization for a single sigma > 5, in standard form (not in
+/// Montgomery form): u = sigma^2 - 5, v = 4 sigma, X = u^3 / v^3 (with Z = 1) and
+/// a24 = (v - u)^3 (3u + v) / (16 u^3 v), the same curve as `suyama_parameterization` and GMP-ECM's sigma (PARAM=0)
+/// build.
+///
+/// ## Returns
+/// * `Ok((X, a24))` - The starting point and the curve modulo n.
+/// * `Err(d)` - The gcd of n with a denominator, when it is not 1.
+pub fn suyama_curve(sigma: u64, n: &Integer) -> Result<(Integer, Integer), Integer> {
+    let u = Integer::from(sigma) * sigma - 5u32;
+    let v = Integer::from(sigma) * 4u32;
+    // both denominators divide 16 u^3 v^3, so a single inversion is enough
+    let denominator = Integer::from(16u32) * u.clone().pow(3) * v.clone().pow(3);
+    let inverse = denominator.invert_ref(n)
+        .map(Integer::from)
+        .ok_or_else(|| denominator.gcd(n))?;
+
+    let X = (u.clone().pow(6) * 16u32 * &inverse).modulo(n);
+    let a24 = ((v.clone() - &u).pow(3) * (u * 3u32 + &v) * v.square() * inverse).modulo(n);
+    Ok((X, a24))
+}
+
 struct Buffer;
 
 thread_local! {
@@ -52,7 +70,14 @@ impl Buffer {
 
 /// Generates n curves and starting points defined by Suyama's parameterization.
 /// Generating them in batches is faster.
-pub fn suyama_parameterization(ctx: &mut Context, params: &[(u32, u32)], curves: &mut [(MontgomeryPoint, Integer)]) {    
+///
+/// ## Returns
+/// * `Ok(())` - The curves are set up, in Montgomery form.
+/// * `Err(d)` - The gcd of n with the first denominator that is not invertible, a factor of n. The curves are
+///   left unusable.
+pub fn suyama_parameterization(ctx: &mut Context, params: &[(u32, u32)], curves: &mut [(MontgomeryPoint, Integer)])
+    -> Result<(), Integer> {
+
     let mont_16 = ctx.to_montgomery(&Integer::from(16));
     let mont_3 = ctx.to_montgomery(&Integer::from(3));
 
@@ -70,27 +95,19 @@ pub fn suyama_parameterization(ctx: &mut Context, params: &[(u32, u32)], curves:
     }
 
     Buffer::get_mut(|arr, value, value2| {
-        let prod = &mut *arr;
-        
-        // Invert the denominators together    
-        (*prod)[0].assign(&curves[0].1);
-        for i in 1..ITERATIONS {
-            let [prod_i_1, prod_i] = (*prod).get_disjoint_mut([i-1, i]).unwrap();
-            prod_i.assign(&*prod_i_1);
-            *prod_i *= ctx.wrap(&curves[i].1);
+        // Invert the denominators together, in the buffer to reuse its allocations
+        for (denominator, (_, a24)) in arr.iter_mut().zip(curves.iter_mut()) {
+            std::mem::swap(denominator, a24);
         }
-
-        // It shouldn't be the case that their gcd with n is not 1, since pollard would have sieved out small factors 
-        value.assign(&(*prod)[ITERATIONS - 1]);
-        ctx.invert_mut(value).unwrap();
-        
-        // calculate mod inverses (the denominators of a)
-        for i in (0..ITERATIONS - 1).rev() {
-            prod[i] *= ctx.wrap(&*value);  // prod[i] is now (curves[i + 1].1)^-1 in montgomery form
-            *value *= ctx.wrap(&curves[i + 1].1);  // value is now (product of curves[0].1 ... curves[i].1)^-1 in montgomery form
-            curves[i + 1].1.assign(&prod[i]);
+        // the primes of the denominators are below 2^32, so they can be above the bound of trial division
+        let inverted = ctx.invert_batch(arr);
+        for (denominator, (_, a24)) in arr.iter_mut().zip(curves.iter_mut()) {
+            std::mem::swap(denominator, a24);  // a24 is now (16 u^3 v)^-1 in montgomery form
+        }
+        if let Err(i) = inverted {
+            // the denominator is in Montgomery form, which doesn't change its gcd with n
+            return Err(curves[i].1.gcd_ref(&ctx.n).into());
         }
-        curves[0].1.assign(&*value);
 
         let (w, y) = (&mut *value, &mut *value2);
     
@@ -114,6 +131,6 @@ pub fn suyama_parameterization(ctx: &mut Context, params: &[(u32, u32)], curves:
             *a24 *= ctx.wrap(&P.Z);
             P.Z.assign(&ctx.r_mod_n);  // Z = 1
         }
-    });
-    
+        Ok(())
+    })
 }
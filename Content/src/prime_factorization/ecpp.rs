@@ -0,0 +1,614 @@
+use std::{collections::BTreeMap, f64::consts::{LN_2, PI}, sync::Mutex};
+
+use once_cell::sync::OnceCell;
+use rug::{Complete, Integer};
+
+use super::data::{ECPP_CANDIDATES, ECPP_LAST_BITS, ECPP_MAX_CLASS_NUMBER, ECPP_MAX_DISCRIMINANT, ECPP_SMOOTH_BOUND};
+use super::pocklington::{pocklington_certificate, verify_pocklington};
+use super::structs::{EcppCertificate, EcppStep, PocklingtonCertificate};
+use crate::number_theory::{is_prime, isqrt_rem};
+use crate::number_theory::random::random_below;
+use crate::number_theory::word::gcd;
+
+/// The product of the primes up to `ECPP_SMOOTH_BOUND`.
+static PRIMORIAL: OnceCell<Integer> = OnceCell::new();
+static DISCRIMINANTS: OnceCell<Vec<Discriminant>> = OnceCell::new();
+/// The Hilbert class polynomials computed so far, by discriminant.
+static CLASS_POLYNOMIALS: Mutex<BTreeMap<i64, Vec<Integer>>> = Mutex::new(BTreeMap::new());
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Point {
+    Infinity,
+    Affine(Integer, Integer),
+}
+
+/// The curve y^2 = x^3 + ax + b over Z/nZ, with affine coordinates in [0, n). The operations return None when they
+/// have to invert a number that is not coprime to n, or meet two points that only a composite n allows, so a result
+/// is also the right one modulo every prime dividing n.
+struct Curve<'a> {
+    n: &'a Integer,
+    a: &'a Integer,
+}
+
+impl Curve<'_> {
+    fn add(&self, p: &Point, q: &Point) -> Option<Point> {
+        let (x1, y1, x2, y2) = match (p, q) {
+            (Point::Infinity, _) => return Some(q.clone()),
+            (_, Point::Infinity) => return Some(p.clone()),
+            (Point::Affine(x1, y1), Point::Affine(x2, y2)) => (x1, y1, x2, y2),
+        };
+        let n = self.n;
+        let slope = match x1 == x2 {
+            true if Integer::from(y1 + y2).is_divisible(n) => return Some(Point::Infinity),
+            // (3x^2 + a) / 2y
+            true if y1 == y2 => {
+                (Integer::from(x1.square_ref()) * 3u32 + self.a) * Integer::from(y1 * 2u32).invert(n).ok()?
+            }
+            true => return None,
+            false => Integer::from(y2 - y1) * Integer::from(x2 - x1).invert(n).ok()?,
+        };
+        let slope = slope.modulo(n);
+        let x3 = (Integer::from(slope.square_ref()) - x1 - x2).modulo(n);
+        let y3 = (slope * Integer::from(x1 - &x3) - y1).modulo(n);
+        Some(Point::Affine(x3, y3))
+    }
+
+    fn mul(&self, k: &Integer, p: &Point) -> Option<Point> {
+        let mut result = Point::Infinity;
+        for i in (0..k.significant_bits()).rev() {
+            result = self.add(&result, &result)?;
+            if k.get_bit(i) {
+                result = self.add(&result, p)?;
+            }
+        }
+        Some(result)
+    }
+}
+
+/// A square root of the quadratic residue a modulo the odd prime p (Tonelli–Shanks).
+fn sqrt_mod(a: &Integer, p: &Integer) -> Integer {
+    let a = a.modulo_ref(p).complete();
+    let p_minus_1 = Integer::from(p - 1u32);
+    // p - 1 = q * 2^s
+    let s = p_minus_1.find_one(0).unwrap();
+    let q = Integer::from(&p_minus_1 >> s);
+    let mut z = Integer::from(2);
+    while z.jacobi(p) != -1 {
+        z += 1;
+    }
+    let power = |x: &Integer, e: &Integer| Integer::from(x.pow_mod_ref(e, p).unwrap());
+
+    let (mut m, mut c) = (s, power(&z, &q));
+    let mut t = power(&a, &q);
+    let mut root = power(&a, &(Integer::from(&q + 1u32) >> 1u32));
+    while t != 1 && a != 0 {
+        // the least i with t^(2^i) = 1
+        let (mut i, mut t_power) = (0, t.clone());
+        while t_power != 1 {
+            t_power = t_power.square() % p;
+            i += 1;
+        }
+        let mut b = c;
+        for _ in 0..m - i - 1 {
+            b = b.square() % p;
+        }
+        root = root * &b % p;
+        c = b.square() % p;
+        t = t * &c % p;
+        m = i;
+    }
+    root
+}
+
+/// Solves 4n = u^2 + |d| v^2 for the prime n and the discriminant d < 0 with (d/n) = 1, with Cornacchia's algorithm:
+/// the Euclidean algorithm on 2n and a square root x = d mod 2 of d, until the remainder drops below 2 sqrt(n).
+fn cornacchia(d: i64, n: &Integer) -> Option<(Integer, Integer)> {
+    let mut x = sqrt_mod(&Integer::from(d), n);
+    if x.is_odd() != (d % 2 != 0) {
+        x = Integer::from(n - &x);
+    }
+    let four_n = Integer::from(n << 2u32);
+    let limit = four_n.sqrt_ref().complete();
+    let mut a = Integer::from(n << 1u32);
+    while x > limit {
+        let r = Integer::from(&a % &x);
+        a = x;
+        x = r;
+    }
+    let rest = four_n - x.square_ref().complete();
+    let abs_d = d.unsigned_abs() as u32;
+    if !rest.is_divisible_u(abs_d) {
+        return None;
+    }
+    let c = rest / abs_d;
+    isqrt_rem(&c).exact_root().map(|root| (x, root))
+}
+
+/// The possible orders n + 1 - t of the curves with complex multiplication by the order of discriminant d, where
+/// 4n = u^2 + |d| v^2: the traces are ±u, and also ±2v for d = -4 and ±(u ± 3v) / 2 for d = -3.
+fn orders(d: i64, n: &Integer, u: &Integer, v: &Integer) -> Vec<Integer> {
+    let mut traces = vec![u.clone()];
+    match d {
+        -4 => traces.push(Integer::from(v << 1u32)),
+        -3 => {
+            let three_v = Integer::from(v * 3u32);
+            traces.push(Integer::from(u + &three_v) >> 1u32);
+            traces.push(Integer::from(u - &three_v) >> 1u32);
+        }
+        _ => {}
+    }
+    let n_plus_1 = Integer::from(n + 1u32);
+    traces.iter()
+        .flat_map(|t| [Integer::from(&n_plus_1 - t), Integer::from(&n_plus_1 + t)])
+        .collect()
+}
+
+/// A negative discriminant D with its reduced primitive forms (a, b, c), b^2 - 4ac = D, one for every class of the
+/// class group of the order of discriminant D.
+struct Discriminant {
+    d: i64,
+    forms: Vec<(i64, i64, i64)>,
+}
+
+/// The discriminants -3 >= D >= -`ECPP_MAX_DISCRIMINANT` of class number up to `ECPP_MAX_CLASS_NUMBER`, the smallest
+/// class numbers first.
+fn discriminants() -> &'static [Discriminant] {
+    DISCRIMINANTS.get_or_init(|| {
+        let mut discriminants: Vec<Discriminant> = (3..=ECPP_MAX_DISCRIMINANT)
+            .filter(|d| d % 4 == 0 || d % 4 == 3)
+            .map(|d| Discriminant { d: -d, forms: reduced_forms(-d) })
+            .filter(|discriminant| discriminant.forms.len() <= ECPP_MAX_CLASS_NUMBER)
+            .collect();
+        discriminants.sort_by_key(|discriminant| (discriminant.forms.len(), -discriminant.d));
+        discriminants
+    })
+}
+
+/// The reduced primitive forms of discriminant d < 0: |b| <= a <= c, with b >= 0 if |b| = a or a = c.
+fn reduced_forms(d: i64) -> Vec<(i64, i64, i64)> {
+    let mut forms = Vec::new();
+    let mut a = 1;
+    while 3 * a * a <= -d {
+        for b in -a + 1..=a {
+            let numerator = b * b - d;
+            if numerator % (4 * a) != 0 {
+                continue;
+            }
+            let c = numerator / (4 * a);
+            if c < a || (b < 0 && a == c) || gcd(gcd(a, b.abs()), c) != 1 {
+                continue;
+            }
+            forms.push((a, b, c));
+        }
+        a += 1;
+    }
+    forms
+}
+
+/// A complex number x + iy in fixed point, as the integers x 2^bits and y 2^bits.
+type Fixed = (Integer, Integer);
+
+/// x y, rounded towards 0 so that the powers of a q with |q| < 1 reach 0.
+fn fixed_mul(x: &Fixed, y: &Fixed, bits: u32) -> Fixed {
+    let scale = Integer::from(1) << bits;
+    let real = Integer::from(&x.0 * &y.0) - Integer::from(&x.1 * &y.1);
+    let imaginary = Integer::from(&x.0 * &y.1) + Integer::from(&x.1 * &y.0);
+    (real / &scale, imaginary / scale)
+}
+
+fn fixed_div(x: &Fixed, y: &Fixed, bits: u32) -> Fixed {
+    let norm = Integer::from(y.0.square_ref()) + Integer::from(y.1.square_ref());
+    let real = Integer::from(&x.0 * &y.0) + Integer::from(&x.1 * &y.1);
+    let imaginary = Integer::from(&x.1 * &y.0) - Integer::from(&x.0 * &y.1);
+    ((real << bits) / &norm, (imaginary << bits) / norm)
+}
+
+/// e^z for a z of small modulus, with its Taylor series.
+fn fixed_exp(z: &Fixed, bits: u32) -> Fixed {
+    let one = Integer::from(1) << bits;
+    let (mut sum, mut term) = ((one.clone(), Integer::new()), (one, Integer::new()));
+    for k in 1u32.. {
+        term = fixed_mul(&term, z, bits);
+        term = (term.0 / k, term.1 / k);
+        if term.0 == 0 && term.1 == 0 {
+            break;
+        }
+        sum = (sum.0 + &term.0, sum.1 + &term.1);
+    }
+    sum
+}
+
+/// π 2^bits, with Machin's formula π = 16 arctan(1/5) - 4 arctan(1/239).
+fn fixed_pi(bits: u32) -> Integer {
+    let arctan_inverse = |x: u32| {
+        let mut power = (Integer::from(1) << bits) / x;
+        let (mut sum, mut k) = (Integer::new(), 0u32);
+        while power != 0 {
+            let term = Integer::from(&power / (2 * k + 1));
+            if k % 2 == 0 { sum += term } else { sum -= term }
+            power /= x * x;
+            k += 1;
+        }
+        sum
+    };
+    arctan_inverse(5) * 16u32 - arctan_inverse(239) * 4u32
+}
+
+/// j((-b + sqrt(d)) / 2a) in fixed point, from the q-expansion of f = Δ(2τ) / Δ(τ) = q ∏(1 + q^k)^24, where
+/// q = e^(2πiτ): j = (256 f + 1)^3 / f.
+fn j_invariant(form: (i64, i64, i64), d: i64, bits: u32) -> Fixed {
+    let (a, b, _) = form;
+    let pi = fixed_pi(bits);
+    // 2πiτ = π(-sqrt|d| - bi) / a, and e^(2πiτ) = e^(real part / 2^halvings)^(2^halvings) keeps the series short
+    let sqrt_d = (Integer::from(-d) << (2 * bits)).sqrt();
+    let exponent: Fixed = (-(Integer::from(&pi * &sqrt_d) >> bits) / a, -Integer::from(&pi * b) / a);
+    let halvings = 16;
+    let mut q = fixed_exp(&(exponent.0 >> halvings, exponent.1 >> halvings), bits);
+    for _ in 0..halvings {
+        q = fixed_mul(&q, &q, bits);
+    }
+
+    let one = Integer::from(1) << bits;
+    let mut product = (one.clone(), Integer::new());
+    let mut power = q.clone();
+    while power.0 != 0 || power.1 != 0 {
+        product = fixed_mul(&product, &(Integer::from(&power.0 + &one), power.1.clone()), bits);
+        power = fixed_mul(&power, &q, bits);
+    }
+    let mut f = q;
+    for _ in 0..24 {
+        f = fixed_mul(&f, &product, bits);
+    }
+    let base = (Integer::from(&f.0 * 256u32) + &one, Integer::from(&f.1 * 256u32));
+    let cube = fixed_mul(&fixed_mul(&base, &base, bits), &base, bits);
+    fixed_div(&cube, &f, bits)
+}
+
+/// The Hilbert class polynomial of the discriminant, ∏(x - j(τ)) over its reduced forms, which has integer
+/// coefficients (from the constant one up, monic). They are rounded from fixed point values with enough bits for the
+/// coefficients (about Σ π sqrt|D| / a) and the loss of precision in 1 / q, doubled until every value is close to an
+/// integer.
+fn class_polynomial(discriminant: &Discriminant) -> Vec<Integer> {
+    let Discriminant { d, forms } = discriminant;
+    if let Some(polynomial) = CLASS_POLYNOMIALS.lock().unwrap().get(d) {
+        return polynomial.clone();
+    }
+    let log_j = |a: i64| PI * (-*d as f64).sqrt() / a as f64 / LN_2;
+    let size: f64 = forms.iter().map(|&(a, _, _)| log_j(a)).sum();
+    let mut bits = (size + 2.0 * log_j(1)) as u32 + 2 * forms.len() as u32 + 64;
+    let polynomial = loop {
+        let mut coefficients: Vec<Fixed> = vec![(Integer::from(1) << bits, Integer::new())];
+        for &form in forms {
+            let j = j_invariant(form, *d, bits);
+            // multiply by x - j
+            let mut next = vec![(Integer::new(), Integer::new()); coefficients.len() + 1];
+            for (i, c) in coefficients.iter().enumerate() {
+                let product = fixed_mul(c, &j, bits);
+                next[i + 1].0 += &c.0;
+                next[i + 1].1 += &c.1;
+                next[i].0 -= product.0;
+                next[i].1 -= product.1;
+            }
+            coefficients = next;
+        }
+        let tolerance = Integer::from(1) << (bits - 16);
+        let rounded: Option<Vec<Integer>> = coefficients.into_iter()
+            .map(|(real, imaginary)| {
+                let integer = (&real + (Integer::from(1) << (bits - 1))) >> bits;
+                let error = real - Integer::from(&integer << bits);
+                (error.abs() < tolerance && imaginary.abs() < tolerance).then_some(integer)
+            })
+            .collect();
+        match rounded {
+            Some(polynomial) => break polynomial,
+            None => bits *= 2,
+        }
+    };
+    CLASS_POLYNOMIALS.lock().unwrap().insert(*d, polynomial.clone());
+    polynomial
+}
+
+/// Removes the leading zero coefficients.
+fn trim(mut f: Vec<Integer>) -> Vec<Integer> {
+    while f.last().is_some_and(|c| *c == 0) {
+        f.pop();
+    }
+    f
+}
+
+/// The remainder of a divided by the monic f, mod n.
+fn poly_rem(mut a: Vec<Integer>, f: &[Integer], n: &Integer) -> Vec<Integer> {
+    let d = f.len() - 1;
+    while a.len() > d {
+        let top = a.pop().unwrap();
+        let shift = a.len() - d;
+        for (x, y) in a[shift..].iter_mut().zip(f) {
+            *x -= Integer::from(&top * y);
+            x.modulo_mut(n);
+        }
+    }
+    trim(a)
+}
+
+/// a * b mod (f, n).
+fn poly_mul_rem(a: &[Integer], b: &[Integer], f: &[Integer], n: &Integer) -> Vec<Integer> {
+    if a.is_empty() || b.is_empty() {
+        return Vec::new();
+    }
+    let mut product = vec![Integer::new(); a.len() + b.len() - 1];
+    for (i, x) in a.iter().enumerate() {
+        for (j, y) in b.iter().enumerate() {
+            product[i + j] += x * y;
+        }
+    }
+    for c in product.iter_mut() {
+        *c %= n;
+    }
+    poly_rem(product, f, n)
+}
+
+/// f divided by its leading coefficient, mod the prime n.
+fn monic(f: Vec<Integer>, n: &Integer) -> Vec<Integer> {
+    let inverse = f.last().unwrap().invert_ref(n).map(Integer::from).unwrap();
+    f.into_iter().map(|c| c * &inverse % n).collect()
+}
+
+/// A root of f mod the prime n, for a monic f that splits into distinct linear factors (as the class polynomial does
+/// when 4n = u^2 + |D| v^2), with Cantor–Zassenhaus: gcd(f, (x + δ)^((n - 1) / 2) - 1) keeps the roots r for which
+/// r + δ is a square, about half of them, so a few random δ bring f down to a linear factor.
+fn find_root(f: &[Integer], n: &Integer) -> Integer {
+    let mut f: Vec<Integer> = f.iter().map(|c| c.modulo_ref(n).complete()).collect();
+    let half = Integer::from(n - 1u32) >> 1u32;
+    while f.len() > 2 {
+        // (x + δ)^((n - 1) / 2) mod f
+        let base = vec![random_below(n), Integer::from(1)];
+        let mut power = vec![Integer::from(1)];
+        for i in (0..half.significant_bits()).rev() {
+            power = poly_mul_rem(&power, &power, &f, n);
+            if half.get_bit(i) {
+                power = poly_mul_rem(&power, &base, &f, n);
+            }
+        }
+        if power.is_empty() {
+            power.push(Integer::new());
+        }
+        power[0] = Integer::from(&power[0] - 1u32).modulo(n);
+        // gcd(f, power)
+        let (mut a, mut b) = (f.clone(), trim(power));
+        while !b.is_empty() {
+            let b_monic = monic(b, n);
+            let r = poly_rem(a, &b_monic, n);
+            (a, b) = (b_monic, r);
+        }
+        if a.len() > 1 && a.len() < f.len() {
+            f = a;
+        }
+    }
+    Integer::from(n - &f[0]) % n
+}
+
+/// The curves y^2 = x^3 + ax + b mod n of j-invariant j, one for every twist: 2 of them in general, 4 for j = 1728
+/// and 6 for j = 0, since one of them has the order that `orders` found.
+fn twists(j: &Integer, n: &Integer) -> Vec<(Integer, Integer)> {
+    // g is neither a square nor (for j = 0) a cube, so its powers go through all the twists
+    let third = Integer::from(n - 1u32) / 3u32;
+    let mut g = Integer::from(2);
+    while g.jacobi(n) != -1 || (*j == 0 && g.pow_mod_ref(&third, n).unwrap().complete() == 1) {
+        g += 1;
+    }
+    let powers = |count: u32| (0..count).map(|i| Integer::from(g.pow_mod_ref(&Integer::from(i), n).unwrap()));
+    if *j == 0 {
+        return powers(6).map(|b| (Integer::new(), b)).collect();
+    }
+    if *j == 1728 {
+        return powers(4).map(|a| (a, Integer::new())).collect();
+    }
+    // k = j / (1728 - j), a = 3k and b = 2k, twisted by g: a g^2 and b g^3
+    let k = Integer::from(1728 - j).invert(n).unwrap() * j;
+    let (a, b) = (Integer::from(&k * 3u32).modulo(n), Integer::from(&k * 2u32).modulo(n));
+    let g_squared = Integer::from(g.square_ref()) % n;
+    let twisted = (Integer::from(&a * &g_squared) % n, b.clone() * g_squared * &g % n);
+    vec![(a, b), twisted]
+}
+
+/// (n^(1/4) + 2)^2, above (n^(1/4) + 1)^2, the bound that the q of a step must exceed.
+fn q_bound(n: &Integer) -> Integer {
+    (n.root_ref(4).complete() + 2u32).square()
+}
+
+/// Goes through the discriminants until it has `ECPP_CANDIDATES` orders of curves with complex multiplication whose
+/// cofactor q, after removing the primes up to `ECPP_SMOOTH_BOUND`, is a (probable) prime above `q_bound`. They are
+/// returned as (order, q, discriminant), smallest q first.
+fn candidates(n: &Integer, discriminants: &mut impl Iterator<Item = &'static Discriminant>)
+    -> Vec<(Integer, Integer, &'static Discriminant)> {
+    let primorial = PRIMORIAL.get_or_init(|| Integer::from(Integer::primorial(ECPP_SMOOTH_BOUND)));
+    let bound = q_bound(n);
+    let mut candidates = Vec::new();
+    for discriminant in discriminants.by_ref() {
+        let d = discriminant.d;
+        if Integer::from(d).jacobi(n) != 1 {
+            continue;
+        }
+        let Some((u, v)) = cornacchia(d, n) else {
+            continue;
+        };
+        for order in orders(d, n, &u, &v) {
+            let mut q = order.clone();
+            loop {
+                let g = q.gcd_ref(primorial).complete();
+                if g == 1 {
+                    break;
+                }
+                q /= g;
+            }
+            if q < order && q > bound && is_prime(&q) {
+                candidates.push((order, q, discriminant));
+            }
+        }
+        if candidates.len() >= ECPP_CANDIDATES {
+            break;
+        }
+    }
+    candidates.sort_by(|x, y| x.1.cmp(&y.1));
+    candidates
+}
+
+/// A random point of y^2 = x^3 + ax + b mod the prime n, other than the points with y = 0.
+fn random_point(n: &Integer, a: &Integer, b: &Integer) -> Point {
+    loop {
+        let x = random_below(n);
+        let rhs = (Integer::from(x.square_ref()) * &x + Integer::from(a * &x) + b).modulo(n);
+        if rhs.jacobi(n) == 1 {
+            let y = sqrt_mod(&rhs, n);
+            return Point::Affine(x, y);
+        }
+    }
+}
+
+/// The step of n for a candidate of `candidates`: a root j of the class polynomial mod n gives the curves with
+/// complex multiplication, and the twist with `order` points is the one where a random point P has
+/// q (order / q) P = O, with (order / q) P != O.
+fn step(n: &Integer, order: &Integer, q: &Integer, discriminant: &Discriminant) -> Option<EcppStep> {
+    let j = find_root(&class_polynomial(discriminant), n);
+    let cofactor = Integer::from(order / q);
+    for (a, b) in twists(&j, n) {
+        let curve = Curve { n, a: &a };
+        // a point with (order / q) P = O is very unlikely, so another try settles it
+        for _ in 0..4 {
+            let point = random_point(n, &a, &b);
+            let multiple = curve.mul(&cofactor, &point)?;
+            if multiple == Point::Infinity {
+                continue;
+            }
+            if curve.mul(q, &multiple)? != Point::Infinity {
+                break;
+            }
+            let Point::Affine(x, y) = point else { unreachable!() };
+            return Some(EcppStep { n: n.clone(), a, b, order: order.clone(), q: q.clone(), x, y });
+        }
+    }
+    None
+}
+
+/// Pushes the steps from n down to a prime of at most `ECPP_LAST_BITS` bits, and returns its Pocklington
+/// certificate. When the chain below every candidate gets stuck (no order of some q has a large prime cofactor), the
+/// next discriminants give more candidates.
+fn descend(n: &Integer, steps: &mut Vec<EcppStep>) -> Option<PocklingtonCertificate> {
+    if n.significant_bits() <= ECPP_LAST_BITS {
+        return pocklington_certificate(n);
+    }
+    let mut discriminants = discriminants().iter();
+    loop {
+        let candidates = candidates(n, &mut discriminants);
+        if candidates.is_empty() {
+            return None;
+        }
+        for (order, q, discriminant) in candidates {
+            let Some(step) = step(n, &order, &q, discriminant) else {
+                continue;
+            };
+            steps.push(step);
+            if let Some(last) = descend(&q, steps) {
+                return Some(last);
+            }
+            steps.pop();
+        }
+    }
+}
+
+/// Proves that n is prime with elliptic curve primality proving (Atkin–Morain), which needs no factorization of
+/// n - 1 or n + 1. Each step finds a curve mod n whose order m = k * q has a prime q above (n^(1/4) + 1)^2 once the
+/// primes up to `ECPP_SMOOTH_BOUND` are removed, and a point of order q on it, which proves n prime if q is. The
+/// curves have complex multiplication by an order of discriminant D, so their orders follow from writing
+/// 4n = u^2 + |D| v^2 without any point counting, and their j-invariant is a root mod n of the Hilbert class
+/// polynomial of D. q is about n / k, so each step takes off the bits of k, down to a q small enough for
+/// Pocklington's theorem.
+///
+/// # Returns
+/// * `Some(certificate)` - The certificate, which `verify_ecpp` accepts.
+/// * `None` - n is not prime, or none of the curves of some q of the chain has an order with a large prime cofactor
+///   (only the discriminants of `discriminants` are tried).
+pub fn ecpp_certificate(n: &Integer) -> Option<EcppCertificate> {
+    if *n < 2 || !is_prime(n) {
+        return None;
+    }
+    let mut steps = Vec::new();
+    let last = descend(n, &mut steps)?;
+    Some(EcppCertificate { prime: n.clone(), steps, last })
+}
+
+/// Checks a step: n is coprime to 6, the curve is not singular and has the point, q > (n^(1/4) + 1)^2 divides the
+/// order, and (order / q) P != O with q (order / q) P = O.
+fn verify_step(step: &EcppStep) -> bool {
+    let EcppStep { n, a, b, order, q, x, y } = step;
+    if *n < 5 || n.gcd_ref(&Integer::from(6)).complete() != 1 || [a, b, x, y].iter().any(|v| **v < 0 || *v >= n) {
+        return false;
+    }
+    let discriminant = Integer::from(a.square_ref()) * a * 4u32 + Integer::from(b.square_ref()) * 27u32;
+    let rhs = Integer::from(x.square_ref()) * x + Integer::from(a * x) + b;
+    if discriminant.gcd(n) != 1 || !(rhs - Integer::from(y.square_ref())).is_divisible(n) {
+        return false;
+    }
+    if *q <= q_bound(n) || !order.is_divisible(q) {
+        return false;
+    }
+    let curve = Curve { n, a };
+    let point = Point::Affine(x.clone(), y.clone());
+    match curve.mul(&Integer::from(order / q), &point) {
+        Some(multiple @ Point::Affine(..)) => curve.mul(q, &multiple) == Some(Point::Infinity),
+        _ => false,
+    }
+}
+
+/// Checks an ECPP certificate: every step proves its n prime if its q is, each q is the n of the next step, and the
+/// last q has a valid Pocklington certificate. Only plain big integer arithmetic is used.
+pub fn verify_ecpp(certificate: &EcppCertificate) -> bool {
+    let mut n = &certificate.prime;
+    for step in &certificate.steps {
+        if step.n != *n || !verify_step(step) {
+            return false;
+        }
+        n = &step.q;
+    }
+    certificate.last.prime == *n && verify_pocklington(&certificate.last)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::number_theory::random::with_seed;
+
+    #[test]
+    fn test_ecpp() {
+        let polynomial = |d: i64| class_polynomial(&Discriminant { d, forms: reduced_forms(d) });
+        assert_eq!(polynomial(-3), [0, 1]);
+        assert_eq!(polynomial(-4), [-1728, 1]);
+        assert_eq!(polynomial(-15), [-121_287_375, 191_025, 1]);
+        assert_eq!(polynomial(-23), [12_771_880_859_375i64, -5_151_296_875, 3_491_750, 1]);
+
+        let p = Integer::from(Integer::u_pow_u(10, 60)).next_prime();
+        let certificate = with_seed(Some(1), || ecpp_certificate(&p)).unwrap();
+        assert!(verify_ecpp(&certificate));
+        assert!(!certificate.steps.is_empty() && certificate.last.prime.significant_bits() <= ECPP_LAST_BITS);
+        assert!(certificate.to_string().starts_with(&format!("{}: y^2 = x^3", p)));
+        // 2^255 - 19
+        let p25519 = (Integer::from(1) << 255u32) - 19u32;
+        assert!(ecpp_certificate(&p25519).is_some_and(|certificate| verify_ecpp(&certificate)));
+
+        let mut forged = certificate.clone();
+        forged.steps[0].x += 1;
+        assert!(!verify_ecpp(&forged));
+        let mut forged = certificate.clone();
+        forged.steps[0].q = Integer::from(&forged.steps[0].q * 3u32);
+        assert!(!verify_ecpp(&forged));
+        let mut forged = certificate.clone();
+        forged.steps.pop();
+        assert!(!verify_ecpp(&forged));
+        // a point of a curve modulo a composite can't pass
+        let n = Integer::from(&p * 3u32);
+        assert_eq!(ecpp_certificate(&n), None);
+        let mut forged = certificate.steps[0].clone();
+        forged.n = Integer::from(&p * &p);
+        assert!(!verify_step(&forged));
+    }
+}
@@ -0,0 +1,485 @@
+use once_cell::sync::OnceCell;
+use rug::Integer;
+
+#[cfg(feature = "ecm")]
+use super::data::{ecm_tables, get_data, EcmTables, BOUNDS1, ECM_EXTRA_DIGITS, ECM_LEVELS, ECM_SCHEDULE_LEVEL,
+    ITERATIONS};
+use super::data::{prime_limit, FERMAT_STEPS, P_MINUS_1_BOUNDS, P_PLUS_1_BOUNDS};
+#[cfg(feature = "ecm")]
+use super::structs::{CurveFamily, EcmRound};
+use super::structs::{Budget, CofactorBound, Effort, FactorizeOptions, Factorization, PartialFactorization, RhoConfig};
+use super::structs::{TracedFactorization, VerifiedFactorization};
+use crate::number_theory::random::with_seed;
+use crate::tuning::profile;
+
+/// A factorization pipeline with its own settings, see `Factorizer::builder`.
+/// `prime_factorize` uses the default one, built from the constants in data.rs and the tuning profile.
+pub struct Factorizer {
+    pub(super) trial_division_bound: u32,
+    pub(super) rho: RhoConfig,
+    pub(super) rho_walks: usize,
+    pub(super) squfof: bool,
+    pub(super) hart_lehman: bool,
+    pub(super) fermat_steps: u64,
+    pub(super) p_minus_1: Option<(u32, u32)>,
+    pub(super) p_plus_1: Option<(u32, u32)>,
+    #[cfg(feature = "ecm")]
+    pub(super) ecm: Vec<EcmTables>,
+    #[cfg(feature = "ecm")]
+    pub(super) pollard_strassen: bool,
+    #[cfg(feature = "qs")]
+    pub(super) qs: bool,
+}
+
+/// Sets up a `Factorizer`. Every setting starts at the value `prime_factorize` uses, and the methods run in this order:
+/// trial division, SQUFOF, Hart's and Lehman's methods or Fermat's method and Pollard's rho on every composite,
+/// Pollard's p − 1, Williams' p + 1, the ECM rounds, the quadratic sieve (with the `qs` feature), then the Pollard–Strassen method.
+#[derive(Clone, Debug)]
+pub struct FactorizerBuilder {
+    trial_division_bound: u32,
+    rho: RhoConfig,
+    rho_walks: usize,
+    squfof: bool,
+    hart_lehman: bool,
+    fermat_steps: u64,
+    p_minus_1: Option<(u32, u32)>,
+    p_plus_1: Option<(u32, u32)>,
+    #[cfg(feature = "ecm")]
+    ecm_rounds: Vec<EcmRound>,
+    #[cfg(feature = "ecm")]
+    polyeval_stage2: bool,
+    #[cfg(feature = "ecm")]
+    ecm_max_level: Option<u32>,
+    #[cfg(feature = "ecm")]
+    curve_family: CurveFamily,
+    #[cfg(feature = "ecm")]
+    pollard_strassen: bool,
+    #[cfg(feature = "qs")]
+    qs: bool,
+}
+
+impl Default for FactorizerBuilder {
+    fn default() -> Self {
+        let profile = profile();
+        let builder = Self {
+            trial_division_bound: profile.trial_division_bound,
+            rho: RhoConfig::default(),
+            rho_walks: 1,
+            squfof: true,
+            hart_lehman: true,
+            fermat_steps: FERMAT_STEPS,
+            p_minus_1: Some(P_MINUS_1_BOUNDS),
+            p_plus_1: Some(P_PLUS_1_BOUNDS),
+            #[cfg(feature = "ecm")]
+            ecm_rounds: Vec::new(),
+            #[cfg(feature = "ecm")]
+            polyeval_stage2: false,
+            #[cfg(feature = "ecm")]
+            ecm_max_level: None,
+            #[cfg(feature = "ecm")]
+            curve_family: CurveFamily::Suyama,
+            #[cfg(feature = "ecm")]
+            pollard_strassen: true,
+            #[cfg(feature = "qs")]
+            qs: true,
+        };
+        #[cfg(feature = "ecm")]
+        let builder = builder.ecm_schedule(ECM_SCHEDULE_LEVEL);
+        builder
+    }
+}
+
+impl FactorizerBuilder {
+    /// Trial divides by all primes below this bound.
+    pub fn trial_division_bound(mut self, bound: u32) -> Self {
+        self.trial_division_bound = bound;
+        self
+    }
+
+    /// How many times Pollard's rho is run on a composite before leaving it to the later methods. 0 disables it.
+    /// Same as setting the `retries` of `rho`.
+    pub fn rho_attempts(mut self, attempts: u32) -> Self {
+        self.rho.retries = attempts;
+        self
+    }
+
+    /// The settings of Pollard's rho: the length of the walks, the steps between two gcds, the number of walks and
+    /// the constant of the sequence.
+    pub fn rho(mut self, config: RhoConfig) -> Self {
+        self.rho = config;
+        self
+    }
+
+    /// How many walks each attempt of Pollard's rho runs at the same time, on as many threads (see
+    /// `pollard_rho_parallel`). 1, the default, runs a single walk on the calling thread.
+    pub fn rho_walks(mut self, walks: usize) -> Self {
+        self.rho_walks = walks;
+        self
+    }
+
+    /// Whether composites below 2^62 are split with SQUFOF before Pollard's rho.
+    pub fn squfof(mut self, enabled: bool) -> Self {
+        self.squfof = enabled;
+        self
+    }
+
+    /// Whether composites of 50 to 70 bits get a few steps of Hart's one line factoring and Lehman's method: after
+    /// SQUFOF fails below 2^62, and before Fermat's method and Pollard's rho above it.
+    pub fn hart_lehman(mut self, enabled: bool) -> Self {
+        self.hart_lehman = enabled;
+        self
+    }
+
+    /// The steps of Fermat's method tried on larger composites before Pollard's rho. 0 disables it.
+    pub fn fermat_steps(mut self, steps: u64) -> Self {
+        self.fermat_steps = steps;
+        self
+    }
+
+    /// The bounds (B1, B2) of Pollard's p − 1, or None to disable it.
+    pub fn p_minus_1(mut self, bounds: Option<(u32, u32)>) -> Self {
+        self.p_minus_1 = bounds;
+        self
+    }
+
+    /// The bounds (B1, B2) of Williams' p + 1, or None to disable it.
+    pub fn p_plus_1(mut self, bounds: Option<(u32, u32)>) -> Self {
+        self.p_plus_1 = bounds;
+        self
+    }
+
+    /// The rounds of ECM, each continuing the curves of the one before it. An empty list disables ECM.
+    #[cfg(feature = "ecm")]
+    pub fn ecm_rounds(mut self, rounds: &[EcmRound]) -> Self {
+        self.ecm_rounds = rounds.to_vec();
+        self.ecm_max_level = None;
+        self
+    }
+
+    /// Replaces the rounds of ECM with the t-levels of `ECM_LEVELS` up to t`max_level`, which pick the bounds and
+    /// the number of curves by the size of what is left to factorize: before each level, the largest composite left
+    /// decides whether it is still needed (see `ecm_schedule`), so small cofactors stop at the low levels.
+    #[cfg(feature = "ecm")]
+    pub fn ecm_schedule(mut self, max_level: u32) -> Self {
+        self.ecm_rounds = ECM_LEVELS.iter().filter(|(level, _)| *level <= max_level).map(|(_, round)| *round).collect();
+        self.ecm_max_level = Some(max_level);
+        self
+    }
+
+    /// Whether ECM stage 2 evaluates polynomials at the giant steps (see `PolyevalPlan`) instead of walking over the
+    /// primes. It costs less per prime for large B2 (such as 1000 * B1), and B2 is no longer limited by the sieve.
+    #[cfg(feature = "ecm")]
+    pub fn polyeval_stage2(mut self, enabled: bool) -> Self {
+        self.polyeval_stage2 = enabled;
+        self
+    }
+
+    /// The family of the curves of ECM, Suyama's curves by default. Stage 1 takes about 7% less time on
+    /// `CurveFamily::Edwards` (at B1 = 250e3 on 200 bit numbers), but GMP-ECM can't rerun their curves.
+    #[cfg(feature = "ecm")]
+    pub fn curve_family(mut self, family: CurveFamily) -> Self {
+        self.curve_family = family;
+        self
+    }
+
+    /// Whether the composites of up to 90 bits that are left at the end go through the Pollard–Strassen method, which
+    /// finds their smallest prime factor deterministically (see `pollard_strassen`).
+    #[cfg(feature = "ecm")]
+    pub fn pollard_strassen(mut self, enabled: bool) -> Self {
+        self.pollard_strassen = enabled;
+        self
+    }
+
+    /// Whether the composites that ECM leaves, up to 90 digits, go through the self-initializing quadratic sieve
+    /// (see `siqs`). Its running time only depends on their size, so it is the one method that doesn't get stuck on two
+    /// large factors, but it takes minutes above 70 digits.
+    #[cfg(feature = "qs")]
+    pub fn quadratic_sieve(mut self, enabled: bool) -> Self {
+        self.qs = enabled;
+        self
+    }
+
+    /// Checks the settings and builds the tables the methods need.
+    /// The bounds must fit within the primes of the data layer (up to `prime_limit()`, 2.5e7 by default), and the ECM
+    /// rounds need 1 to 200 curves, increasing values of B1, and B1 < B2. With `polyeval_stage2`, the B2 of ECM may go
+    /// beyond that.
+    pub fn build(self) -> Result<Factorizer, String> {
+        let max_bound = prime_limit() as usize;
+        if self.trial_division_bound < 3 || self.trial_division_bound as usize > max_bound {
+            return Err(format!("the trial division bound must be in [3, {}]", max_bound));
+        }
+        if self.rho_walks == 0 {
+            return Err("Pollard's rho needs at least one walk".to_string());
+        }
+        if !(1..=40).contains(&self.rho.max_iterations) || self.rho.batch_size == 0 {
+            return Err("Pollard's rho needs 1 to 40 iterations and a batch size of at least 1".to_string());
+        }
+        if let Some((B1, B2)) = self.p_minus_1 && (B1 < 2 || B2 < B1 || B2 as usize > max_bound) {
+            return Err(format!("the bounds of p - 1 need 2 <= B1 <= B2 <= {}", max_bound));
+        }
+        if let Some((B1, B2)) = self.p_plus_1 && (B1 < 2 || B2 < B1 || B2 as usize > max_bound) {
+            return Err(format!("the bounds of p + 1 need 2 <= B1 <= B2 <= {}", max_bound));
+        }
+
+        #[cfg(feature = "ecm")]
+        let ecm = {
+            let profile = profile();
+            let max_B2 = if self.polyeval_stage2 { usize::MAX } else { max_bound };
+            let mut previous_B1 = 0;
+            for (i, round) in self.ecm_rounds.iter().enumerate() {
+                let block_size = if i == 0 { profile.ecm_block_size_1 } else { profile.ecm_block_size_2 };
+                if round.curves == 0 || round.curves > ITERATIONS {
+                    return Err(format!("ECM rounds need 1 to {} curves", ITERATIONS));
+                }
+                // stage 2 walks over the blocks from the one around B1, which must not be the first one
+                if round.B1 <= previous_B1 || round.B1 < block_size || round.B2 <= round.B1 || round.B2 > max_B2 {
+                    return Err(format!("ECM rounds need increasing values of B1, and {} <= B1 < B2 <= {}", block_size, max_B2));
+                }
+                previous_B1 = round.B1;
+            }
+            let mut tables = ecm_tables(&self.ecm_rounds, &get_data().primes, self.polyeval_stage2);
+            for table in tables.iter_mut() {
+                table.family = self.curve_family;
+            }
+            if self.ecm_max_level.is_some() {
+                for (table, (level, _)) in tables.iter_mut().zip(&ECM_LEVELS) {
+                    table.level = Some(*level);
+                }
+            }
+            tables
+        };
+
+        Ok(Factorizer {
+            trial_division_bound: self.trial_division_bound,
+            rho: self.rho,
+            rho_walks: self.rho_walks,
+            squfof: self.squfof,
+            hart_lehman: self.hart_lehman,
+            fermat_steps: self.fermat_steps,
+            p_minus_1: self.p_minus_1,
+            p_plus_1: self.p_plus_1,
+            #[cfg(feature = "ecm")]
+            ecm,
+            #[cfg(feature = "ecm")]
+            pollard_strassen: self.pollard_strassen,
+            #[cfg(feature = "qs")]
+            qs: self.qs,
+        })
+    }
+}
+
+static DEFAULT: OnceCell<Factorizer> = OnceCell::new();
+static LOW_EFFORT: OnceCell<Factorizer> = OnceCell::new();
+static MEDIUM_EFFORT: OnceCell<Factorizer> = OnceCell::new();
+
+impl Factorizer {
+    pub fn builder() -> FactorizerBuilder {
+        FactorizerBuilder::default()
+    }
+
+    /// The factorizer that `prime_factorize` uses. Its tables are built on first use.
+    pub fn default_ref() -> &'static Factorizer {
+        DEFAULT.get_or_init(|| Factorizer::builder().build().unwrap())
+    }
+
+    /// The factorizer that `prime_factorize_partial` uses for an effort.
+    pub fn for_effort(effort: Effort) -> &'static Factorizer {
+        match effort {
+            Effort::Low => LOW_EFFORT.get_or_init(|| {
+                let builder = Factorizer::builder().p_minus_1(None).p_plus_1(None);
+                #[cfg(feature = "ecm")]
+                let builder = builder.ecm_rounds(&[]).pollard_strassen(false);
+                #[cfg(feature = "qs")]
+                let builder = builder.quadratic_sieve(false);
+                builder.build().unwrap()
+            }),
+            Effort::Medium => MEDIUM_EFFORT.get_or_init(|| {
+                let builder = Factorizer::builder();
+                #[cfg(feature = "ecm")]
+                let builder = builder.ecm_rounds(&[EcmRound { curves: ITERATIONS, B1: BOUNDS1.0, B2: BOUNDS1.1 }])
+                    .pollard_strassen(false);
+                #[cfg(feature = "qs")]
+                let builder = builder.quadratic_sieve(false);
+                builder.build().unwrap()
+            }),
+            Effort::High => Factorizer::default_ref(),
+        }
+    }
+
+    /// Returns the prime factorization of n, like `prime_factorize`.
+    /// Factors that none of the enabled methods could find are left out.
+    pub fn factorize(&self, n: &Integer) -> Factorization {
+        Factorization::new(super::factorize_with(n, self, &Budget::unlimited(), None).primes)
+    }
+
+    /// Same as `factorize`, but the composite cofactors that could not be split are returned as well.
+    pub fn factorize_partial(&self, n: &Integer) -> PartialFactorization {
+        super::factorize_with(n, self, &Budget::unlimited(), None)
+    }
+
+    /// Same as `factorize_partial`, but stops when the deadline of the options passes or their cancel flag is set,
+    /// like `prime_factorize_with_options` (including its seed).
+    pub fn factorize_with_options(&self, n: &Integer, options: &FactorizeOptions) -> PartialFactorization {
+        with_seed(options.seed, || super::factorize_with(n, self, &options.budget(), None))
+    }
+
+    /// Same as `factorize_with_options`, but every prime comes with its origin, like `prime_factorize_traced`.
+    pub fn factorize_traced(&self, n: &Integer, options: &FactorizeOptions) -> TracedFactorization {
+        super::factorize_traced(n, self, options)
+    }
+
+    /// Same as `factorize`, but the primes are checked again on a pool of threads, like `prime_factorize_verified`.
+    pub fn factorize_verified(&self, n: &Integer, prove: bool) -> VerifiedFactorization {
+        super::verify::factorize_verified(n, self, prove)
+    }
+
+    /// What is known of the prime factors of a cofactor that this factorizer left without running out of budget, see
+    /// `cofactor_bound`: it was trial divided up to the bound, and went through every ECM round that the schedule
+    /// runs on composites of its size (all of them for fixed rounds).
+    pub fn cofactor_bound(&self, cofactor: &Integer) -> CofactorBound {
+        #[cfg(feature = "ecm")]
+        let rounds: Vec<EcmRound> = {
+            let digits = (cofactor.significant_bits() as f64 * std::f64::consts::LOG10_2).ceil() as u32;
+            self.ecm.iter()
+                .filter(|tables| tables.level.is_none_or(|level| level <= digits / 2 + ECM_EXTRA_DIGITS))
+                .map(|tables| tables.round)
+                .collect()
+        };
+        #[cfg(not(feature = "ecm"))]
+        let rounds = Vec::new();
+        super::cofactor_bound(cofactor, self.trial_division_bound, &rounds)
+    }
+
+    /// The tables of the ECM rounds.
+    #[cfg(feature = "ecm")]
+    pub(crate) fn ecm_tables(&self) -> &[EcmTables] {
+        &self.ecm
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prime_factorization::prime_factorize;
+
+    #[test]
+    fn test_factorizer() {
+        let n = Integer::from(2 * 3 * 3 * 101 * 10_007) * 1_000_000_007u64 * 998_244_353u64;
+        let factorizer = Factorizer::builder().trial_division_bound(100).fermat_steps(0).p_minus_1(None).build().unwrap();
+        assert_eq!(factorizer.factorize(&n), prime_factorize(&n));
+
+        // without rho and p - 1, the 31 bit factors are left to a single small round of ECM
+        #[cfg(feature = "ecm")]
+        {
+            let n = Integer::from(2_147_483_647u64) * 2_147_483_629u64 * 1_073_741_789u64;
+            let factorizer = Factorizer::builder().rho_attempts(0).p_minus_1(None).p_plus_1(None)
+                .ecm_rounds(&[EcmRound { curves: 100, B1: 10_000, B2: 1_000_000 }]).build().unwrap();
+            assert_eq!(factorizer.factorize(&n), prime_factorize(&n));
+
+            // ECM reports every batch of curves to the progress callback, and the curve of every factor it finds
+            use std::sync::{atomic::{AtomicBool, AtomicUsize, Ordering}, Arc, Mutex};
+            use crate::prime_factorization::{ecm::EcmCheckpoint, FactorEvent, Method, Progress};
+            let batches = Arc::new(AtomicUsize::new(0));
+            let counter = batches.clone();
+            let found = Arc::new(Mutex::new(Vec::new()));
+            let log = found.clone();
+            let progress = Progress::new(move |event| match event {
+                FactorEvent::EcmProgress { total: 100, B1: 10_000, .. } => {
+                    counter.fetch_add(1, Ordering::Relaxed);
+                }
+                FactorEvent::FactorFound { method: Method::Ecm, n, .. } => log.lock().unwrap().push((n.clone(), None)),
+                FactorEvent::EcmCurveFound { curve, .. } => log.lock().unwrap().last_mut().unwrap().1 = Some(*curve),
+                _ => {}
+            });
+            factorizer.factorize_with_options(&n, &FactorizeOptions { progress: Some(progress), ..Default::default() });
+            assert!(batches.load(Ordering::Relaxed) > 0);
+            // the same curve finds a factor again on its own
+            let found = found.lock().unwrap();
+            assert!(!found.is_empty());
+            let never = AtomicBool::new(false);
+            for (m, curve) in found.iter() {
+                let curve = curve.unwrap();
+                assert_eq!((curve.B1, curve.B2), (10_000, 1_000_000));
+                let mut checkpoint = EcmCheckpoint::new(m, [curve.sigma]).unwrap();
+                assert!(checkpoint.stage1(curve.B1, &never).or_else(|| checkpoint.stage2(curve.B2, &never)).is_some());
+            }
+
+            // a traced run gives every prime the curve that split it off
+            let traced = factorizer.factorize_traced(&n, &FactorizeOptions::default());
+            assert!(traced.composites.is_empty() && traced.primes.iter().all(|p| p.method == Method::Ecm
+                && p.curve.is_some_and(|curve| (curve.B1, curve.B2) == (10_000, 1_000_000))));
+            assert!(traced.method_times.iter().any(|&(method, _)| method == Method::Ecm));
+
+            // the Edwards curves find them too, also when a second round continues their residues
+            let families = Arc::new(Mutex::new(Vec::new()));
+            let log = families.clone();
+            let progress = Progress::new(move |event| if let FactorEvent::EcmCurveFound { curve, .. } = event {
+                log.lock().unwrap().push(curve.family);
+            });
+            let rounds = [EcmRound { curves: 4, B1: 3_000, B2: 100_000 },
+                EcmRound { curves: 100, B1: 10_000, B2: 1_000_000 }];
+            let edwards = Factorizer::builder().rho_attempts(0).p_minus_1(None).p_plus_1(None)
+                .curve_family(CurveFamily::Edwards).ecm_rounds(&rounds).build().unwrap();
+            let options = FactorizeOptions { progress: Some(progress), seed: Some(3765), ..Default::default() };
+            let mut result = edwards.factorize_with_options(&n, &options);
+            result.primes.sort();
+            assert!(result.is_complete() && result.primes == prime_factorize(&n).factors());
+            let families = families.lock().unwrap();
+            assert!(!families.is_empty() && families.iter().all(|&family| family == CurveFamily::Edwards));
+            assert!(Factorizer::builder().ecm_rounds(&[EcmRound { curves: 201, B1: 10_000, B2: 1_000_000 }]).build().is_err());
+            assert!(Factorizer::builder().ecm_rounds(&[EcmRound { curves: 10, B1: 50_000, B2: 100_000 },
+                EcmRound { curves: 10, B1: 50_000, B2: 200_000 }]).build().is_err());
+        }
+        let factorizer = Factorizer::builder().squfof(false).fermat_steps(0).rho_walks(4).build().unwrap();
+        assert_eq!(factorizer.factorize(&n), prime_factorize(&n));
+        assert!(Factorizer::builder().rho_walks(0).build().is_err());
+
+        // short walks with a fixed constant and frequent gcds, without the other methods
+        let rho = RhoConfig { max_iterations: 16, batch_size: 64, retries: 10, poly_constant: Some(3) };
+        let n = Integer::from(1_000_003u64) * 998_244_353u64 * 1_000_000_007u64;
+        let factorizer = Factorizer::builder().squfof(false).hart_lehman(false).fermat_steps(0).p_minus_1(None).rho(rho);
+        #[cfg(feature = "ecm")]
+        let factorizer = factorizer.ecm_rounds(&[]);
+        assert_eq!(factorizer.build().unwrap().factorize(&n), prime_factorize(&n));
+
+        // a 68 bit semiprime with q close to 3p, which only Hart's and Lehman's methods are left to split
+        let (p, q) = (Integer::from(8_589_934_609u64), Integer::from(25_769_803_831u64));
+        let factorizer = Factorizer::builder().rho_attempts(0).fermat_steps(0).p_minus_1(None).p_plus_1(None);
+        #[cfg(feature = "ecm")]
+        let factorizer = factorizer.ecm_rounds(&[]).pollard_strassen(false);
+        assert_eq!(factorizer.build().unwrap().factorize(&Integer::from(&p * &q)).into_factors(), [(p, 1), (q, 1)]);
+        assert!(Factorizer::builder().rho(RhoConfig { batch_size: 0, ..rho }).build().is_err());
+        assert!(Factorizer::builder().trial_division_bound(1).build().is_err());
+        assert!(Factorizer::builder().p_minus_1(Some((100, 30_000_000))).build().is_err());
+
+        // 1001839 + 1 = 2^4 * 5 * 7 * 1789, which Williams' p + 1 catches on its own
+        let (p, q) = (Integer::from(1_001_839), Integer::from(2_147_483_647));
+        let factorizer = Factorizer::builder().squfof(false).hart_lehman(false).rho_attempts(0).fermat_steps(0)
+            .p_minus_1(None).p_plus_1(Some((100, 2000)));
+        #[cfg(feature = "ecm")]
+        let factorizer = factorizer.ecm_rounds(&[]).pollard_strassen(false);
+        assert_eq!(factorizer.build().unwrap().factorize(&Integer::from(&p * &q)).into_factors(), [(p, 1), (q, 1)]);
+        assert!(Factorizer::builder().p_plus_1(Some((100, 30_000_000))).build().is_err());
+
+        // a 40 bit semiprime that only the Pollard–Strassen method is left to split
+        #[cfg(feature = "ecm")]
+        {
+            let (p, q) = (Integer::from(1_000_003), Integer::from(1_000_033));
+            let factorizer = Factorizer::builder().squfof(false).hart_lehman(false).rho_attempts(0).fermat_steps(0)
+                .p_minus_1(None).p_plus_1(None).ecm_rounds(&[]);
+            #[cfg(feature = "qs")]
+            let factorizer = factorizer.quadratic_sieve(false);
+            assert_eq!(factorizer.build().unwrap().factorize(&Integer::from(&p * &q)).into_factors(), [(p, 1), (q, 1)]);
+        }
+
+        // a 40 digit semiprime with 20 digit factors, left by rho and p - 1 to the quadratic sieve
+        #[cfg(feature = "qs")]
+        {
+            let (p, q) = (Integer::from(10_000_000_000_000_000_051u128), Integer::from(10_000_000_000_000_000_087u128));
+            let factorizer = Factorizer::builder().rho_attempts(0).fermat_steps(0).p_minus_1(None).p_plus_1(None);
+            #[cfg(feature = "ecm")]
+            let factorizer = factorizer.ecm_rounds(&[]);
+            assert_eq!(factorizer.build().unwrap().factorize(&Integer::from(&p * &q)).into_factors(), [(p, 1), (q, 1)]);
+        }
+    }
+}
@@ -0,0 +1,59 @@
+use rug::Integer;
+
+use crate::number_theory::roots::{isqrt_rem, RootRem};
+
+/// Fermat's method: looks for n = a^2 - b^2 = (a - b)(a + b), starting from a = ceil(sqrt(n)) and stepping a up by one.
+/// It finds the factors of an odd n = pq within k steps when (p + q) / 2 - sqrt(n) < k, i.e when |p - q| is about
+/// sqrt(8k) * n^(1/4) or less, which is exactly the case (RSA moduli with close primes) where Pollard's rho and ECM
+/// are hopeless.
+///
+/// # Returns
+/// * `Some(d)` - The non-trivial factor a - b of n, the one closest to sqrt(n) from below.
+/// * `None` - n is even or smaller than 9, or no factor was found within max_steps steps.
+pub fn fermat_factor(n: &Integer, max_steps: u64) -> Option<Integer> {
+    if n.is_even() || *n < 9 {
+        return None;
+    }
+    let RootRem { root: mut a, remainder: mut b2 } = isqrt_rem(n);
+    if b2 == 0 {
+        return Some(a);
+    }
+    // b2 = a^2 - n for a = ceil(sqrt(n))
+    b2 -= Integer::from(&a * 2u32) + 1u32;
+    b2 = -b2;
+    a += 1;
+
+    for _ in 0..max_steps {
+        if b2.is_perfect_square() {
+            let factor = a - b2.sqrt();
+            // a - b = 1 is the trivial n = 1 * n
+            return (factor != 1).then_some(factor);
+        }
+        // (a + 1)^2 - n = a^2 - n + 2a + 1
+        b2 += &a;
+        b2 += &a;
+        b2 += 1;
+        a += 1;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fermat() {
+        // 2^127 - 1 and the next prime after 2^127 + 2^69, about 256 steps apart
+        let p = (Integer::from(1) << 127u32) - 1u32;
+        let q = ((Integer::from(1) << 127u32) + (Integer::from(1) << 69u32)).next_prime();
+        let n = Integer::from(&p * &q);
+        assert_eq!(fermat_factor(&n, 1000), Some(p.clone()));
+        assert_eq!(fermat_factor(&Integer::from(&p * &p), 1), Some(p.clone()));
+        assert_eq!(fermat_factor(&Integer::from(101 * 103), 10), Some(Integer::from(101)));
+
+        // too far apart
+        assert_eq!(fermat_factor(&(p * 3u32), 1000), None);
+        assert_eq!(fermat_factor(&Integer::from(1_000_000_007), 1000), None);
+    }
+}
@@ -0,0 +1,61 @@
+use crate::number_theory::word::gcd;
+
+/// Hart's multiplier, 480 = 2^5 * 3 * 5: with it, s^2 mod n turns out to be a square after fewer steps.
+const MULTIPLIER: u128 = 480;
+
+/// Hart's one line factoring: for i = 1, 2, ..., takes s = ceil(sqrt(480 i n)) and checks whether s^2 mod n is a
+/// square t^2, in which case s^2 = t^2 mod n and gcd(s - t, n) is usually a factor. Like Lehman's method it takes
+/// about n^(1/3) steps in general, but each one is a square root and a square test on machine words, and it is
+/// much faster when the ratio of the factors is close to a fraction with a small denominator. `prime_factorize` runs
+/// it with a small bound on the composites of 50 to 70 bits (see `HART_LEHMAN_BITS`).
+///
+/// # Returns
+/// * `Some(d)` - A non-trivial factor of n.
+/// * `None` - n is prime or smaller than 4, or no factor was found within max_steps steps (or before 480 i n
+///   overflows, for n above 2^100 or so).
+pub fn hart_one_line(n: u128, max_steps: u64) -> Option<u128> {
+    if n < 4 {
+        return None;
+    }
+    if n.is_multiple_of(2) {
+        return Some(2);
+    }
+    let r = n.isqrt();
+    if r * r == n {
+        return Some(r);
+    }
+
+    for i in 1..=max_steps as u128 {
+        let product = n.checked_mul(MULTIPLIER * i)?;
+        let mut s = product.isqrt();
+        if s * s < product {
+            s += 1;
+        }
+        let m = s.checked_mul(s)? % n;
+        let t = m.isqrt();
+        if t * t == m {
+            let factor = gcd(s - t, n);
+            if factor != 1 && factor != n {
+                return Some(factor);
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hart_one_line() {
+        let semiprimes: [u128; 5] = [11 * 13, 10_007 * 10_009, 1_000_003 * 3_000_017, 998_244_353 * 1_000_000_007,
+            4_294_967_291 * 1_073_741_789];
+        for n in semiprimes {
+            let d = hart_one_line(n, 1 << 20).unwrap_or_else(|| panic!("failed for n = {}", n));
+            assert!(d > 1 && d < n && n.is_multiple_of(d), "failed for n = {}", n);
+        }
+        assert_eq!(hart_one_line(1_000_000_007, 1000), None);
+        assert_eq!(hart_one_line(1_000_000_007 * 1_000_000_007, 1), Some(1_000_000_007));
+    }
+}
@@ -0,0 +1,79 @@
+use crate::number_theory::word::gcd;
+
+/// The integer cube root of n, floor(n^(1/3)).
+fn icbrt(n: u128) -> u128 {
+    let mut r = (n as f64).cbrt() as u128;
+    while r.checked_pow(3).is_none_or(|cube| cube > n) {
+        r -= 1;
+    }
+    while (r + 1).checked_pow(3).is_some_and(|cube| cube <= n) {
+        r += 1;
+    }
+    r
+}
+
+/// Lehman's method: for k = 1, 2, ... up to n^(1/3), looks for a^2 - 4kn = b^2 with a between sqrt(4kn) and
+/// sqrt(4kn) + n^(1/6) / (4 sqrt(k)), which gives the factor gcd(a + b, n). For k = 1 this is Fermat's method, and
+/// the other k catch the factors whose ratio is close to a small fraction. When n has no prime factor up to n^(1/3)
+/// and max_k does not cut the search short, a factor is always found within about n^(1/3) steps, so n is prime if
+/// none is; smaller factors are left to trial division. `prime_factorize` runs it with a small max_k on the
+/// composites of 50 to 70 bits (see `HART_LEHMAN_BITS`).
+///
+/// # Returns
+/// * `Some(d)` - A non-trivial factor of n.
+/// * `None` - n is smaller than 4, or no factor was found for the k up to max_k (or before 4kn overflows).
+pub fn lehman(n: u128, max_k: u64) -> Option<u128> {
+    if n < 4 {
+        return None;
+    }
+    if n.is_multiple_of(2) {
+        return Some(2);
+    }
+    let cbrt = icbrt(n);
+    // (sqrt(4kn) + n^(1/6) / (4 sqrt(k)))^2 = 4kn + n^(2/3) + n^(1/3) / 16k, so a^2 - 4kn never goes beyond
+    let width = (cbrt + 1) * (cbrt + 1) + cbrt + 1;
+
+    for k in 1..=cbrt.min(max_k as u128) {
+        let four_kn = n.checked_mul(4 * k)?;
+        let mut a = four_kn.isqrt();
+        if a * a < four_kn {
+            a += 1;
+        }
+        let a_max = four_kn.checked_add(width)?.isqrt();
+        while a <= a_max {
+            let b2 = a * a - four_kn;
+            let b = b2.isqrt();
+            if b * b == b2 {
+                let factor = gcd(a + b, n);
+                if factor != 1 && factor != n {
+                    return Some(factor);
+                }
+            }
+            a += 1;
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lehman() {
+        assert_eq!(icbrt(26), 2);
+        assert_eq!(icbrt(27), 3);
+        assert_eq!(icbrt(u128::MAX), 6_981_463_658_331);
+
+        // the smallest factor is above n^(1/3), so the full search always finds it
+        let semiprimes: [u128; 5] = [11 * 13, 10_007 * 10_009, 1_000_003 * 3_000_017, 1_000_003 * 998_244_353,
+            998_244_353 * 1_000_000_007];
+        for n in semiprimes {
+            let d = lehman(n, u64::MAX).unwrap_or_else(|| panic!("failed for n = {}", n));
+            assert!(d > 1 && d < n && n.is_multiple_of(d), "failed for n = {}", n);
+        }
+        assert_eq!(lehman(1_000_000_007, u64::MAX), None);
+        // close factors are found at k = 1, like Fermat's method
+        assert!(lehman(4_294_967_291 * 4_294_967_279, 1).is_some());
+    }
+}
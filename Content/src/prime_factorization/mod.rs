@@ -1,22 +1,69 @@
 #![allow(non_snake_case)]
-use std::{cell::RefCell, ops::ShrAssign};
-use ecm::{ecm_trial, suyama::suyama_parameterization, MontgomeryPoint};
-use pollards_rho::pollard_rho_brent;
-use rug::{integer::IsPrime, Assign, Integer};
+use std::{cell::RefCell, ops::ShrAssign, sync::{Arc, Mutex}, time::Instant};
+#[cfg(feature = "ecm")]
+use ecm::{ecm_trial, suyama::suyama_parameterization, EcmState, EdwardsResidue, Stage1Residue};
+#[cfg(feature = "ecm")]
+use pollard_strassen::pollard_strassen;
+use fermat::fermat_factor;
+use hart::hart_one_line;
+use lehman::lehman;
+use pollards_p_minus_1::pollard_p_minus_1_stage2;
+use pollards_rho::{pollard_rho_parallel, pollard_rho_with};
+use squfof::squfof;
+use williams_p_plus_1::williams_p_plus_1;
+use rug::{Assign, Complete, Integer};
 
 
 pub mod structs;
+#[cfg(feature = "ecm")]
 pub mod ecm;
+pub mod ecpp;
+pub mod factorizer;
+pub mod fermat;
+pub mod hart;
+pub mod lehman;
+pub mod pocklington;
 pub mod pollards_rho;
+pub mod pollards_p_minus_1;
+#[cfg(feature = "ecm")]
+pub mod pollard_strassen;
+pub mod pratt;
+#[cfg(feature = "qs")]
+pub mod qs;
+#[cfg(feature = "ecm")]
+pub mod race;
+pub mod small;
+pub mod squfof;
+pub mod stage2;
+pub mod williams_p_plus_1;
 pub mod data;
-use data::{get_data, BLOCK_SIZE_1, BLOCK_SIZE_2, BOUNDS1, BOUNDS2, ITERATIONS, SIZE};
-use structs::{Factor, FixedVec};
+mod verify;
+use data::{get_data, prime_limit, HART_LEHMAN_BITS, HART_LEHMAN_MIN_BITS, HART_STEPS, LEHMAN_MAX_K, SIZE,
+    SQUFOF_BITS};
+#[cfg(feature = "ecm")]
+use data::{CURVE_BATCH, ECM_EXTRA_DIGITS, ITERATIONS, STRASSEN_MAX_BITS};
+#[cfg(feature = "qs")]
+use data::QS_MAX_DIGITS;
+use structs::{Factor, FactorQueue, GrowVec, InvalidHint};
+use verify::PrimeVerifier;
+pub use structs::{Budget, Certificate, CofactorBound, Divisors, Effort, FactorEvent, FactorizeOptions, Factorization,
+    Method, PartialFactorization, Progress, RhoConfig, TracedFactorization, TracedPrime, VerifiedFactorization,
+    VerifiedPrime};
+pub use factorizer::{Factorizer, FactorizerBuilder};
+pub use structs::{CurveFamily, EcmCurve, EcmRound};
+pub use data::stage1_exponent;
+#[cfg(feature = "ecm")]
+pub use data::{ecm_schedule, PolyevalPlan, Stage2, Stage2Plan, ECM_LEVELS};
+
+use crate::number_theory::{factorize_small, is_prime, isqrt_rem, smallest_prime_factor, SPF_LIMIT};
+use crate::number_theory::random::with_seed;
 
 use crate::montgomery_mod_mult::Context;
 // pub use self::structs::{BufferData, Instance};
 
-fn trial_division(n: &mut Integer, factors: &mut Vec<(Integer, u32)>, primes: &Vec<u32>)  {
-    for p in &primes[1..1230] { // skip 2 because it already has been factored, trial divide up to 1e4
+fn trial_division(n: &mut Integer, factors: &mut Vec<(Integer, u32)>, primes: &[u32], bound: u32)  {
+    let end = primes.partition_point(|&p| p < bound);
+    for p in &primes[1..end] { // skip 2 because it already has been factored, trial divide up to the bound
         if n.is_divisible_u(*p) {
             factors.push((Integer::from(*p), 1));
             n.div_exact_u_mut(*p);
@@ -28,35 +75,43 @@ fn trial_division(n: &mut Integer, factors: &mut Vec<(Integer, u32)>, primes: &V
     }
 }
 
+/// If n < SPF_LIMIT, splits it into primes with the smallest prime factor table and pushes the ones
+/// that are not in prime_factors yet. Returns false (and does nothing) for larger n.
+fn push_small_factors(n: &Integer, prime_factors: &mut GrowVec<Integer, SIZE>) -> bool {
+    let Some(mut small) = n.to_u32().filter(|&small| small < SPF_LIMIT) else {
+        return false;
+    };
+    while let Some(p) = smallest_prime_factor(small) {
+        while small % p == 0 {
+            small /= p;
+        }
+        if !(0..prime_factors.len()).any(|i| *prime_factors.get(i) == p) {
+            prime_factors.next().assign(p);
+            prime_factors.inc();
+        }
+    }
+    true
+}
+
 /// Reduces the value of n based on the prime factors we have found so far.
 /// We iterate through the entries that aren't fully factorized (stored in temporary_factors)
 /// and remove any prime factors that have been found from them.
-fn find_exponents(n: &mut Integer, prime_factors: &mut FixedVec<Integer, SIZE>,
-    factors: &mut Vec<(Integer, u32)>, temporary_factors: &mut FixedVec<Factor, SIZE>) {
+fn find_exponents(n: &mut Integer, prime_factors: &mut GrowVec<Integer, SIZE>,
+    factors: &mut Vec<(Integer, u32)>, temporary_factors: &mut FactorQueue) {
     
-    for i in  0..temporary_factors.len() { 
+    for i in 0..temporary_factors.len() {
         let factor = temporary_factors.get_mut(i);
-        let curval = &mut factor.n;
-        let index = factor.idx;
-
-        for idx in index..prime_factors.len() {
-            let p = prime_factors.get(idx);
-            while curval.is_divisible(p) {
-                curval.div_exact_mut(p);
-            }
-        }
+        factor.remove_primes(prime_factors);
         factor.idx = 0;
     }
      
     for i in 0..prime_factors.len() {
         let p = prime_factors.get(i);
-        let mut exponent = 1;
-        n.div_exact_mut(p);
-        while n.is_divisible(p) {
-            n.div_exact_mut(p);
-            exponent += 1;
+        // a prime can be found twice (e.g from both halves of p^2), only its first copy still divides n
+        let exponent = n.remove_factor_mut(p);
+        if exponent > 0 {
+            factors.push((p.clone(), exponent));
         }
-        factors.push((p.clone(), exponent));
     }
 
     prime_factors.clear();
@@ -65,21 +120,15 @@ fn find_exponents(n: &mut Integer, prime_factors: &mut FixedVec<Integer, SIZE>,
 
 thread_local! {
     static BUFFER: RefCell<(Integer, 
-        FixedVec<Integer, SIZE>,
-        FixedVec<Factor, SIZE>,
-        [(MontgomeryPoint, Integer); ITERATIONS],
-        [bool; SIZE],
+        GrowVec<Integer, SIZE>,
+        FactorQueue,
         Factor,
-        Context,
         
         )> = RefCell::new((
             Integer::new(),
-            FixedVec::new(Integer::new()), 
-            FixedVec::new(Factor::new()),
-            std::array::from_fn(|_| (MontgomeryPoint::default(), Integer::new())),
-            std::array::from_fn(|_| true),
+            GrowVec::new(Integer::new()), 
+            FactorQueue::default(),
             Factor::new(),
-            Context::new(Integer::ONE.clone()),
         ));
 }
 
@@ -90,35 +139,256 @@ impl Buffer {
     where
         F: FnOnce(
             &mut Integer,
-            &mut FixedVec<Integer, SIZE>,
-            &mut FixedVec<Factor, SIZE>,
-            &mut [(MontgomeryPoint, Integer); ITERATIONS],
-            &mut [bool; SIZE],
+            &mut GrowVec<Integer, SIZE>,
+            &mut FactorQueue,
             &mut Factor,
-            &mut Context,
         ) -> R,
     {
         BUFFER.with(|cell| {
-            let (n, prime_factors, temporary_factors,
-                curves, failed_pollard, factor, ctx) = &mut *cell.borrow_mut();
-            f(n, prime_factors, temporary_factors, curves, failed_pollard, factor, ctx)
+            let (n, prime_factors, temporary_factors, factor) = &mut *cell.borrow_mut();
+            f(n, prime_factors, temporary_factors, factor)
         })
     }
 }
 
-/// Given an integer n, the function returns a vector of tuples (prime, exponent) for each prime factor of n.
-pub fn prime_factorize(n_: &Integer) -> Vec<(Integer, u32)> {
+#[cfg(feature = "ecm")]
+thread_local! {
+    static ECM_BUFFER: RefCell<EcmState> = RefCell::new(EcmState::default());
+}
+
+#[cfg(feature = "ecm")]
+struct EcmBuffer;
+
+#[cfg(feature = "ecm")]
+impl EcmBuffer {
+    fn get_mut<F, R>(f: F) -> R
+    where
+        F: FnOnce(&mut EcmState) -> R,
+    {
+        ECM_BUFFER.with(|cell| f(&mut cell.borrow_mut()))
+    }
+}
+
+/// Runs a method that splits composites on the numbers in temporary_factors that the earlier methods failed to
+/// factorize, and on the pieces it splits them into. Any prime factors found are divided out of n and pushed into
+/// factors, and the composites that are left go back into temporary_factors, as do the ones left when the budget runs
+/// out. `split` returns a proper factor of its argument, or None when it fails.
+fn split_pass(n: &mut Integer, prime_factors: &mut GrowVec<Integer, SIZE>, factors: &mut Vec<(Integer, u32)>,
+    temporary_factors: &mut FactorQueue, budget: &Budget, method: Method,
+    mut split: impl FnMut(&Integer) -> Option<Integer>) {
+    let mut pending = temporary_factors.take_values();
+    budget.report(|| FactorEvent::MethodStarted { method, composites: pending.len() });
+
+    while let Some(mut m) = pending.pop() {
+        // drop the primes found so far, a piece split off by the method can still contain them
+        for i in 0..prime_factors.len() {
+            m.remove_factor_mut(prime_factors.get(i));
+        }
+        while m > 1 && let Some(root) = isqrt_rem(&m).exact_root() {
+            m = root;
+        }
+        if m == 1 || push_small_factors(&m, prime_factors) {
+            continue;
+        }
+        if is_prime(&m) {
+            prime_factors.next().assign(&m);
+            prime_factors.inc();
+            continue;
+        }
+
+        if !budget.is_exhausted() && let Some(g) = split(&m) {
+            budget.report(|| FactorEvent::FactorFound { method, n: m.clone(), factor: g.clone() });
+            m.div_exact_mut(&g);
+            pending.push(m);
+            pending.push(g);
+            continue;
+        }
+        // the factor keeps index 0, so find_exponents removes every prime found in this pass from it
+        temporary_factors.push(&m, 0);
+    }
+
+    find_exponents(n, prime_factors, factors, temporary_factors);
+}
+
+/// Runs Pollard's p − 1 with stage 2 (with bounds (B1, B2)) on the numbers in temporary_factors that
+/// Pollard's rho failed to factorize, see `split_pass`. It is far cheaper than the ECM passes and catches the factors p
+/// with a smooth p - 1.
+fn p_minus_1_pass(n: &mut Integer, primes: &[u32], (B1, B2): (u32, u32), prime_factors: &mut GrowVec<Integer, SIZE>,
+    factors: &mut Vec<(Integer, u32)>, temporary_factors: &mut FactorQueue, budget: &Budget) {
+    let mut g = Integer::new();
+    split_pass(n, prime_factors, factors, temporary_factors, budget, Method::PollardPMinus1, |m| {
+        let mut ctx = Context::new(m.clone());
+        pollard_p_minus_1_stage2(m, &mut ctx, primes, B1, B2, budget.cancel_flag(), &mut g).map(|_| g.clone())
+    });
+}
+
+/// Runs Williams' p + 1 with stage 2 (with bounds (B1, B2)) on the numbers in temporary_factors that Pollard's rho and
+/// p − 1 failed to factorize, see `split_pass`. It catches the factors p with a smooth p + 1.
+fn p_plus_1_pass(n: &mut Integer, primes: &[u32], (B1, B2): (u32, u32), prime_factors: &mut GrowVec<Integer, SIZE>,
+    factors: &mut Vec<(Integer, u32)>, temporary_factors: &mut FactorQueue, budget: &Budget) {
+    let mut g = Integer::new();
+    split_pass(n, prime_factors, factors, temporary_factors, budget, Method::WilliamsPPlus1, |m| {
+        let mut ctx = Context::new(m.clone());
+        williams_p_plus_1(m, &mut ctx, primes, B1, B2, budget.cancel_flag(), &mut g).map(|_| g.clone())
+    });
+}
+
+/// Runs the self-initializing quadratic sieve on the composites of up to `QS_MAX_DIGITS` digits that are left in
+/// temporary_factors after ECM, see `split_pass`. These have no factor ECM could find, so the two largest are
+/// likely of about the same size, which doesn't slow the quadratic sieve down.
+#[cfg(feature = "qs")]
+fn qs_pass(n: &mut Integer, prime_factors: &mut GrowVec<Integer, SIZE>, factors: &mut Vec<(Integer, u32)>,
+    temporary_factors: &mut FactorQueue, budget: &Budget) {
+    split_pass(n, prime_factors, factors, temporary_factors, budget, Method::QuadraticSieve, |m| {
+        let digits = (m.significant_bits() as f64 * std::f64::consts::LOG10_2).ceil() as u32;
+        (digits <= QS_MAX_DIGITS).then(|| qs::siqs(m, budget.cancel_flag())).flatten()
+    });
+}
+
+/// Runs the Pollard–Strassen method on the composites of up to `STRASSEN_MAX_BITS` bits that are left in
+/// temporary_factors at the end, see `split_pass`. It is slower than the methods before it, but deterministic, so it
+/// splits the ones they were unlucky with.
+#[cfg(feature = "ecm")]
+fn strassen_pass(n: &mut Integer, prime_factors: &mut GrowVec<Integer, SIZE>, factors: &mut Vec<(Integer, u32)>,
+    temporary_factors: &mut FactorQueue, budget: &Budget) {
+    split_pass(n, prime_factors, factors, temporary_factors, budget, Method::PollardStrassen, |m| {
+        (m.significant_bits() <= STRASSEN_MAX_BITS).then(|| pollard_strassen(m, budget.cancel_flag())).flatten()
+    });
+}
+
+/// Runs the rounds of ECM on the numbers in temporary_factors that Pollard's rho and p − 1 failed to factorize.
+/// Any prime factors found are divided out of n and pushed into factors. The rounds of a schedule stop at the first
+/// one whose level is too high for the largest composite left (see `ecm_schedule`). The rounds, with the family of
+/// their curves, are the ones of the factorizer.
+#[cfg(feature = "ecm")]
+fn ecm_passes(n: &mut Integer, factorizer: &Factorizer, prime_factors: &mut GrowVec<Integer, SIZE>,
+    factors: &mut Vec<(Integer, u32)>, temporary_factors: &mut FactorQueue, budget: &Budget,
+    verifier: Option<&PrimeVerifier>) {
+    let data = get_data();
+    EcmBuffer::get_mut(|state| {
+        state.residues.iter_mut().for_each(Stage1Residue::clear);
+        state.edwards_residues.iter_mut().for_each(EdwardsResidue::clear);
+        for round in &factorizer.ecm {
+            if temporary_factors.is_empty() || budget.is_exhausted() {
+                return;
+            }
+            if let Some(level) = round.level {
+                let bits = temporary_factors.values().map(Integer::significant_bits).max();
+                let digits = (bits.unwrap_or(0) as f64 * std::f64::consts::LOG10_2).ceil() as u32;
+                if level > digits / 2 + ECM_EXTRA_DIGITS {
+                    return;
+                }
+            }
+            budget.report(|| FactorEvent::MethodStarted { method: Method::Ecm, composites: temporary_factors.len() });
+            // generate curve parameters. After the first round, the fresh curves are only used by curves without
+            // a residue that is valid for what is left of n: the others continue from their stage 1 residues, so
+            // stage 1 only covers the prime powers between the two values of B1. Edwards curves are set up by
+            // ecm_trial itself.
+            let EcmState { curves, ctx, .. } = state;
+            ctx.change_mod(n);
+            if round.family == CurveFamily::Suyama && let Err(d) = suyama_parameterization(ctx, &data.params1, curves) {
+                // a denominator of the curves shares a factor with n, which splits the composites it divides
+                split_pass(n, prime_factors, factors, temporary_factors, budget, Method::Ecm, |m| {
+                    let g = m.gcd_ref(&d).complete();
+                    (g != 1 && g != *m).then_some(g)
+                });
+                if temporary_factors.is_empty() {
+                    return;
+                }
+                ctx.change_mod(n);
+                if suyama_parameterization(ctx, &data.params1, curves).is_err() {
+                    continue;
+                }
+            }
+            ecm_trial(n, state, round, temporary_factors, prime_factors, data, budget);
+            find_exponents(n, prime_factors, factors, temporary_factors);
+            if let Some(verifier) = verifier {
+                verifier.report(factors);
+            }
+        }
+    });
+}
+
+/// Given an integer n, the function returns its `Factorization`: each prime factor of n with its exponent.
+/// Without the `ecm` feature only trial division, Pollard's rho and p − 1 are run, so large factors may be left out.
+/// To change the methods or their bounds, use a `Factorizer`.
+pub fn prime_factorize(n_: &Integer) -> Factorization {
+    // tiny inputs are looked up in the smallest prime factor table, without building the factorization data
+    if let Some(factors) = n_.to_u32().and_then(factorize_small) {
+        return Factorization::new(factors.into_iter().map(|(p, e)| (Integer::from(p), e)).collect());
+    }
+    Factorization::new(factorize_with(n_, Factorizer::default_ref(), &Budget::unlimited(), None).primes)
+}
+
+/// Like `prime_factorize`, but with a limited budget: the methods that are run are set by the effort (see `Effort`).
+/// Instead of leaving out the factors that could not be found, it returns the composite cofactors that are left,
+/// so the result always multiplies back to n.
+pub fn prime_factorize_partial(n: &Integer, effort: Effort) -> PartialFactorization {
+    factorize_with(n, Factorizer::for_effort(effort), &Budget::unlimited(), None)
+}
+
+/// Like `prime_factorize`, but stops when the deadline of the options passes or their cancel flag is set,
+/// e.g to give up on an ECM run that is taking too long. The result is a `PartialFactorization` like the one of
+/// `prime_factorize_partial`: the primes found so far, and the composites that are left. With a seed, the random
+/// choices are the same in every run.
+pub fn prime_factorize_with_options(n: &Integer, options: &FactorizeOptions) -> PartialFactorization {
+    with_seed(options.seed, || factorize_with(n, Factorizer::default_ref(), &options.budget(), None))
+}
+
+/// Like `prime_factorize`, but every prime is checked again, with BPSW and extra Miller-Rabin rounds, and with a
+/// Pratt certificate if `prove` is set. The checks run on a pool of threads as soon as each stage of the
+/// factorization finds the primes, so they overlap with the ECM work on the other cofactors.
+/// The primes are returned in increasing order.
+pub fn prime_factorize_verified(n: &Integer, prove: bool) -> VerifiedFactorization {
+    verify::factorize_verified(n, Factorizer::default_ref(), prove)
+}
+
+/// Like `prime_factorize_with_options`, but every prime comes with the method that found it (and the ECM curve), and
+/// the time it took to get there, along with the time spent in each method, e.g to see where the time of a hard
+/// composite goes. The progress callback of the options, if any, still gets every event.
+pub fn prime_factorize_traced(n: &Integer, options: &FactorizeOptions) -> TracedFactorization {
+    factorize_traced(n, Factorizer::default_ref(), options)
+}
+
+/// Runs `factorize_with` with a progress callback that records the events of the run with their times, and traces
+/// the primes back through them.
+fn factorize_traced(n: &Integer, factorizer: &Factorizer, options: &FactorizeOptions) -> TracedFactorization {
+    let start = Instant::now();
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let log = events.clone();
+    let forward = options.progress.clone();
+    let progress = Progress::new(move |event| {
+        log.lock().unwrap().push((start.elapsed(), event.clone()));
+        if let Some(progress) = &forward {
+            (progress.0)(event);
+        }
+    });
+    let options = FactorizeOptions { progress: Some(progress), ..options.clone() };
+    let partial = with_seed(options.seed, || factorize_with(n, factorizer, &options.budget(), None));
+    let elapsed = start.elapsed();
+    let events = std::mem::take(&mut *events.lock().unwrap());
+    TracedFactorization::new(partial, &events, elapsed)
+}
+
+/// The pipeline behind all the `prime_factorize` variants. The verifier, if there is one, gets the primes after
+/// each stage.
+fn factorize_with(n_: &Integer, factorizer: &Factorizer, budget: &Budget, verifier: Option<&PrimeVerifier>)
+    -> PartialFactorization {
+    if let Some(factors) = n_.to_u32().and_then(factorize_small) {
+        let primes = factors.into_iter().map(|(p, e)| (Integer::from(p), e)).collect();
+        return PartialFactorization { primes, composites: Vec::new() };
+    }
+
     let data = get_data();
     let primes = &data.primes;
     let mut factors: Vec<(Integer, u32)> = Vec::new();
     
-    Buffer::get_mut(|n, prime_factors, temporary_factors,
-        curves, failed_pollard, factor, ctx| {
+    Buffer::get_mut(|n, prime_factors, temporary_factors, factor| {
 
+        prime_factors.clear();
         temporary_factors.clear();
         // prime_factors: stores factors but without exponent
         // temporary_factors: stores the numbers that have yet to be fully factored
-        // failed_pollard: stores the numbers that failed to get factored by pollard
         
         n.assign(n_);
         // removes the even factor
@@ -129,131 +399,481 @@ pub fn prime_factorize(n_: &Integer) -> Vec<(Integer, u32)> {
         }
     
         // do trial division up to 1e4 remove small prime factors
-        trial_division(n, &mut factors, primes);
+        trial_division(n, &mut factors, primes, factorizer.trial_division_bound);
+        budget.report(|| FactorEvent::TrialDivisionDone { remaining: n.clone() });
     
         if n == Integer::ONE {
-            return factors;
+            return PartialFactorization { primes: factors, composites: Vec::new() };
         }
         
-        temporary_factors.next().update_all(&*n, prime_factors.len());
-        temporary_factors.inc();
-        // println!("temporary_factors: {:?}", temporary_factors.top());
-        failed_pollard[0] = false;
+        temporary_factors.push(n, prime_factors.len());
+        temporary_factors.top().update_ctx();
 
         let mut index = 1;
-        while index > 0 {
+        // when the budget runs out, the numbers left in temporary_factors become the composites of the result
+        while index > 0 && !budget.is_exhausted() {
             index -= 1;
-            // println!("index: {:?}", index);
 
-            let curval = &temporary_factors.get(index).n;
-
-            // println!("curval: {:?}", curval);
+            // the primes found since the entry was last looked at are divided out first, and a smaller value gets
+            // another try even if the methods failed on the old one
+            let entry = temporary_factors.get_mut(index);
+            if entry.remove_primes(prime_factors) {
+                entry.failed = false;
+            }
+            let curval = &entry.n;
+            if *curval == 1 || push_small_factors(curval, prime_factors) {
+                temporary_factors.swap_remove(index);
+                continue;
+            }
 
-            if curval.is_probably_prime(20) != IsPrime::No {
-                // println!("curval is prime: {:?}", curval);
+            if is_prime(curval) {
                 prime_factors.next().assign(curval);
                 prime_factors.inc();
-
-                failed_pollard[index] = true;
-                temporary_factors.dec();
-                temporary_factors.swap(index, temporary_factors.len());
+                temporary_factors.swap_remove(index);
                 continue;
             }
 
-            factor.n.assign(&temporary_factors.get(index).n);
-            let mut value_changed = false;
-
-            for i in factor.idx..prime_factors.len() {
-                let p = prime_factors.get(i);
-                while factor.n.is_divisible(p) {
-                    value_changed = true;
-                    factor.n.div_exact_mut(p);
-                }
+            if entry.failed {
+                continue;  // the methods failed on this value before
             }
-
-            temporary_factors.get_mut(index).idx = prime_factors.len();
-            if failed_pollard[index] && !value_changed {
-                continue;  // if it failed pollard before and we haven't reduced it further, skip it
-            }
-            
-            failed_pollard[index] = true;
+            entry.failed = true;
+            factor.n.assign(&entry.n);
             
-            // update the ctx before calling pollard_rho_brent
-            if temporary_factors.get(index).ctx.n == factor.n {
-                // if the ctx is the same, just assign it to the factor
-                factor.ctx.assign(&temporary_factors.get(index).ctx);
-            } else {
-                factor.update_ctx();
-            }
+            // cofactors below 2^SQUFOF_BITS are split with SQUFOF on machine words, without a Montgomery context.
+            // From HART_LEHMAN_MIN_BITS to HART_LEHMAN_BITS bits, Hart's and Lehman's methods get a few steps on
+            // machine words too, where SQUFOF failed or can't go. Larger ones get a few steps of Fermat's method
+            // first, for factors p and q close together that Pollard's rho and ECM can't find. These splits are
+            // only used if the parts are coprime, so that no prime is found in both of them.
+            let bits = factor.n.significant_bits();
+            let small = factor.n.to_u64().filter(|_| bits <= SQUFOF_BITS);
+            let mid = factor.n.to_u128().filter(|_| factorizer.hart_lehman && (HART_LEHMAN_MIN_BITS..=HART_LEHMAN_BITS).contains(&bits));
+            let hart_lehman = || mid.and_then(|m| hart_one_line(m, HART_STEPS).or_else(|| lehman(m, LEHMAN_MAX_K)))
+                .map(Integer::from)
+                .filter(|d| Integer::from(&factor.n / d).gcd(d) == 1)
+                .map(|d| (d, Method::HartLehman));
+            let found = match small {
+                Some(m) if factorizer.squfof => {
+                    squfof(m).map(|d| (Integer::from(d), Method::Squfof)).or_else(hart_lehman)
+                }
+                Some(_) => hart_lehman(),
+                None => hart_lehman().or_else(|| (factorizer.fermat_steps > 0)
+                    .then(|| fermat_factor(&factor.n, factorizer.fermat_steps))
+                    .flatten()
+                    .filter(|d| Integer::from(&factor.n / d).gcd(d) == 1)
+                    .map(|d| (d, Method::Fermat))),
+            };
+            let method = found.as_ref().map_or(Method::PollardRho, |&(_, method)| method);
+            let split = match found {
+                Some((d, _)) => {
+                    temporary_factors.spare().n.assign(d);
+                    true
+                }
+                None => {
+                    // update the ctx before calling pollard_rho_with
+                    if temporary_factors.get(index).ctx.n == factor.n {
+                        // if the ctx is the same, just assign it to the factor
+                        factor.ctx.assign(&temporary_factors.get(index).ctx);
+                    } else {
+                        factor.update_ctx();
+                    }
+                    // directly assign the result of pollard_rho_with to the next entry in temporary_factors
+                    let result = &mut temporary_factors.spare().n;
+                    (0..factorizer.rho.retries)
+                        .take_while(|_| !budget.is_exhausted())
+                        .any(|_| match factorizer.rho_walks {
+                            1 => pollard_rho_with(&factor.n, &mut factor.ctx, &factorizer.rho, result),
+                            walks => pollard_rho_parallel(&factor.n, walks, &factorizer.rho, result),
+                        }.is_some())
+                }
+            };
 
-            for _ in 0..3 {
-                // println!("factor: {:?}", factor.n);
-                // directly assign the result of pollard_rho_brent to the next entry in temporary_factors
-                match pollard_rho_brent(&factor.n, &mut factor.ctx, &mut temporary_factors.next().n) {
-                    None => continue,
-                    Some(()) => {
-                        // println!("found factor: {:?}", temporary_factors.next().n);
-                        factor.n.div_exact_mut(&temporary_factors.next().n);
-                        failed_pollard[index] = false;
-
-                        // don't change the ctx-es yet, if its prime doing so is redundant
-                        // changing the ctx is left to before calling pollard_rho_brent
-                        temporary_factors.get_mut(index).n.assign(&factor.n);
-                        temporary_factors.get_mut(index).idx = prime_factors.len();
-                        
-                        temporary_factors.next().idx = prime_factors.len();
-                        // println!("factored result: {:?}", temporary_factors.next());
-                        temporary_factors.inc();
-                        
-                        let len = temporary_factors.len();
-                        if len > 1 && temporary_factors.get(index).n < temporary_factors.get(len - 1).n {
-                            temporary_factors.swap(index, len - 1);
-                        }
-                        
-                        index = len;
-                        failed_pollard[index - 1] = false;
-
-                        break;
-                    },
-                };
+            if split {
+                budget.report(|| FactorEvent::FactorFound {
+                    method, n: factor.n.clone(), factor: temporary_factors.spare().n.clone()
+                });
+                factor.n.div_exact_mut(&temporary_factors.spare().n);
+
+                // don't change the ctx-es yet, if its prime doing so is redundant
+                // changing the ctx is left to before calling pollard_rho_with
+                let entry = temporary_factors.get_mut(index);
+                entry.n.assign(&factor.n);
+                entry.failed = false;
+                temporary_factors.push_next(prime_factors.len());
+
+                let len = temporary_factors.len();
+                if len > 1 && temporary_factors.get(index).n < temporary_factors.get(len - 1).n {
+                    temporary_factors.swap(index, len - 1);
+                }
+
+                index = len;
             }
         }
         
         find_exponents(n, prime_factors, &mut factors, temporary_factors);
         // println!("after pollard: {:?}\n left with n = {}", factors, n);
-        
-        // generate curve parameters.
-        ctx.change_mod(n);
-        suyama_parameterization(ctx, &data.params1, curves);
-        // do 200 rounds of ECM with B1 = 5e4, B2 = 50 * B1 = 2.5e6
-        ecm_trial(n, ctx, BOUNDS1.0, BOUNDS1.1, &data.params1, curves, &data.s1, temporary_factors,
-            prime_factors, &primes, &data.gaps1.1, &data.gaps1.0);
+        if let Some(verifier) = verifier {
+            verifier.report(&factors);
+        }
 
-        find_exponents(n, prime_factors, &mut factors, temporary_factors);
-        
-        if n == Integer::ONE {
-            return factors;
+        if let Some(bounds) = factorizer.p_minus_1.filter(|_| !temporary_factors.is_empty()) {
+            p_minus_1_pass(n, primes, bounds, prime_factors, &mut factors, temporary_factors, budget);
+            if let Some(verifier) = verifier {
+                verifier.report(&factors);
+            }
+        }
+
+        if let Some(bounds) = factorizer.p_plus_1.filter(|_| !temporary_factors.is_empty()) {
+            p_plus_1_pass(n, primes, bounds, prime_factors, &mut factors, temporary_factors, budget);
+            if let Some(verifier) = verifier {
+                verifier.report(&factors);
+            }
         }
         
-        // println!("after ecm with B1 = 5e4, B2 = 50 * B1: {:?}\n left with n = {}", factors, n);
+        #[cfg(feature = "ecm")]
+        ecm_passes(n, factorizer, prime_factors, &mut factors, temporary_factors, budget, verifier);
 
-        // println!("curves: {:?}", curves);
-        // println!("so far we have: {:?}, {:?}", factors, temporary_factors);
+        #[cfg(feature = "qs")]
+        if factorizer.qs && !temporary_factors.is_empty() {
+            qs_pass(n, prime_factors, &mut factors, temporary_factors, budget);
+            if let Some(verifier) = verifier {
+                verifier.report(&factors);
+            }
+        }
 
-        ctx.change_mod(n);
-        suyama_parameterization(ctx, &data.params2, curves);
-    
-        // increase the bounds of ECM: B1 = 5e5, B2 = 50 * B1 = 2.5e7 
-        ecm_trial(n, ctx, BOUNDS2.0, BOUNDS2.1, &data.params2, curves, &data.s2, temporary_factors,
-            prime_factors, &primes, &data.gaps2.1, &data.gaps2.0);
-    
-        /*
-        if !temporary_factors.is_empty() {
-            println!("failed to fully factorize");
+        #[cfg(feature = "ecm")]
+        if factorizer.pollard_strassen && !temporary_factors.is_empty() {
+            strassen_pass(n, prime_factors, &mut factors, temporary_factors, budget);
+            if let Some(verifier) = verifier {
+                verifier.report(&factors);
+            }
         }
-        */
-    
-        find_exponents(n, prime_factors, &mut factors, temporary_factors);
-        factors
+
+        // what is left of n is made of the composites that none of the methods could split. If the budget ran out,
+        // some of the numbers may not have been tested yet, so they can still be prime.
+        let mut composites: Vec<(Integer, u32)> = Vec::new();
+        let mut leftovers = temporary_factors.take_values();
+        leftovers.push(n.clone());
+        for mut m in leftovers {
+            while m > 1 && let Some(root) = isqrt_rem(&m).exact_root() {
+                m = root;
+            }
+            if m > 1 {
+                let exponent = n.remove_factor_mut(&m);
+                if exponent == 0 {
+                    continue;
+                }
+                match is_prime(&m) {
+                    true => factors.push((m, exponent)),
+                    false => composites.push((m, exponent)),
+                }
+            }
+        }
+        PartialFactorization { primes: factors, composites }
     })
-}
\ No newline at end of file
+}
+
+/// What is known of the prime factors of a cofactor after trial division up to `trial_division_bound` and the ECM
+/// `rounds` that were run on it (or on a multiple of it). The trial division is checked again: the cofactor is
+/// divided by every prime of the sieve below the bound (which stops at `prime_limit()`), and `proven` is the smallest
+/// one that divides it if there is one, so the bound holds whatever the cofactor went through. `ecm_level` is the
+/// highest t-level of `ECM_LEVELS` that one of the rounds covers with at least its curves and bounds, which only
+/// makes a factor of that size unlikely. Pollard's rho, p − 1 and p + 1 prove nothing either, so they don't count.
+pub fn cofactor_bound(cofactor: &Integer, trial_division_bound: u32, rounds: &[EcmRound]) -> CofactorBound {
+    let primes = &get_data().primes;
+    let end = primes.partition_point(|&p| p < trial_division_bound);
+    let proven = match primes[..end].iter().find(|&&p| cofactor.is_divisible_u(p)) {
+        Some(&p) => p,
+        None => trial_division_bound.min(prime_limit() + 1),
+    };
+    #[cfg(feature = "ecm")]
+    let ecm_level = ECM_LEVELS.iter()
+        .filter(|(_, level)| rounds.iter().any(|round| round.curves >= level.curves && round.B1 >= level.B1
+            && round.B2 >= level.B2))
+        .map(|&(t, _)| t)
+        .max();
+    #[cfg(not(feature = "ecm"))]
+    let ecm_level = {
+        let _ = rounds;
+        None
+    };
+    CofactorBound { proven, ecm_level }
+}
+
+/// Like `prime_factorize`, but uses known divisors of n (e.g from a previous run or from algebraic structure).
+/// Each hint is checked to be a divisor of n greater than 1, then n is split along the gcds with the hints, and the
+/// pieces along their gcds with each other until they are pairwise coprime, so only the parts the hints don't
+/// separate have to go through `prime_factorize`. The hints don't need to be prime,
+/// and powers of them are handled. The primes are returned in increasing order.
+///
+/// # Returns
+/// * `Ok(factorization)` - The factorization of n.
+/// * `Err(InvalidHint(h))` - The hint h is not a divisor of n greater than 1.
+pub fn prime_factorize_with_hints(n: &Integer, hints: &[Integer]) -> Result<Factorization, InvalidHint> {
+    let mut pieces = vec![n.clone()];
+    for hint in hints {
+        if *hint <= 1 || !n.is_divisible(hint) {
+            return Err(InvalidHint(hint.clone()));
+        }
+        let mut split = Vec::with_capacity(pieces.len() + 1);
+        for mut piece in pieces {
+            let g = Integer::from(piece.gcd_ref(hint));
+            if g != 1 && g != piece {
+                piece.div_exact_mut(&g);
+                split.push(g);
+            }
+            split.push(piece);
+        }
+        pieces = split;
+    }
+
+    // the pieces can still share primes (n = p^2 q with the hint pq leaves pq and p), so they are split along their
+    // gcds until they are pairwise coprime, which merges what the hints know about each other
+    loop {
+        let shared = (0..pieces.len())
+            .flat_map(|i| (i + 1..pieces.len()).map(move |j| (i, j)))
+            .find(|&(i, j)| pieces[i].gcd_ref(&pieces[j]).complete() != 1);
+        let Some((i, j)) = shared else {
+            break;
+        };
+        let b = pieces.swap_remove(j);
+        let a = pieces.swap_remove(i);
+        let g = a.gcd_ref(&b).complete();
+        pieces.extend([a / &g, b / &g, g].into_iter().filter(|piece| *piece != 1));
+    }
+
+    let mut primes: Vec<Integer> = Vec::new();
+    for mut piece in pieces {
+        while piece > 1 && let Some(root) = isqrt_rem(&piece).exact_root() {
+            piece = root;
+        }
+        if is_prime(&piece) {
+            primes.push(piece);
+        } else {
+            primes.extend(prime_factorize(&piece).into_iter().map(|(p, _)| p));
+        }
+    }
+
+    primes.sort();
+    primes.dedup();
+    let mut n = n.clone();
+    Ok(Factorization::new(primes.into_iter()
+        .map(|p| {
+            let exponent = n.remove_factor_mut(&p);
+            (p, exponent)
+        })
+        .collect()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use num_traits::One;
+
+    #[test]
+    fn test_cofactor_bound() {
+        // two primes above 10^6: trial division to 10^4 leaves room for three factors, to 10^6 only for two
+        let n = Integer::from(1_000_003u64 * 1_000_033);
+        let bound = cofactor_bound(&n, 10_000, &[]);
+        assert_eq!(bound, CofactorBound { proven: 10_000, ecm_level: None });
+        assert_eq!(bound.max_prime_factors(&n), 3);
+        assert_eq!(cofactor_bound(&n, 1_000_000, &[]).max_prime_factors(&n), 2);
+        assert_eq!(cofactor_bound(&n, u32::MAX, &[]).proven, 1_000_003);
+        let m61 = Integer::from(2_147_483_647u64) * 2_305_843_009_213_693_951u64;
+        assert_eq!(cofactor_bound(&m61, u32::MAX, &[]).proven, prime_limit() + 1);
+
+        // a small factor the caller missed lowers the bound to it
+        assert_eq!(cofactor_bound(&(n.clone() * 7u32), 10_000, &[]).proven, 7);
+
+        // ECM only counts up to the highest level it covers in full
+        #[cfg(feature = "ecm")]
+        {
+            assert_eq!(cofactor_bound(&n, 10_000, &[ECM_LEVELS[1].1]).ecm_level, Some(25));
+            let weaker = EcmRound { curves: 100, ..ECM_LEVELS[1].1 };
+            assert_eq!(cofactor_bound(&n, 10_000, &[weaker]).ecm_level, Some(20));
+            let m = Integer::from(Integer::u_pow_u(10, 59)) + 1u32;
+            // the schedule runs every level on a 60 digit cofactor, but none on a 13 digit one
+            assert_eq!(Factorizer::default_ref().cofactor_bound(&m).ecm_level, Some(data::ECM_SCHEDULE_LEVEL));
+            assert_eq!(Factorizer::default_ref().cofactor_bound(&n).ecm_level, None);
+        }
+    }
+
+    #[test]
+    fn test_prime_factorize_with_hints() {
+        // Mersenne primes, far too large for the factorization pipeline once multiplied together
+        let m89 = Integer::from(Integer::u_pow_u(2, 89)) - 1u32;
+        let m107 = Integer::from(Integer::u_pow_u(2, 107)) - 1u32;
+        let m127 = Integer::from(Integer::u_pow_u(2, 127)) - 1u32;
+        let n = Integer::from(9) * &m89 * &m89 * &m107 * &m127;
+        let hints = [Integer::from(&m89 * &m89), Integer::from(&m107 * 3), m127.clone()];
+        let expected = vec![(Integer::from(3), 2), (m89.clone(), 2), (m107.clone(), 1), (m127.clone(), 1)];
+        assert_eq!(prime_factorize_with_hints(&n, &hints), Ok(Factorization::new(expected)));
+
+        // the hint m89 * m107 splits n into m89 * m107 and m107 * m127, whose gcd gives the rest
+        let n = Integer::from(&m89 * &m107) * &m107 * &m127;
+        let expected = vec![(m89.clone(), 1), (m107.clone(), 2), (m127.clone(), 1)];
+        let factorization = prime_factorize_with_hints(&n, &[Integer::from(&m89 * &m107)]).unwrap();
+        assert_eq!(factorization.factors(), expected);
+        assert_eq!(factorization.value(), n);
+
+        assert_eq!(prime_factorize_with_hints(&n, &[Integer::from(5)]), Err(InvalidHint(Integer::from(5))));
+        assert_eq!(prime_factorize_with_hints(&n, &[Integer::from(1)]), Err(InvalidHint(Integer::from(1))));
+    }
+
+    // without ECM, prime_factorize can leave out the larger primes
+    #[cfg(feature = "ecm")]
+    #[test]
+    fn test_prime_factorize_repeated_factors() {
+        use crate::number_theory::random::{generate_prime, rand_state, random_below_u64, with_seed};
+
+        // products of random primes of up to 40 bits with random exponents, where the same prime is often drawn
+        // again, against the factorization they are built from: no prime is dropped, and every exponent is recovered
+        with_seed(Some(3760), || {
+            let rand = &mut rand_state();
+            for _ in 0..30 {
+                let mut expected: Vec<(Integer, u32)> = Vec::new();
+                for _ in 0..1 + random_below_u64(6) {
+                    let e = 1 + random_below_u64(4) as u32;
+                    if !expected.is_empty() && random_below_u64(3) == 0 {
+                        let i = random_below_u64(expected.len() as u64) as usize;
+                        expected[i].1 += e;
+                        continue;
+                    }
+                    let p = generate_prime(2 + random_below_u64(39) as u32, rand);
+                    match expected.iter_mut().find(|(q, _)| *q == p) {
+                        Some((_, f)) => *f += e,
+                        None => expected.push((p, e)),
+                    }
+                }
+                let expected = Factorization::new(expected);
+                assert_eq!(prime_factorize(&expected.value()), expected);
+            }
+        });
+    }
+
+    #[test]
+    fn test_factorization() {
+        let factorization = prime_factorize(&Integer::from(680));
+        assert_eq!(factorization.to_string(), "2^3 · 5 · 17");
+        assert_eq!(factorization.value(), 680);
+        assert_eq!(factorization.totient(), 256);
+        assert_eq!(factorization.divisor_count(), 16);
+        assert_eq!(factorization.divisor_sum(), 1620);
+        assert_eq!(factorization.radical(), 170);
+        let mut divisors: Vec<Integer> = factorization.divisors().collect();
+        divisors.sort();
+        let expected: Vec<Integer> = (1..=680u32).filter(|d| 680 % d == 0).map(Integer::from).collect();
+        assert_eq!(divisors, expected);
+
+        let one = prime_factorize(&Integer::from(1));
+        assert_eq!((one.to_string(), one.divisors().count()), ("1".to_string(), 1));
+        assert_eq!(Factorization::new(vec![(Integer::from(7), 2), (Integer::from(3), 1)]).to_string(), "3 · 7^2");
+
+        // the multiplicative structure, through the num-traits traits
+        let other = prime_factorize(&Integer::from(1050));
+        assert_eq!((&factorization * &other).value(), 680 * 1050);
+        assert_eq!(factorization.gcd(&other).value(), 10);
+        assert_eq!(factorization.lcm(&other).value(), 71400);
+        assert!(factorization.is_multiple_of(&prime_factorize(&Integer::from(40))));
+        assert!(!factorization.is_multiple_of(&other));
+        assert_eq!(num_traits::Pow::pow(&factorization, 3).value(), 680u64.pow(3));
+        assert!(one.is_one() && Factorization::one() == one);
+        let product: Factorization = [680u32, 1050, 1].into_iter().map(|n| prime_factorize(&Integer::from(n))).product();
+        assert_eq!(product, &factorization * &other);
+    }
+
+    #[test]
+    fn test_prime_factorize_partial() {
+        let p = (Integer::from(1) << 100u32).next_prime();
+        let q = (Integer::from(3) << 110u32).next_prime();
+        let hard = Integer::from(&p * &q);
+        let n = Integer::from(&hard * &hard) * 2u32 * 1_000_000_007u32;
+
+        let partial = prime_factorize_partial(&n, Effort::Low);
+        assert!(!partial.is_complete());
+        assert_eq!(partial.primes, vec![(Integer::from(2), 1), (Integer::from(1_000_000_007), 1)]);
+        assert_eq!(partial.composites, vec![(hard, 2)]);
+
+        let n = Integer::from(998_244_353u32) * 1_000_000_007u32 * 3u32;
+        let partial = prime_factorize_partial(&n, Effort::Low);
+        assert!(partial.is_complete());
+        assert_eq!(Factorization::new(partial.primes), prime_factorize(&n));
+
+        use rug::ops::Pow;
+        // a prime found twice in a pass, from both halves of p^2, is counted once
+        for (p, e) in [(1_000_003u64, 3), (159_122_399_213, 2), (159_122_399_213, 4)] {
+            let n = Integer::from(p).pow(e) * 7u32;
+            assert_eq!(prime_factorize(&n).into_factors(), vec![(Integer::from(7), 1), (Integer::from(p), e)]);
+        }
+    }
+
+    #[test]
+    fn test_prime_factorize_with_options() {
+        use rug::ops::Pow;
+        use std::{sync::{atomic::{AtomicBool, Ordering}, Arc, Mutex}, thread, time::{Duration, Instant}};
+
+        // two 100 bit primes, far beyond what the pipeline can find in a reasonable time
+        let p = (Integer::from(1) << 100u32).next_prime();
+        let q = (Integer::from(3) << 99u32).next_prime();
+        let hard = Integer::from(&p * &q);
+        let n = Integer::from(&hard * 8u32);
+        let product = |partial: &PartialFactorization| partial.primes.iter().chain(&partial.composites)
+            .fold(Integer::from(1), |product, (m, e)| product * Integer::from(m.pow(*e)));
+
+        let cancel = Arc::new(AtomicBool::new(false));
+        let options = FactorizeOptions { cancel: Some(cancel.clone()), ..Default::default() };
+        let canceller = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(200));
+            cancel.store(true, Ordering::Relaxed);
+        });
+        let partial = prime_factorize_with_options(&n, &options);
+        canceller.join().unwrap();
+        assert_eq!(partial.primes, vec![(Integer::from(2), 3)]);
+        assert_eq!(partial.composites, vec![(hard.clone(), 1)]);
+
+        let start = Instant::now();
+        let options = FactorizeOptions { deadline: Some(Duration::from_millis(300)), ..Default::default() };
+        let partial = prime_factorize_with_options(&Integer::from(&n * 1_000_000_007u32), &options);
+        assert!(start.elapsed() < Duration::from_secs(10));
+        assert!(!partial.is_complete());
+        assert_eq!(product(&partial), Integer::from(&n * 1_000_000_007u32));
+
+        // an untested piece that turns out to be prime is still reported as one
+        let options = FactorizeOptions { deadline: Some(Duration::ZERO), ..Default::default() };
+        let partial = prime_factorize_with_options(&Integer::from(&p * 2u32), &options);
+        assert_eq!(partial.primes, vec![(Integer::from(2), 1), (p, 1)]);
+
+        // the order in which the primes are found depends on the walks of Pollard's rho, which a seed replays
+        let m = Integer::from(1_000_000_007u64) * 998_244_353u64 * 1_000_000_009u64;
+        let options = FactorizeOptions { seed: Some(42), ..Default::default() };
+        let mut primes = prime_factorize_with_options(&m, &options).primes;
+        assert!((0..5).all(|_| prime_factorize_with_options(&m, &options).primes == primes));
+        primes.sort();
+        assert_eq!(primes, prime_factorize(&m).into_factors());
+
+        // the progress callback sees the end of trial division, then every split as it is found
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let log = events.clone();
+        let progress = Progress::new(move |event| log.lock().unwrap().push(event.clone()));
+        let options = FactorizeOptions { progress: Some(progress), ..Default::default() };
+        let partial = prime_factorize_with_options(&Integer::from(&m * 12u32), &options);
+        assert!(partial.is_complete());
+        let events = events.lock().unwrap();
+        assert_eq!(events[0], FactorEvent::TrialDivisionDone { remaining: m.clone() });
+        let splits: Vec<_> = events.iter().filter_map(|event| match event {
+            FactorEvent::FactorFound { n, factor, .. } => Some((n, factor)),
+            _ => None,
+        }).collect();
+        assert_eq!(splits.len(), 2);
+        assert!(splits.iter().all(|&(n, factor)| *factor > 1 && factor < n && n.is_divisible(factor)));
+
+        // a traced run gives every prime the method of the split that left it on its own, and the time of every method
+        let traced = prime_factorize_traced(&Integer::from(&m * 12u32), &FactorizeOptions::default());
+        let primes: Vec<_> = traced.primes.iter().map(|p| (p.prime.clone(), p.exponent)).collect();
+        assert_eq!(primes, prime_factorize(&Integer::from(&m * 12u32)).into_factors());
+        assert!(traced.primes.iter().all(|p| (p.method == Method::TrialDivision) == (p.prime < 5)));
+        assert!(traced.primes.iter().all(|p| p.curve.is_none() && p.found_after <= traced.elapsed));
+        assert_eq!(traced.method_times[0].0, Method::TrialDivision);
+        assert!(traced.method_times.iter().map(|(_, time)| *time).sum::<Duration>() <= traced.elapsed);
+        assert!(traced.to_string().starts_with("2^2 (trial division, "));
+    }
+}
@@ -0,0 +1,205 @@
+use std::collections::HashMap;
+
+use rug::{ops::Pow, rand::RandState, Complete, Integer};
+
+use super::data::{ECPP_MIN_BITS, PROVABLE_PRIME_DIRECT_BITS};
+use super::ecpp::ecpp_certificate;
+use super::prime_factorize;
+use super::structs::{Certificate, PocklingtonCertificate, PocklingtonFactor};
+use crate::montgomery_mod_mult::Context;
+use crate::number_theory::is_prime;
+use crate::number_theory::random::generate_prime;
+use crate::number_theory::residues::pow_mod;
+
+/// Proves that n is prime: with `pocklington_certificate` below `ECPP_MIN_BITS` bits, and with `ecpp_certificate`
+/// from there on, where half factoring n - 1 gets too slow.
+///
+/// # Returns
+/// * `Some(certificate)` - The certificate, whose `verify` succeeds.
+/// * `None` - n is not prime, or the prover of its size got stuck.
+pub fn prove_prime(n: &Integer) -> Option<Certificate> {
+    match n.significant_bits() < ECPP_MIN_BITS {
+        true => pocklington_certificate(n).map(Certificate::Pocklington),
+        false => ecpp_certificate(n).map(Certificate::Ecpp),
+    }
+}
+
+/// Proves that n is prime with a Pocklington certificate. n - 1 is factored with `prime_factorize`, and its primes
+/// are taken in increasing order until their product F is above the square root of n, so the largest primes of
+/// n - 1, whose own certificates are the most expensive, are usually left out (and don't even need to be found).
+/// Every prime of F is proven the same way, down to 2.
+///
+/// # Returns
+/// * `Some(certificate)` - The certificate, which `verify_pocklington` accepts.
+/// * `None` - n is not prime, or the primes found in n - 1 (or in the n - 1 of a prime of the tree) are not enough.
+pub fn pocklington_certificate(n: &Integer) -> Option<PocklingtonCertificate> {
+    certify(n, &mut HashMap::new())
+}
+
+/// The same as `pocklington_certificate`, sharing the certificates of primes that appear more than once in the tree.
+fn certify(n: &Integer, known: &mut HashMap<Integer, PocklingtonCertificate>) -> Option<PocklingtonCertificate> {
+    if let Some(certificate) = known.get(n) {
+        return Some(certificate.clone());
+    }
+    if *n == 2 {
+        return Some(PocklingtonCertificate { prime: n.clone(), factors: Vec::new() });
+    }
+    if *n < 2 || n.is_even() || !is_prime(n) {
+        return None;
+    }
+
+    let n_minus_1 = Integer::from(n - 1u32);
+    let factorization = prime_factorize(&n_minus_1);
+    // without the ecm feature prime_factorize can leave out a large factor, which is fine if it is a prime
+    let rest = &n_minus_1 / factorization.value();
+    let mut primes = factorization.into_factors();
+    if rest > 1 && is_prime(&rest) {
+        primes.push((rest, 1));
+    }
+    primes.sort();
+
+    // the smallest primes whose product F has F^2 > n
+    let mut f = Integer::from(1);
+    let taken = primes.iter()
+        .position(|(q, e)| {
+            f *= q.clone().pow(*e);
+            f.square_ref().complete() > *n
+        })?;
+    primes.truncate(taken + 1);
+
+    let mut ctx = Context::new(n.clone());
+    let mut factors = Vec::with_capacity(primes.len());
+    for (q, exponent) in primes {
+        // a^((n - 1) / q) != 1 makes the gcd with n equal to 1 for a prime n
+        let e = Integer::from(&n_minus_1 / &q);
+        let mut witness = Integer::from(2);
+        while pow_mod(&witness, &e, &mut ctx) == 1 {
+            witness += 1;
+        }
+        factors.push(PocklingtonFactor { certificate: certify(&q, known)?, exponent, witness });
+    }
+    let certificate = PocklingtonCertificate { prime: n.clone(), factors };
+    known.insert(n.clone(), certificate.clone());
+    Some(certificate)
+}
+
+/// A random prime of exactly `bits` bits together with its Pocklington certificate, built Shawe-Taylor style (a
+/// simpler relative of Maurer's method) for cryptographic parameters that need a proof rather than a probable prime.
+/// A proven prime q of ceil(bits / 2) + 1 bits comes first, then candidates n = 2Rq + 1 with a random R are drawn
+/// until one has a witness for q. Since q^2 > n, q alone satisfies Pocklington's theorem, so each level of the tree
+/// costs a single factor. Up to `PROVABLE_PRIME_DIRECT_BITS` bits the prime comes from `generate_prime` and is proven
+/// by factoring p - 1. Panics if bits < 2.
+pub fn generate_provable_prime(bits: u32, rand: &mut RandState) -> (Integer, PocklingtonCertificate) {
+    assert!(bits >= 2, "a prime has at least 2 bits");
+    if bits <= PROVABLE_PRIME_DIRECT_BITS {
+        let p = generate_prime(bits, rand);
+        let certificate = pocklington_certificate(&p).expect("a prime below 2^32 has a Pocklington certificate");
+        return (p, certificate);
+    }
+
+    let (q, q_certificate) = generate_provable_prime(bits.div_ceil(2) + 1, rand);
+    // 2^(bits - 1) <= 2Rq + 1 < 2^bits
+    let two_q = Integer::from(&q << 1u32);
+    let low = ((Integer::from(1) << (bits - 1)) - 2u32 + &two_q) / &two_q;
+    let high = ((Integer::from(1) << bits) - 2u32) / &two_q;
+    let range = Integer::from(&high - &low) + 1u32;
+    loop {
+        let r = Integer::from(range.random_below_ref(rand)) + &low;
+        let n = Integer::from(&two_q * &r) + 1u32;
+        if !is_prime(&n) {
+            continue;
+        }
+        // the conditions are checked in full, so the certificate holds even if BPSW were wrong about n
+        let n_minus_1 = Integer::from(&n - 1u32);
+        let e = r << 1u32;
+        let mut ctx = Context::new(n.clone());
+        let mut witness = Integer::from(2);
+        let mut partial = pow_mod(&witness, &e, &mut ctx);
+        while partial == 1 {
+            witness += 1;
+            partial = pow_mod(&witness, &e, &mut ctx);
+        }
+        if pow_mod(&witness, &n_minus_1, &mut ctx) != 1 || (partial - 1u32).gcd(&n) != 1 {
+            continue;
+        }
+        let factors = vec![PocklingtonFactor { certificate: q_certificate, exponent: 1, witness }];
+        return (n.clone(), PocklingtonCertificate { prime: n, factors });
+    }
+}
+
+/// Checks a Pocklington certificate, along with the certificates of all the primes in it. Only plain big integer
+/// arithmetic is used, so the check doesn't depend on the code that built the certificate.
+pub fn verify_pocklington(certificate: &PocklingtonCertificate) -> bool {
+    let PocklingtonCertificate { prime: n, factors } = certificate;
+    if *n == 2 {
+        return factors.is_empty();
+    }
+    if *n < 3 {
+        return false;
+    }
+
+    let n_minus_1 = Integer::from(n - 1u32);
+    let mut f = Integer::from(1);
+    for (i, factor) in factors.iter().enumerate() {
+        // the primes are distinct, so F doesn't count one twice
+        if factor.exponent == 0 || (i > 0 && factors[i - 1].certificate.prime >= factor.certificate.prime) {
+            return false;
+        }
+        f *= (&factor.certificate.prime).pow(factor.exponent).complete();
+    }
+    if !n_minus_1.is_divisible(&f) || f.square() <= *n {
+        return false;
+    }
+
+    let power = |a: &Integer, e: &Integer| a.pow_mod_ref(e, n).map(Integer::from);
+    factors.iter().all(|PocklingtonFactor { certificate: factor, witness, .. }| {
+        if *witness < 2 || witness >= n || power(witness, &n_minus_1) != Some(Integer::from(1)) {
+            return false;
+        }
+        let Some(partial) = power(witness, &Integer::from(&n_minus_1 / &factor.prime)) else {
+            return false;
+        };
+        (partial - 1u32).gcd(n) == 1 && verify_pocklington(factor)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pocklington() {
+        let m127 = Integer::from(Integer::u_pow_u(2, 127)) - 1u32;
+        let certificate = prove_prime(&m127).unwrap();
+        assert!(certificate.verify());
+        assert_eq!(*certificate.prime(), m127);
+        // 2^127 - 2 = 2 * 3^3 * ... * 92737 * 649657 * 77158673929, and the first 10 primes are above 2^63.5
+        let Certificate::Pocklington(pocklington) = &certificate else { unreachable!() };
+        assert_eq!(pocklington.factors.len(), 10);
+        assert!(certificate.to_string().lines().last().unwrap().ends_with("* 50126672628692353"));
+
+        let mut forged = pocklington.clone();
+        forged.factors[0].witness = Integer::from(1);
+        assert!(!verify_pocklington(&forged));
+        let mut forged = pocklington.clone();
+        forged.factors.pop();
+        assert!(!verify_pocklington(&forged));
+        let mut forged = pocklington.clone();
+        forged.factors[1].exponent += 1;
+        assert!(!verify_pocklington(&forged));
+
+        assert_eq!(prove_prime(&Integer::from(561)), None);
+        assert!(prove_prime(&Integer::from(2)).is_some_and(|certificate| certificate.verify()));
+        // a safe prime, which needs the certificate of (p - 1) / 2
+        let p = Integer::from(1_000_000_000_000_000_000_367u128);
+        assert!(prove_prime(&p).is_some_and(|certificate| certificate.verify()));
+
+        let mut rand = RandState::new();
+        rand.seed(&Integer::from(3785));
+        for bits in [2, 20, 33, 64, 256] {
+            let (p, certificate) = generate_provable_prime(bits, &mut rand);
+            assert_eq!(p.significant_bits(), bits);
+            assert!(certificate.prime == p && verify_pocklington(&certificate), "failed for {} bits", bits);
+        }
+    }
+}
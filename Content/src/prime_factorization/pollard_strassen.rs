@@ -0,0 +1,71 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use rug::{Complete, Integer};
+
+use super::data::STRASSEN_MAX_DEGREE;
+use super::ecm::poly::{evaluate, product_tree};
+use crate::number_theory::is_prime;
+
+/// The smallest prime factor of n up to `bound`, from the polynomial f(X) = (X + 1)(X + 2)...(X + d) of degree
+/// d <= max_degree: f(id) is the product of the block id + 1, ..., id + d, so the first i for which gcd(f(id), n) > 1
+/// holds the factor, which is then found by going through that block. The values f(id) are computed with a
+/// remainder tree, for up to d points at a time.
+fn smallest_factor(n: &Integer, bound: u64, max_degree: u64, cancel: &AtomicBool) -> Option<Integer> {
+    let d = ((bound as f64).sqrt().ceil() as u64).clamp(1, max_degree);
+    let roots: Vec<Integer> = (1..=d).map(|j| Integer::from(n - j)).collect();
+    let f = product_tree(&roots, n).pop()?.pop()?;
+
+    let blocks = bound.div_ceil(d);
+    for start in (0..blocks).step_by(d as usize) {
+        if cancel.load(Ordering::Relaxed) {
+            return None;
+        }
+        let points: Vec<Integer> = (start..blocks.min(start + d)).map(|i| Integer::from(i) * d).collect();
+        let values = evaluate(&f, &product_tree(&points, n), n);
+        if let Some(i) = (start..).zip(&values).find(|(_, value)| value.gcd_ref(n).complete() != 1).map(|(i, _)| i) {
+            // the first k with a common factor with n is its smallest prime factor
+            let first = Integer::from(i) * d + 1;
+            return (0..d).map(|j| Integer::from(&first + j)).find(|k| k.gcd_ref(n).complete() != 1);
+        }
+    }
+    None
+}
+
+/// The Pollard–Strassen method: finds the smallest prime factor of n deterministically, in about n^(1/4) operations
+/// on polynomials modulo n (up to log factors). The numbers up to sqrt(n) are split into blocks of d = n^(1/4), and
+/// the products of the blocks are the values of the polynomial (X + 1)...(X + d) at the multiples of d, which a
+/// remainder tree evaluates all at once (see `ecm::poly`). The degree is capped at `STRASSEN_MAX_DEGREE` to bound
+/// the memory, so above 64 bits the time grows with sqrt(n) again: a few seconds at 64 bits, but hours at 90.
+/// `prime_factorize` only runs it on the composites of up to `STRASSEN_MAX_BITS` bits that every other method failed
+/// to split, as a last resort that always terminates.
+///
+/// # Returns
+/// * `Some(p)` - The smallest prime factor of n.
+/// * `None` - n is smaller than 4 or prime, or the search was cancelled (checked between groups of blocks).
+pub fn pollard_strassen(n: &Integer, cancel: &AtomicBool) -> Option<Integer> {
+    if *n < 4 || is_prime(n) {
+        return None;
+    }
+    if n.is_even() {
+        return Some(Integer::from(2));
+    }
+    let bound = n.sqrt_ref().complete().to_u64().unwrap_or(u64::MAX);
+    smallest_factor(n, bound, STRASSEN_MAX_DEGREE, cancel)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pollard_strassen() {
+        let cancel = AtomicBool::new(false);
+        // the smallest of three factors, and a factor past the first group of blocks with a small degree
+        let n = Integer::from(10_009u64 * 10_037 * 10_007);
+        assert_eq!(pollard_strassen(&n, &cancel), Some(Integer::from(10_007)));
+        let n = Integer::from(1_000_003u64 * 1_000_033);
+        assert_eq!(smallest_factor(&n, 1_000_016, 16, &cancel), Some(Integer::from(1_000_003)));
+        assert_eq!(pollard_strassen(&Integer::from(1_000_000_007), &cancel), None);
+        assert_eq!(pollard_strassen(&n, &AtomicBool::new(true)), None);
+    }
+}
@@ -0,0 +1,144 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use rug::{Assign, Integer};
+
+use super::stage2::lucas_stage2;
+use crate::montgomery_mod_mult::Context;
+
+/// Number of primes between two gcd checks (and checks of the cancel flag).
+const CHUNK: usize = 1024;
+
+/// Stores gcd(a - 1, n) in g, where a is in Montgomery form.
+fn gcd_minus_one(a: &Integer, n: &Integer, ctx: &mut Context, g: &mut Integer) {
+    g.assign(ctx.from_montgomery(a));
+    *g -= 1;
+    g.gcd_mut(n);
+}
+
+/// How stage 1 ended.
+enum Stage1 {
+    /// A non-trivial factor was stored in g.
+    Factor,
+    /// No factor, and a holds 2^E, ready for stage 2.
+    Continue,
+    /// The search was cancelled, or every prime factor of n showed up at once.
+    GaveUp,
+}
+
+/// Stage 1 of Pollard's p − 1 method: computes a = 2^E mod n, where E is the product of the largest powers
+/// of all primes up to B1 that are at most B1, and looks for a factor in gcd(a - 1, n).
+fn stage1(n: &Integer, ctx: &mut Context, primes: &[u32], B1: u32, cancel: &AtomicBool, a: &mut Integer,
+    g: &mut Integer) -> Stage1 {
+    let end = primes.partition_point(|&p| p <= B1);
+    a.assign(ctx.to_montgomery(Integer::from(2)));
+    let mut saved = Integer::new();
+
+    for chunk in primes[..end].chunks(CHUNK) {
+        if cancel.load(Ordering::Relaxed) {
+            return Stage1::GaveUp;
+        }
+        saved.assign(&*a);
+        for &p in chunk {
+            let mut power = p as u64;
+            while power * p as u64 <= B1 as u64 {
+                power *= p as u64;
+            }
+            *a = ctx.pow_mod(a, &Integer::from(power));
+        }
+
+        gcd_minus_one(a, n, ctx, g);
+        if *g == 1 {
+            continue;
+        }
+        if *g != *n {
+            return Stage1::Factor;
+        }
+
+        // every prime factor of n was found in this chunk, redo it one prime at a time to separate them
+        a.assign(&saved);
+        for &p in chunk {
+            let mut power = 1u64;
+            while power * p as u64 <= B1 as u64 {
+                power *= p as u64;
+                *a = ctx.pow_mod(a, &Integer::from(p));
+                gcd_minus_one(a, n, ctx, g);
+                if *g == *n {
+                    return Stage1::GaveUp;  // the factors have the same largest prime power, p - 1 can't tell them apart
+                }
+                if *g != 1 {
+                    return Stage1::Factor;
+                }
+            }
+        }
+        return Stage1::GaveUp;
+    }
+    Stage1::Continue
+}
+
+/// Stage 1 of Pollard's p − 1 method: computes a = 2^E mod n, where E is the product of the largest powers
+/// of all primes up to B1 that are at most B1, and looks for a factor in gcd(a - 1, n).
+/// It finds a prime factor p of n when p - 1 is B1-powersmooth.
+///
+/// ## Arguments
+/// * `n` - The composite number to factorize (must be odd).
+/// * `ctx` - A Context with n as the modulus
+/// * `primes` - The primes up to (at least) B1, in increasing order
+/// * `cancel` - Checked between chunks of primes, the search stops when it is set
+/// ## Returns
+/// * `Some(())` - A non-trivial factor of `n` was stored in `g`.
+/// * `None` - No factor was found, or the search was cancelled.
+pub fn pollard_p_minus_1(n: &Integer, ctx: &mut Context, primes: &[u32], B1: u32, cancel: &AtomicBool,
+    g: &mut Integer) -> Option<()> {
+    let mut a = Integer::new();
+    match stage1(n, ctx, primes, B1, cancel, &mut a, g) {
+        Stage1::Factor => Some(()),
+        _ => None,
+    }
+}
+
+/// Pollard's p − 1 method with the standard stage 2: after stage 1 (see `pollard_p_minus_1`), it also finds a prime
+/// factor p when p - 1 is B1-powersmooth apart from a single prime q in (B1, B2]. The Lucas sequence
+/// V_k = a^k + a^(-k) of a = 2^E shares its stage 2 with Williams' p + 1 (see `stage2::lucas_stage2`):
+/// p divides V_c - V_j when it divides a^(c - j) - 1 or a^(c + j) - 1. `primes` must reach B2.
+///
+/// ## Returns
+/// * `Some(())` - A non-trivial factor of `n` was stored in `g`.
+/// * `None` - No factor was found, or the search was cancelled.
+pub fn pollard_p_minus_1_stage2(n: &Integer, ctx: &mut Context, primes: &[u32], B1: u32, B2: u32, cancel: &AtomicBool,
+    g: &mut Integer) -> Option<()> {
+    let mut a = Integer::new();
+    match stage1(n, ctx, primes, B1, cancel, &mut a, g) {
+        Stage1::Factor => return Some(()),
+        Stage1::GaveUp => return None,
+        Stage1::Continue => {}
+    }
+
+    // a^k + a^(-k) = V_k(a + 1 / a), and a is invertible as a power of 2 modulo an odd n
+    let mut v = a.clone();
+    ctx.invert_mut(&mut v)?;
+    ctx.add_assign(&mut v, &a);
+    lucas_stage2(n, ctx, primes, (B1, B2), &v, cancel, g)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::number_theory::generate_primes;
+
+    #[test]
+    fn test_pollard_p_minus_1() {
+        let primes = generate_primes();
+        // 1000003 - 1 = 2 * 3 * 166667, 998244353 - 1 = 2^23 * 7 * 17
+        let n = Integer::from(1_000_003u64 * 998_244_353);
+        let mut ctx = Context::new(n.clone());
+        let mut g = Integer::new();
+        assert_eq!(pollard_p_minus_1(&n, &mut ctx, &primes, 10_000_000, &AtomicBool::new(false), &mut g), Some(()));
+        assert_eq!(g, 998_244_353);
+        assert_eq!(pollard_p_minus_1(&n, &mut ctx, &primes, 1000, &AtomicBool::new(false), &mut g), None);
+
+        // 1000003 - 1 = 2 * 3 * 166667: stage 1 up to 1000 leaves a single prime 166667 for stage 2
+        assert_eq!(pollard_p_minus_1_stage2(&n, &mut ctx, &primes, 1000, 200_000, &AtomicBool::new(false), &mut g), Some(()));
+        assert_eq!(g, 1_000_003);
+        assert_eq!(pollard_p_minus_1_stage2(&n, &mut ctx, &primes, 1000, 100_000, &AtomicBool::new(false), &mut g), None);
+    }
+}
@@ -1,12 +1,11 @@
-use std::{cell::RefCell, cmp::min};
-use rug::{rand::RandState, Assign, Integer};
+use std::{cell::RefCell, cmp::min, sync::atomic::{AtomicBool, Ordering}, thread};
+use rug::{Assign, Integer};
 
+use super::data::{RHO_BATCH_SIZE, RHO_MAX_ITERATIONS};
+use super::structs::RhoConfig;
 use crate::montgomery_mod_mult::Context;
-
-
-thread_local! {
-    static RAND_STATE: RefCell<RandState<'static>> = RefCell::new(RandState::new());
-}
+use crate::number_theory::is_prime;
+use crate::number_theory::random::{child_seed, random_bits, with_seed};
 
 /// Computes the next value in the sequence: f(y) = (y^2 + 1) mod n.
 fn f(x: &mut Integer, c: &Integer, ctx: &mut Context) {
@@ -33,6 +32,23 @@ impl BufferIntegers {
     }
 }
 
+/// Pollard's rho without the setup: returns a non-trivial factor of n, or None if n is below 4, prime, or if all the
+/// walks of `RhoConfig::default()` (as many as its retries) fail. Even numbers give 2, and n goes through a primality
+/// test first, so that a prime doesn't take the full length of the walks. To factorize many numbers, or with other
+/// settings, `pollard_rho_with` reuses the context and the output.
+pub fn pollard_rho(n: &Integer) -> Option<Integer> {
+    if *n < 4 || is_prime(n) {
+        return None;
+    }
+    if n.is_even() {
+        return Some(Integer::from(2));
+    }
+    let config = RhoConfig::default();
+    let mut ctx = Context::new(n.clone());
+    let mut g = Integer::new();
+    (0..config.retries.max(1)).any(|_| pollard_rho_with(n, &mut ctx, &config, &mut g).is_some()).then_some(g)
+}
+
 /// Implements Pollard's Rho algorithm with Brent's cycle detection for integer factorization.
 ///
 /// ## Arguments
@@ -45,23 +61,35 @@ impl BufferIntegers {
 /// ## Notes
 /// you need to provide it all the variables 
 pub fn pollard_rho_brent(n: &Integer, ctx: &mut Context, g: &mut Integer) -> Option<()> {
+    let config = RhoConfig { max_iterations: RHO_MAX_ITERATIONS, batch_size: RHO_BATCH_SIZE, retries: 1, poly_constant: None };
+    pollard_rho_with(n, ctx, &config, g)
+}
+
+/// Same as `pollard_rho_brent`, with the length, batch size and constant of the walk taken from config (its retries
+/// are left to the caller).
+pub fn pollard_rho_with(n: &Integer, ctx: &mut Context, config: &RhoConfig, g: &mut Integer) -> Option<()> {
+    let c = config.poly_constant.map_or_else(|| random_bits(10), Integer::from);
+    pollard_rho_walk(n, ctx, &c, config, &AtomicBool::new(false), g)
+}
+
+/// One walk of `pollard_rho_brent` with the sequence f(y) = y^2 + c from a random start, which stops (with None) at
+/// the next gcd once `cancel` is set. The factor is stored in g, and ctx must have n as the modulus.
+pub fn pollard_rho_walk(n: &Integer, ctx: &mut Context, c_: &Integer, config: &RhoConfig, cancel: &AtomicBool,
+    g: &mut Integer) -> Option<()> {
     // println!("running pollard. n: {}", n);
     g.assign(0);
 
     BufferIntegers::get_mut(|x, y, ys, c, t| {
         
-        RAND_STATE.with(|rand_state| {
-            let mut rng = rand_state.borrow_mut();
-            c.assign(Integer::random_bits(10, &mut *rng));
-            y.assign(Integer::random_bits(10, &mut *rng));
-        });
+        c.assign(c_);
+        y.assign(random_bits(10));
 
         ctx.to_montgomery_mut(c);
         ctx.to_montgomery_mut(y);
 
-        let iterations = 4096;
-        let mut r = 1;
-        for _ in 0..19 {
+        let iterations = config.batch_size;
+        let mut r: u64 = 1;
+        for _ in 0..config.max_iterations {
             x.assign(&*y);
     
             // Advance y by r steps
@@ -71,6 +99,9 @@ pub fn pollard_rho_brent(n: &Integer, ctx: &mut Context, g: &mut Integer) -> Opt
 
             let mut k = 0;
             while k < r && *g < 2 {
+                if cancel.load(Ordering::Relaxed) {
+                    return None;
+                }
                 g.assign(&ctx.r_mod_n);
                 ys.assign(&*y);
 
@@ -124,4 +155,67 @@ pub fn pollard_rho_brent(n: &Integer, ctx: &mut Context, g: &mut Integer) -> Opt
 
         Some(())
     })
-}
\ No newline at end of file
+}
+
+/// Runs `walks` walks of `pollard_rho_walk` at the same time, each on its own thread with its own constant c (the
+/// constant of config plus the index of the walk, or random ones), and
+/// stores in g the first non-trivial factor found; the other walks stop at their next gcd. A walk finds a factor p
+/// after about sqrt(p) steps, and the first of k independent walks after about sqrt(p / k), which brings the speedup
+/// close to sqrt(k) for the 30 to 45 bit factors that a single walk takes long to find. n should be odd and composite.
+///
+/// ## Returns
+/// * `Some(())` - A non-trivial factor of n was stored in g.
+/// * `None` - None of the walks found one.
+pub fn pollard_rho_parallel(n: &Integer, walks: usize, config: &RhoConfig, g: &mut Integer) -> Option<()> {
+    let cancel = AtomicBool::new(false);
+    // distinct constants, away from c = 0 and c = -2 whose sequences don't look random
+    let base = config.poly_constant.map_or_else(|| random_bits(16) + 1u32, Integer::from);
+
+    let found = thread::scope(|scope| {
+        let handles: Vec<_> = (0..walks)
+            .map(|i| {
+                let (c, cancel, seed) = (Integer::from(&base + i), &cancel, child_seed());
+                scope.spawn(move || with_seed(seed, || {
+                    let mut ctx = Context::new(n.clone());
+                    let mut factor = Integer::new();
+                    pollard_rho_walk(n, &mut ctx, &c, config, cancel, &mut factor)?;
+                    // swap returns the previous value, so exactly one walk claims the factor
+                    (!cancel.swap(true, Ordering::Relaxed)).then_some(factor)
+                }))
+            })
+            .collect();
+        handles.into_iter()
+            .filter_map(|handle| handle.join().unwrap())
+            .next()
+    })?;
+    g.assign(found);
+    Some(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pollard_rho_parallel() {
+        // two 35 bit primes
+        let (p, q) = (Integer::from(17_179_869_209u64), Integer::from(17_179_870_223u64));
+        let n = Integer::from(&p * &q);
+        let mut g = Integer::new();
+        let config = RhoConfig::default();
+        assert!(pollard_rho_parallel(&n, 4, &config, &mut g).is_some());
+        assert!(g == p || g == q);
+
+        // a cancelled walk gives up at its first gcd
+        let mut ctx = Context::new(n.clone());
+        assert!(pollard_rho_walk(&n, &mut ctx, &Integer::from(1), &config, &AtomicBool::new(true), &mut g).is_none());
+        assert!(pollard_rho_parallel(&n, 0, &config, &mut g).is_none());
+
+        // the wrapper sets everything up itself
+        let factor = pollard_rho(&n).unwrap();
+        assert!(factor == p || factor == q);
+        assert_eq!(pollard_rho(&Integer::from(&p * 2u32)), Some(Integer::from(2)));
+        assert!(pollard_rho(&Integer::from(&p * &p)).is_some_and(|factor| factor == p));
+        assert!([Integer::from(3), p.clone()].iter().all(|n| pollard_rho(n).is_none()));
+    }
+}
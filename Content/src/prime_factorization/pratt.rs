@@ -0,0 +1,127 @@
+use std::collections::HashMap;
+
+use rug::{ops::Pow, Integer};
+
+use super::prime_factorize;
+use super::structs::PrattCertificate;
+use crate::montgomery_mod_mult::Context;
+use crate::number_theory::is_prime;
+use crate::number_theory::residues::pow_mod;
+
+/// Builds a Pratt certificate for p, factoring p - 1 with `prime_factorize` and finding an element of order p - 1
+/// by checking a^((p - 1) / q) != 1 for every prime q dividing p - 1.
+///
+/// # Returns
+/// * `Some(certificate)` - The certificate, which `verify_pratt` accepts.
+/// * `None` - p is not prime, or p - 1 (or one of the numbers below it in the tree) could not be fully factorized.
+pub fn pratt_certificate(p: &Integer) -> Option<PrattCertificate> {
+    certify(p, &mut HashMap::new())
+}
+
+/// The same as `pratt_certificate`, sharing the certificates of primes that appear more than once in the tree.
+fn certify(p: &Integer, known: &mut HashMap<Integer, PrattCertificate>) -> Option<PrattCertificate> {
+    if let Some(certificate) = known.get(p) {
+        return Some(certificate.clone());
+    }
+    if *p == 2 {
+        return Some(PrattCertificate { prime: p.clone(), witness: Integer::from(1), factors: Vec::new() });
+    }
+    if !is_prime(p) {
+        return None;
+    }
+
+    let p_minus_1 = Integer::from(p - 1u32);
+    let factorization = prime_factorize(&p_minus_1);
+    // without the ecm feature prime_factorize can leave out a large factor, which is fine if it is a prime
+    let rest = &p_minus_1 / factorization.value();
+    let mut primes = factorization.into_factors();
+    if rest > 1 {
+        if !is_prime(&rest) {
+            return None;
+        }
+        primes.push((rest, 1));
+        primes.sort();
+    }
+
+    let mut ctx = Context::new(p.clone());
+    let exponents: Vec<Integer> = primes.iter().map(|(q, _)| Integer::from(&p_minus_1 / q)).collect();
+    let mut witness = Integer::from(2);
+    while exponents.iter().any(|e| pow_mod(&witness, e, &mut ctx) == 1) {
+        witness += 1;
+    }
+
+    let mut factors = Vec::with_capacity(primes.len());
+    for (q, e) in primes {
+        factors.push((certify(&q, known)?, e));
+    }
+    let certificate = PrattCertificate { prime: p.clone(), witness, factors };
+    known.insert(p.clone(), certificate.clone());
+    Some(certificate)
+}
+
+/// Checks a Pratt certificate, along with the certificates of all the primes in it. Only plain big integer
+/// arithmetic is used, so the check doesn't depend on the code that built the certificate.
+pub fn verify_pratt(certificate: &PrattCertificate) -> bool {
+    let PrattCertificate { prime: p, witness, factors } = certificate;
+    if *p < 2 || *witness < 1 || witness >= p {
+        return false;
+    }
+
+    let p_minus_1 = Integer::from(p - 1u32);
+    let mut product = Integer::from(1);
+    for (factor, e) in factors {
+        if *e == 0 {
+            return false;
+        }
+        product *= Integer::from((&factor.prime).pow(*e));
+    }
+    if product != p_minus_1 {
+        return false;
+    }
+
+    let is_one = |e: &Integer| witness.clone().pow_mod(e, p).unwrap() == 1;
+    is_one(&p_minus_1)
+        && factors.iter().all(|(factor, _)| !is_one(&Integer::from(&p_minus_1 / &factor.prime)))
+        && factors.iter().all(|(factor, _)| verify_pratt(factor))
+}
+
+/// `prime_factorize` with a Pratt certificate for every prime factor, in the same (increasing) order.
+///
+/// # Returns
+/// * `Some(factors)` - The factorization of n, where every prime comes with its certificate.
+/// * `None` - A prime factor could not be certified, because a p - 1 in its tree could not be fully factorized.
+pub fn prime_factorize_certified(n: &Integer) -> Option<Vec<(PrattCertificate, u32)>> {
+    let mut known = HashMap::new();
+    prime_factorize(n).into_iter()
+        .map(|(p, e)| Some((certify(&p, &mut known)?, e)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pratt() {
+        let m127 = Integer::from(Integer::u_pow_u(2, 127)) - 1u32;
+        let certificate = pratt_certificate(&m127).unwrap();
+        assert!(verify_pratt(&certificate));
+        // 2^127 - 2 = 2 * 3^3 * 7^2 * 19 * 43 * 73 * 127 * 337 * 5419 * 92737 * 649657 * 77158673929
+        assert_eq!(certificate.factors.len(), 12);
+        assert_eq!(certificate.to_string().lines().next(), Some("2: witness 1"));
+
+        let mut forged = certificate.clone();
+        forged.witness = Integer::from(1);
+        assert!(!verify_pratt(&forged));
+        let mut forged = certificate.clone();
+        forged.factors.pop();
+        assert!(!verify_pratt(&forged));
+
+        assert_eq!(pratt_certificate(&Integer::from(561)), None);
+        let n = Integer::from(2 * 2 * 3) * &m127;
+        let factors = prime_factorize_certified(&n).unwrap();
+        assert_eq!(factors.iter().map(|(c, e)| (c.prime.clone(), *e)).collect::<Vec<_>>(),
+            vec![(Integer::from(2), 2), (Integer::from(3), 1), (m127, 1)]);
+        assert!(factors.iter().all(|(c, _)| verify_pratt(c)));
+    }
+}
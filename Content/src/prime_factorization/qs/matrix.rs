@@ -0,0 +1,63 @@
+/// The subsets of the rows that sum to zero over GF(2), where every row lists the columns (below `columns`) of its
+/// ones. Gaussian elimination on bit rows, each extended with the identity so that it remembers which of the original
+/// rows it is the sum of: every column is cleared from the rows that are left with the first of them that has it,
+/// which is then set aside, and the rows that end up empty are the dependencies.
+/// This is dense elimination, cubic in the number of rows, which is fine up to the ~25000 relations of the largest
+/// factor bases.
+pub(super) fn dependencies(rows: &[Vec<usize>], columns: usize) -> Vec<Vec<usize>> {
+    let words = columns.div_ceil(64);
+    let width = words + rows.len().div_ceil(64);
+    let mut matrix: Vec<Vec<u64>> = rows.iter().enumerate().map(|(i, row)| {
+        let mut bits = vec![0u64; width];
+        for &column in row {
+            bits[column / 64] ^= 1 << (column % 64);
+        }
+        bits[words + i / 64] |= 1 << (i % 64);
+        bits
+    }).collect();
+
+    // the rows that have not been used as a pivot yet
+    let mut left: Vec<usize> = (0..rows.len()).collect();
+    for column in 0..columns {
+        let (word, bit) = (column / 64, 1u64 << (column % 64));
+        let Some(position) = left.iter().position(|&i| matrix[i][word] & bit != 0) else {
+            continue;
+        };
+        let pivot_row = left.swap_remove(position);
+        let pivot = std::mem::take(&mut matrix[pivot_row]);
+        for &i in &left {
+            if matrix[i][word] & bit != 0 {
+                // the columns before this one are already clear in both rows
+                for (a, b) in matrix[i][word..].iter_mut().zip(&pivot[word..]) {
+                    *a ^= b;
+                }
+            }
+        }
+    }
+
+    left.iter()
+        .map(|&i| (0..rows.len()).filter(|&j| matrix[i][words + j / 64] >> (j % 64) & 1 == 1).collect())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dependencies() {
+        // rows 0 + 1 + 2 and rows 3 + 4 sum to zero, row 5 is on its own
+        let rows = vec![vec![0, 1], vec![1, 2], vec![0, 2], vec![3, 70], vec![3, 70], vec![4]];
+        let found = dependencies(&rows, 71);
+        assert_eq!(found.len(), 2);
+        for dependency in &found {
+            let mut parity = vec![0; 71];
+            for &i in dependency {
+                for &column in &rows[i] {
+                    parity[column] ^= 1;
+                }
+            }
+            assert!(!dependency.is_empty() && parity.iter().all(|&bit| bit == 0));
+        }
+    }
+}
@@ -0,0 +1,463 @@
+use std::{collections::HashSet, sync::atomic::{AtomicBool, Ordering}};
+
+use rug::Integer;
+
+use super::data::{QS_DOUBLE_LARGE_PRIME_DIGITS, QS_DOUBLE_LARGE_PRIME_TOLERANCE, QS_EXTRA_RELATIONS,
+    QS_LARGE_PRIME_MULTIPLIER, QS_MULTIPLIERS, QS_PARAMETERS, QS_SIEVE_START, QS_TOLERANCE};
+use super::{small::pollard_rho_u64, squfof::squfof};
+use crate::number_theory::{iroot_rem, is_prime, is_prime_u64, random::random_below_u64, stored_primes};
+use crate::number_theory::word::pow_mod;
+use partial::Partials;
+
+mod matrix;
+mod partial;
+
+/// A prime of the factor base, with a square root of kN modulo it and its log2 rounded to the nearest integer.
+struct FactorBasePrime {
+    p: u32,
+    sqrt: u32,
+    log: u8,
+}
+
+/// A relation (Ax + B)^2 = A g(x) mod n: y = Ax + B, and the indices of the primes of A g(x) in the factor base,
+/// with repetitions, where -1 comes first and the factor base starts at index 1.
+/// The primes of A g(x) above the factor base are the large primes: none for a full relation, one or two for a partial
+/// relation, and for a relation combined from a cycle of partial relations (see `partial`), every one of them an
+/// even number of times, with y the product of theirs.
+struct Relation {
+    y: Integer,
+    factors: Vec<usize>,
+    large_primes: Vec<u64>,
+}
+
+/// The inverse of a modulo the prime p, for a not divisible by p.
+fn inverse_mod(a: u64, p: u64) -> u64 {
+    pow_mod(a, p - 2, p)
+}
+
+/// A square root of the quadratic residue a modulo the odd prime p (Tonelli–Shanks), with small numbers.
+fn sqrt_mod(a: u64, p: u64) -> u64 {
+    let a = a % p;
+    if a == 0 {
+        return 0;
+    }
+    let s = (p - 1).trailing_zeros();
+    let q = (p - 1) >> s;
+    let z = (2..p).find(|&z| pow_mod(z, (p - 1) / 2, p) == p - 1).unwrap();
+    let (mut m, mut c, mut t) = (s, pow_mod(z, q, p), pow_mod(a, q, p));
+    let mut root = pow_mod(a, q.div_ceil(2), p);
+    while t != 1 {
+        // the least i with t^(2^i) = 1
+        let (mut i, mut t_power) = (0, t);
+        while t_power != 1 {
+            t_power = t_power * t_power % p;
+            i += 1;
+        }
+        let b = pow_mod(c, 1 << (m - i - 1), p);
+        root = root * b % p;
+        c = b * b % p;
+        t = t * c % p;
+        m = i;
+    }
+    root
+}
+
+/// The multiplier k of Knuth and Schroeppel's function among `QS_MULTIPLIERS`: the one for which the small primes
+/// divide the values of the polynomials of kN the most, which is worth about ln(p) * 2 / (p - 1) for a prime p that
+/// kN is a square modulo, and ln(p) / p for a p dividing k, against the ln(k) / 2 of the larger values.
+fn choose_multiplier(n: &Integer, primes: &[u32]) -> u32 {
+    let score = |k: u32| {
+        let kn = Integer::from(n * k);
+        let mut score = -0.5 * (k as f64).ln() + match kn.mod_u(8) {
+            1 => 2.0,
+            5 => 1.0,
+            _ => 0.5,
+        } * 2f64.ln();
+        for &p in primes.iter().skip(1).take(300) {
+            let logp = (p as f64).ln();
+            if k.is_multiple_of(p) {
+                score += logp / p as f64;
+            } else if pow_mod(kn.mod_u(p) as u64, (p as u64 - 1) / 2, p as u64) == 1 {
+                score += 2.0 * logp / (p - 1) as f64;
+            }
+        }
+        score
+    };
+    QS_MULTIPLIERS.iter().copied().max_by(|&a, &b| score(a).total_cmp(&score(b))).unwrap()
+}
+
+/// The number of decimal digits of n, rounded up.
+fn digits(n: &Integer) -> u32 {
+    (n.significant_bits() as f64 * std::f64::consts::LOG10_2).ceil() as u32
+}
+
+/// The size of the factor base and the half width M of the sieve interval [-M, M) for n.
+fn parameters(n: &Integer) -> (usize, usize) {
+    let digits = digits(n);
+    let &(_, size, m) = QS_PARAMETERS.iter().find(|(d, _, _)| digits <= *d).unwrap_or(QS_PARAMETERS.last().unwrap());
+    (size, m)
+}
+
+/// The state of the sieve for one value of A: the primes q_l of A, the B_l whose sums ±B_0 ± ... ± B_(s-1) give
+/// the values of B, and for every prime of the factor base the inverse of A and 2 B_l / A modulo it.
+struct Polynomials {
+    a: Integer,
+    /// The indices of the primes of A in the factor base.
+    a_primes: Vec<usize>,
+    b_parts: Vec<Integer>,
+    b: Integer,
+    /// The sign of every B_l in b.
+    signs: Vec<bool>,
+    /// 2 B_l / A mod p, for every l and every prime p of the factor base.
+    b_inverses: Vec<Vec<u32>>,
+    /// The positions in the sieve interval where p divides g(x), u32::MAX for the primes of A.
+    roots: Vec<(u32, u32)>,
+}
+
+impl Polynomials {
+    /// The polynomials (Ax + B)^2 - kN = A g(x) for a new A, close to sqrt(2kN) / M so that g(x) stays small over
+    /// [-M, M). A is the product of s primes of the factor base: s - 1 of them are drawn at random around the size
+    /// they should have, and the last one brings the product as close to the target as possible. The B_l are the
+    /// square roots of kN modulo q_l lifted to 0 mod the other primes, so that B^2 = kN mod A for every choice of
+    /// the signs. None if no new A could be found.
+    fn new(kn: &Integer, m: usize, base: &[FactorBasePrime], k: u32, used: &mut HashSet<Integer>) -> Option<Self> {
+        let target = Integer::from(kn * 2u32).sqrt() / m as u64;
+        let target_bits = target.significant_bits() as f64;
+        // the primes of A are taken around 2^11, or smaller when the factor base doesn't go that far
+        let largest = base.last().unwrap().p as f64;
+        let s = ((target_bits / 11f64.min(largest.log2() - 1.0)).round() as usize).max(1);
+        let size = 2f64.powf(target_bits / s as f64);
+        let candidates: Vec<usize> = (1..base.len())
+            .filter(|&i| !k.is_multiple_of(base[i].p) && (size / 2.0..size * 2.0).contains(&(base[i].p as f64)))
+            .collect();
+        if candidates.len() < s + 2 {
+            return None;
+        }
+
+        for _ in 0..100 {
+            let mut a_primes: Vec<usize> = Vec::with_capacity(s);
+            while a_primes.len() < s - 1 {
+                let i = candidates[random_below_u64(candidates.len() as u64) as usize];
+                if !a_primes.contains(&i) {
+                    a_primes.push(i);
+                }
+            }
+            let product = a_primes.iter().fold(Integer::from(1), |product, &i| product * base[i].p);
+            let last = Integer::from(&target / &product).to_f64();
+            let Some(&closest) = candidates.iter().filter(|i| !a_primes.contains(i))
+                .min_by(|&&i, &&j| (base[i].p as f64 - last).abs().total_cmp(&(base[j].p as f64 - last).abs())) else {
+                continue;
+            };
+            a_primes.push(closest);
+            let a = product * base[closest].p;
+            if used.insert(a.clone()) {
+                return Some(Self::with_a(kn, m, base, a, a_primes));
+            }
+        }
+        None
+    }
+
+    fn with_a(kn: &Integer, m: usize, base: &[FactorBasePrime], a: Integer, a_primes: Vec<usize>) -> Self {
+        let b_parts: Vec<Integer> = a_primes.iter().map(|&i| {
+            let q = base[i].p as u64;
+            let cofactor = Integer::from(&a / q as u32);
+            let mut gamma = base[i].sqrt as u64 * inverse_mod(cofactor.mod_u(q as u32) as u64, q) % q;
+            if gamma > q / 2 {
+                gamma = q - gamma;
+            }
+            cofactor * gamma
+        }).collect();
+        let b: Integer = b_parts.iter().sum();
+        debug_assert!(Integer::from(&b * &b - kn).is_divisible(&a));
+
+        let mut roots = vec![(u32::MAX, u32::MAX); base.len()];
+        let mut b_inverses = vec![vec![0; base.len()]; b_parts.len()];
+        for (i, prime) in base.iter().enumerate().skip(1) {
+            let p = prime.p as u64;
+            let a_mod = a.mod_u(prime.p) as u64;
+            if a_mod == 0 {
+                continue;
+            }
+            let a_inverse = inverse_mod(a_mod, p);
+            for (l, part) in b_parts.iter().enumerate() {
+                b_inverses[l][i] = (2 * part.mod_u(prime.p) as u64 * a_inverse % p) as u32;
+            }
+            // the roots of g are (±sqrt(kN) - B) / A, shifted by M to positions in the sieve interval
+            let (b_mod, t, shift) = (b.mod_u(prime.p) as u64, prime.sqrt as u64, m as u64 % p);
+            let root1 = (a_inverse * ((t + p - b_mod) % p) + shift) % p;
+            let root2 = (a_inverse * ((2 * p - t - b_mod) % p) + shift) % p;
+            roots[i] = (root1 as u32, root2 as u32);
+        }
+        let signs = vec![true; b_parts.len()];
+        Polynomials { a, a_primes, b_parts, b, signs, b_inverses, roots }
+    }
+
+    /// The number of values of B for this A: the sign of the last B_l only flips the sign of x.
+    fn count(&self) -> usize {
+        1 << (self.b_parts.len() - 1)
+    }
+
+    /// Switches to the i-th value of B (for i from 1 to `count() - 1`), which differs from the previous one in the
+    /// sign of a single B_l (a Gray code), so the roots only move by ±2 B_l / A.
+    fn next(&mut self, i: usize, base: &[FactorBasePrime]) {
+        let l = i.trailing_zeros() as usize;
+        self.signs[l] = !self.signs[l];
+        match self.signs[l] {
+            true => self.b += Integer::from(&self.b_parts[l] * 2u32),
+            false => self.b -= Integer::from(&self.b_parts[l] * 2u32),
+        }
+        for (j, prime) in base.iter().enumerate().skip(1) {
+            let (root1, root2) = &mut self.roots[j];
+            if *root1 == u32::MAX {
+                continue;
+            }
+            let (p, delta) = (prime.p, self.b_inverses[l][j]);
+            let shift = if self.signs[l] { p - delta } else { delta };
+            *root1 = ((*root1 as u64 + shift as u64) % p as u64) as u32;
+            *root2 = ((*root2 as u64 + shift as u64) % p as u64) as u32;
+        }
+    }
+}
+
+/// The large primes of the cofactor left after dividing a value by the factor base, if the relation is worth
+/// keeping: none for 1, a prime below `bound`, or with `double`, two primes below it. Composite cofactors are split
+/// with SQUFOF, or Pollard's rho in the rare cases where it fails.
+fn large_primes(cofactor: &Integer, bound: u64, double: bool) -> Option<Vec<u64>> {
+    let r = cofactor.to_u64()?;
+    if r == 1 {
+        return Some(vec![]);
+    }
+    if is_prime_u64(r) {
+        return (r < bound).then(|| vec![r]);
+    }
+    if !double || r / bound >= bound {
+        return None;
+    }
+    let p = squfof(r).or_else(|| pollard_rho_u64(r))?;
+    let (p, q) = (p.min(r / p), p.max(r / p));
+    (q < bound && is_prime_u64(p) && is_prime_u64(q)).then(|| vec![p, q])
+}
+
+/// Sieves the polynomial g(x) = ((Ax + B)^2 - kN) / A over [-M, M) and pushes the relations of the values whose
+/// sieved logs reach the threshold and that split over the factor base, apart from large primes below `large_bound`
+/// (see `large_primes`).
+fn sieve(kn: &Integer, m: usize, base: &[FactorBasePrime], polynomials: &Polynomials,
+    (threshold, large_bound, double): (u8, u64, bool), sieve: &mut [u8], relations: &mut Vec<Relation>) {
+    sieve.fill(0);
+    for (prime, &(root1, root2)) in base.iter().zip(&polynomials.roots).skip(QS_SIEVE_START) {
+        if root1 == u32::MAX {
+            continue;
+        }
+        let p = prime.p as usize;
+        for root in [root1, root2].into_iter().take(if root1 == root2 { 1 } else { 2 }) {
+            for value in sieve.iter_mut().skip(root as usize).step_by(p) {
+                *value = value.wrapping_add(prime.log);
+            }
+        }
+    }
+
+    let Polynomials { a, a_primes, b, roots, .. } = polynomials;
+    let c = Integer::from(b * b - kn) / a;
+    let two_b = Integer::from(b * 2u32);
+    for (i, _) in sieve.iter().enumerate().filter(|&(_, &value)| value >= threshold) {
+        let x = i as i64 - m as i64;
+        // g(x) = (Ax + 2B) x + C
+        let mut value = Integer::from(a * x) + &two_b;
+        value *= x;
+        value += &c;
+        let mut factors: Vec<usize> = a_primes.iter().map(|&j| j + 1).collect();
+        if value < 0 {
+            factors.push(0);
+            value = -value;
+        }
+        if value == 0 {
+            continue;
+        }
+        let twos = value.find_one(0).unwrap();
+        value >>= twos;
+        factors.extend(std::iter::repeat_n(1, twos as usize));
+        for (j, (prime, &(root1, root2))) in base.iter().zip(roots).enumerate().skip(1) {
+            let position = (i % prime.p as usize) as u32;
+            if root1 != u32::MAX && position != root1 && position != root2 {
+                continue;
+            }
+            while value.is_divisible_u(prime.p) {
+                value.div_exact_u_mut(prime.p);
+                factors.push(j + 1);
+            }
+        }
+        if let Some(large_primes) = large_primes(&value, large_bound, double) {
+            relations.push(Relation { y: Integer::from(a * x) + b, factors, large_primes });
+        }
+    }
+}
+
+/// A factor of n from the dependencies among the relations: the product of the y over a dependency is x, and its
+/// right-hand sides multiply to a square y^2 with y from half the exponents, so x^2 = y^2 mod n and gcd(x - y, n)
+/// is a proper factor for about half of the dependencies.
+fn combine(n: &Integer, base: &[FactorBasePrime], relations: &[Relation]) -> Option<Integer> {
+    let columns = base.len() + 1;
+    let rows: Vec<Vec<usize>> = relations.iter().map(|relation| {
+        let mut odd = relation.factors.clone();
+        odd.sort_unstable();
+        let mut row = Vec::with_capacity(odd.len());
+        for column in odd {
+            if row.last() == Some(&column) {
+                row.pop();
+            } else {
+                row.push(column);
+            }
+        }
+        row
+    }).collect();
+
+    for dependency in matrix::dependencies(&rows, columns) {
+        let (mut x, mut y) = (Integer::from(1), Integer::from(1));
+        let mut exponents = vec![0u32; columns];
+        for &i in &dependency {
+            x = x * &relations[i].y % n;
+            for &column in &relations[i].factors {
+                exponents[column] += 1;
+            }
+            // the large primes of a relation come in pairs
+            let mut large_primes = relations[i].large_primes.clone();
+            large_primes.sort_unstable();
+            for &q in large_primes.iter().step_by(2) {
+                y = y * q % n;
+            }
+        }
+        for (column, &exponent) in exponents.iter().enumerate().skip(1) {
+            if exponent > 0 {
+                let power = Integer::from(base[column - 1].p).pow_mod(&Integer::from(exponent / 2), n).unwrap();
+                y = y * power % n;
+            }
+        }
+        let g = (x - y).gcd(n);
+        if g != 1 && g != *n {
+            return Some(g);
+        }
+    }
+    None
+}
+
+/// The self-initializing quadratic sieve (SIQS): finds a proper factor of n by collecting relations
+/// (Ax + B)^2 = A g(x) mod kN whose A g(x) splits over a factor base of small primes (the ones kN is a square modulo,
+/// for a multiplier k chosen by `choose_multiplier`), then combining them into a congruence of squares x^2 = y^2
+/// mod n with linear algebra over GF(2). Each A serves 2^(s-1) polynomials, whose roots modulo the factor base are
+/// updated from one to the next with additions only. The sizes come from `QS_PARAMETERS`.
+/// Values that split apart from one large prime (or two, from `QS_DOUBLE_LARGE_PRIME_DIGITS` digits on) are kept as
+/// partial relations, and the cycles of their graph (see `partial::Partials`) give full relations, which makes the
+/// sieving about twice as fast from 60 digits on.
+/// Unlike ECM, its running time depends only on the size of n, not on the size of its factors, so it is the method
+/// for composites with two large factors of about the same size. It is practical up to about 80 digits.
+///
+/// ## Returns
+/// * `Some(factor)` - A proper factor of n (a root of n if it is a perfect power, 2 if it is even).
+/// * `None` - n is 1 or prime, or the search was cancelled (checked between polynomials).
+pub fn siqs(n: &Integer, cancel: &AtomicBool) -> Option<Integer> {
+    if *n <= 3 || is_prime(n) {
+        return None;
+    }
+    if n.is_even() {
+        return Some(Integer::from(2));
+    }
+    if n.is_perfect_power() {
+        // the smallest root, from the largest exponent
+        return (2..n.significant_bits()).rev().find_map(|k| iroot_rem(n, k).exact_root());
+    }
+
+    let primes = stored_primes();
+    let k = choose_multiplier(n, primes);
+    if let Some(&p) = primes[..64].iter().find(|&&p| n.is_divisible_u(p)) {
+        return Some(Integer::from(p));
+    }
+    let kn = Integer::from(n * k);
+    let (size, m) = parameters(n);
+
+    // 2, then the odd primes that kN is a square modulo, including the ones dividing k
+    let mut base = vec![FactorBasePrime { p: 2, sqrt: 1, log: 1 }];
+    for &p in &primes[1..] {
+        if base.len() == size {
+            break;
+        }
+        let residue = kn.mod_u(p) as u64;
+        if residue == 0 && n.is_divisible_u(p) {
+            return Some(Integer::from(p));
+        }
+        if residue == 0 || pow_mod(residue, (p as u64 - 1) / 2, p as u64) == 1 {
+            let (sqrt, log) = (sqrt_mod(residue, p as u64) as u32, (p as f64).log2().round() as u8);
+            base.push(FactorBasePrime { p, sqrt, log });
+        }
+    }
+
+    // |g(x)| goes up to about M sqrt(kN / 2), a value is tried when the logs of its sieved primes come within
+    // QS_TOLERANCE * log2(largest prime) of that, plus the bits a single large prime has above the largest prime
+    // (and more with double large primes)
+    let largest = base.last().unwrap().p as f64;
+    let bits = (m as f64).log2() + (kn.significant_bits() as f64 - 1.0) / 2.0;
+    let large_bound = largest as u64 * QS_LARGE_PRIME_MULTIPLIER;
+    let double = digits(n) >= QS_DOUBLE_LARGE_PRIME_DIGITS;
+    let tolerance = if double { QS_TOLERANCE + QS_DOUBLE_LARGE_PRIME_TOLERANCE } else { QS_TOLERANCE };
+    let slack = tolerance * largest.log2() + (QS_LARGE_PRIME_MULTIPLIER as f64).log2();
+    let threshold = (bits - slack).max(1.0) as u8;
+
+    let wanted = base.len() + 1 + QS_EXTRA_RELATIONS;
+    let mut relations: Vec<Relation> = Vec::with_capacity(wanted);
+    let mut partials = Partials::new();
+    let mut seen: HashSet<Integer> = HashSet::new();
+    let mut used: HashSet<Integer> = HashSet::new();
+    let mut values = vec![0u8; 2 * m];
+    let mut found: Vec<Relation> = Vec::new();
+    loop {
+        let mut polynomials = Polynomials::new(&kn, m, &base, k, &mut used)?;
+        for i in 0..polynomials.count() {
+            if cancel.load(Ordering::Relaxed) {
+                return None;
+            }
+            if i > 0 {
+                polynomials.next(i, &base);
+            }
+            sieve(&kn, m, &base, &polynomials, (threshold, large_bound, double), &mut values, &mut found);
+            // the same y can come from two values of A, and would only give a trivial dependency
+            for relation in found.drain(..).filter(|relation| seen.insert(relation.y.clone())) {
+                match relation.large_primes.is_empty() {
+                    true => relations.push(relation),
+                    false => partials.insert(relation),
+                }
+            }
+        }
+        if relations.len() + partials.cycles < wanted {
+            continue;
+        }
+        let full = relations.len();
+        relations.extend(partials.cycle_relations(n));
+        if let Some(factor) = combine(n, &base, &relations) {
+            return Some(factor);
+        }
+        // every dependency was trivial, which is rare: collect a few more relations
+        relations.truncate(full);
+        relations.drain(..full.min(QS_EXTRA_RELATIONS / 2));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_siqs() {
+        let cancel = AtomicBool::new(false);
+        // semiprimes of 20 to 40 digits with factors of the same size, where ECM needs the most curves
+        for (p, q) in [("10000000019", "10000000033"), ("100000000000000003", "1000000000000000003"),
+            ("10000000000000000051", "100000000000000000039")] {
+            let (p, q): (Integer, Integer) = (p.parse().unwrap(), q.parse().unwrap());
+            let n = Integer::from(&p * &q);
+            let factor = siqs(&n, &cancel).unwrap();
+            assert!(factor == p || factor == q, "failed for n = {}", n);
+        }
+        assert_eq!(siqs(&Integer::from(1_000_000_007), &cancel), None);
+        assert_eq!(siqs(&Integer::from(1_000_003u64 * 1_000_003 * 1_000_003), &cancel), Some(Integer::from(1_000_003)));
+        assert!(siqs(&(Integer::from(10_000_000_019u64) * 10_000_000_033u64), &AtomicBool::new(true)).is_none());
+        assert_eq!(sqrt_mod(10, 13) * sqrt_mod(10, 13) % 13, 10);
+    }
+}
@@ -0,0 +1,136 @@
+use std::collections::{hash_map::Entry, HashMap, VecDeque};
+
+use rug::Integer;
+
+use super::Relation;
+
+/// The partial relations, as the edges of a graph on their large primes, where 1 stands for the missing second prime of
+/// a relation with a single large prime. Along a cycle of the graph every large prime is on two edges, so the product
+/// of the relations of a cycle has all its large primes squared, and can be used as a full relation. A union-find
+/// keeps the number of independent cycles (edges - vertices + components) up to date as the relations come in, and
+/// `cycle_relations` builds them once there are enough.
+pub(super) struct Partials {
+    relations: Vec<Relation>,
+    /// Every vertex points towards the root of its component.
+    parent: HashMap<u64, u64>,
+    pub cycles: usize,
+}
+
+/// The vertices of the edge of a partial relation.
+fn endpoints(relation: &Relation) -> (u64, u64) {
+    match relation.large_primes[..] {
+        [q] => (1, q),
+        [p, q] => (p, q),
+        _ => unreachable!("a partial relation has one or two large primes"),
+    }
+}
+
+impl Partials {
+    pub fn new() -> Self {
+        Partials { relations: Vec::new(), parent: HashMap::new(), cycles: 0 }
+    }
+
+    fn root(&mut self, mut vertex: u64) -> u64 {
+        self.parent.entry(vertex).or_insert(vertex);
+        loop {
+            let parent = self.parent[&vertex];
+            if parent == vertex {
+                return vertex;
+            }
+            // path halving
+            let grandparent = self.parent[&parent];
+            self.parent.insert(vertex, grandparent);
+            vertex = grandparent;
+        }
+    }
+
+    pub fn insert(&mut self, relation: Relation) {
+        let (u, v) = endpoints(&relation);
+        let (root_u, root_v) = (self.root(u), self.root(v));
+        if root_u == root_v {
+            self.cycles += 1;
+        } else {
+            self.parent.insert(root_u, root_v);
+        }
+        self.relations.push(relation);
+    }
+
+    /// The relations of the independent cycles: a breadth-first search gives a spanning forest of the graph, and
+    /// every edge outside of it closes a cycle with the paths from its vertices up to their lowest common ancestor.
+    pub fn cycle_relations(&self, n: &Integer) -> Vec<Relation> {
+        let mut edges: HashMap<u64, Vec<(u64, usize)>> = HashMap::new();
+        for (i, relation) in self.relations.iter().enumerate() {
+            let (u, v) = endpoints(relation);
+            edges.entry(u).or_default().push((v, i));
+            edges.entry(v).or_default().push((u, i));
+        }
+
+        // the parent of every vertex in the forest, the relation of the edge to it, and the depth of the vertex
+        let mut tree: HashMap<u64, (u64, usize, usize)> = HashMap::with_capacity(edges.len());
+        let mut in_tree = vec![false; self.relations.len()];
+        let mut queue = VecDeque::new();
+        for relation in &self.relations {
+            let (start, _) = endpoints(relation);
+            if tree.contains_key(&start) {
+                continue;
+            }
+            tree.insert(start, (start, usize::MAX, 0));
+            queue.push_back(start);
+            while let Some(vertex) = queue.pop_front() {
+                let depth = tree[&vertex].2;
+                for &(next, i) in &edges[&vertex] {
+                    if let Entry::Vacant(entry) = tree.entry(next) {
+                        entry.insert((vertex, i, depth + 1));
+                        in_tree[i] = true;
+                        queue.push_back(next);
+                    }
+                }
+            }
+        }
+
+        self.relations.iter().enumerate().filter(|&(i, _)| !in_tree[i]).map(|(i, relation)| {
+            let (mut u, mut v) = endpoints(relation);
+            let mut cycle = vec![i];
+            while u != v {
+                let vertex = if tree[&u].2 >= tree[&v].2 { &mut u } else { &mut v };
+                let (parent, edge, _) = tree[&*vertex];
+                cycle.push(edge);
+                *vertex = parent;
+            }
+
+            let mut y = Integer::from(1);
+            let (mut factors, mut large_primes) = (Vec::new(), Vec::new());
+            for &j in &cycle {
+                y = y * &self.relations[j].y % n;
+                factors.extend_from_slice(&self.relations[j].factors);
+                large_primes.extend_from_slice(&self.relations[j].large_primes);
+            }
+            Relation { y, factors, large_primes }
+        }).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cycle_relations() {
+        let relation = |y: u32, large_primes: &[u64]| Relation { y: Integer::from(y), factors: vec![y as usize],
+            large_primes: large_primes.to_vec() };
+        let mut partials = Partials::new();
+        // 1 - 11 - 13 - 1 is a triangle, and 17 twice is a cycle of length 2, while 19 - 23 is left alone
+        for (y, large_primes) in [(2, &[11][..]), (3, &[11, 13]), (5, &[19, 23]), (7, &[17]), (4, &[13]), (6, &[17])] {
+            partials.insert(relation(y, large_primes));
+        }
+        assert_eq!(partials.cycles, 2);
+
+        let mut cycles: Vec<(Integer, Vec<u64>)> = partials.cycle_relations(&Integer::from(1000)).into_iter()
+            .map(|mut relation| {
+                relation.large_primes.sort_unstable();
+                (relation.y, relation.large_primes)
+            }).collect();
+        cycles.sort();
+        assert_eq!(cycles, [(Integer::from(24), vec![11, 11, 13, 13]), (Integer::from(42), vec![17, 17])]);
+    }
+}
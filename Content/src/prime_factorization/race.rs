@@ -0,0 +1,140 @@
+use std::{sync::atomic::{AtomicBool, Ordering}, thread};
+
+use rug::Integer;
+
+use super::data::{get_data, RACE_ECM_CURVES, RACE_P_MINUS_1_B1, RACE_RHO_ATTEMPTS};
+use super::ecm::ecm_curves;
+use super::pollards_p_minus_1::pollard_p_minus_1;
+use super::pollards_rho::pollard_rho_brent;
+use super::{prime_factorize, trial_division, Factorization, Factorizer};
+use crate::montgomery_mod_mult::Context;
+use crate::number_theory::random::{child_seed, with_seed};
+use crate::number_theory::{is_prime, isqrt_rem, SPF_LIMIT};
+use crate::tuning::profile;
+
+/// The method that found the factor in `race_find_factor`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RaceWinner {
+    PollardRho,
+    PMinus1,
+    Ecm,
+}
+
+/// Runs Pollard's rho, Pollard's p − 1 and the first ECM curves on n at the same time, each on its own thread.
+/// The first method to find a non-trivial factor sets a shared flag that makes the others stop at their next check.
+/// n should be odd, composite, and free of the small factors that trial division removes.
+///
+/// # Returns
+/// * `Some((factor, winner))` - A non-trivial factor of n, and the method that found it first.
+/// * `None` - None of the methods found a factor within its budget (see the RACE_* constants in data.rs).
+pub fn race_find_factor(n: &Integer) -> Option<(Integer, RaceWinner)> {
+    let data = get_data();
+    let cancel = AtomicBool::new(false);
+
+    // runs one method, and claims the win if it finds a factor before the others
+    let racer = |winner: RaceWinner, method: &(dyn Fn(&mut Integer) -> Option<()> + Sync)| {
+        let mut g = Integer::new();
+        method(&mut g)?;
+        // swap returns the previous value, so exactly one racer sees false
+        if cancel.swap(true, Ordering::Relaxed) {
+            return None;
+        }
+        Some((g, winner))
+    };
+
+    let rho = |g: &mut Integer| {
+        let mut ctx = Context::new(n.clone());
+        for _ in 0..RACE_RHO_ATTEMPTS {
+            if cancel.load(Ordering::Relaxed) {
+                return None;
+            }
+            if pollard_rho_brent(n, &mut ctx, g).is_some() {
+                return Some(());
+            }
+        }
+        None
+    };
+    let p_minus_1 = |g: &mut Integer| {
+        let mut ctx = Context::new(n.clone());
+        pollard_p_minus_1(n, &mut ctx, &data.primes, RACE_P_MINUS_1_B1, &cancel, g)
+    };
+    let ecm = |g: &mut Integer| ecm_curves(n, data, &Factorizer::default_ref().ecm_tables()[0], RACE_ECM_CURVES, &cancel, g);
+
+    // inside a `with_seed`, each racer draws from its own replayable generator
+    let seeds = [child_seed(), child_seed(), child_seed()];
+    thread::scope(|scope| {
+        let handles = [
+            scope.spawn(|| with_seed(seeds[0], || racer(RaceWinner::PollardRho, &rho))),
+            scope.spawn(|| with_seed(seeds[1], || racer(RaceWinner::PMinus1, &p_minus_1))),
+            scope.spawn(|| with_seed(seeds[2], || racer(RaceWinner::Ecm, &ecm))),
+        ];
+        handles.into_iter()
+            .filter_map(|handle| handle.join().unwrap())
+            .next()
+    })
+}
+
+/// Same result as `prime_factorize`, but every composite left after trial division is split with `race_find_factor`.
+/// Numbers that none of the racers can split go through the sequential pipeline of `prime_factorize`.
+pub fn prime_factorize_racing(n_: &Integer) -> Factorization {
+    if *n_ < SPF_LIMIT {
+        return prime_factorize(n_);
+    }
+
+    let mut n = n_.clone();
+    let mut factors: Vec<(Integer, u32)> = Vec::new();
+    let two_exponent = n.remove_factor_mut(&Integer::from(2));
+    if two_exponent > 0 {
+        factors.push((Integer::from(2), two_exponent));
+    }
+    trial_division(&mut n, &mut factors, &get_data().primes, profile().trial_division_bound);
+
+    let mut primes: Vec<Integer> = Vec::new();
+    let mut pending = vec![n.clone()];
+    while let Some(mut m) = pending.pop() {
+        if m == 1 {
+            continue;
+        }
+        while m > 1 && let Some(root) = isqrt_rem(&m).exact_root() {
+            m = root;
+        }
+        if is_prime(&m) {
+            primes.push(m);
+            continue;
+        }
+        match race_find_factor(&m) {
+            Some((factor, _)) => {
+                m.div_exact_mut(&factor);
+                pending.push(m);
+                pending.push(factor);
+            }
+            None => primes.extend(prime_factorize(&m).into_iter().map(|(p, _)| p)),
+        }
+    }
+
+    primes.sort();
+    primes.dedup();
+    for p in primes {
+        let exponent = n.remove_factor_mut(&p);
+        factors.push((p, exponent));
+    }
+    Factorization::new(factors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_race() {
+        // 998244353 - 1 = 2^23 * 7 * 17 is smooth, so p - 1 (or rho) splits it off quickly
+        let n = Integer::from(998_244_353u64) * Integer::from(1_000_000_007u64) * Integer::from(1_000_000_009u64);
+        let (factor, _) = race_find_factor(&n).unwrap();
+        assert!(factor > 1 && factor < n && n.is_divisible(&factor));
+
+        let n = n * Integer::from(2 * 2 * 3 * 10_007);
+        let expected: Vec<(Integer, u32)> = [(2u64, 2), (3, 1), (10_007, 1), (998_244_353, 1), (1_000_000_007, 1), (1_000_000_009, 1)]
+            .iter().map(|&(p, e)| (Integer::from(p), e)).collect();
+        assert_eq!(prime_factorize_racing(&n).into_factors(), expected);
+    }
+}
@@ -0,0 +1,144 @@
+use super::squfof::squfof;
+use crate::number_theory::primality::is_prime_u64;
+use crate::number_theory::word::{gcd, mul_mod};
+
+/// Odd primes that `factorize_u64` divides out before anything else.
+const SMALL_PRIMES: [u64; 24] = [3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83,
+    89, 97];
+/// Steps between two gcds of `pollard_rho_u64`.
+const BATCH: u64 = 128;
+
+/// Pollard's rho with Brent's cycle detection on machine words, for an odd composite n: the walk y -> y^2 + c starts
+/// from 2 with c = 1, and moves on to the next c if it only finds n itself, so the result is always the same.
+///
+/// # Returns
+/// * `Some(d)` - A non-trivial factor of n.
+/// * `None` - n is prime or smaller than 4 (or none of the first 64 constants split it, which does not happen in
+///   practice).
+pub fn pollard_rho_u64(n: u64) -> Option<u64> {
+    if n < 4 || is_prime_u64(n) {
+        return None;
+    }
+    if n.is_multiple_of(2) {
+        return Some(2);
+    }
+    let f = |y: u64, c: u64| ((y as u128 * y as u128 + c as u128) % n as u128) as u64;
+
+    for c in 1..=64 {
+        let (mut x, mut y, mut ys) = (2, 2, 2);
+        let (mut g, mut q, mut r) = (1, 1, 1u64);
+        while g == 1 {
+            x = y;
+            for _ in 0..r {
+                y = f(y, c);
+            }
+            let mut k = 0;
+            while k < r && g == 1 {
+                ys = y;
+                for _ in 0..BATCH.min(r - k) {
+                    y = f(y, c);
+                    q = mul_mod(q, x.abs_diff(y), n);
+                }
+                g = gcd(q, n);
+                k += BATCH;
+            }
+            r <<= 1;
+        }
+        // the batch overshot, go over it again one step at a time
+        if g == n {
+            loop {
+                ys = f(ys, c);
+                g = gcd(x.abs_diff(ys), n);
+                if g > 1 {
+                    break;
+                }
+            }
+        }
+        if g != n {
+            return Some(g);
+        }
+    }
+    None
+}
+
+/// Splits the composite n (odd, without the small primes), with SQUFOF and Pollard's rho as a fallback. If rho runs out
+/// of constants, n is trial divided, which always finds its smallest prime factor (below 2^32).
+fn split(n: u64) -> u64 {
+    squfof(n).or_else(|| pollard_rho_u64(n)).unwrap_or_else(|| {
+        (SMALL_PRIMES[SMALL_PRIMES.len() - 1] + 2..).step_by(2).find(|&d| n.is_multiple_of(d)).unwrap()
+    })
+}
+
+/// The prime factorization of n as (prime, exponent) pairs in increasing order, entirely on machine words: trial
+/// division by the primes below 100, then `is_prime_u64`, SQUFOF and `pollard_rho_u64` on what is left. Unlike
+/// `prime_factorize`, it needs no sieve and allocates no `Integer`, so it is much cheaper per call for n < 2^64.
+/// Returns an empty list for n = 0 and n = 1.
+pub fn factorize_u64(mut n: u64) -> Vec<(u64, u32)> {
+    let mut factors: Vec<(u64, u32)> = Vec::new();
+    if n == 0 {
+        return factors;
+    }
+    let twos = n.trailing_zeros();
+    if twos > 0 {
+        factors.push((2, twos));
+        n >>= twos;
+    }
+    for p in SMALL_PRIMES {
+        let mut exponent = 0;
+        while n.is_multiple_of(p) {
+            n /= p;
+            exponent += 1;
+        }
+        if exponent > 0 {
+            factors.push((p, exponent));
+        }
+    }
+
+    let mut pending = vec![n];
+    let mut primes: Vec<u64> = Vec::new();
+    while let Some(m) = pending.pop() {
+        if m == 1 {
+            continue;
+        }
+        if is_prime_u64(m) {
+            primes.push(m);
+            continue;
+        }
+        let d = split(m);
+        pending.push(d);
+        pending.push(m / d);
+    }
+    primes.sort_unstable();
+    for p in primes {
+        match factors.last_mut() {
+            Some((q, exponent)) if *q == p => *exponent += 1,
+            _ => factors.push((p, 1)),
+        }
+    }
+    factors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prime_factorization::prime_factorize;
+    use rug::Integer;
+
+    #[test]
+    fn test_factorize_u64() {
+        assert_eq!(pollard_rho_u64(1_000_000_007), None);
+
+        let numbers = [1, 2, 360, 1 << 63, 97 * 97 * 97, 4_294_967_291 * 4_294_967_279, 998_244_353 * 1_000_000_007,
+            3_825_123_056_546_413_051, 18_446_744_073_709_551_615, 18_446_744_073_709_551_557, 600_851_475_143];
+        for n in numbers {
+            let mut expected: Vec<(u64, u32)> = prime_factorize(&Integer::from(n)).into_iter()
+                .map(|(p, e)| (p.to_u64().unwrap(), e))
+                .collect();
+            expected.sort();
+            assert_eq!(factorize_u64(n), expected, "failed for n = {}", n);
+        }
+        // SQUFOF splits the square of a prime into the prime twice
+        assert_eq!(factorize_u64(2_147_483_647 * 2_147_483_647 * 3), [(3, 1), (2_147_483_647, 2)]);
+        assert!(factorize_u64(0).is_empty());
+    }
+}
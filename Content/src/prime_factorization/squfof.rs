@@ -0,0 +1,97 @@
+use crate::number_theory::word::gcd;
+
+/// Multipliers tried by `squfof`, products of the small odd primes 3, 5, 7 and 11.
+const MULTIPLIERS: [i128; 16] = [1, 3, 5, 7, 11, 3 * 5, 3 * 7, 3 * 11, 5 * 7, 5 * 11, 7 * 11, 3 * 5 * 7, 3 * 5 * 11,
+    3 * 7 * 11, 5 * 7 * 11, 3 * 5 * 7 * 11];
+
+/// Runs the continued fraction expansion of sqrt(kn) until it reaches a square Q at an even index,
+/// then walks the reverse cycle from there to a symmetry point, whose Q shares a factor with n.
+/// Returns None if no square form turned up within the iteration bound, or the one found only gives a trivial factor.
+fn squfof_multiplier(n: u64, k: i128, bound: u64) -> Option<u64> {
+    let d = k * n as i128;
+    let p0 = d.isqrt();
+    let (mut p, mut p_prev) = (p0, p0);
+    let (mut q, mut q_prev) = (d - p0 * p0, 1);
+    if q == 0 {
+        return None;
+    }
+
+    // forward cycle
+    let mut r = 0;
+    for i in 2..bound {
+        let b = (p0 + p) / q;
+        p = b * q - p;
+        let q_next = q_prev + b * (p_prev - p);
+        q_prev = q;
+        q = q_next;
+        p_prev = p;
+        r = q.isqrt();
+        if i % 2 == 0 && r * r == q {
+            break;
+        }
+        r = 0;
+    }
+    if r == 0 {
+        return None;
+    }
+
+    // reverse cycle, starting from the square root of the square form
+    let b = (p0 - p) / r;
+    p += b * r;
+    q_prev = r;
+    q = (d - p * p) / q_prev;
+    loop {
+        let b = (p0 + p) / q;
+        p_prev = p;
+        p = b * q - p;
+        let q_next = q_prev + b * (p_prev - p);
+        q_prev = q;
+        q = q_next;
+        if p == p_prev {
+            break;
+        }
+    }
+    let factor = gcd(n, q_prev as u64);
+    (factor != 1 && factor != n).then_some(factor)
+}
+
+/// Shanks' square forms factorization, on machine words instead of `Integer`s and Montgomery contexts.
+/// It takes about n^(1/4) steps, so it is the fastest way to split the small composites left over during
+/// `prime_factorize`, which uses it for everything below 2^SQUFOF_BITS.
+///
+/// # Returns
+/// * `Some(d)` - A non-trivial factor of n.
+/// * `None` - n is prime, 1, or none of the multipliers found a factor (this is rare).
+pub fn squfof(n: u64) -> Option<u64> {
+    if n < 4 {
+        return None;
+    }
+    if n.is_multiple_of(2) {
+        return Some(2);
+    }
+    let s = n.isqrt();
+    if s * s == n {
+        return Some(s);
+    }
+
+    // the forward cycle takes about 2 * sqrt(2 * sqrt(n)) steps, 3 times that is enough in practice
+    let bound = 3 * 2 * (2 * s).isqrt();
+    MULTIPLIERS.iter().find_map(|&k| squfof_multiplier(n, k, bound))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_squfof() {
+        let semiprimes = [11 * 13, 10_007 * 10_009, 998_244_353 * 1_000_000_007, 2_147_483_647 * 2_147_483_629,
+            4_294_967_291 * 1_073_741_789, 3 * 1_537_228_672_809_129_301];
+        for n in semiprimes {
+            let d = squfof(n).unwrap_or_else(|| panic!("failed for n = {}", n));
+            assert!(d > 1 && d < n && n.is_multiple_of(d), "failed for n = {}", n);
+        }
+        assert_eq!(squfof(1_000_000_007), None);
+        assert_eq!(squfof(1_000_000_007 * 1_000_000_007), Some(1_000_000_007));
+    }
+}
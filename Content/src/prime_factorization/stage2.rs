@@ -0,0 +1,219 @@
+use std::ops::Range;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use rug::{Assign, Integer};
+
+use super::data::{Stage2Plan, LUCAS_BLOCK_SIZES};
+use crate::montgomery_mod_mult::{recoding::ladder_bits_u64, Context};
+
+/// Number of primes between two gcd checks (and checks of the cancel flag) in `lucas_stage2`.
+const CHUNK: usize = 1024;
+
+/// The operations of a group (written additively) that a stage 2 walk needs. Elements only have to be known up to
+/// sign, and only differential additions are needed (P + Q from P, Q and P - Q), so the X : Z coordinates of a point
+/// on a Montgomery curve or the values α^k + α^(-k) of a Lucas sequence are enough.
+pub trait Stage2Group {
+    type Element: Clone;
+
+    /// P = 2P.
+    fn double(&mut self, P: &mut Self::Element);
+
+    /// P = P + Q, where difference = ±(P - Q).
+    fn add(&mut self, P: &mut Self::Element, Q: &Self::Element, difference: &Self::Element);
+
+    /// Multiplies product by a value that is 0 modulo a prime factor p of n when P = ±Q modulo p.
+    fn multiply_difference(&mut self, product: &mut Integer, P: &Self::Element, Q: &Self::Element);
+
+    /// (kP, (k + 1)P) for k >= 1, with a Montgomery ladder.
+    fn ladder(&mut self, P: &Self::Element, k: u64) -> (Self::Element, Self::Element) {
+        let (mut low, mut high) = (P.clone(), P.clone());
+        self.double(&mut high);
+        for bit in ladder_bits_u64(k) {
+            if bit {
+                self.add(&mut low, &high, P);
+                self.double(&mut high);
+            } else {
+                self.add(&mut high, &low, P);
+                self.double(&mut low);
+            }
+        }
+        (low, high)
+    }
+}
+
+/// The walk of stage 2 over the primes in (B1, B2] with a `Stage2Plan`, for any `Stage2Group`. The baby steps jQ are
+/// precomputed for the values j of the plan, the giant steps cQ go over the multiples c of the block size with a
+/// differential addition each, and a prime q = c ± j costs a single `multiply_difference(cQ, jQ)`, which vanishes
+/// modulo p exactly when the order of Q modulo p divides c - j or c + j.
+pub struct Stage2Walk<'a, G: Stage2Group> {
+    plan: &'a Stage2Plan,
+    /// jQ for the values j of the plan.
+    baby_steps: Vec<G::Element>,
+    /// DQ, where D is the block size.
+    giant_step: G::Element,
+    /// cQ and (c + D)Q.
+    current: G::Element,
+    next: G::Element,
+    c: u64,
+}
+
+impl<'a, G: Stage2Group> Stage2Walk<'a, G> {
+    /// Precomputes the baby steps of Q, the element after stage 1, and starts the giant steps at the multiple of the
+    /// block size nearest to B1. The block size must be at most B1.
+    pub fn new(group: &mut G, Q: &G::Element, plan: &'a Stage2Plan, B1: u32) -> Self {
+        let block_size = plan.block_size as u64;
+        let mut Q2 = Q.clone();
+        group.double(&mut Q2);
+
+        // (j + 2)Q = jQ + 2Q, with (j - 2)Q as the difference (and -Q = Q up to sign for j = 1)
+        let mut baby_steps = Vec::with_capacity(plan.values.len());
+        let (mut previous, mut current, mut j) = (Q.clone(), Q.clone(), 1);
+        for &value in &plan.values {
+            while j < value {
+                let mut following = current.clone();
+                group.add(&mut following, &Q2, &previous);
+                previous = std::mem::replace(&mut current, following);
+                j += 2;
+            }
+            baby_steps.push(current.clone());
+        }
+
+        let (giant_step, _) = group.ladder(Q, block_size);
+        let c = ((B1 as u64 + block_size / 2) / block_size).max(1);
+        let (current, next) = group.ladder(&giant_step, c);
+        Self { plan, baby_steps, giant_step, current, next, c: c * block_size }
+    }
+
+    /// Multiplies product by the terms of the primes with indices in `range`, which must all be in (B1, B2] and come
+    /// after the ones of the previous calls. `primes` must be the primes the plan was built with.
+    pub fn multiply(&mut self, group: &mut G, primes: &[u32], range: Range<usize>, product: &mut Integer) {
+        let block_size = self.plan.block_size as u64;
+        for (&q, &gap) in primes[range.clone()].iter().zip(&self.plan.gaps[range]) {
+            while q as u64 > self.c + block_size / 2 {
+                let mut following = self.next.clone();
+                group.add(&mut following, &self.giant_step, &self.current);
+                self.current = std::mem::replace(&mut self.next, following);
+                self.c += block_size;
+            }
+            group.multiply_difference(product, &self.current, &self.baby_steps[gap]);
+        }
+    }
+}
+
+/// The group of the Lucas sequences V_k = α^k + α^(-k) modulo n, in Montgomery form, where the element V_k stands
+/// for ±k: V_2k = V_k^2 - 2 and V_(k+l) = V_k V_l - V_(k-l).
+pub struct Lucas<'a> {
+    pub ctx: &'a mut Context,
+    /// 2 in Montgomery form.
+    two: Integer,
+}
+
+impl<'a> Lucas<'a> {
+    pub fn new(ctx: &'a mut Context) -> Self {
+        let one = ctx.one();
+        let two = ctx.add(one.clone(), &one);
+        Self { ctx, two }
+    }
+}
+
+impl Stage2Group for Lucas<'_> {
+    type Element = Integer;
+
+    fn double(&mut self, P: &mut Integer) {
+        self.ctx.square_mut(P);
+        self.ctx.sub_assign(P, &self.two);
+    }
+
+    fn add(&mut self, P: &mut Integer, Q: &Integer, difference: &Integer) {
+        self.ctx.mul_assign(P, Q);
+        self.ctx.sub_assign(P, difference);
+    }
+
+    fn multiply_difference(&mut self, product: &mut Integer, P: &Integer, Q: &Integer) {
+        let mut term = P.clone();
+        self.ctx.sub_assign(&mut term, Q);
+        self.ctx.mul_assign(product, &term);
+    }
+}
+
+/// The stage 2 of Pollard's p - 1 and Williams' p + 1 from v = V_E (in Montgomery form), the Lucas sequence after
+/// stage 1: finds p when the order of α modulo p divides E q for a single prime q in (B1, B2], with a `Stage2Walk`
+/// over a plan whose block size is the largest of `LUCAS_BLOCK_SIZES` up to B1. `primes` must reach B2.
+///
+/// ## Returns
+/// * `Some(())` - A non-trivial factor of `n` was stored in `g`.
+/// * `None` - No factor was found, or the search was cancelled (checked between chunks of primes).
+pub fn lucas_stage2(n: &Integer, ctx: &mut Context, primes: &[u32], (B1, B2): (u32, u32), v: &Integer,
+    cancel: &AtomicBool, g: &mut Integer) -> Option<()> {
+    let start = primes.partition_point(|&p| p <= B1);
+    let end = primes.partition_point(|&p| p <= B2);
+    if start >= end {
+        return None;
+    }
+    let block_size = LUCAS_BLOCK_SIZES.into_iter().find(|&size| size <= B1 as usize).unwrap_or(2);
+    let plan = Stage2Plan::new(block_size, B2, primes).ok()?;
+
+    let lucas = &mut Lucas::new(ctx);
+    let mut walk = Stage2Walk::new(lucas, v, &plan, B1);
+    let mut product = lucas.ctx.one();
+    for chunk_start in (start..end).step_by(CHUNK) {
+        if cancel.load(Ordering::Relaxed) {
+            return None;
+        }
+        walk.multiply(lucas, primes, chunk_start..end.min(chunk_start + CHUNK), &mut product);
+
+        // gcd(x R, n) = gcd(x, n), so the product doesn't need to leave Montgomery form
+        g.assign(product.gcd_ref(n));
+        if *g == *n {
+            return None;
+        }
+        if *g != 1 {
+            return Some(());
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::number_theory::stored_primes;
+
+    /// The integers modulo m under addition, where the order of 1 modulo a prime factor p of m is p.
+    struct Integers(Integer);
+
+    impl Stage2Group for Integers {
+        type Element = Integer;
+
+        fn double(&mut self, P: &mut Integer) {
+            *P <<= 1;
+            *P %= &self.0;
+        }
+
+        fn add(&mut self, P: &mut Integer, Q: &Integer, _: &Integer) {
+            *P += Q;
+            *P %= &self.0;
+        }
+
+        fn multiply_difference(&mut self, product: &mut Integer, P: &Integer, Q: &Integer) {
+            *product *= Integer::from(P - Q) * Integer::from(P + Q);
+            *product %= &self.0;
+        }
+    }
+
+    #[test]
+    fn test_stage2_walk() {
+        let primes = stored_primes();
+        let plan = Stage2Plan::new(210, 20_000, primes).unwrap();
+        let (start, end) = (primes.partition_point(|&p| p <= 1000), primes.partition_point(|&p| p <= 20_000));
+
+        // every prime q in (B1, B2] is c ± j, so the product is 0 modulo 10007 and no other prime in (B1, B2]
+        let group = &mut Integers(Integer::from(10_007u64 * 30_011));
+        let mut walk = Stage2Walk::new(group, &Integer::from(1), &plan, 1000);
+        let mut product = Integer::from(1);
+        walk.multiply(group, primes, start..start + 100, &mut product);
+        walk.multiply(group, primes, start + 100..end, &mut product);
+        assert_eq!(product.gcd(&group.0), 10_007);
+        assert_eq!(group.ladder(&Integer::from(1), 1000), (Integer::from(1000), Integer::from(1001)));
+    }
+}
@@ -1,25 +1,33 @@
-use super::Context;
+use std::{collections::BTreeMap, fmt, iter::Product, ops::{Mul, MulAssign}, sync::{atomic::{AtomicBool, Ordering}, Arc}, time::{Duration, Instant}};
 
-use rug::{Assign, Integer};
+use super::data::{RHO_BATCH_SIZE, RHO_MAX_ITERATIONS, SIZE};
+use super::ecpp::verify_ecpp;
+use super::pocklington::verify_pocklington;
+use super::pratt::verify_pratt;
+use crate::montgomery_mod_mult::Context;
+use crate::tuning::profile;
 
-/// Fixed-size array of (Integer, usize, Context) with length tracking
-/// All elements are pre-initialized with (Integer::ONE, 0, Context(Integer::ONE))
+use num_traits::One;
+use rug::{ops::Pow, Assign, Integer};
+
+/// Vector of pre-initialized elements with length tracking, which are reused instead of reallocated.
+/// It starts with N copies of the template and grows with more of them when more than N elements are pushed.
 #[derive(Clone, Debug)]
-pub struct FixedVec<T, const N: usize> {
-    pub data: [T; N],
+pub struct GrowVec<T, const N: usize> {
+    pub data: Vec<T>,
     pub length: usize,
+    template: T,
 }
 
-impl<T: Clone, const N: usize> FixedVec<T, N> {
+impl<T: Clone, const N: usize> GrowVec<T, N> {
     /// Creates a new array with all elements cloned from a template
     pub fn new(template: T) -> Self {
-        let data = std::array::from_fn(|_| template.clone());
-        Self { data, length: 0 }
+        let data = vec![template.clone(); N];
+        Self { data, length: 0, template }
     }
 
     /// call this before assigning a new item into the array
     pub fn inc(&mut self) {
-        // assert!(self.length < N, "FixedVec overflow");
         self.length += 1;
     }
     
@@ -43,8 +51,11 @@ impl<T: Clone, const N: usize> FixedVec<T, N> {
         &mut self.data[self.length - 1]
     }
 
-    /// Returns the last pushed element
+    /// Returns the slot after the last pushed element
     pub fn next(&mut self) -> &mut T {
+        if self.length == self.data.len() {
+            self.data.push(self.template.clone());
+        }
         &mut self.data[self.length]
     }
 
@@ -72,8 +83,11 @@ impl<T: Clone, const N: usize> FixedVec<T, N> {
 #[derive(Clone, Debug)]
 pub struct Factor {
     pub n: Integer,
+    /// The primes of prime_factors before this index have been divided out of n.
     pub idx: usize,
     pub ctx: Context,
+    /// Whether the methods of the first pass failed to split n since it last changed.
+    pub failed: bool,
 }
 
 impl Factor {
@@ -81,31 +95,929 @@ impl Factor {
         let n = Integer::new();
         let idx = 0;
         let ctx = Context::new(Integer::ONE.clone());
-        Factor { n, idx, ctx }
-    }
-    
-    /// Changes the factor with the given n and index, and updates the context
-    pub fn update_all(&mut self, n: &Integer, idx: usize) {
-        self.n.assign(n);
-        self.idx = idx;
-        self.ctx.change_mod(n);
+        Factor { n, idx, ctx, failed: false }
     }
 
+    /// Divides out of n every prime of prime_factors from idx on, and moves idx past them.
+    /// Returns whether n changed.
+    pub fn remove_primes(&mut self, prime_factors: &GrowVec<Integer, SIZE>) -> bool {
+        let mut changed = false;
+        for i in self.idx..prime_factors.len() {
+            changed |= self.n.remove_factor_mut(prime_factors.get(i)) > 0;
+        }
+        self.idx = prime_factors.len();
+        changed
+    }
+    
     /// Updates the context with the current n value
     pub fn update_ctx(&mut self) {
         self.ctx.change_mod(&self.n);
     }
-    
-    /// Updates n and the index. this does NOT update the context
-    pub fn update_n_and_index(&mut self, n: &Integer, idx: usize) {
-        self.idx = idx;
-        self.n.assign(n);
-    }
 
     /// Assigns the value of another factor to this one
     pub fn assign(&mut self, other: &Factor) {
         self.n.assign(&other.n);
         self.idx = other.idx;
         self.ctx.assign(&other.ctx);
+        self.failed = other.failed;
+    }
+}
+
+/// The prime factorization of an integer n >= 1, as returned by `prime_factorize`: its primes in increasing order,
+/// each with its exponent. The arithmetic functions of n follow from it without factorizing n again.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Factorization {
+    factors: Vec<(Integer, u32)>,
+}
+
+impl Factorization {
+    /// The factorization with these (prime, exponent) pairs, given in any order with every prime at most once.
+    pub fn new(mut factors: Vec<(Integer, u32)>) -> Self {
+        factors.retain(|&(_, e)| e > 0);
+        factors.sort();
+        Self { factors }
+    }
+
+    /// The (prime, exponent) pairs, in increasing order of the primes.
+    pub fn factors(&self) -> &[(Integer, u32)] {
+        &self.factors
+    }
+
+    pub fn into_factors(self) -> Vec<(Integer, u32)> {
+        self.factors
+    }
+
+    /// n itself.
+    pub fn value(&self) -> Integer {
+        self.factors.iter().map(|(p, e)| p.clone().pow(*e)).product()
+    }
+
+    /// Euler's totient phi(n), the product of p^(e - 1) (p - 1).
+    pub fn totient(&self) -> Integer {
+        self.factors.iter().map(|(p, e)| p.clone().pow(e - 1) * (p.clone() - 1u32)).product()
+    }
+
+    /// The number of divisors of n, the product of e + 1.
+    pub fn divisor_count(&self) -> Integer {
+        self.factors.iter().map(|&(_, e)| Integer::from(e) + 1u32).product()
+    }
+
+    /// The sum of the divisors of n, sigma(n), the product of (p^(e + 1) - 1) / (p - 1).
+    pub fn divisor_sum(&self) -> Integer {
+        self.factors.iter().map(|(p, e)| (p.clone().pow(e + 1) - 1u32).div_exact(&(p.clone() - 1u32))).product()
+    }
+
+    /// The divisors of n, starting from 1. They are not in increasing order, and there are `divisor_count` of them.
+    pub fn divisors(&self) -> Divisors<'_> {
+        Divisors { factors: &self.factors, exponents: vec![0; self.factors.len()], next: Some(Integer::from(1)) }
+    }
+
+    /// The product of the distinct primes of n.
+    pub fn radical(&self) -> Integer {
+        self.factors.iter().map(|(p, _)| p).product()
+    }
+
+    /// The factorization of gcd(n, m), where m is the number factorized by other, with the smaller exponent of every
+    /// prime.
+    pub fn gcd(&self, other: &Factorization) -> Factorization {
+        self.merge(other, u32::min)
+    }
+
+    /// The factorization of lcm(n, m), with the larger exponent of every prime.
+    pub fn lcm(&self, other: &Factorization) -> Factorization {
+        self.merge(other, u32::max)
+    }
+
+    /// Whether m divides n.
+    pub fn is_multiple_of(&self, other: &Factorization) -> bool {
+        self.gcd(other) == *other
+    }
+
+    /// Combines the exponents of the primes of both factorizations, a prime missing from one having exponent 0.
+    fn merge(&self, other: &Factorization, f: impl Fn(u32, u32) -> u32) -> Factorization {
+        let mut exponents: BTreeMap<&Integer, (u32, u32)> = BTreeMap::new();
+        for (p, e) in &self.factors {
+            exponents.entry(p).or_default().0 = *e;
+        }
+        for (p, e) in &other.factors {
+            exponents.entry(p).or_default().1 = *e;
+        }
+        let factors = exponents.into_iter().map(|(p, (a, b))| (p.clone(), f(a, b))).filter(|&(_, e)| e > 0).collect();
+        Factorization { factors }
+    }
+}
+
+/// The product of the numbers, with the exponents of their primes added up.
+impl Mul<&Factorization> for &Factorization {
+    type Output = Factorization;
+
+    fn mul(self, other: &Factorization) -> Factorization {
+        self.merge(other, |a, b| a + b)
+    }
+}
+
+impl Mul for Factorization {
+    type Output = Factorization;
+
+    fn mul(self, other: Factorization) -> Factorization {
+        &self * &other
+    }
+}
+
+impl MulAssign<&Factorization> for Factorization {
+    fn mul_assign(&mut self, other: &Factorization) {
+        *self = &*self * other;
+    }
+}
+
+impl Product for Factorization {
+    fn product<I: Iterator<Item = Factorization>>(iter: I) -> Self {
+        iter.fold(Factorization::default(), |acc, x| &acc * &x)
+    }
+}
+
+/// The factorization of 1, which has no primes. There is no `Zero`, since 0 has no factorization.
+impl One for Factorization {
+    fn one() -> Self {
+        Factorization::default()
+    }
+
+    fn is_one(&self) -> bool {
+        self.factors.is_empty()
+    }
+}
+
+/// n^k, with the exponents multiplied by k.
+impl num_traits::Pow<u32> for &Factorization {
+    type Output = Factorization;
+
+    fn pow(self, k: u32) -> Factorization {
+        Factorization::new(self.factors.iter().map(|(p, e)| (p.clone(), e * k)).collect())
+    }
+}
+
+/// The primes with their exponents, e.g `2^3 · 5 · 17`, and `1` for n = 1.
+impl fmt::Display for Factorization {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.factors.is_empty() {
+            return write!(f, "1");
+        }
+        for (i, (p, e)) in self.factors.iter().enumerate() {
+            if i > 0 {
+                write!(f, " · ")?;
+            }
+            match e {
+                1 => write!(f, "{}", p)?,
+                e => write!(f, "{}^{}", p, e)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl IntoIterator for Factorization {
+    type Item = (Integer, u32);
+    type IntoIter = std::vec::IntoIter<(Integer, u32)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.factors.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Factorization {
+    type Item = &'a (Integer, u32);
+    type IntoIter = std::slice::Iter<'a, (Integer, u32)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.factors.iter()
+    }
+}
+
+/// The iterator of `Factorization::divisors`. The exponents of the divisor count up like the digits of a mixed radix
+/// counter, with exponent e of p as the last digit.
+pub struct Divisors<'a> {
+    factors: &'a [(Integer, u32)],
+    exponents: Vec<u32>,
+    next: Option<Integer>,
+}
+
+impl Iterator for Divisors<'_> {
+    type Item = Integer;
+
+    fn next(&mut self) -> Option<Integer> {
+        let divisor = self.next.take()?;
+        let mut next = divisor.clone();
+        for ((p, e), exponent) in self.factors.iter().zip(&mut self.exponents) {
+            if *exponent < *e {
+                *exponent += 1;
+                next *= p;
+                self.next = Some(next);
+                break;
+            }
+            // the digit wraps around to 0
+            next.remove_factor_mut(p);
+            *exponent = 0;
+        }
+        Some(divisor)
+    }
+}
+
+/// The parts of n that are left to factorize, as `Factor`s. Every entry carries its own state (the primes divided
+/// out of it, its Montgomery context and whether it failed), so removing or reordering entries can't mix up the state
+/// of two of them. The storage is reused from one factorization to the next, and grows when it is full.
+#[derive(Clone, Debug, Default)]
+pub struct FactorQueue {
+    entries: Vec<Factor>,
+    length: usize,
+}
+
+impl FactorQueue {
+    pub fn len(&self) -> usize {
+        self.length
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    /// Removes every entry (the storage is kept).
+    pub fn clear(&mut self) {
+        self.length = 0;
+    }
+
+    pub fn get(&self, index: usize) -> &Factor {
+        &self.entries[..self.length][index]
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> &mut Factor {
+        &mut self.entries[..self.length][index]
+    }
+
+    /// The last entry.
+    pub fn top(&mut self) -> &mut Factor {
+        self.get_mut(self.length - 1)
+    }
+
+    /// The slot after the last entry, where a method can write the n of a new entry before `push_next` adds it.
+    pub fn spare(&mut self) -> &mut Factor {
+        if self.length == self.entries.len() {
+            self.entries.push(Factor::new());
+        }
+        &mut self.entries[self.length]
+    }
+
+    /// Adds the slot of `spare` as an entry that has not failed yet, whose primes before idx have been divided out.
+    pub fn push_next(&mut self, idx: usize) {
+        let entry = self.spare();
+        entry.idx = idx;
+        entry.failed = false;
+        self.length += 1;
+    }
+
+    /// Adds n as an entry, see `push_next`. Its context is left to be changed by the method that needs it.
+    pub fn push(&mut self, n: &Integer, idx: usize) {
+        self.spare().n.assign(n);
+        self.push_next(idx);
+    }
+
+    /// Removes the last entry.
+    pub fn pop(&mut self) {
+        assert!(self.length > 0, "pop from an empty FactorQueue");
+        self.length -= 1;
+    }
+
+    /// Removes the entry at index, and moves the last entry into its place.
+    pub fn swap_remove(&mut self, index: usize) {
+        let last = self.length - 1;
+        self.swap(index, last);
+        self.length = last;
+    }
+
+    pub fn swap(&mut self, a: usize, b: usize) {
+        self.entries[..self.length].swap(a, b);
+    }
+
+    /// The n of every entry.
+    pub fn values(&self) -> impl Iterator<Item = &Integer> {
+        self.entries[..self.length].iter().map(|entry| &entry.n)
+    }
+
+    /// Removes every entry, and returns their values.
+    pub fn take_values(&mut self) -> Vec<Integer> {
+        let values = self.values().cloned().collect();
+        self.clear();
+        values
+    }
+}
+
+/// A hint passed to `prime_factorize_with_hints` that is not a divisor of n greater than 1.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InvalidHint(pub Integer);
+
+impl fmt::Display for InvalidHint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "the hint {} is not a non-trivial divisor", self.0)
+    }
+}
+
+impl std::error::Error for InvalidHint {}
+
+/// The result of `prime_factorize_partial`: n is the product of primes^exponent and composites^exponent.
+/// The primes pass a probable prime test, and the composites are the cofactors that could not be split.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PartialFactorization {
+    pub primes: Vec<(Integer, u32)>,
+    pub composites: Vec<(Integer, u32)>,
+}
+
+impl PartialFactorization {
+    /// Whether n was fully factorized.
+    pub fn is_complete(&self) -> bool {
+        self.composites.is_empty()
+    }
+}
+
+/// A prime factor found by `prime_factorize_verified`, after it was checked again on the verification threads.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VerifiedPrime {
+    pub prime: Integer,
+    pub exponent: u32,
+    /// The certificate of the prime (from `prove_prime`), if proving was asked for and enough of p - 1 could be
+    /// factorized.
+    pub certificate: Option<Certificate>,
+}
+
+/// The result of `prime_factorize_verified`. The probable primes that failed the second check are in `composites`,
+/// along with the cofactors that could not be split.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VerifiedFactorization {
+    /// In increasing order.
+    pub primes: Vec<VerifiedPrime>,
+    pub composites: Vec<(Integer, u32)>,
+}
+
+/// A prime factor found by `prime_factorize_traced`, with where and when it was found.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TracedPrime {
+    pub prime: Integer,
+    pub exponent: u32,
+    /// The method of the last split of a composite the prime divided, which left it in a piece of its own (up to the
+    /// primes found elsewhere), or `TrialDivision` if it never had to be split off.
+    pub method: Method,
+    /// The curve of that split, if it was ECM.
+    pub curve: Option<EcmCurve>,
+    /// The time from the start of the run to that split.
+    pub found_after: Duration,
+}
+
+/// The result of `prime_factorize_traced`: a `PartialFactorization` with the origin of every prime, and the time
+/// the run spent in each method.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TracedFactorization {
+    /// In increasing order.
+    pub primes: Vec<TracedPrime>,
+    pub composites: Vec<(Integer, u32)>,
+    /// The methods in the order they ran, each with its time over all its passes. The time of Pollard's rho includes
+    /// SQUFOF and the methods of Hart, Lehman and Fermat, which run before it on every composite.
+    pub method_times: Vec<(Method, Duration)>,
+    pub elapsed: Duration,
+}
+
+impl TracedFactorization {
+    /// Traces the primes of a run back through its events, each with the time it happened.
+    pub(super) fn new(partial: PartialFactorization, events: &[(Duration, FactorEvent)], elapsed: Duration) -> Self {
+        let trial_division_time = events.iter()
+            .find_map(|(time, event)| matches!(event, FactorEvent::TrialDivisionDone { .. }).then_some(*time))
+            .unwrap_or(elapsed);
+        let mut primes: Vec<_> = partial.primes.into_iter().map(|(prime, exponent)| {
+            let split = events.iter().enumerate().rev().find_map(|(i, (time, event))| match event {
+                FactorEvent::FactorFound { method, n, .. } if n.is_divisible(&prime) => Some((i, *method, *time)),
+                _ => None,
+            });
+            let Some((i, method, found_after)) = split else {
+                return TracedPrime { prime, exponent, method: Method::TrialDivision, curve: None,
+                    found_after: trial_division_time };
+            };
+            let curve = match events.get(i + 1) {
+                Some((_, FactorEvent::EcmCurveFound { curve, .. })) => Some(*curve),
+                _ => None,
+            };
+            TracedPrime { prime, exponent, method, curve, found_after }
+        }).collect();
+        primes.sort_by(|a, b| a.prime.cmp(&b.prime));
+
+        // trial division runs until its event, Pollard's rho from there to the first pass, and every pass until the
+        // next one starts
+        let mut method_times: Vec<(Method, Duration)> = Vec::new();
+        let mut current = (Method::TrialDivision, Duration::ZERO);
+        let starts = events.iter().filter_map(|(time, event)| match event {
+            FactorEvent::TrialDivisionDone { .. } => Some((Method::PollardRho, *time)),
+            FactorEvent::MethodStarted { method, .. } => Some((*method, *time)),
+            _ => None,
+        });
+        for next in starts.chain([(Method::TrialDivision, elapsed)]) {
+            let time = next.1 - current.1;
+            match method_times.iter_mut().find(|(method, _)| *method == current.0) {
+                Some((_, total)) => *total += time,
+                None => method_times.push((current.0, time)),
+            }
+            current = next;
+        }
+        Self { primes, composites: partial.composites, method_times, elapsed }
+    }
+}
+
+/// One line per prime with its origin, e.g `1000000007 (ECM sigma=0:1234 B1=50000 B2=2500000, 1.2s)`, then one per
+/// composite and the time of every method.
+impl fmt::Display for TracedFactorization {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for p in &self.primes {
+            match p.exponent {
+                1 => write!(f, "{}", p.prime)?,
+                e => write!(f, "{}^{}", p.prime, e)?,
+            }
+            match &p.curve {
+                Some(curve) => writeln!(f, " ({} {}, {:.1?})", p.method, curve, p.found_after)?,
+                None => writeln!(f, " ({}, {:.1?})", p.method, p.found_after)?,
+            }
+        }
+        for (composite, exponent) in &self.composites {
+            writeln!(f, "could not split {}^{}", composite, exponent)?;
+        }
+        let times: Vec<_> = self.method_times.iter().map(|(method, time)| format!("{} {:.1?}", method, time)).collect();
+        write!(f, "{:.1?} in all: {}", self.elapsed, times.join(", "))
+    }
+}
+
+/// What is known of the prime factors of a cofactor that a factorization left, see `cofactor_bound`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CofactorBound {
+    /// Proven: the cofactor has no prime factor below it. `cofactor_bound` checks this itself, by dividing the
+    /// cofactor by every prime of the sieve below it.
+    pub proven: u32,
+    /// The highest t-level of `ECM_LEVELS` whose curves and bounds were run in full, if any: a prime factor of that
+    /// many digits is missed with probability about 1/e, and a smaller one much less likely. This is not a proof.
+    pub ecm_level: Option<u32>,
+}
+
+impl CofactorBound {
+    /// The most prime factors (with multiplicity) a cofactor can have with the proven bound, the largest k with
+    /// proven^k <= cofactor. At most 2 means a composite cofactor is a semiprime, and at most 1 that it is prime.
+    pub fn max_prime_factors(&self, cofactor: &Integer) -> u32 {
+        let mut power = Integer::from(self.proven.max(2));
+        let mut k = 0;
+        while power <= *cofactor {
+            power *= self.proven.max(2);
+            k += 1;
+        }
+        k
+    }
+}
+
+/// How much work `prime_factorize_partial` puts into n.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Effort {
+    /// Trial division, SQUFOF, Fermat's method and Pollard's rho, which only find small factors
+    /// (and factors close to sqrt(n)).
+    Low,
+    /// Also Pollard's p − 1, Williams' p + 1 and the first round of ECM (B1 = 5e4), which is aimed at factors of about
+    /// 25 digits.
+    Medium,
+    /// Everything `prime_factorize` runs, including the whole ECM schedule (up to B1 = 1e6) and, with the `qs`
+    /// feature, the quadratic sieve.
+    High,
+}
+
+/// Limits and the seed of a `prime_factorize_with_options` run. When the deadline passes or the cancel flag is set, the
+/// run stops and returns what it found so far, with the pieces it did not get to as composites.
+/// They are checked between the steps of the methods (Pollard's rho attempts, numbers in the p − 1 pass and
+/// batches of ECM curves), so the run can overshoot the deadline by about one step.
+#[derive(Clone, Debug, Default)]
+pub struct FactorizeOptions {
+    /// The time the run may take, from when it starts.
+    pub deadline: Option<Duration>,
+    /// Stops the run when it is set, e.g from another thread.
+    pub cancel: Option<Arc<AtomicBool>>,
+    /// Draws all the random numbers of the run (the walks of Pollard's rho, the ECM curves, ...) from a generator
+    /// started from this seed, see `with_seed`, so that the run can be replayed exactly.
+    pub seed: Option<u64>,
+    /// Gets the `FactorEvent`s of the run as they happen, e.g to show its progress.
+    pub progress: Option<Progress>,
+}
+
+impl FactorizeOptions {
+    /// Starts the clock of the deadline.
+    pub fn budget(&self) -> Budget {
+        let deadline = self.deadline.map(|deadline| Instant::now() + deadline);
+        Budget { deadline, cancel: self.cancel.clone(), progress: self.progress.clone() }
+    }
+}
+
+/// The `FactorizeOptions` of a run that has started, which the methods check to see whether they should stop, and
+/// report their progress to.
+#[derive(Clone, Debug, Default)]
+pub struct Budget {
+    deadline: Option<Instant>,
+    cancel: Option<Arc<AtomicBool>>,
+    progress: Option<Progress>,
+}
+
+impl Budget {
+    /// A budget that never runs out.
+    pub fn unlimited() -> Self {
+        Self::default()
+    }
+
+    /// Whether the deadline has passed or the run was cancelled.
+    pub fn is_exhausted(&self) -> bool {
+        self.cancel.as_ref().is_some_and(|cancel| cancel.load(Ordering::Relaxed))
+            || self.deadline.is_some_and(|deadline| Instant::now() >= deadline)
+    }
+
+    /// The cancel flag, for the methods that take one (it is never set if there is none).
+    pub fn cancel_flag(&self) -> &AtomicBool {
+        static NEVER: AtomicBool = AtomicBool::new(false);
+        self.cancel.as_deref().unwrap_or(&NEVER)
+    }
+
+    /// Passes an event to the progress callback. The event is only built if there is one.
+    pub fn report(&self, event: impl FnOnce() -> FactorEvent) {
+        if let Some(progress) = &self.progress {
+            (progress.0)(&event());
+        }
+    }
+}
+
+/// A callback that gets the `FactorEvent`s of a run, see `FactorizeOptions::progress`. It is called on the thread
+/// that runs the factorization.
+#[derive(Clone)]
+pub struct Progress(pub Arc<dyn Fn(&FactorEvent) + Send + Sync>);
+
+impl Progress {
+    pub fn new(callback: impl Fn(&FactorEvent) + Send + Sync + 'static) -> Self {
+        Self(Arc::new(callback))
+    }
+}
+
+impl fmt::Debug for Progress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Progress(..)")
+    }
+}
+
+/// The methods of the factorization pipeline, in the order it runs them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Method {
+    /// Trial division, which also leaves the primes of n that no method had to split off.
+    TrialDivision,
+    Squfof,
+    /// Hart's one line factoring algorithm, then Lehman's method.
+    HartLehman,
+    Fermat,
+    PollardRho,
+    PollardPMinus1,
+    WilliamsPPlus1,
+    Ecm,
+    QuadraticSieve,
+    PollardStrassen,
+}
+
+impl fmt::Display for Method {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Method::TrialDivision => "trial division",
+            Method::Squfof => "SQUFOF",
+            Method::HartLehman => "Hart/Lehman",
+            Method::Fermat => "Fermat",
+            Method::PollardRho => "Pollard rho",
+            Method::PollardPMinus1 => "p - 1",
+            Method::WilliamsPPlus1 => "p + 1",
+            Method::Ecm => "ECM",
+            Method::QuadraticSieve => "SIQS",
+            Method::PollardStrassen => "Pollard-Strassen",
+        })
+    }
+}
+
+/// A step of a factorization run, passed to the `progress` callback of its `FactorizeOptions`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FactorEvent {
+    /// Trial division (and the removal of the factors of 2) is done, and `remaining` is what is left of n.
+    TrialDivisionDone { remaining: Integer },
+    /// A method starts on the `composites` that the methods before it could not split.
+    MethodStarted { method: Method, composites: usize },
+    /// A method split the composite `n`, and `factor` is the proper divisor of n it found (not always a prime).
+    FactorFound { method: Method, n: Integer, factor: Integer },
+    /// `curves` of the `total` curves of the ECM round with bound `B1` have been run.
+    EcmProgress { curves: usize, total: usize, B1: usize },
+    /// Follows the `FactorFound` event of ECM, with the curve that found `factor`.
+    EcmCurveFound { curve: EcmCurve, factor: Integer },
+}
+
+/// One line per event, e.g `ECM curve 40/200 at B1=50000`.
+impl fmt::Display for FactorEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FactorEvent::TrialDivisionDone { remaining } => write!(f, "trial division done, {} left", remaining),
+            FactorEvent::MethodStarted { method, composites } => write!(f, "{} on {} composites", method, composites),
+            FactorEvent::FactorFound { method, n, factor } => write!(f, "{} found factor {} of {}", method, factor, n),
+            FactorEvent::EcmProgress { curves, total, B1 } => write!(f, "ECM curve {}/{} at B1={}", curves, total, B1),
+            FactorEvent::EcmCurveFound { curve, factor } => write!(f, "ECM curve {} found factor {}", curve, factor),
+        }
+    }
+}
+
+/// The family of the curves of ECM (see `FactorizerBuilder::curve_family`).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum CurveFamily {
+    /// Suyama's Montgomery curves of a random sigma, with a point of order 6 and an order divisible by 12, on which
+    /// stage 1 runs the Montgomery ladder. GMP-ECM runs the same curves (see `EcmCurve`).
+    #[default]
+    Suyama,
+    /// Edwards curves with a point of order 12 (see `edwards_curve`), on which stage 1 runs in extended coordinates
+    /// with the wNAF of its scalar, which takes fewer multiplications per bit. Stage 2 runs on the equivalent
+    /// Montgomery curve.
+    Edwards,
+}
+
+/// The ECM curve that found a factor, with the bounds B1 and B2. `sigma` is the parameter of the curve in its
+/// family: Suyama's sigma, for which GMP-ECM runs the curve with `ecm -sigma 0:<sigma> <B1> <B2>` (the curves of this
+/// crate are its PARAM=0 curves), or the k of `edwards_curve`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct EcmCurve {
+    pub family: CurveFamily,
+    pub sigma: u64,
+    pub B1: u64,
+    pub B2: u64,
+}
+
+/// In GMP-ECM's notation, e.g `sigma=0:1234 B1=50000 B2=2500000`, and `edwards=Z12:1234 B1=50000 B2=2500000` for
+/// the Edwards curves, which GMP-ECM doesn't have.
+impl fmt::Display for EcmCurve {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.family {
+            CurveFamily::Suyama => write!(f, "sigma=0:{}", self.sigma)?,
+            CurveFamily::Edwards => write!(f, "edwards=Z12:{}", self.sigma)?,
+        }
+        write!(f, " B1={} B2={}", self.B1, self.B2)
+    }
+}
+
+/// The settings of Pollard's rho in a `Factorizer` (see `FactorizerBuilder::rho`). The default is the one of
+/// `prime_factorize`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RhoConfig {
+    /// How many times a walk doubles the length of its cycle (Brent's r) before giving up, so a walk takes up to
+    /// about 2^max_iterations steps. At most 40.
+    pub max_iterations: u32,
+    /// The steps whose differences are multiplied together before each gcd.
+    pub batch_size: u64,
+    /// The walks tried on a composite before leaving it to the later methods. 0 disables Pollard's rho.
+    pub retries: u32,
+    /// The constant c of the sequence y -> y^2 + c, or None for a random one in every walk. A fixed constant only
+    /// changes the starting point from one retry to the next (and is increased by one for each parallel walk).
+    pub poly_constant: Option<u64>,
+}
+
+impl Default for RhoConfig {
+    fn default() -> Self {
+        RhoConfig {
+            max_iterations: RHO_MAX_ITERATIONS,
+            batch_size: RHO_BATCH_SIZE,
+            retries: profile().rho_attempts,
+            poly_constant: None,
+        }
+    }
+}
+
+/// One round of ECM in a `Factorizer`: `curves` curves with stage 1 bound B1 and stage 2 bound B2.
+/// A round continues the curves of the round before it, so stage 1 only covers the primes between the two B1.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EcmRound {
+    pub curves: usize,
+    pub B1: usize,
+    pub B2: usize,
+}
+
+/// A Pratt certificate that `prime` is prime: `witness` has multiplicative order exactly prime - 1 modulo prime,
+/// which is checked against the complete factorization of prime - 1, where every factor has a certificate of its own.
+/// The certificate of 2 has witness 1 and no factors.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PrattCertificate {
+    pub prime: Integer,
+    pub witness: Integer,
+    /// The prime factorization of prime - 1, in increasing order.
+    pub factors: Vec<(PrattCertificate, u32)>,
+}
+
+impl PrattCertificate {
+    /// Collects the certificates of every prime in the tree, each prime once.
+    fn collect<'a>(&'a self, certificates: &mut BTreeMap<&'a Integer, &'a PrattCertificate>) {
+        if certificates.insert(&self.prime, self).is_none() {
+            for (factor, _) in &self.factors {
+                factor.collect(certificates);
+            }
+        }
+    }
+}
+
+/// One line per prime of the tree, in increasing order: `p: witness a, p - 1 = q1^e1 * q2 * ...`
+impl fmt::Display for PrattCertificate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut certificates = BTreeMap::new();
+        self.collect(&mut certificates);
+        for (i, (prime, certificate)) in certificates.into_iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}: witness {}", prime, certificate.witness)?;
+            for (j, (factor, exponent)) in certificate.factors.iter().enumerate() {
+                let separator = if j == 0 { format!(", {} - 1 = ", prime) } else { " * ".to_string() };
+                match exponent {
+                    1 => write!(f, "{}{}", separator, factor.prime)?,
+                    _ => write!(f, "{}{}^{}", separator, factor.prime, exponent)?,
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A Pocklington certificate that `prime` is prime: prime - 1 = F * R where F is the product of the factors, which
+/// are proven primes, and F^2 > prime. Every factor q comes with a witness a such that a^(prime - 1) = 1 and
+/// gcd(a^((prime - 1) / q) - 1, prime) = 1, so every prime p dividing `prime` is 1 mod F, and `prime` has no prime
+/// factor below its square root. The certificate of 2 has no factors.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PocklingtonCertificate {
+    pub prime: Integer,
+    /// The factored part of prime - 1, in increasing order of the primes.
+    pub factors: Vec<PocklingtonFactor>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PocklingtonFactor {
+    pub certificate: PocklingtonCertificate,
+    pub exponent: u32,
+    pub witness: Integer,
+}
+
+impl PocklingtonCertificate {
+    /// Collects the certificates of every prime in the tree, each prime once.
+    fn collect<'a>(&'a self, certificates: &mut BTreeMap<&'a Integer, &'a PocklingtonCertificate>) {
+        if certificates.insert(&self.prime, self).is_none() {
+            for factor in &self.factors {
+                factor.certificate.collect(certificates);
+            }
+        }
+    }
+}
+
+/// One line per prime of the tree, in increasing order: `p: p - 1 = q1^e1 (witness a1) * q2 (witness a2) * R`, where
+/// R is the unfactored part.
+impl fmt::Display for PocklingtonCertificate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut certificates = BTreeMap::new();
+        self.collect(&mut certificates);
+        for (i, (prime, certificate)) in certificates.into_iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", prime)?;
+            let mut rest = Integer::from(prime - 1u32);
+            for (j, factor) in certificate.factors.iter().enumerate() {
+                let PocklingtonFactor { certificate: factor, exponent, witness } = factor;
+                let separator = if j == 0 { format!(": {} - 1 = ", prime) } else { " * ".to_string() };
+                match exponent {
+                    1 => write!(f, "{}{} (witness {})", separator, factor.prime, witness)?,
+                    _ => write!(f, "{}{}^{} (witness {})", separator, factor.prime, exponent, witness)?,
+                }
+                for _ in 0..*exponent {
+                    rest /= &factor.prime;
+                }
+            }
+            if rest > 1 {
+                write!(f, " * {}", rest)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// One step of an ECPP certificate (Goldwasser–Kilian, with the curves of Atkin–Morain): the point (x, y) of
+/// y^2 = x^3 + ax + b mod n has (order / q) P != O and q (order / q) P = O, where q > (n^(1/4) + 1)^2. If q is prime,
+/// so is n: modulo a prime p <= sqrt(n) dividing n, the point would have order q > p + 1 + 2 sqrt(p).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EcppStep {
+    pub n: Integer,
+    pub a: Integer,
+    pub b: Integer,
+    pub order: Integer,
+    pub q: Integer,
+    pub x: Integer,
+    pub y: Integer,
+}
+
+/// An ECPP certificate: a chain of steps, each proving its n prime if its q is, from `prime` down to a q small
+/// enough for a Pocklington certificate.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EcppCertificate {
+    pub prime: Integer,
+    pub steps: Vec<EcppStep>,
+    /// The certificate of the q of the last step (or of `prime` if there are no steps).
+    pub last: PocklingtonCertificate,
+}
+
+/// One line per step, `n: y^2 = x^3 + ax + b, order k * q, P = (x, y)`, then the Pocklington certificate.
+impl fmt::Display for EcppCertificate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for EcppStep { n, a, b, order, q, x, y } in &self.steps {
+            let k = Integer::from(order / q);
+            writeln!(f, "{}: y^2 = x^3 + {}x + {}, order {} * {}, P = ({}, {})", n, a, b, k, q, x, y)?;
+        }
+        self.last.fmt(f)
+    }
+}
+
+/// A primality certificate, which `verify` checks with plain big integer arithmetic only.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Certificate {
+    Pratt(PrattCertificate),
+    Pocklington(PocklingtonCertificate),
+    Ecpp(EcppCertificate),
+}
+
+impl Certificate {
+    /// The prime the certificate is for.
+    pub fn prime(&self) -> &Integer {
+        match self {
+            Certificate::Pratt(certificate) => &certificate.prime,
+            Certificate::Pocklington(certificate) => &certificate.prime,
+            Certificate::Ecpp(certificate) => &certificate.prime,
+        }
+    }
+
+    /// Whether the certificate proves that its prime is prime.
+    pub fn verify(&self) -> bool {
+        match self {
+            Certificate::Pratt(certificate) => verify_pratt(certificate),
+            Certificate::Pocklington(certificate) => verify_pocklington(certificate),
+            Certificate::Ecpp(certificate) => verify_ecpp(certificate),
+        }
     }
-}
\ No newline at end of file
+}
+
+impl fmt::Display for Certificate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Certificate::Pratt(certificate) => certificate.fmt(f),
+            Certificate::Pocklington(certificate) => certificate.fmt(f),
+            Certificate::Ecpp(certificate) => certificate.fmt(f),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_factor_queue() {
+        // twice as many entries as SIZE: the storage grows instead of overflowing
+        let mut queue = FactorQueue::default();
+        for i in 0..2 * SIZE as u32 {
+            queue.push(&Integer::from(i), 0);
+        }
+        assert_eq!(queue.len(), 2 * SIZE);
+
+        // the entries keep their own state when another one is removed or moved
+        queue.get_mut(5).failed = true;
+        queue.swap_remove(3);
+        assert_eq!(queue.get(3).n, 2 * SIZE - 1);
+        assert!(!queue.get(3).failed && queue.get(5).failed);
+        queue.pop();
+        queue.push(&Integer::from(1000), 7);
+        assert!(!queue.top().failed && queue.top().idx == 7);
+
+        // no entry is lost or duplicated
+        let mut values = queue.take_values();
+        values.sort();
+        let mut expected: Vec<Integer> = (0..2 * SIZE as u32 - 2).filter(|&i| i != 3).map(Integer::from).collect();
+        expected.extend([Integer::from(2 * SIZE - 1), Integer::from(1000)]);
+        expected.sort();
+        assert_eq!(values, expected);
+        assert!(queue.is_empty());
+
+        // the primes found after idx are divided out once
+        let mut prime_factors = GrowVec::<Integer, SIZE>::new(Integer::new());
+        for i in 0..2 * SIZE as u32 {
+            prime_factors.next().assign(Integer::from(i).next_prime() + 1000u32);
+            prime_factors.inc();
+        }
+        prime_factors.get_mut(2 * SIZE - 1).assign(3);
+        let mut factor = Factor::new();
+        factor.n.assign(Integer::from(3).pow(4) * 7u32);
+        assert!(factor.remove_primes(&prime_factors));
+        assert_eq!((factor.n.clone(), factor.idx), (Integer::from(7), 2 * SIZE));
+        assert!(!factor.remove_primes(&prime_factors));
+    }
+}
@@ -0,0 +1,100 @@
+use std::{cell::Cell, sync::{mpsc::{self, Sender}, Mutex}, thread};
+
+use rug::{integer::IsPrime, Integer};
+
+use super::data::VERIFY_REPS;
+use super::factorizer::Factorizer;
+use super::pocklington::prove_prime;
+use super::structs::{Budget, VerifiedFactorization, VerifiedPrime};
+
+/// Hands the primes found by a factorization over to the verification threads, as soon as each stage finds them.
+pub(super) struct PrimeVerifier {
+    sender: Sender<(Integer, u32)>,
+    /// How many of the factors have been handed over so far.
+    reported: Cell<usize>,
+}
+
+impl PrimeVerifier {
+    /// Sends the factors that were pushed since the last call. The factorization only ever pushes complete
+    /// (prime, exponent) pairs, so the ones before them don't change.
+    pub(super) fn report(&self, factors: &[(Integer, u32)]) {
+        for (p, e) in &factors[self.reported.get()..] {
+            // the threads only stop once the sender is dropped, so this can't fail
+            self.sender.send((p.clone(), *e)).unwrap();
+        }
+        self.reported.set(factors.len());
+    }
+}
+
+/// Checks a prime again with `VERIFY_REPS` rounds, and proves it with `prove_prime` if asked to.
+fn verify(prime: Integer, exponent: u32, prove: bool) -> Result<VerifiedPrime, (Integer, u32)> {
+    if prime.is_probably_prime(VERIFY_REPS) == IsPrime::No {
+        return Err((prime, exponent));
+    }
+    let certificate = if prove { prove_prime(&prime) } else { None };
+    Ok(VerifiedPrime { prime, exponent, certificate })
+}
+
+/// Factorizes n on the current thread, while a pool of threads verifies the primes as each stage reports them,
+/// so that the checks of the first primes overlap with the ECM rounds still running on the other cofactors.
+pub(super) fn factorize_verified(n: &Integer, factorizer: &Factorizer, prove: bool) -> VerifiedFactorization {
+    let (sender, receiver) = mpsc::channel::<(Integer, u32)>();
+    let receiver = Mutex::new(receiver);
+    // one thread is left for the factorization
+    let threads = thread::available_parallelism().map_or(1, |threads| threads.get().saturating_sub(1).max(1));
+
+    thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads).map(|_| scope.spawn(|| {
+            let mut results = Vec::new();
+            loop {
+                // the lock is released before the prime is checked
+                let next = receiver.lock().unwrap().recv();
+                let Ok((prime, exponent)) = next else {
+                    return results;
+                };
+                results.push(verify(prime, exponent, prove));
+            }
+        })).collect();
+
+        let verifier = PrimeVerifier { sender, reported: Cell::new(0) };
+        let partial = super::factorize_with(n, factorizer, &Budget::unlimited(), Some(&verifier));
+        verifier.report(&partial.primes);
+        drop(verifier);
+
+        let mut factorization = VerifiedFactorization { primes: Vec::new(), composites: partial.composites };
+        for worker in workers {
+            for result in worker.join().unwrap() {
+                match result {
+                    Ok(prime) => factorization.primes.push(prime),
+                    Err(composite) => factorization.composites.push(composite),
+                }
+            }
+        }
+        factorization.primes.sort_by(|a, b| a.prime.cmp(&b.prime));
+        factorization
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prime_factorization::{prime_factorize, prime_factorize_verified, Certificate};
+    use super::*;
+
+    #[test]
+    fn test_prime_factorize_verified() {
+        let m61 = Integer::from(Integer::u_pow_u(2, 61)) - 1u32;
+        let m89 = Integer::from(Integer::u_pow_u(2, 89)) - 1u32;
+        let n = Integer::from(12) * &m61 * &m61 * &m89 * 1_000_000_007u32;
+        let expected = prime_factorize(&n).into_factors();
+
+        let verified = prime_factorize_verified(&n, false);
+        assert!(verified.composites.is_empty());
+        assert_eq!(verified.primes.iter().map(|p| (p.prime.clone(), p.exponent)).collect::<Vec<_>>(), expected);
+        assert!(verified.primes.iter().all(|p| p.certificate.is_none()));
+
+        let proven = prime_factorize_verified(&n, true);
+        assert!(proven.primes.iter().all(|p| p.certificate.as_ref().is_some_and(Certificate::verify)));
+
+        assert!(verify(Integer::from(561), 1, false).is_err());
+    }
+}
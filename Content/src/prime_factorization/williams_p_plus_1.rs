@@ -0,0 +1,169 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use rug::{Assign, Integer};
+
+use super::data::P_PLUS_1_SEEDS;
+use super::stage2::lucas_stage2;
+use crate::montgomery_mod_mult::{recoding::ladder_bits_u64, Context};
+
+/// Number of primes between two gcd checks (and checks of the cancel flag).
+const CHUNK: usize = 1024;
+
+/// Replaces v with V_k(v), where V_0 = 2, V_1 = v and V_(i+1) = v V_i - V_(i-1) is the Lucas sequence of v, all in
+/// Montgomery form (two is 2 in Montgomery form). The ladder keeps (V_i, V_(i+1)), with V_(2i) = V_i^2 - 2 and
+/// V_(2i+1) = V_i V_(i+1) - v.
+fn lucas_v(v: &mut Integer, k: u64, two: &Integer, ctx: &mut Context) {
+    if k == 0 {
+        v.assign(two);
+        return;
+    }
+    let base = v.clone();
+    let mut high = ctx.square(&*v);
+    ctx.sub_assign(&mut high, two);
+    for bit in ladder_bits_u64(k) {
+        if bit {
+            ctx.mul_assign(v, &high);
+            ctx.sub_assign(v, &base);
+            ctx.square_mut(&mut high);
+            ctx.sub_assign(&mut high, two);
+        } else {
+            ctx.mul_assign(&mut high, &*v);
+            ctx.sub_assign(&mut high, &base);
+            ctx.square_mut(v);
+            ctx.sub_assign(v, two);
+        }
+    }
+}
+
+/// 2 in Montgomery form.
+fn two(ctx: &mut Context) -> Integer {
+    let one = ctx.one();
+    ctx.add(one.clone(), &one)
+}
+
+/// Stores gcd(v - 2, n) in g, where v is in Montgomery form.
+fn gcd_minus_two(v: &Integer, n: &Integer, ctx: &mut Context, g: &mut Integer) {
+    g.assign(ctx.from_montgomery(v));
+    *g -= 2;
+    g.gcd_mut(n);
+}
+
+/// How stage 1 ended.
+enum Stage1 {
+    /// A non-trivial factor was stored in g.
+    Factor,
+    /// No factor, V_E(seed) is ready for stage 2.
+    Continue(Integer),
+    /// The search was cancelled, or every prime factor of n showed up at once.
+    GaveUp,
+}
+
+/// Stage 1 of Williams' p + 1 method: computes v = V_E(seed) mod n, where E is the product of the largest powers of
+/// all primes up to B1 that are at most B1, and looks for a factor in gcd(v - 2, n).
+fn stage1(n: &Integer, ctx: &mut Context, primes: &[u32], B1: u32, seed: u32, cancel: &AtomicBool, g: &mut Integer)
+    -> Stage1 {
+    let end = primes.partition_point(|&p| p <= B1);
+    let two = &two(ctx);
+    let v = &mut ctx.to_montgomery(Integer::from(seed));
+    let mut saved = Integer::new();
+
+    for chunk in primes[..end].chunks(CHUNK) {
+        if cancel.load(Ordering::Relaxed) {
+            return Stage1::GaveUp;
+        }
+        saved.assign(&*v);
+        for &p in chunk {
+            let mut power = p as u64;
+            while power * p as u64 <= B1 as u64 {
+                power *= p as u64;
+            }
+            lucas_v(v, power, two, ctx);
+        }
+
+        gcd_minus_two(v, n, ctx, g);
+        if *g == 1 {
+            continue;
+        }
+        if *g != *n {
+            return Stage1::Factor;
+        }
+
+        // every prime factor of n was found in this chunk, redo it one prime at a time to separate them
+        v.assign(&saved);
+        for &p in chunk {
+            let mut power = 1u64;
+            while power * p as u64 <= B1 as u64 {
+                power *= p as u64;
+                lucas_v(v, p as u64, two, ctx);
+                gcd_minus_two(v, n, ctx, g);
+                if *g == *n {
+                    return Stage1::GaveUp;
+                }
+                if *g != 1 {
+                    return Stage1::Factor;
+                }
+            }
+        }
+        return Stage1::GaveUp;
+    }
+    Stage1::Continue(v.clone())
+}
+
+/// Williams' p + 1 method: finds a prime factor p of n when p + 1 is B1-powersmooth apart from a single prime in
+/// (B1, B2]. It raises the root α of X^2 - seed X + 1 to a highly composite power E in the field or ring it lives in
+/// modulo p, with the Lucas sequence V_k = α^k + α^(-k), and looks for V_E = 2 modulo p. Depending on whether
+/// seed^2 - 4 is a square modulo p, this catches a smooth p - 1 or a smooth p + 1, so it is tried with every seed of
+/// `P_PLUS_1_SEEDS`, whose seed^2 - 4 have different squarefree parts. It costs about twice as much as Pollard's p - 1
+/// per seed, and catches the factors with a smooth p + 1 that neither p - 1 nor ECM's random curves aim at.
+/// Stage 2 (see `stage2::lucas_stage2`) then covers a single prime in (B1, B2] on top of E.
+///
+/// ## Arguments
+/// * `n` - The composite number to factorize (must be odd).
+/// * `ctx` - A Context with n as the modulus
+/// * `primes` - The primes up to (at least) B2, in increasing order
+/// * `cancel` - Checked between chunks of primes, the search stops when it is set
+/// ## Returns
+/// * `Some(())` - A non-trivial factor of `n` was stored in `g`.
+/// * `None` - No factor was found, or the search was cancelled.
+pub fn williams_p_plus_1(n: &Integer, ctx: &mut Context, primes: &[u32], B1: u32, B2: u32, cancel: &AtomicBool,
+    g: &mut Integer) -> Option<()> {
+    for &seed in &P_PLUS_1_SEEDS {
+        // the sequence degenerates modulo a prime that divides seed^2 - 4, which is then a factor
+        g.assign(seed * seed - 4);
+        g.gcd_mut(n);
+        if *g != 1 && *g != *n {
+            return Some(());
+        }
+        let v = match stage1(n, ctx, primes, B1, seed, cancel, g) {
+            Stage1::Factor => return Some(()),
+            Stage1::GaveUp if cancel.load(Ordering::Relaxed) => return None,
+            Stage1::GaveUp => continue,
+            Stage1::Continue(v) => v,
+        };
+        if lucas_stage2(n, ctx, primes, (B1, B2), &v, cancel, g).is_some() {
+            return Some(());
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::number_theory::generate_primes;
+
+    #[test]
+    fn test_williams_p_plus_1() {
+        let primes = generate_primes();
+        // 1001839 + 1 = 2^4 * 5 * 7 * 1789, found by the second seed (4, as 4^2 - 4 = 12 is not a square modulo it),
+        // while 1001839 - 1 = 2 * 3 * 166973 and 2^31 - 1 + 1 = 2^31 are not smooth
+        let p = Integer::from(1_001_839u64);
+        let n = Integer::from(&p * 2_147_483_647u64);
+        let mut ctx = Context::new(n.clone());
+        let mut g = Integer::new();
+        assert_eq!(williams_p_plus_1(&n, &mut ctx, &primes, 100, 2000, &AtomicBool::new(false), &mut g), Some(()));
+        assert_eq!(g, p);
+        assert_eq!(williams_p_plus_1(&n, &mut ctx, &primes, 100, 1000, &AtomicBool::new(false), &mut g), None);
+        assert_eq!(williams_p_plus_1(&n, &mut ctx, &primes, 100, 2000, &AtomicBool::new(true), &mut g), None);
+    }
+}
@@ -0,0 +1,139 @@
+use rug::{Complete, Integer};
+
+/// A balanced product tree over a list of integers.
+///
+/// Level 0 holds the values themselves, each node of level k + 1 is the product of two adjacent nodes of level k
+/// (a trailing odd node is carried up unchanged), and the last level holds the product of all values.
+/// The subproducts are kept, so one tree can be reused for several remainder trees.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProductTree {
+    levels: Vec<Vec<Integer>>,
+}
+
+impl ProductTree {
+    /// Builds the product tree of values. Multiplying balanced halves keeps the operands of similar size,
+    /// which is where GMP's subquadratic multiplication pays off.
+    pub fn new(values: &[Integer]) -> Self {
+        let mut levels = vec![values.to_vec()];
+        while levels.last().unwrap().len() > 1 {
+            let next = levels.last().unwrap()
+                .chunks(2)
+                .map(|pair| match pair {
+                    [a, b] => (a * b).complete(),
+                    [a] => a.clone(),
+                    _ => unreachable!(),
+                })
+                .collect();
+            levels.push(next);
+        }
+        ProductTree { levels }
+    }
+
+    /// Number of values (leaves) in the tree.
+    pub fn len(&self) -> usize {
+        self.levels[0].len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.levels[0].is_empty()
+    }
+
+    /// The values the tree was built from.
+    pub fn leaves(&self) -> &[Integer] {
+        &self.levels[0]
+    }
+
+    /// All levels of the tree, from the leaves (index 0) up to the root.
+    pub fn levels(&self) -> &[Vec<Integer>] {
+        &self.levels
+    }
+
+    /// The product of all values (1 for an empty tree).
+    pub fn product(&self) -> Integer {
+        match self.levels.last().unwrap().first() {
+            Some(root) => root.clone(),
+            None => Integer::from(1),
+        }
+    }
+
+    /// Remainder tree: computes n mod v for every value v, in the order of the leaves.
+    /// n is reduced modulo each node on the way down, so every division is by a number of about the size of the dividend.
+    /// The remainders are non-negative. Panics if a value is 0.
+    pub fn remainders(&self, n: &Integer) -> Vec<Integer> {
+        self.remainder_tree(n, false)
+    }
+
+    /// Like `remainders`, but computes n mod v^2 for every value v.
+    /// This is the remainder tree used by batch gcd: with n the product of all values, (n mod v^2) / v = (n / v) mod v.
+    pub fn remainders_squared(&self, n: &Integer) -> Vec<Integer> {
+        self.remainder_tree(n, true)
+    }
+
+    fn remainder_tree(&self, n: &Integer, squared: bool) -> Vec<Integer> {
+        let reduce = |x: &Integer, node: &Integer| if squared {
+            rem_euc(x, &node.square_ref().complete())
+        } else {
+            rem_euc(x, node)
+        };
+
+        let mut remainders = vec![n.clone()];
+        for level in self.levels.iter().rev() {
+            // the parent of node i is node i / 2 of the level above, also for a carried up odd node
+            remainders = level.iter()
+                .enumerate()
+                .map(|(i, node)| reduce(&remainders[i / 2], node))
+                .collect();
+        }
+        remainders
+    }
+}
+
+/// n mod m in [0, |m|).
+fn rem_euc(n: &Integer, m: &Integer) -> Integer {
+    let mut r = (n % m).complete();
+    if r < 0 {
+        if *m < 0 {
+            r -= m;
+        } else {
+            r += m;
+        }
+    }
+    r
+}
+
+/// Product of all values, multiplied along a balanced tree. Returns 1 for an empty slice.
+pub fn product(values: &[Integer]) -> Integer {
+    match values {
+        [] => Integer::from(1),
+        [a] => a.clone(),
+        _ => {
+            let (left, right) = values.split_at(values.len() / 2);
+            product(left) * product(right)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_product_tree() {
+        for len in 0..12u32 {
+            let values: Vec<Integer> = (0..len).map(|i| Integer::from(3 * i * i + 7 * i + 2)).collect();
+            let tree = ProductTree::new(&values);
+            let expected = values.iter().fold(Integer::from(1), |acc, v| acc * v);
+            assert_eq!(tree.product(), expected);
+            assert_eq!(product(&values), expected);
+            assert_eq!(tree.leaves(), &values[..]);
+
+            let n = Integer::from(Integer::u_pow_u(10, 40)) - 17;
+            let remainders = tree.remainders(&n);
+            let squared = tree.remainders_squared(&n);
+            for (i, v) in values.iter().enumerate() {
+                assert_eq!(remainders[i], Integer::from(&n % v));
+                assert_eq!(squared[i], &n % Integer::from(v * v));
+            }
+        }
+    }
+}
@@ -0,0 +1,221 @@
+use rug::{ops::{DivRounding, Pow}, Complete, Integer};
+
+// the largest lattice that `factor_with_high_bits` reduces; its LLL gets slow beyond this, for little gain in the bound
+pub const COPPERSMITH_MAX_DIMENSION: usize = 12;
+
+/// Reduces the rows of basis with LLL (δ = 3/4), in place. This is the integral version (Cohen, Algorithm 2.6.7):
+/// instead of the rational Gram-Schmidt coefficients it keeps d_i (the Gram determinants of the first i rows) and
+/// λ_ij = d_j · μ_ij, which are integers, so no fractions grow along the way. The rows must be independent.
+pub fn lll(basis: &mut [Vec<Integer>]) {
+    let rows = basis.len();
+    if rows < 2 {
+        return;
+    }
+    let dot = |a: &[Integer], b: &[Integer]| a.iter().zip(b).fold(Integer::new(), |acc, (x, y)| acc + (x * y).complete());
+    // 1-based as in the algorithm: d[0] = 1 and d[i] belongs to row i - 1
+    let mut d = vec![Integer::from(1); rows + 1];
+    let mut lambda = vec![vec![Integer::new(); rows + 1]; rows + 1];
+    d[1] = dot(&basis[0], &basis[0]);
+    let (mut k, mut k_max) = (2, 1);
+
+    // subtracts the multiple of row l that makes |μ_kl| at most 1/2
+    let reduce = |basis: &mut [Vec<Integer>], lambda: &mut [Vec<Integer>], d: &[Integer], k: usize, l: usize| {
+        if Integer::from(&lambda[k][l] * 2u32).abs() > d[l] {
+            let q = (Integer::from(&lambda[k][l] * 2u32) + &d[l]).div_floor(Integer::from(&d[l] * 2u32));
+            let (low, high) = basis.split_at_mut(k - 1);
+            for (x, y) in high[0].iter_mut().zip(&low[l - 1]) {
+                *x -= (&q * y).complete();
+            }
+            lambda[k][l] -= (&q * &d[l]).complete();
+            let (low, high) = lambda.split_at_mut(k);
+            for (x, y) in high[0][1..l].iter_mut().zip(&low[l][1..l]) {
+                *x -= (&q * y).complete();
+            }
+        }
+    };
+
+    while k <= rows {
+        if k > k_max {
+            k_max = k;
+            for j in 1..=k {
+                let mut u = dot(&basis[k - 1], &basis[j - 1]);
+                for i in 1..j {
+                    u = (Integer::from(&d[i] * &u) - Integer::from(&lambda[k][i] * &lambda[j][i])).div_exact(&d[i - 1]);
+                }
+                match j < k {
+                    true => lambda[k][j] = u,
+                    false => d[k] = u,
+                }
+            }
+            assert!(d[k] != 0, "the rows of the basis are not independent");
+        }
+        reduce(basis, &mut lambda, &d, k, k - 1);
+
+        // Lovász condition d_k d_{k-2} >= 3/4 d_{k-1}^2 - λ_{k,k-1}^2, multiplied by 4
+        let left = Integer::from(&d[k] * &d[k - 2]) * 4u32;
+        let right = Integer::from(d[k - 1].square_ref()) * 3u32 - Integer::from(lambda[k][k - 1].square_ref()) * 4u32;
+        if left < right {
+            basis.swap(k - 1, k - 2);
+            for j in 1..k - 1 {
+                let (low, high) = lambda.split_at_mut(k);
+                std::mem::swap(&mut low[k - 1][j], &mut high[0][j]);
+            }
+            let l = lambda[k][k - 1].clone();
+            let b = (Integer::from(&d[k - 2] * &d[k]) + l.square_ref()).div_exact(&d[k - 1]);
+            for row in &mut lambda[k + 1..=k_max] {
+                let t = row[k].clone();
+                row[k] = (Integer::from(&d[k] * &row[k - 1]) - Integer::from(&l * &t)).div_exact(&d[k - 1]);
+                row[k - 1] = (Integer::from(&b * &t) + Integer::from(&l * &row[k])).div_exact(&d[k]);
+            }
+            d[k - 1] = b;
+            k = (k - 1).max(2);
+        } else {
+            for l in (1..k - 1).rev() {
+                reduce(basis, &mut lambda, &d, k, l);
+            }
+            k += 1;
+        }
+    }
+}
+
+/// h(x) with Horner's rule.
+fn evaluate(h: &[Integer], x: &Integer) -> Integer {
+    h.iter().rev().fold(Integer::new(), |acc, c| acc * x + c)
+}
+
+/// A sorted list of integers in [low, high] that contains the floor of every real root of h in [low, high] (and maybe
+/// some more). The floors of the roots of h' split [low, high] into segments where h is monotone, so each segment
+/// holds at most one root, which a binary search on the sign of h finds.
+fn root_floors(h: &[Integer], low: &Integer, high: &Integer) -> Vec<Integer> {
+    let degree = h.iter().rposition(|c| *c != 0).unwrap_or(0);
+    if degree == 0 {
+        return Vec::new();
+    }
+    let derivative: Vec<Integer> = (1..=degree).map(|i| Integer::from(&h[i] * i as u32)).collect();
+    let breaks = root_floors(&derivative, low, high);
+
+    let mut floors = breaks.clone();
+    let mut start = low.clone();
+    for end in breaks.iter().chain(std::iter::once(high)) {
+        if start <= *end {
+            let (mut a, mut b) = (start.clone(), end.clone());
+            let (sign_a, sign_b) = (evaluate(h, &a).signum(), evaluate(h, &b).signum());
+            if sign_a == 0 {
+                floors.push(a);
+            } else if sign_a != sign_b {
+                // h(a) != 0 and h(a), h(b) have different signs (or h(b) = 0)
+                while Integer::from(&b - &a) > 1 {
+                    let middle = Integer::from(&a + &b) >> 1u32;
+                    match evaluate(h, &middle).signum() == sign_a {
+                        true => a = middle,
+                        false => b = middle,
+                    }
+                }
+                floors.push(if sign_b == 0 || evaluate(h, &b) == 0 { b } else { a });
+            }
+        }
+        start = Integer::from(end + 1u32);
+    }
+    floors.sort();
+    floors.dedup();
+    floors
+}
+
+/// The integer roots of h in [low, high], in increasing order.
+pub fn integer_roots(h: &[Integer], low: &Integer, high: &Integer) -> Vec<Integer> {
+    root_floors(h, low, high).into_iter().filter(|x| evaluate(h, x) == 0).collect()
+}
+
+/// The smallest lattice (m, t) of `factor_with_high_bits` whose reduced first row is short enough: with
+/// d = m + t, LLL guarantees |v| <= 2^((d - 1) / 4) det^(1/d), and h(x0) = 0 holds over the integers once
+/// sqrt(d) |v| < p^m, where det = n^(m(m + 1) / 2) X^(d(d - 1) / 2).
+fn lattice_shape(n_bits: u32, unknown_bits: u32) -> Option<(usize, usize)> {
+    let p_bits = (n_bits as f64 - 1.0) / 2.0;
+    (2..=COPPERSMITH_MAX_DIMENSION).flat_map(|d| (1..d).map(move |m| (m, d - m))).find(|&(m, t)| {
+        let (m_, d) = (m as f64, (m + t) as f64);
+        let det_bits = n_bits as f64 * m_ * (m_ + 1.0) / 2.0 + unknown_bits as f64 * d * (d - 1.0) / 2.0;
+        det_bits / d + (d - 1.0) / 4.0 + d.log2() / 2.0 < m_ * p_bits
+    })
+}
+
+/// Factors n = pq when the high bits of p are known (Coppersmith's method, in Howgrave-Graham's form):
+/// p = approximation + x0 with 0 <= x0 < 2^unknown_bits, e.g with the low bits of a leaked prime zeroed.
+/// The polynomials n^(m - i) f^i and x^j f^m with f(x) = approximation + x all vanish at x0 modulo p^m, and LLL on
+/// their coefficients (scaled by powers of X = 2^unknown_bits) finds a combination small enough to vanish at x0 over
+/// the integers, whose roots are then tried. It works up to about a quarter of the bits of n for balanced primes;
+/// closer to that bound takes larger lattices than `COPPERSMITH_MAX_DIMENSION`.
+///
+/// # Returns
+/// * `Some(p)` - The prime p.
+/// * `None` - The unknown part is too large for the lattices tried, or p is not of the given form.
+pub fn factor_with_high_bits(n: &Integer, approximation: &Integer, unknown_bits: u32) -> Option<Integer> {
+    let bound = Integer::from(1) << unknown_bits;
+    let (m, t) = lattice_shape(n.significant_bits(), unknown_bits)?;
+    let dimension = m + t;
+
+    // the coefficients of f^i, from f^0 = 1 up to f^m
+    let f = [approximation.clone(), Integer::from(1)];
+    let mut powers: Vec<Vec<Integer>> = vec![vec![Integer::from(1)]];
+    for _ in 0..m {
+        let last = powers.last().unwrap();
+        let mut next = vec![Integer::new(); last.len() + 1];
+        for (i, c) in last.iter().enumerate() {
+            for (j, g) in f.iter().enumerate() {
+                next[i + j] += (c * g).complete();
+            }
+        }
+        powers.push(next);
+    }
+    let row = |shift: usize, scale: &Integer, power: &[Integer]| -> Vec<Integer> {
+        let mut coefficients = vec![Integer::new(); dimension];
+        for (i, c) in power.iter().enumerate() {
+            coefficients[i + shift] = Integer::from(c * scale) << (unknown_bits * (i + shift) as u32);
+        }
+        coefficients
+    };
+    let mut basis: Vec<Vec<Integer>> = (0..m)
+        .map(|i| row(0, &n.clone().pow(m as u32 - i as u32), &powers[i]))
+        .chain((0..t).map(|j| row(j, &Integer::from(1), &powers[m])))
+        .collect();
+    lll(&mut basis);
+
+    let high = Integer::from(&bound - 1u32);
+    for reduced in basis.iter().take(2) {
+        // back from h(xX) to h(x)
+        let h: Vec<Integer> = reduced.iter().enumerate()
+            .map(|(i, c)| Integer::from(c >> (unknown_bits * i as u32)))
+            .collect();
+        for x0 in integer_roots(&h, &Integer::new(), &high) {
+            let p = Integer::from(approximation + &x0);
+            if p > 1 && p < *n && n.is_divisible(&p) {
+                return Some(p);
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rug::rand::RandState;
+
+    #[test]
+    fn test_coppersmith() {
+        // (x - 3)(x + 5)(x - 1000)^2, where 1000 is also a root of h', and 2x^2 - 7x, whose other root 7/2 is not an integer
+        let h = [-15_000_000, 2_030_000, 995_985, -1998, 1].map(Integer::from);
+        assert_eq!(integer_roots(&h, &Integer::from(-10), &Integer::from(10_000)), [-5, 3, 1000]);
+        assert_eq!(integer_roots(&[0, -7, 2].map(Integer::from), &Integer::from(-10), &Integer::from(10)), [0]);
+
+        let mut rand = RandState::new();
+        let mut prime = || Integer::from(Integer::random_bits(256, &mut rand)).next_prime();
+        let (p, q) = (prime(), prime());
+        let n = Integer::from(&p * &q);
+        for unknown_bits in [40, 90] {
+            let approximation = Integer::from(&p >> unknown_bits) << unknown_bits;
+            assert_eq!(factor_with_high_bits(&n, &approximation, unknown_bits), Some(p.clone()));
+        }
+        let approximation = Integer::from(&p >> 200u32) << 200u32;
+        assert_eq!(factor_with_high_bits(&n, &approximation, 200), None);
+    }
+}
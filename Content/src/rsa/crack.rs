@@ -0,0 +1,152 @@
+use rug::{Complete, Integer};
+
+use super::coppersmith::factor_with_high_bits;
+use super::structs::{CrackHints, RsaPrivateKey};
+use crate::prime_factorization::fermat::fermat_factor;
+use crate::prime_factorization::{prime_factorize_partial, prime_factorize_with_hints};
+use crate::number_theory::isqrt_rem;
+use crate::product_tree::product;
+
+// steps of Fermat's method in `crack_rsa_modulus` when the hints don't set them: primes generated from the same
+// random starting point (or with a too small gap) are found within these
+pub const CRACK_FERMAT_STEPS: u64 = 1 << 16;
+
+/// Wiener's attack: when d < n^(1/4) / 3, k/d is one of the convergents of the continued fraction of e/n
+/// (from ed = 1 + kφ(n)). Each convergent gives a candidate φ = (ed - 1) / k, and p, q are the roots of
+/// x^2 - (n - φ + 1) x + n.
+///
+/// # Returns
+/// * `Some(p)` - The smaller prime of n = pq.
+/// * `None` - d is too large for the attack, or n is not a product of two primes.
+pub fn wiener_attack(n: &Integer, e: &Integer) -> Option<Integer> {
+    let (mut a, mut b) = (e.clone(), n.clone());
+    // the numerators (k) and denominators (d) of the last two convergents
+    let (mut k, mut k_previous) = (Integer::from(1), Integer::new());
+    let (mut d, mut d_previous) = (Integer::new(), Integer::from(1));
+    while b != 0 {
+        let (q, r) = a.div_rem_floor_ref(&b).complete();
+        (a, b) = (b, r);
+        (k, k_previous) = (Integer::from(&q * &k) + &k_previous, k);
+        (d, d_previous) = (q * &d + &d_previous, d);
+        if k == 0 {
+            continue;
+        }
+        let (phi, remainder) = (Integer::from(e * &d) - 1u32).div_rem_floor(k.clone());
+        if remainder != 0 {
+            continue;
+        }
+        // p + q
+        let sum = Integer::from(n - &phi) + 1u32;
+        let discriminant = Integer::from(sum.square_ref()) - Integer::from(n * 4u32);
+        if discriminant > 0 && let Some(root) = isqrt_rem(&discriminant).exact_root() {
+            let p = (sum - root) >> 1u32;
+            if p > 1 && n.is_divisible(&p) {
+                return Some(p);
+            }
+        }
+    }
+    None
+}
+
+/// A factor of n shared with one of the other moduli, e.g keys generated with a bad random number generator.
+/// gcd(n, the product of the other moduli mod n) finds it unless every prime of n is shared, in which case the moduli
+/// are tried one by one.
+pub fn shared_factor(n: &Integer, co_moduli: &[Integer]) -> Option<Integer> {
+    if co_moduli.is_empty() {
+        return None;
+    }
+    let g = (product(co_moduli) % n).gcd(n);
+    if g > 1 && g < *n {
+        return Some(g);
+    }
+    co_moduli.iter()
+        .map(|m| Integer::from(m.gcd_ref(n)))
+        .find(|g| *g > 1 && g != n)
+}
+
+/// The key of n from one non-trivial factor, with the rest of n factorized by `prime_factorize_with_hints`.
+fn key_from_factor(n: &Integer, e: &Integer, factor: &Integer) -> Option<RsaPrivateKey> {
+    let factors = prime_factorize_with_hints(n, std::slice::from_ref(factor)).ok()?;
+    let primes: Vec<Integer> = factors.into_iter()
+        .map(|(p, exponent)| (exponent == 1).then_some(p))
+        .collect::<Option<_>>()?;
+    if Integer::product(primes.iter()).complete() != *n {
+        return None;
+    }
+    RsaPrivateKey::from_primes(&primes, e).ok()
+}
+
+/// Recovers the private key of the public key (n, e), trying the attacks from the cheapest and most specific to the
+/// general factorization:
+/// 1. a factor shared with the `co_moduli` of the hints,
+/// 2. Fermat's method, for primes that are too close to each other,
+/// 3. Wiener's attack, for a small d,
+/// 4. Coppersmith's method, when the hints give the high bits of a prime,
+/// 5. `prime_factorize_partial` with the effort of the hints, for primes that are small or have a smooth p - 1.
+///
+/// # Returns
+/// * `Some(key)` - The private key, whose primes multiply to n.
+/// * `None` - None of the attacks worked, or n is not made of distinct primes, or e is not invertible.
+pub fn crack_rsa_modulus(n: &Integer, e: &Integer, hints: &CrackHints) -> Option<RsaPrivateKey> {
+    let factor = shared_factor(n, &hints.co_moduli)
+        .or_else(|| fermat_factor(n, hints.fermat_steps))
+        .or_else(|| wiener_attack(n, e))
+        .or_else(|| {
+            let (approximation, unknown_bits) = hints.prime_approximation.as_ref()?;
+            factor_with_high_bits(n, approximation, *unknown_bits)
+        });
+    if let Some(key) = factor.and_then(|factor| key_from_factor(n, e, &factor)) {
+        return Some(key);
+    }
+
+    let factorization = prime_factorize_partial(n, hints.effort);
+    if !factorization.is_complete() || factorization.primes.iter().any(|(_, exponent)| *exponent > 1) {
+        return None;
+    }
+    let primes: Vec<Integer> = factorization.primes.into_iter().map(|(p, _)| p).collect();
+    RsaPrivateKey::from_primes(&primes, e).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prime_factorization::Effort;
+    use rug::rand::RandState;
+
+    #[test]
+    fn test_crack_rsa_modulus() {
+        let mut rand = RandState::new();
+        let e = Integer::from(65537);
+        let mut prime = |bits: u32| Integer::from(Integer::random_bits(bits, &mut rand)).next_prime();
+        let hints = CrackHints::default();
+
+        // close primes
+        let p = prime(256);
+        let q = Integer::from(&p + 1_000_000u32).next_prime();
+        let key = crack_rsa_modulus(&Integer::from(&p * &q), &e, &hints).unwrap();
+        assert_eq!(key, RsaPrivateKey::from_primes(&[p.clone(), q], &e).unwrap());
+
+        // a prime shared with another key
+        let (q, r) = (prime(256), prime(256));
+        let n = Integer::from(&p * &q);
+        let co_hints = CrackHints { co_moduli: vec![prime(512), Integer::from(&p * &r)], ..CrackHints::default() };
+        assert_eq!(crack_rsa_modulus(&n, &e, &co_hints).unwrap().n, n);
+        assert_eq!(crack_rsa_modulus(&n, &e, &CrackHints { effort: Effort::Low, ..hints.clone() }), None);
+
+        // the high bits of p
+        let approximation = Integer::from(&p >> 64u32) << 64u32;
+        let leak = CrackHints { prime_approximation: Some((approximation, 64)), effort: Effort::Low, ..hints.clone() };
+        assert_eq!(crack_rsa_modulus(&n, &e, &leak).unwrap().primes[0].p, p.clone().min(q.clone()));
+
+        // a small d, with e chosen from it
+        let phi = Integer::from(&p - 1u32) * Integer::from(&q - 1u32);
+        let d = (1001u32..).map(Integer::from).find(|d| d.gcd_ref(&phi).complete() == 1).unwrap();
+        let e_wiener = d.clone().invert(&phi).unwrap();
+        assert_eq!(wiener_attack(&n, &e_wiener), Some(p.clone().min(q.clone())));
+        assert_eq!(crack_rsa_modulus(&n, &e_wiener, &hints).unwrap().d, d);
+
+        // small primes are left to the factorization
+        let n = prime(40) * prime(40) * prime(40);
+        assert_eq!(crack_rsa_modulus(&n, &e, &hints).map(|key| key.primes.len()), Some(3));
+    }
+}
@@ -0,0 +1,149 @@
+use rug::{ops::RemRounding, rand::RandState, Complete, Integer};
+
+pub mod coppersmith;
+pub mod crack;
+pub mod structs;
+pub use coppersmith::factor_with_high_bits;
+pub use crack::{crack_rsa_modulus, shared_factor, wiener_attack};
+pub use structs::{CrackHints, RsaError, RsaPrime, RsaPrivateKey};
+
+use crate::montgomery_mod_mult::Context;
+use crate::number_theory::is_prime;
+use crate::number_theory::residues::pow_mod;
+use crate::prime_factorization::prime_factorize;
+
+/// A random prime of exactly `bits` bits with gcd(e, p - 1) = 1.
+fn generate_prime(bits: u32, e: &Integer, rand: &mut RandState) -> Integer {
+    loop {
+        let mut candidate = Integer::from(Integer::random_bits(bits, rand));
+        candidate.set_bit(bits - 1, true);
+        candidate.set_bit(0, true);
+        if is_prime(&candidate) && Integer::from(&candidate - 1u32).gcd(e) == 1 {
+            return candidate;
+        }
+    }
+}
+
+impl RsaPrivateKey {
+    /// Generates a key with an n of exactly `bits` bits made of `prime_count` primes of similar size.
+    /// Panics if prime_count < 2 or e is even.
+    pub fn generate(bits: u32, prime_count: u32, e: &Integer, rand: &mut RandState) -> Self {
+        assert!(prime_count >= 2 && e.is_odd(), "a key needs at least two primes and an odd e");
+        loop {
+            let mut primes: Vec<Integer> = (0..prime_count)
+                .map(|i| generate_prime((bits + i) / prime_count, e, rand))
+                .collect();
+            primes.sort();
+            primes.dedup();
+            if primes.len() as u32 != prime_count || Integer::product(primes.iter()).complete().significant_bits() != bits {
+                continue;
+            }
+            return Self::from_primes(&primes, e).unwrap();
+        }
+    }
+
+    /// Builds the private key from the distinct primes of n (in any order) and the public exponent.
+    ///
+    /// # Returns
+    /// * `Ok(key)` - The key, with d and the CRT values of every prime.
+    /// * `Err(error)` - Fewer than two primes, a repeated or composite factor, or an e that is not invertible.
+    pub fn from_primes(primes: &[Integer], e: &Integer) -> Result<Self, RsaError> {
+        if primes.len() < 2 {
+            return Err(RsaError::TooFewPrimes);
+        }
+        let mut primes = primes.to_vec();
+        primes.sort();
+        for pair in primes.windows(2) {
+            if pair[0] == pair[1] {
+                return Err(RsaError::RepeatedPrime(pair[0].clone()));
+            }
+        }
+        if let Some(p) = primes.iter().find(|p| !is_prime(p)) {
+            return Err(RsaError::NotPrime(p.clone()));
+        }
+
+        // Carmichael's function of n
+        let mut lambda = Integer::from(1);
+        for p in &primes {
+            lambda.lcm_mut(&Integer::from(p - 1u32));
+        }
+        let d = e.clone().invert(&lambda).map_err(|_| RsaError::InvalidExponent)?;
+
+        let mut product = Integer::from(1);
+        let primes = primes.into_iter()
+            .map(|p| {
+                let exponent = &d % Integer::from(&p - 1u32);
+                let coefficient = Integer::from(&product % &p).invert(&p).unwrap();
+                product *= &p;
+                RsaPrime { p, exponent, coefficient }
+            })
+            .collect();
+        Ok(RsaPrivateKey { n: product, e: e.clone(), d, primes })
+    }
+
+    /// Rebuilds the private key from the public key alone, by factorizing n with `prime_factorize`.
+    /// Only practical when n is small or its primes are weak, which is the point of the factorization tools here.
+    pub fn from_factorization(n: &Integer, e: &Integer) -> Result<Self, RsaError> {
+        let factors = prime_factorize(n);
+        if let Some((p, _)) = factors.factors().iter().find(|(_, exponent)| *exponent > 1) {
+            return Err(RsaError::RepeatedPrime(p.clone()));
+        }
+        // without the ecm feature prime_factorize can leave out a factor
+        if factors.value() != *n {
+            return Err(RsaError::NotPrime(n.clone()));
+        }
+        let primes: Vec<Integer> = factors.into_iter().map(|(p, _)| p).collect();
+        Self::from_primes(&primes, e)
+    }
+
+    /// m^e mod n.
+    pub fn encrypt(&self, m: &Integer) -> Integer {
+        let mut ctx = Context::new(self.n.clone());
+        pow_mod(m, &self.e, &mut ctx)
+    }
+
+    /// c^d mod n, computed as c^(d mod (p - 1)) mod p for every prime p, which Garner's algorithm merges:
+    /// each step adds the product of the previous primes times the correction for the next prime.
+    pub fn decrypt(&self, c: &Integer) -> Integer {
+        let mut m = Integer::new();
+        let mut product = Integer::from(1);
+        for RsaPrime { p, exponent, coefficient } in &self.primes {
+            let mut ctx = Context::new(p.clone());
+            let mut correction = pow_mod(c, exponent, &mut ctx) - Integer::from(&m % p);
+            correction *= coefficient;
+            correction = correction.rem_euc(p);
+            m += correction * &product;
+            product *= p;
+        }
+        m
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_multi_prime_rsa() {
+        let mut rand = RandState::new();
+        let e = Integer::from(65537);
+        let key = RsaPrivateKey::generate(256, 3, &e, &mut rand);
+        assert_eq!(key.n.significant_bits(), 256);
+        assert_eq!(key.primes.len(), 3);
+
+        let m = Integer::from(Integer::random_below_ref(&key.n, &mut rand));
+        let c = key.encrypt(&m);
+        assert_eq!(key.decrypt(&c), m);
+        assert_eq!(c, m.clone().pow_mod(&e, &key.n).unwrap());
+
+        // small enough for prime_factorize to recover the key
+        let small = RsaPrivateKey::generate(96, 4, &e, &mut rand);
+        assert_eq!(RsaPrivateKey::from_factorization(&small.n, &e), Ok(small));
+
+        let p = Integer::from(1_000_000_007u64);
+        assert_eq!(RsaPrivateKey::from_primes(&[p.clone(), p.clone()], &e), Err(RsaError::RepeatedPrime(p.clone())));
+        assert_eq!(RsaPrivateKey::from_primes(&[p.clone(), Integer::from(15)], &e), Err(RsaError::NotPrime(Integer::from(15))));
+        // 3 divides 7 - 1
+        assert_eq!(RsaPrivateKey::from_primes(&[p, Integer::from(7)], &Integer::from(3)), Err(RsaError::InvalidExponent));
+    }
+}
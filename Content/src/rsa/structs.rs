@@ -0,0 +1,73 @@
+use std::fmt;
+
+use rug::Integer;
+
+use super::crack::CRACK_FERMAT_STEPS;
+use crate::prime_factorization::Effort;
+
+/// A prime factor of an RSA modulus along with its CRT values.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RsaPrime {
+    pub p: Integer,
+    /// d mod (p - 1)
+    pub exponent: Integer,
+    /// The inverse of the product of the primes before this one, modulo p (1 for the first prime).
+    pub coefficient: Integer,
+}
+
+/// An RSA private key with any number of prime factors (n = p·q·r·...).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RsaPrivateKey {
+    pub n: Integer,
+    pub e: Integer,
+    /// The inverse of e modulo lcm(p - 1, q - 1, ...).
+    pub d: Integer,
+    /// The distinct primes of n in increasing order.
+    pub primes: Vec<RsaPrime>,
+}
+
+/// What `crack_rsa_modulus` knows about a modulus besides n and e, and how much work it puts into it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CrackHints {
+    /// Other moduli that may share a prime with n, e.g keys from the same device.
+    pub co_moduli: Vec<Integer>,
+    /// A prime of n with its low bits unknown (and set to 0), along with the number of unknown bits.
+    /// Coppersmith's method recovers the prime when they are up to about a quarter of the bits of n.
+    pub prime_approximation: Option<(Integer, u32)>,
+    /// The steps of Fermat's method, for primes that are close to each other.
+    pub fermat_steps: u64,
+    /// The effort of the general factorization, the last attack tried.
+    pub effort: Effort,
+}
+
+impl Default for CrackHints {
+    fn default() -> Self {
+        CrackHints { co_moduli: Vec::new(), prime_approximation: None, fermat_steps: CRACK_FERMAT_STEPS, effort: Effort::High }
+    }
+}
+
+/// Why a private key could not be built.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RsaError {
+    /// Fewer than two primes were given.
+    TooFewPrimes,
+    /// A factor that is not prime, or the factorization of n could not be completed.
+    NotPrime(Integer),
+    /// A prime that divides n more than once.
+    RepeatedPrime(Integer),
+    /// e is not invertible modulo lcm(p - 1, q - 1, ...).
+    InvalidExponent,
+}
+
+impl fmt::Display for RsaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RsaError::TooFewPrimes => write!(f, "an RSA modulus needs at least two primes"),
+            RsaError::NotPrime(p) => write!(f, "{} is not a prime", p),
+            RsaError::RepeatedPrime(p) => write!(f, "the prime {} appears more than once", p),
+            RsaError::InvalidExponent => write!(f, "e is not invertible modulo lcm(p - 1, q - 1, ...)"),
+        }
+    }
+}
+
+impl std::error::Error for RsaError {}
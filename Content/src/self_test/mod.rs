@@ -0,0 +1,184 @@
+use std::{fmt, panic::{catch_unwind, AssertUnwindSafe}, time::{Duration, Instant}};
+
+use rug::Integer;
+
+use crate::montgomery_mod_mult::Context;
+use crate::number_theory::chinese_remainder_theorem;
+use crate::number_theory::random::{random_below, random_below_u64, random_bits, random_u64, with_seed};
+#[cfg(feature = "factorization")]
+use crate::number_theory::{is_prime, random::{generate_prime, rand_state}};
+
+/// The outcome of one of the checks of `self_test`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CheckReport {
+    pub name: &'static str,
+    pub trials: usize,
+    /// What went wrong in every trial that failed, with its inputs (a panic counts as a failure).
+    pub failures: Vec<String>,
+    pub elapsed: Duration,
+}
+
+/// The report of `self_test`: one `CheckReport` per check, in the order they ran.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SelfTestReport {
+    /// The seed the inputs were drawn from: `self_test_with_seed` with it replays the same trials.
+    pub seed: u64,
+    pub checks: Vec<CheckReport>,
+}
+
+impl SelfTestReport {
+    /// Whether every trial of every check passed.
+    pub fn passed(&self) -> bool {
+        self.checks.iter().all(|check| check.failures.is_empty())
+    }
+}
+
+/// One line per check (`name: trials, failures, time`), the failures below it, then the verdict.
+impl fmt::Display for SelfTestReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "self test (seed {})", self.seed)?;
+        for check in &self.checks {
+            let status = match check.failures.len() {
+                0 => "ok".to_string(),
+                failures => format!("{} FAILED", failures),
+            };
+            writeln!(f, "{}: {} trials, {} ({:.1?})", check.name, check.trials, status, check.elapsed)?;
+            for failure in &check.failures {
+                writeln!(f, "    {}", failure)?;
+            }
+        }
+        writeln!(f, "{}", if self.passed() { "passed" } else { "FAILED" })
+    }
+}
+
+/// Runs a trial `trials` times, where a trial returns a description of what went wrong when it fails.
+fn check(name: &'static str, trials: usize, mut trial: impl FnMut() -> Result<(), String>) -> CheckReport {
+    let start = Instant::now();
+    let failures = (0..trials).filter_map(|_| match catch_unwind(AssertUnwindSafe(&mut trial)) {
+        Ok(result) => result.err(),
+        Err(_) => Some("panicked".to_string()),
+    }).collect();
+    CheckReport { name, trials, failures, elapsed: start.elapsed() }
+}
+
+/// A Montgomery product and power modulo a random odd n of up to 512 bits, against GMP's plain arithmetic.
+fn montgomery_trial() -> Result<(), String> {
+    let mut n = random_bits(2 + random_below_u64(511) as u32);
+    n.set_bit(0, true);
+    if n == 1 {
+        n += 2;
+    }
+    let (a, b, e) = (random_below(&n), random_below(&n), random_bits(128));
+    let mut ctx = Context::new(n.clone());
+    let (a_m, b_m) = (ctx.to_montgomery(&a), ctx.to_montgomery(&b));
+    let product = ctx.mul(&a_m, &b_m);
+    let product = ctx.from_montgomery(product);
+    if product != Integer::from(&a * &b) % &n {
+        return Err(format!("{} * {} mod {} gave {}", a, b, n, product));
+    }
+    let power = ctx.pow_mod(&a_m, &e);
+    let power = ctx.from_montgomery(power);
+    if power != a.clone().pow_mod(&e, &n).unwrap() {
+        return Err(format!("{}^{} mod {} gave {}", a, e, n, power));
+    }
+    Ok(())
+}
+
+/// The residues of a random x modulo two random moduli of up to 128 bits, which often share a factor, are combined
+/// back into x modulo their lcm, and changing one of them by 1 must have no solution when they do share one.
+fn crt_trial() -> Result<(), String> {
+    let m = random_bits(2 + random_below_u64(127) as u32) + 1u32;
+    let k = random_bits(2 + random_below_u64(127) as u32) + 1u32;
+    let x = random_bits(256);
+    let (a, b) = (Integer::from(&x % &m), Integer::from(&x % &k));
+    let lcm: Integer = m.lcm_ref(&k).into();
+    match chinese_remainder_theorem(&a, &m, &b, &k) {
+        Some((y, modulus)) if modulus == lcm && y < modulus && Integer::from(&x - &y).is_divisible(&modulus) => {}
+        result => return Err(format!("x = {} mod {} and {}: got {:?}", x, m, k, result)),
+    }
+    if Integer::from(m.gcd_ref(&k)) != 1 {
+        let b = Integer::from(&b + 1u32) % &k;
+        if let Some(result) = chinese_remainder_theorem(&a, &m, &b, &k) {
+            return Err(format!("{} mod {} and {} mod {} have no solution, got {:?}", a, m, b, k, result));
+        }
+    }
+    Ok(())
+}
+
+/// A product of 2 to 5 random primes of up to 32 bits (a square now and then) factorized by `prime_factorize`, whose
+/// result must be distinct primes (in increasing order) that multiply back to it.
+#[cfg(feature = "factorization")]
+fn factorization_trial() -> Result<(), String> {
+    use crate::prime_factorization::prime_factorize;
+
+    let rand = &mut rand_state();
+    let mut n = Integer::from(1);
+    for _ in 0..2 + random_below_u64(4) {
+        let p = generate_prime(2 + random_below_u64(31) as u32, rand);
+        n *= &p;
+        if random_below_u64(4) == 0 {
+            n *= &p;
+        }
+    }
+    let factors = prime_factorize(&n);
+    let primes = factors.factors().iter().all(|(p, _)| is_prime(p));
+    let distinct = factors.factors().windows(2).all(|pair| pair[0].0 < pair[1].0);
+    if factors.value() != n || !primes || !distinct {
+        return Err(format!("{} was factorized as {:?}", n, factors));
+    }
+    Ok(())
+}
+
+/// A discrete log g^x = h modulo a random prime of 32 bits, whose solution must satisfy g^exponent = h and
+/// g^period = 1.
+#[cfg(feature = "dlog")]
+fn dlog_trial() -> Result<(), String> {
+    use crate::discrete_logarithm::discrete_log;
+
+    let p = generate_prime(32, &mut rand_state());
+    let g = random_below(&Integer::from(&p - 2u32)) + 2u32;
+    let x = random_below(&p);
+    let h = g.clone().pow_mod(&x, &p).unwrap();
+    match discrete_log(&g, &h, &p) {
+        Ok(solution) if g.clone().pow_mod(&solution.exponent, &p).unwrap() == h
+            && g.clone().pow_mod(&solution.period, &p).unwrap() == 1 => Ok(()),
+        result => Err(format!("{}^x = {} mod {} (x = {}): got {:?}", g, h, p, x, result)),
+    }
+}
+
+/// A quick battery of randomized cross-checks of the crate against plain arithmetic on this platform: Montgomery
+/// products and powers against GMP, CRT solutions against the residues they come from, factorizations multiplied
+/// back (with the `factorization` feature) and discrete logs checked by exponentiation (with `dlog`). It takes about
+/// a second in release builds. The inputs are random, from a seed kept in the report.
+pub fn self_test() -> SelfTestReport {
+    self_test_with_seed(random_u64())
+}
+
+/// `self_test` with the inputs drawn from the seed, e.g to replay a failed report.
+pub fn self_test_with_seed(seed: u64) -> SelfTestReport {
+    with_seed(Some(seed), || {
+        let checks = vec![
+            check("montgomery", 200, montgomery_trial),
+            check("crt", 200, crt_trial),
+            #[cfg(feature = "factorization")]
+            check("factorization", 30, factorization_trial),
+            #[cfg(feature = "dlog")]
+            check("discrete log", 20, dlog_trial),
+        ];
+        SelfTestReport { seed, checks }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_self_test() {
+        let report = self_test_with_seed(7);
+        assert!(report.passed(), "{}", report);
+        let names: Vec<&str> = report.checks.iter().map(|check| check.name).collect();
+        assert_eq!(names[..2], ["montgomery", "crt"]);
+        assert!(report.to_string().ends_with("passed\n"));
+    }
+}
@@ -0,0 +1,157 @@
+use std::{env, fmt, fs, io, path::Path};
+
+use once_cell::sync::OnceCell;
+
+/// The environment variable holding the path of the profile file loaded by `profile()`.
+pub const PROFILE_ENV: &str = "MATH_ALGORITHMS_PROFILE";
+
+/// Crossover points and sizes used by the algorithms in this crate.
+/// The defaults were picked by hand, the `tune` binary measures better values for the host machine.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TuningProfile {
+    /// prime_factorize trial divides by all primes below this bound.
+    pub trial_division_bound: u32,
+    /// How many times Pollard's rho is run on a number before leaving it to ECM.
+    pub rho_attempts: u32,
+    /// Block size of ECM phase 2 in the first round (B1 = 11e3 in the default schedule).
+    pub ecm_block_size_1: usize,
+    /// Block size of ECM phase 2 in the later rounds.
+    pub ecm_block_size_2: usize,
+    /// Discrete logs in subgroups of prime order up to this bound use baby-step giant-step, larger ones use Pollard's rho.
+    pub bsgs_limit: u64,
+}
+
+impl Default for TuningProfile {
+    fn default() -> Self {
+        Self {
+            trial_division_bound: 10_000,
+            rho_attempts: 3,
+            ecm_block_size_1: 2000,
+            ecm_block_size_2: 5000,
+            bsgs_limit: 1 << 24,
+        }
+    }
+}
+
+impl fmt::Display for TuningProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "trial_division_bound = {}", self.trial_division_bound)?;
+        writeln!(f, "rho_attempts = {}", self.rho_attempts)?;
+        writeln!(f, "ecm_block_size_1 = {}", self.ecm_block_size_1)?;
+        writeln!(f, "ecm_block_size_2 = {}", self.ecm_block_size_2)?;
+        writeln!(f, "bsgs_limit = {}", self.bsgs_limit)
+    }
+}
+
+/// Largest trial division bound: the sieve only generates primes up to 2.5e7.
+const MAX_TRIAL_DIVISION_BOUND: u32 = 25_000_000;
+/// Largest ECM block size: phase 2 precomputes at most 2000 points, one per odd residue coprime to 5 below half the block size.
+const MAX_ECM_BLOCK_SIZE: usize = 10_000;
+/// Largest number of Pollard's rho runs, past this point ECM is always the better use of the time.
+pub const MAX_RHO_ATTEMPTS: u32 = 64;
+/// Smallest BSGS limit: Pollard's rho needs enough group elements for its random walk to mix.
+pub const MIN_BSGS_LIMIT: u64 = 1 << 8;
+/// Largest BSGS limit: the giant-step table of a subgroup of order q holds sqrt(q) elements, 2^24 at most.
+pub const MAX_BSGS_LIMIT: u64 = 1 << 48;
+
+impl TuningProfile {
+    /// Parses a profile from lines of the form `key = value`. Blank lines and lines starting with `#` are skipped.
+    /// Keys that are missing keep their default value.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut profile = Self::default();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, value) = line.split_once('=')
+                .ok_or_else(|| format!("line {}: expected `key = value`", number + 1))?;
+            let (key, value) = (key.trim(), value.trim());
+            let invalid = |_| format!("line {}: invalid value `{}` for {}", number + 1, value, key);
+            match key {
+                "trial_division_bound" => profile.trial_division_bound = value.parse().map_err(invalid)?,
+                "rho_attempts" => profile.rho_attempts = value.parse().map_err(invalid)?,
+                "ecm_block_size_1" => profile.ecm_block_size_1 = value.parse().map_err(invalid)?,
+                "ecm_block_size_2" => profile.ecm_block_size_2 = value.parse().map_err(invalid)?,
+                "bsgs_limit" => profile.bsgs_limit = value.parse().map_err(invalid)?,
+                _ => return Err(format!("line {}: unknown key `{}`", number + 1, key)),
+            }
+        }
+        profile.validate()?;
+        Ok(profile)
+    }
+
+    /// Checks that the values can be used by the algorithms.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.trial_division_bound < 3 || self.trial_division_bound > MAX_TRIAL_DIVISION_BOUND {
+            return Err(format!("trial_division_bound must be in [3, {}]", MAX_TRIAL_DIVISION_BOUND));
+        }
+        // 0 attempts would silently turn Pollard's rho off, the factorizer builder is the place to do that
+        if self.rho_attempts == 0 || self.rho_attempts > MAX_RHO_ATTEMPTS {
+            return Err(format!("rho_attempts must be in [1, {}]", MAX_RHO_ATTEMPTS));
+        }
+        if !(MIN_BSGS_LIMIT..=MAX_BSGS_LIMIT).contains(&self.bsgs_limit) {
+            return Err(format!("bsgs_limit must be in [{}, {}]", MIN_BSGS_LIMIT, MAX_BSGS_LIMIT));
+        }
+        for block_size in [self.ecm_block_size_1, self.ecm_block_size_2] {
+            // primes are reached from multiples of the block size, so the offsets must skip multiples of 2 and 5
+            if block_size == 0 || block_size % 10 != 0 || block_size > MAX_ECM_BLOCK_SIZE {
+                return Err(format!("ECM block sizes must be positive multiples of 10, up to {}", MAX_ECM_BLOCK_SIZE));
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads a profile file.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let text = fs::read_to_string(path)?;
+        Self::parse(&text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Writes the profile to a file, in the format read by `load`.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        fs::write(path, format!("# generated by the tune binary\n{}", self))
+    }
+}
+
+static PROFILE: OnceCell<TuningProfile> = OnceCell::new();
+
+/// Returns the profile used by the library.
+/// On first use, it is loaded from the file named by the `MATH_ALGORITHMS_PROFILE` environment variable,
+/// or the defaults are used if it isn't set. Panics if the variable is set but the file can't be loaded.
+pub fn profile() -> &'static TuningProfile {
+    PROFILE.get_or_init(|| match env::var_os(PROFILE_ENV) {
+        Some(path) => TuningProfile::load(&path)
+            .unwrap_or_else(|e| panic!("failed to load the tuning profile {:?}: {}", path, e)),
+        None => TuningProfile::default(),
+    })
+}
+
+/// Sets the profile used by the library. This only works before the profile is first used,
+/// otherwise the given profile is handed back.
+pub fn set_profile(profile: TuningProfile) -> Result<(), TuningProfile> {
+    if profile.validate().is_err() {
+        return Err(profile);
+    }
+    PROFILE.set(profile)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_round_trip() {
+        let profile = TuningProfile { trial_division_bound: 30_000, rho_attempts: 5, ecm_block_size_1: 3000,
+            ecm_block_size_2: 6000, bsgs_limit: 1 << 20 };
+        assert_eq!(TuningProfile::parse(&profile.to_string()), Ok(profile));
+        assert_eq!(TuningProfile::parse("# comment\n\nrho_attempts = 2\n").unwrap().rho_attempts, 2);
+        assert!(TuningProfile::parse("ecm_block_size_1 = 2005").is_err());
+        assert!(TuningProfile::parse("unknown = 1").is_err());
+        assert!(TuningProfile::parse("rho_attempts = 0").is_err());
+        assert!(TuningProfile::parse("rho_attempts = 65").is_err());
+        assert!(TuningProfile::parse("bsgs_limit = 0").is_err());
+        assert!(TuningProfile::parse(&format!("bsgs_limit = {}", u64::MAX)).is_err());
+        assert_eq!(TuningProfile::parse("bsgs_limit = 256").unwrap().bsgs_limit, MIN_BSGS_LIMIT);
+    }
+}